@@ -8,8 +8,13 @@
 //
 
 // dataset imports
-size_t _reef_dataset_len() __attribute__((__import_module__("reef"), __import_name__("dataset_len"), ));
-void _reef_dataset_write(uint8_t *ptr) __attribute__((__import_module__("reef"), __import_name__("dataset_write"), ));
+void _reef_dataset_write(uint32_t index, uint8_t *ptr)
+    __attribute__((__import_module__("reef"), __import_name__("dataset_write"), ));
+int32_t _reef_dataset_find(const char *name, size_t name_len)
+    __attribute__((__import_module__("reef"), __import_name__("dataset_find"), ));
+
+// time import
+uint64_t _reef_time_now(int32_t clock) __attribute__((__import_module__("reef"), __import_name__("time_now"), ));
 
 // result import
 void _reef_result(size_t result_type, uint8_t *ptr, size_t len)
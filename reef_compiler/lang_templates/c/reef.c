@@ -5,19 +5,49 @@
 
 // #include "input.c"
 
+// Reef ABI version this program is built against, as a little endian u32 in the `reef_abi` custom section
+__attribute__((used, section(".custom_section.reef_abi"))) static const uint8_t reef_abi_version[4] = {7, 0, 0, 0};
+
 //
 // Wasm entry point
 //
 void reef_main() {
-    size_t len = _reef_dataset_len();
+    if (run_streaming) {
+        reef_result_int(0);
+
+        run_streaming(reef_dataset_len(0));
+        return;
+    }
+
+    uint8_t *dataset_mem = reef_dataset_load(0);
+
+    reef_result_int(0);
+
+    run(dataset_mem, (size_t)reef_dataset_len(0));
+}
+
+// Dataset functions
+int32_t reef_dataset(const char *name) { return _reef_dataset_find(name, strlen(name)); }
+
+uint8_t *reef_dataset_load(uint32_t index) {
+    size_t len = (size_t)reef_dataset_len(index);
     size_t len_alloc = (len + 7) & ~0x07;
 
     uint8_t *dataset_mem = malloc(len_alloc);
-    _reef_dataset_write(dataset_mem);
+    _reef_dataset_write(index, dataset_mem);
 
-    reef_result_int(0);
+    return dataset_mem;
+}
+
+// Time functions
+uint64_t reef_time_cycles() { return _reef_time_now(0); }
+uint64_t reef_time_millis() { return _reef_time_now(1); }
 
-    run(dataset_mem, len);
+// Random functions
+int rand() {
+    uint32_t value;
+    reef_random_fill((uint8_t *)&value, sizeof(value));
+    return (int)(value & RAND_MAX);
 }
 
 // Result functions
@@ -9,8 +9,44 @@
 void reef_progress(float done) __attribute__((__import_module__("reef"), __import_name__("progress"), ));
 void reef_sleep(float seconds) __attribute__((__import_module__("reef"), __import_name__("sleep"), ));
 
-// User main function definition
-void run(uint8_t *dataset, size_t len);
+// Sync control. `reef_checkpoint` syncs the state of the job right away, for example after freeing a large buffer.
+// While `reef_defer_sync(1)` is in effect syncs are deferred, but only for up to a minute.
+void reef_checkpoint() __attribute__((__import_module__("reef"), __import_name__("checkpoint"), ));
+void reef_defer_sync(int defer) __attribute__((__import_module__("reef"), __import_name__("defer_sync"), ));
+
+// Time functions. `reef_time_cycles` is a virtual clock counting the instructions executed since the job was started,
+// which is the same on every node. `reef_time_millis` returns the milliseconds since the Unix epoch, only use it for
+// progress estimates since results must not depend on it.
+uint64_t reef_time_cycles();
+uint64_t reef_time_millis();
+
+// Fill a buffer with random bytes, they are the same every time the job is run so results stay reproducible.
+void reef_random_fill(uint8_t *ptr, size_t len)
+    __attribute__((__import_module__("reef"), __import_name__("random_fill"), ));
+
+// Dataset functions. Jobs can have multiple named datasets, which are accessed by their index.
+// The dataset passed to `run` is the first one, with index 0.
+uint32_t reef_dataset_count() __attribute__((__import_module__("reef"), __import_name__("dataset_count"), ));
+uint64_t reef_dataset_len(uint32_t index) __attribute__((__import_module__("reef"), __import_name__("dataset_len"), ));
+// Read a window of a dataset instead of copying all of it into memory.
+// Returns the number of bytes read, which is less than `len` only at the end of the dataset.
+size_t reef_dataset_read(uint32_t index, uint64_t offset, uint8_t *ptr, size_t len)
+    __attribute__((__import_module__("reef"), __import_name__("dataset_read"), ));
+
+// Index of the dataset with the given name, or -1 if the job has none.
+int32_t reef_dataset(const char *name);
+// Copy an entire dataset into memory. Every dataset can only be loaded once.
+uint8_t *reef_dataset_load(uint32_t index);
+
+// User main function definitions, define one of them.
+// `run` gets the entire dataset copied into memory,
+// `run_streaming` reads it with `reef_dataset_read`, which works for datasets larger than memory.
+void run(uint8_t *dataset, size_t len) __attribute__((weak));
+void run_streaming(uint64_t len) __attribute__((weak));
+
+// Random functions, like in the C standard library
+#define RAND_MAX 0x7fffffff
+int rand();
 
 // Result functions
 void reef_result_int(int value);
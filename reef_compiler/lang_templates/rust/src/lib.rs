@@ -9,8 +9,16 @@ pub mod reef {
         fn log(ptr: *const u8, len: usize);
         fn progress(done: f32);
         fn sleep(seconds: f32);
-        fn dataset_len() -> usize;
-        fn dataset_write(ptr: *mut u8);
+        fn checkpoint();
+        fn defer_sync(defer: i32);
+        fn random_fill(ptr: *mut u8, len: usize);
+        fn time_now(clock: i32) -> u64;
+        fn dataset_count() -> u32;
+        fn dataset_find(name_ptr: *const u8, name_len: usize) -> i32;
+        fn dataset_name(index: u32, ptr: *mut u8, len: usize) -> usize;
+        fn dataset_len(index: u32) -> u64;
+        fn dataset_write(index: u32, ptr: *mut u8);
+        fn dataset_read(index: u32, offset: u64, ptr: *mut u8, len: usize) -> usize;
         fn result(result_type: i32, ptr: *const u8, len: usize);
     }
 
@@ -30,21 +38,188 @@ pub mod reef {
         unsafe { sleep(seconds) }
     }
 
+    /// Sync the state of this job right away, for example after freeing a large buffer
+    pub fn reef_checkpoint() {
+        unsafe { checkpoint() }
+    }
+
+    /// Defer syncs of the state of this job, during critical sections
+    ///
+    /// Syncs are deferred for at most a minute, afterwards the job is synced anyway.
+    pub fn reef_defer_sync(defer: bool) {
+        unsafe { defer_sync(defer as i32) }
+    }
+
+    /// Number of instructions executed since the job was started
+    ///
+    /// This virtual clock is the same on every node, so it can be used without breaking reproducibility.
+    pub fn reef_time_cycles() -> u64 {
+        unsafe { time_now(0) }
+    }
+
+    /// Current wall-clock time
+    ///
+    /// Only use this for progress estimates, the results of the job must not depend on it.
+    pub fn reef_time_wall() -> std::time::SystemTime {
+        std::time::UNIX_EPOCH + std::time::Duration::from_millis(unsafe { time_now(1) })
+    }
+
+    /// Fill `buf` with random bytes
+    ///
+    /// The random bytes are the same every time the job is run, so results stay reproducible.
+    pub fn reef_random_fill(buf: &mut [u8]) {
+        unsafe { random_fill(buf.as_mut_ptr(), buf.len()) }
+    }
+
+    // `getrandom`, and with it crates like `rand`, get their random bytes from the reef imports
+    fn getrandom_reef(buf: &mut [u8]) -> Result<(), getrandom::Error> {
+        reef_random_fill(buf);
+        Ok(())
+    }
+    getrandom::register_custom_getrandom!(getrandom_reef);
+
+    /// Reef ABI version this program is built against, read by the node before instantiating it
+    #[used]
+    #[link_section = "reef_abi"]
+    static REEF_ABI_VERSION: [u8; 4] = 7u32.to_le_bytes();
+
     const PAGE_SIZE: usize = 65536;
 
-    /// SAFETY: only call once.
-    #[doc(hidden)]
-    pub unsafe fn _get_dataset() -> &'static [u8] {
-        let len = unsafe { dataset_len() };
-        // Note: the alignment to entire pages is not required, but nice
-        let pages = len.div_ceil(PAGE_SIZE);
+    /// Dataset of the job with the given name
+    pub fn dataset(name: &str) -> Option<Dataset> {
+        let index = unsafe { dataset_find(name.as_ptr(), name.len()) };
+        u32::try_from(index).ok().map(Dataset::at)
+    }
+
+    /// All datasets of the job, the first one is passed to `run`
+    pub fn datasets() -> impl Iterator<Item = Dataset> {
+        (0..unsafe { dataset_count() }).map(Dataset::at)
+    }
+
+    /// Dataset of the job, which is read in windows instead of copying all of it into memory
+    ///
+    /// Take this instead of `&[u8]` as the argument of `run` for datasets larger than memory.
+    #[derive(Debug, Clone, Copy)]
+    pub struct Dataset {
+        index: u32,
+        len: u64,
+    }
+
+    impl Dataset {
+        fn at(index: u32) -> Self {
+            Dataset { index, len: unsafe { dataset_len(index) } }
+        }
+
+        /// Name of the dataset, empty for the dataset of jobs which only have one
+        pub fn name(&self) -> String {
+            let mut name = vec![0; 64];
+            let len = unsafe { dataset_name(self.index, name.as_mut_ptr(), name.len()) };
+            if len > name.len() {
+                name.resize(len, 0);
+                unsafe { dataset_name(self.index, name.as_mut_ptr(), name.len()) };
+            }
+            name.truncate(len);
+            String::from_utf8_lossy(&name).into_owned()
+        }
+
+        /// Length of the dataset in bytes
+        pub fn len(&self) -> u64 {
+            self.len
+        }
+
+        pub fn is_empty(&self) -> bool {
+            self.len == 0
+        }
+
+        /// Read the dataset starting at `offset` into `buf`
+        ///
+        /// Returns the number of bytes read, which is less than `buf.len()` only at the end of the dataset.
+        pub fn read_at(&self, offset: u64, buf: &mut [u8]) -> usize {
+            unsafe { dataset_read(self.index, offset, buf.as_mut_ptr(), buf.len()) }
+        }
+
+        /// Read the dataset sequentially, for example with the `csv` crate
+        pub fn reader(&self) -> DatasetReader {
+            DatasetReader { dataset: *self, offset: 0 }
+        }
+
+        /// Copy the entire dataset into memory
+        ///
+        /// The dataset is only copied on the first call, later calls return the same memory. Once loaded,
+        /// [`Dataset::read_at`] and [`Dataset::reader`] read from that memory.
+        pub fn load(&self) -> &'static [u8] {
+            static LOADED: std::sync::Mutex<std::collections::BTreeMap<u32, &'static [u8]>> =
+                std::sync::Mutex::new(std::collections::BTreeMap::new());
 
-        let layout = std::alloc::Layout::from_size_align(pages * PAGE_SIZE, PAGE_SIZE).unwrap();
-        let dataset_mem = unsafe { std::alloc::alloc(layout) };
+            let mut loaded = LOADED.lock().unwrap();
+            loaded.entry(self.index).or_insert_with(|| {
+                let len = self.len as usize;
+                if len == 0 {
+                    return &[];
+                }
+
+                // Note: the alignment to entire pages is not required, but nice
+                let pages = len.div_ceil(PAGE_SIZE);
+
+                let layout = std::alloc::Layout::from_size_align(pages * PAGE_SIZE, PAGE_SIZE).unwrap();
+                let dataset_mem = unsafe { std::alloc::alloc(layout) };
+                if dataset_mem.is_null() {
+                    std::alloc::handle_alloc_error(layout);
+                }
 
-        unsafe { dataset_write(dataset_mem) };
+                unsafe { dataset_write(self.index, dataset_mem) };
 
-        unsafe { std::slice::from_raw_parts(dataset_mem, len) }
+                unsafe { std::slice::from_raw_parts(dataset_mem, len) }
+            })
+        }
+    }
+
+    /// Reader over a [`Dataset`], see [`Dataset::reader`]
+    #[derive(Debug, Clone)]
+    pub struct DatasetReader {
+        dataset: Dataset,
+        offset: u64,
+    }
+
+    impl std::io::Read for DatasetReader {
+        fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+            let read = self.dataset.read_at(self.offset, buf);
+            self.offset += read as u64;
+            Ok(read)
+        }
+    }
+
+    impl std::io::Seek for DatasetReader {
+        fn seek(&mut self, pos: std::io::SeekFrom) -> std::io::Result<u64> {
+            let offset = match pos {
+                std::io::SeekFrom::Start(offset) => Some(offset),
+                std::io::SeekFrom::End(delta) => self.dataset.len.checked_add_signed(delta),
+                std::io::SeekFrom::Current(delta) => self.offset.checked_add_signed(delta),
+            };
+            self.offset = offset.ok_or_else(|| {
+                std::io::Error::new(std::io::ErrorKind::InvalidInput, "seek to a negative or overflowing position")
+            })?;
+            Ok(self.offset)
+        }
+    }
+
+    /// Argument types `run` can take the dataset as
+    #[doc(hidden)]
+    pub trait FromDataset {
+        /// SAFETY: only call once.
+        unsafe fn from_dataset() -> Self;
+    }
+
+    impl FromDataset for &'static [u8] {
+        unsafe fn from_dataset() -> Self {
+            Dataset::at(0).load()
+        }
+    }
+
+    impl FromDataset for Dataset {
+        unsafe fn from_dataset() -> Self {
+            Dataset::at(0)
+        }
     }
 
     #[doc(hidden)]
@@ -107,7 +282,10 @@ pub mod reef {
 
     pub mod prelude {
         // Reef
-        pub use super::{reef_log, reef_progress, reef_sleep, JsonOutput, ReefOutput};
+        pub use super::{
+            reef_checkpoint, reef_defer_sync, reef_log, reef_progress, reef_random_fill, reef_sleep, reef_time_cycles,
+            reef_time_wall, Dataset, JsonOutput, ReefOutput,
+        };
 
         // Dynamic borrow checking
         pub use std::cell::{Cell, RefCell};
@@ -158,10 +336,11 @@ pub extern "C" fn reef_main() {
     std::panic::set_hook(Box::new(|info| {
         reef::reef_log(&format!("PANIC: {}", info.to_string()));
     }));
-    let dataset = unsafe { reef::_get_dataset() };
+    // The first dataset is copied into memory or read on demand, depending on the argument of `run`
+    let dataset = unsafe { reef::FromDataset::from_dataset() };
 
     // Run user code
-    let res: ReefOutput = input::run(&dataset).into();
+    let res: ReefOutput = input::run(dataset).into();
 
     // Return result
     unsafe { _set_result(res.content_type, &res.data) }
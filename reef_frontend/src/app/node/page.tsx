@@ -17,7 +17,10 @@ import { ILogEntry, ILogKind } from '@/types/log';
 
 import init, {
   get_connect_path,
+  get_defer_sync_max_millis,
   reset_node,
+  set_record_host_calls,
+  get_recording,
   init_node,
   run_node,
   serialize_state,
@@ -27,6 +30,8 @@ import init, {
   serialize_job_result,
   NodeMessageKind,
   NodeMessage,
+  FailureKind,
+  JobError,
 } from '@/lib/node_web_generated/reef_node_web';
 
 const STATE_SYNC_MILLIS = 1337;
@@ -36,6 +41,16 @@ interface NodeState {
   jobId?: string;
   progress: number;
   logs: ILogEntry[];
+  // Base name of the recording of the last job started with recording enabled.
+  recordingName?: string;
+}
+
+// Recording of the host calls of a job, for the replay of the Node Native.
+interface Recording {
+  // Named `<job>-<millis>`, as expected by the replay.
+  name: string;
+  // State the job was resumed from, empty if it was started from the beginning.
+  state: Uint8Array;
 }
 
 export default function Page() {
@@ -61,6 +76,12 @@ export default function Page() {
   };
 
   const [url, setUrl] = useState<string>('');
+  const [recordEnabled, setRecordEnabled] = useState<boolean>(false);
+
+  const toggleRecording = () => {
+    recordHostCalls = !recordEnabled;
+    setRecordEnabled(recordHostCalls);
+  };
 
   /* eslint-disable react-hooks/exhaustive-deps */
   useEffect(() => {
@@ -214,10 +235,21 @@ export default function Page() {
             })()}
           </div>
 
-          <div className="mt-4">
+          <div className="mt-4 flex flex-wrap gap-2">
             <Button variant={'destructive'} onClick={closeNode}>
               Disconnect
             </Button>
+            <Button
+              variant={recordEnabled ? 'default' : 'outline'}
+              onClick={toggleRecording}
+            >
+              {recordEnabled ? 'Recording Host Calls' : 'Record Host Calls'}
+            </Button>
+            {nodeState.recordingName ? (
+              <Button variant={'outline'} onClick={downloadRecording}>
+                Download Recording
+              </Button>
+            ) : null}
           </div>
         </CardContent>
       </Card>
@@ -240,6 +272,7 @@ export default function Page() {
               job={{
                 id: nodeState.jobId,
                 datasetId: '',
+                datasets: [],
                 wasmId: '',
                 name: '',
                 submitted: '',
@@ -258,6 +291,30 @@ export default function Page() {
 
 let ws: WebSocket | undefined;
 let wasmInit = false;
+// Applies to jobs started afterwards.
+let recordHostCalls = false;
+let lastRecording: Recording | undefined;
+
+function downloadRecording() {
+  if (!lastRecording) return;
+
+  downloadFile(`${lastRecording.name}.calls`, get_recording());
+  // The replay has to start from the same state as the job.
+  if (lastRecording.state.length > 0) {
+    downloadFile(`${lastRecording.name}.state`, lastRecording.state);
+  }
+}
+
+function downloadFile(name: string, data: Uint8Array) {
+  const url = URL.createObjectURL(
+    new Blob([data], { type: 'application/binary' })
+  );
+  const link = document.createElement('a');
+  link.href = url;
+  link.download = name;
+  link.click();
+  setTimeout(() => URL.revokeObjectURL(url), 0);
+}
 
 async function runNode(
   setNodeState: Dispatch<SetStateAction<NodeState | undefined>>,
@@ -344,6 +401,9 @@ async function runNode(
     logs: [] as ILogEntry[],
     logsFlush: [] as string[],
     lastSync: 0,
+    // Sync control requested by the job, see `RunResult`.
+    checkpoint: false,
+    syncDeferred: false,
   };
 
   const updateUi = () => {
@@ -352,6 +412,7 @@ async function runNode(
       jobId: internalState.jobId,
       progress: internalState.progress,
       logs: internalState.logs,
+      recordingName: lastRecording?.name,
     });
   };
   updateUi();
@@ -363,6 +424,8 @@ async function runNode(
     internalState.logs = [];
     internalState.logsFlush = [];
     internalState.lastSync = 0;
+    internalState.checkpoint = false;
+    internalState.syncDeferred = false;
   };
   const enc = new TextEncoder();
 
@@ -372,6 +435,7 @@ async function runNode(
     if (!ws) break;
 
     let errorMessage: string | undefined;
+    let failureKind = FailureKind.Internal;
 
     // read message from WebSocket
     let message = messageQueue.shift();
@@ -387,17 +451,22 @@ async function runNode(
           'font-weight:bold;'
         );
 
-        // fetch dataset
-        let res = await fetch(
-          `/api/dataset/${message.start_job_data.dataset_id}`
+        // fetch datasets
+        let datasets = await Promise.all(
+          message.start_job_data.dataset_ids.map(async (datasetId: string) => {
+            let res = await fetch(`/api/dataset/${datasetId}`);
+            return new Uint8Array(await res.arrayBuffer());
+          })
         );
-        let dataset = new Uint8Array(await res.arrayBuffer());
 
         try {
+          set_record_host_calls(recordHostCalls);
           init_node(
+            message.start_job_data.job_id,
             message.start_job_data.program_byte_code,
             message.start_job_data.interpreter_state,
-            dataset,
+            message.start_job_data.dataset_names,
+            datasets,
             (log_message: string) => {
               if (internalState.logsFlush.length >= 0x400 - 1) {
                 throw 'aborting job due to excessive logs';
@@ -429,9 +498,20 @@ async function runNode(
           }
 
           internalState.jobId = message.start_job_data.job_id;
+          if (recordHostCalls) {
+            lastRecording = {
+              name: `${internalState.jobId}-${Date.now()}`,
+              state: message.start_job_data.interpreter_state,
+            };
+          }
         } catch (e: any) {
           console.log('Error starting:', e);
-          errorMessage = e;
+          if (e instanceof JobError) {
+            errorMessage = e.message;
+            failureKind = e.failure_kind;
+          } else {
+            errorMessage = e;
+          }
         }
 
         updateUi();
@@ -445,6 +525,7 @@ async function runNode(
         }
 
         errorMessage = 'Job aborted';
+        failureKind = FailureKind.Aborted;
       }
     }
 
@@ -452,11 +533,18 @@ async function runNode(
 
     // Only run if job is running
     if (internalState.jobId) {
-      // State sync
-      if (internalState.lastSync + STATE_SYNC_MILLIS < Date.now()) {
-        let interpreterState = serialize_state();
-
-        const stream = new Blob([interpreterState])
+      // State sync, the job can ask for one right away or defer them for a limited time
+      let now = Date.now();
+      let syncDue = internalState.lastSync + STATE_SYNC_MILLIS < now;
+      let deferMax = STATE_SYNC_MILLIS + get_defer_sync_max_millis();
+      let deferExpired = internalState.lastSync + deferMax < now;
+      if (
+        internalState.checkpoint ||
+        (syncDue && (!internalState.syncDeferred || deferExpired))
+      ) {
+        let stateSync = serialize_state();
+
+        const stream = new Blob([stateSync.state])
           .stream()
           .pipeThrough(new CompressionStream('gzip'));
         const chunks = [];
@@ -476,13 +564,17 @@ async function runNode(
           serialize_job_state_sync(
             internalState.progress,
             compressedState,
-            internalState.logsFlush
+            internalState.logsFlush,
+            stateSync.cycles,
+            stateSync.state_hash,
+            stateSync.wall_clock
           )
         );
 
         internalState.logsFlush = [];
 
         internalState.lastSync = Date.now();
+        internalState.checkpoint = false;
       }
 
       // actually execute Wasm
@@ -499,7 +591,10 @@ async function runNode(
             serialize_job_state_sync(
               1,
               new Uint8Array(),
-              internalState.logsFlush
+              internalState.logsFlush,
+              BigInt(0),
+              new Uint8Array(),
+              new BigUint64Array()
             )
           );
 
@@ -509,13 +604,18 @@ async function runNode(
             result.job_output.content_type > 3
           ) {
             errorMessage = 'Invalid job output content type';
+            failureKind = FailureKind.HostLimit;
             throw 'invalid content type';
           }
           ws.send(
             serialize_job_result(
               true,
               result.job_output.data,
-              result.job_output.content_type
+              result.job_output.content_type,
+              FailureKind.None,
+              result.job_output.cycles,
+              result.job_output.state_hash,
+              result.job_output.wall_clock
             )
           );
 
@@ -527,14 +627,31 @@ async function runNode(
           reset();
         } else {
           sleepDuration = result.sleep_for ?? 0;
+          internalState.checkpoint = result.checkpoint;
+          internalState.syncDeferred = result.sync_deferred;
         }
       } catch (e: any) {
         console.log('Error executing:', e);
-        errorMessage = errorMessage ?? e;
+        if (!errorMessage && e instanceof JobError) {
+          errorMessage = e.message;
+          failureKind = e.failure_kind;
+        } else {
+          errorMessage = errorMessage ?? e;
+        }
       }
 
       if (errorMessage) {
-        ws.send(serialize_job_result(false, enc.encode(errorMessage), 2));
+        ws.send(
+          serialize_job_result(
+            false,
+            enc.encode(errorMessage),
+            2,
+            failureKind,
+            BigInt(0),
+            new Uint8Array(),
+            new BigUint64Array()
+          )
+        );
 
         console.log(
           `%c==> Job ${internalState.jobId} has has failed.`,
@@ -10,6 +10,13 @@ const mainSection: DocSection = {
         'As arguments you are passed a pointer to the specified dataset and the length of the dataset.',
       ],
     },
+    {
+      signature: 'void run_streaming(uint64_t dataset_len);',
+      description: [
+        'Alternative entry function for datasets larger than memory, define it instead of `run`.',
+        'The dataset is not copied into memory, you are only passed its length. Read it in windows with `reef_dataset_read` and index 0.',
+      ],
+    },
   ],
 };
 
@@ -31,6 +38,64 @@ const reefSection: DocSection = {
         'As an argument you have to pass the time in seconds as a float.',
       ],
     },
+    {
+      signature: 'void reef_checkpoint();',
+      description: [
+        'Syncs the state of the job right away, a good moment is after freeing large buffers because the state is small then.',
+      ],
+    },
+    {
+      signature: 'void reef_defer_sync(int defer);',
+      description: [
+        'Defers syncs of the state of the job while enabled, for example during critical sections.',
+        'Syncs are deferred for at most a minute, afterwards the job is synced anyway.',
+      ],
+    },
+    {
+      signature: 'uint64_t reef_time_cycles();',
+      description: [
+        'Returns the number of instructions executed since the job was started.',
+        'This virtual clock is the same on every node, so it can be used without breaking reproducibility.',
+      ],
+    },
+    {
+      signature: 'uint64_t reef_time_millis();',
+      description: [
+        'Returns the wall-clock time in milliseconds since the Unix epoch.',
+        'Only use it for progress estimates, the result of the job must not depend on it.',
+      ],
+    },
+    {
+      signature: 'void reef_random_fill(uint8_t *ptr, size_t len);',
+      description: [
+        'Fills the buffer with random bytes.',
+        'The random bytes are the same every time the job is run, so results stay reproducible. `rand()` uses them as well.',
+      ],
+    },
+    {
+      signature: 'int32_t reef_dataset(const char *name);',
+      description: [
+        'Looks up one of the datasets of the job by its name.',
+        'Returns the index of the dataset or -1 if the job has no dataset with this name. The dataset passed to `run` has index 0.',
+      ],
+    },
+    {
+      signature: 'uint8_t *reef_dataset_load(uint32_t index);',
+      description: [
+        'Copies the dataset with the given index into memory and returns a pointer to it, its length is returned by `reef_dataset_len`.',
+        'Every dataset can only be loaded once.',
+      ],
+      example: 'uint8_t *weights = reef_dataset_load(reef_dataset("weights"));',
+    },
+    {
+      signature:
+        'size_t reef_dataset_read(uint32_t index, uint64_t offset, uint8_t *ptr, size_t len);',
+      description: [
+        'Reads `len` bytes of the dataset with the given index starting at `offset` into the buffer, instead of copying all of it into memory.',
+        'Returns the number of bytes read, which is less than `len` only at the end of the dataset.',
+      ],
+      example: 'size_t read = reef_dataset_read(0, offset, buf, sizeof(buf));',
+    },
     {
       signature: 'void reef_result_int(int value);',
       description: [
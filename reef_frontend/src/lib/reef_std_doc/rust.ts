@@ -9,6 +9,7 @@ const mainSection: DocSection = {
         'The entry function for a job written by Reef users. This function must be provided in each job submission because it will be called by wrapper code during execution on a Reef Node.',
         'As an argument you are passed s slice (pointer) to the dataset which you can safely read from.',
         'You can set the job output by returning any datastructure which can be converted to a supported output type. See `ReefOutput` for more information.',
+        'For datasets larger than memory, take a `Dataset` instead of `&[u8]` and read it with `read_at` or `reader`.',
       ],
     },
   ],
@@ -32,6 +33,63 @@ const reefSection: DocSection = {
         'As an argument you have to pass the time in seconds as a f32.',
       ],
     },
+    {
+      signature: 'pub fn reef_checkpoint() {}',
+      description: [
+        'Syncs the state of the job right away, a good moment is after freeing large buffers because the state is small then.',
+      ],
+    },
+    {
+      signature: 'pub fn reef_defer_sync(defer: bool) {}',
+      description: [
+        'Defers syncs of the state of the job while enabled, for example during critical sections.',
+        'Syncs are deferred for at most a minute, afterwards the job is synced anyway.',
+      ],
+    },
+    {
+      signature: 'pub fn reef_time_cycles() -> u64 {}',
+      description: [
+        'Returns the number of instructions executed since the job was started.',
+        'This virtual clock is the same on every node, so it can be used without breaking reproducibility.',
+      ],
+    },
+    {
+      signature: 'pub fn reef_time_wall() -> SystemTime {}',
+      description: [
+        'Returns the current wall-clock time.',
+        'Only use it for progress estimates, the result of the job must not depend on it.',
+      ],
+    },
+    {
+      signature: 'pub fn reef_random_fill(buf: &mut [u8]) {}',
+      description: [
+        'Fills the buffer with random bytes.',
+        'The random bytes are the same every time the job is run, so results stay reproducible. The `rand` crate uses them as well, for example through `rand::thread_rng()`.',
+      ],
+    },
+    {
+      signature: 'pub fn dataset(name: &str) -> Option<Dataset> {}',
+      description: [
+        'Looks up one of the datasets of the job by its name. The dataset passed to `run` is the first one, `reef::datasets()` iterates over all of them.',
+        'A `Dataset` can be read in windows with `read_at` and `reader`, or copied into memory once with `load`.',
+      ],
+      example: 'let weights: &[u8] = reef::dataset("weights").unwrap().load();',
+    },
+    {
+      signature:
+        'pub fn Dataset::read_at(&self, offset: u64, buf: &mut [u8]) -> usize {}',
+      description: [
+        'Reads the dataset starting at `offset` into the buffer, instead of copying all of it into memory.',
+        'Returns the number of bytes read, which is less than `buf.len()` only at the end of the dataset.',
+      ],
+    },
+    {
+      signature: 'pub fn Dataset::reader(&self) -> DatasetReader {}',
+      description: [
+        'Reads the dataset sequentially. `DatasetReader` implements `std::io::Read` and `std::io::Seek`, so it works with crates like `csv`.',
+      ],
+      example: 'let mut reader = csv::Reader::from_reader(dataset.reader());',
+    },
     {
       signature: 'pub fn reef_log(msg: &str) {}',
       description: [
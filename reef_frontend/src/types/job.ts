@@ -60,6 +60,13 @@ export interface IJobResult {
   created: string;
 }
 
+export interface IJobDataset {
+  // Name the program looks the dataset up by.
+  name: string;
+  // The ID of the dataset, always 64 characters long.
+  datasetId: string;
+}
+
 export interface IJob {
   // Primary key of each job. Server guarantees that this is unique.
   // SHA256 hash, always 64 characters long.
@@ -78,6 +85,8 @@ export interface IJob {
   // The ID of the attached dataset, always 64 characters long.
   // The backend guarantees that this is always valid.
   datasetId: string;
+  // Named datasets in the order the program sees them, they replace `datasetId` if there are any.
+  datasets: IJobDataset[];
   // Session ID / hash of the job's owner.
   owner: string;
 
@@ -0,0 +1,68 @@
+//! Throughput of a compute-heavy guest with and without superinstructions, and with the slot backend.
+//!
+//! Run with `cargo bench -p reef_interpreter`.
+
+#![feature(test)]
+
+extern crate test;
+
+use reef_interpreter::{exec::CallResultTyped, imports::Imports, parse_bytes, types::Backend, Instance, Module};
+use test::Bencher;
+
+const ITERATIONS: i32 = 100_000;
+
+/// Sums a xorshift sequence, a loop shaped like typical compiler output.
+const GUEST: &str = r#"
+(module
+    (func (export "main") (param $n i32) (result i32) (local $i i32) (local $acc i32) (local $x i32)
+        (local.set $x (i32.const 2463534242))
+        (block $done
+            (loop $next
+                (br_if $done (i32.ge_u (local.get $i) (local.get $n)))
+                (local.set $x (i32.xor (local.get $x) (i32.shl (local.get $x) (i32.const 13))))
+                (local.set $x (i32.xor (local.get $x) (i32.shr_u (local.get $x) (i32.const 17))))
+                (local.set $x (i32.xor (local.get $x) (i32.shl (local.get $x) (i32.const 5))))
+                (local.set $acc (i32.add (local.get $acc) (local.get $x)))
+                (local.set $i (i32.add (local.get $i) (i32.const 1)))
+                (br $next)))
+        (local.get $acc)))
+"#;
+
+fn guest(superinstructions: bool) -> Module {
+    let wasm = wat::parse_str(GUEST).expect("invalid wat");
+    let mut module = parse_bytes(&wasm).expect("failed to parse module");
+    module.set_superinstructions(superinstructions);
+    module
+}
+
+fn run(module: &Module) -> i32 {
+    let (instance, stack, _) = Instance::instantiate(module.clone(), Imports::new(), (), None).unwrap();
+    let main = instance.exported_func::<i32, i32>("main").unwrap();
+    let mut exec_handle = main.call(instance, ITERATIONS, stack).unwrap();
+
+    // Pause regularly like the nodes do
+    loop {
+        if let CallResultTyped::Done(res) = exec_handle.run(0x10000).unwrap() {
+            return res;
+        }
+    }
+}
+
+#[bench]
+fn bench_unfused(b: &mut Bencher) {
+    let module = guest(false);
+    b.iter(|| run(&module));
+}
+
+#[bench]
+fn bench_superinstructions(b: &mut Bencher) {
+    let module = guest(true);
+    b.iter(|| run(&module));
+}
+
+#[bench]
+fn bench_slots(b: &mut Bencher) {
+    let mut module = guest(true);
+    module.set_backend(Backend::Slots).unwrap();
+    b.iter(|| run(&module));
+}
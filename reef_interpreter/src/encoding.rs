@@ -0,0 +1,690 @@
+//! Binary encoding of snapshots and cached modules
+//!
+//! The format is the one of `bincode` 1 with its default options, so existing snapshots stay readable:
+//! integers and floats are little endian with a fixed size, lengths of sequences, strings and maps are `u64`,
+//! enum variants are `u32` and options are prefixed with a `u8` tag. Unlike `bincode` it works without `std`.
+
+use alloc::{string::String, string::ToString, vec::Vec};
+use core::fmt::Display;
+
+use serde::de::{self, DeserializeSeed, IntoDeserializer, Visitor};
+use serde::ser::{self, Serialize};
+
+use crate::error::{Error, Result};
+use crate::io::Write;
+
+/// Serialize `value` into `writer`
+pub(crate) fn serialize_into<W: Write, T: Serialize + ?Sized>(writer: W, value: &T) -> Result<()> {
+    value.serialize(&mut Serializer { writer })
+}
+
+/// Serialize `value` into a new buffer
+pub(crate) fn serialize<T: Serialize + ?Sized>(value: &T) -> Result<Vec<u8>> {
+    let mut bytes = Vec::new();
+    value.serialize(&mut Serializer { writer: &mut bytes })?;
+    Ok(bytes)
+}
+
+/// Deserialize a value from the start of `bytes`, trailing bytes are ignored
+pub(crate) fn deserialize<'de, T: serde::Deserialize<'de>>(bytes: &'de [u8]) -> Result<T> {
+    T::deserialize(&mut Deserializer { reader: bytes })
+}
+
+/// Deserialize a value from a reader
+#[cfg(feature = "std")]
+pub(crate) fn deserialize_from<R: std::io::Read, T: serde::de::DeserializeOwned>(reader: R) -> Result<T> {
+    T::deserialize(&mut Deserializer { reader: IoReader(reader) })
+}
+
+/// Serialize `value` into `writer`, compressed with gzip
+#[cfg(feature = "std")]
+pub(crate) fn serialize_gzip<W: Write, T: Serialize + ?Sized>(writer: W, value: &T) -> Result<()> {
+    let mut encoder = flate2::write::GzEncoder::new(crate::io::StdWriter(writer), flate2::Compression::best());
+    serialize_into(&mut encoder, value)?;
+    encoder.try_finish()?;
+    Ok(())
+}
+
+/// Serialize `value` into `writer`, compressed with gzip
+#[cfg(not(feature = "std"))]
+pub(crate) fn serialize_gzip<W: Write, T: Serialize + ?Sized>(mut writer: W, value: &T) -> Result<()> {
+    writer.write_all(&gzip::compress(&serialize(value)?))
+}
+
+/// Deserialize a value compressed with gzip
+#[cfg(feature = "std")]
+pub(crate) fn deserialize_gzip<T: serde::de::DeserializeOwned>(bytes: &[u8]) -> Result<T> {
+    deserialize_from(flate2::read::GzDecoder::new(bytes))
+}
+
+/// Deserialize a value compressed with gzip
+#[cfg(not(feature = "std"))]
+pub(crate) fn deserialize_gzip<T: serde::de::DeserializeOwned>(bytes: &[u8]) -> Result<T> {
+    deserialize(&gzip::decompress(bytes)?)
+}
+
+/// Decompress `bytes` written by [`serialize_gzip`] into `writer`
+#[cfg(feature = "std")]
+pub(crate) fn decompress_gzip_into<W: Write>(bytes: &[u8], writer: W) -> Result<()> {
+    std::io::copy(&mut flate2::read::GzDecoder::new(bytes), &mut crate::io::StdWriter(writer))?;
+    Ok(())
+}
+
+/// Decompress `bytes` written by [`serialize_gzip`] into `writer`
+#[cfg(not(feature = "std"))]
+pub(crate) fn decompress_gzip_into<W: Write>(bytes: &[u8], mut writer: W) -> Result<()> {
+    writer.write_all(&gzip::decompress(bytes)?)
+}
+
+/// Gzip framing around the deflate streams of `miniz_oxide`, as `flate2` needs `std`
+#[cfg(not(feature = "std"))]
+mod gzip {
+    use alloc::{string::ToString, vec::Vec};
+
+    use crate::error::{Error, Result};
+
+    const HEADER: [u8; 10] = [0x1f, 0x8b, 8, 0, 0, 0, 0, 0, 2, 0xff];
+    const FHCRC: u8 = 1 << 1;
+    const FEXTRA: u8 = 1 << 2;
+    const FNAME: u8 = 1 << 3;
+    const FCOMMENT: u8 = 1 << 4;
+
+    pub(super) fn compress(data: &[u8]) -> Vec<u8> {
+        let mut bytes = HEADER.to_vec();
+        bytes.extend(miniz_oxide::deflate::compress_to_vec(data, 9));
+        bytes.extend(crc32(data).to_le_bytes());
+        bytes.extend((data.len() as u32).to_le_bytes());
+        bytes
+    }
+
+    pub(super) fn decompress(bytes: &[u8]) -> Result<Vec<u8>> {
+        let invalid = || Error::Encoding("invalid gzip data".to_string());
+
+        if bytes.len() < HEADER.len() + 8 || bytes[..3] != HEADER[..3] {
+            return Err(invalid());
+        }
+        let flags = bytes[3];
+        let (mut body, trailer) = bytes[HEADER.len()..].split_at(bytes.len() - HEADER.len() - 8);
+
+        if flags & FEXTRA != 0 {
+            let len = u16::from_le_bytes([*body.first().ok_or_else(invalid)?, *body.get(1).ok_or_else(invalid)?]);
+            body = body.get(2 + len as usize..).ok_or_else(invalid)?;
+        }
+        for flag in [FNAME, FCOMMENT] {
+            if flags & flag != 0 {
+                let end = body.iter().position(|&b| b == 0).ok_or_else(invalid)?;
+                body = &body[end + 1..];
+            }
+        }
+        if flags & FHCRC != 0 {
+            body = body.get(2..).ok_or_else(invalid)?;
+        }
+
+        let data = miniz_oxide::inflate::decompress_to_vec(body).map_err(|_| invalid())?;
+        let (crc, len) = trailer.split_at(4);
+        if crc != crc32(&data).to_le_bytes() || len != (data.len() as u32).to_le_bytes() {
+            return Err(invalid());
+        }
+        Ok(data)
+    }
+
+    fn crc32(data: &[u8]) -> u32 {
+        const TABLE: [u32; 256] = {
+            let mut table = [0; 256];
+            let mut i = 0;
+            while i < 256 {
+                let mut crc = i as u32;
+                let mut bit = 0;
+                while bit < 8 {
+                    crc = if crc & 1 != 0 { 0xedb8_8320 ^ (crc >> 1) } else { crc >> 1 };
+                    bit += 1;
+                }
+                table[i] = crc;
+                i += 1;
+            }
+            table
+        };
+
+        !data.iter().fold(!0, |crc, &b| TABLE[((crc ^ b as u32) & 0xff) as usize] ^ (crc >> 8))
+    }
+}
+
+impl ser::Error for Error {
+    fn custom<T: Display>(msg: T) -> Self {
+        Error::Encoding(msg.to_string())
+    }
+}
+
+impl de::Error for Error {
+    fn custom<T: Display>(msg: T) -> Self {
+        Error::Encoding(msg.to_string())
+    }
+}
+
+fn unsupported(what: &str) -> Error {
+    Error::Encoding(alloc::format!("{what} is not supported"))
+}
+
+struct Serializer<W> {
+    writer: W,
+}
+
+impl<W: Write> Serializer<W> {
+    fn write_len(&mut self, len: Option<usize>) -> Result<()> {
+        let len = len.ok_or_else(|| unsupported("sequence without a length"))?;
+        self.writer.write_all(&(len as u64).to_le_bytes())
+    }
+}
+
+macro_rules! serialize_le {
+    ($($name:ident($ty:ty)),*) => {
+        $(
+            fn $name(self, v: $ty) -> Result<()> {
+                self.writer.write_all(&v.to_le_bytes())
+            }
+        )*
+    };
+}
+
+impl<'a, W: Write> ser::Serializer for &'a mut Serializer<W> {
+    type Ok = ();
+    type Error = Error;
+    type SerializeSeq = Self;
+    type SerializeTuple = Self;
+    type SerializeTupleStruct = Self;
+    type SerializeTupleVariant = Self;
+    type SerializeMap = Self;
+    type SerializeStruct = Self;
+    type SerializeStructVariant = Self;
+
+    serialize_le!(
+        serialize_i8(i8),
+        serialize_i16(i16),
+        serialize_i32(i32),
+        serialize_i64(i64),
+        serialize_i128(i128),
+        serialize_u8(u8),
+        serialize_u16(u16),
+        serialize_u32(u32),
+        serialize_u64(u64),
+        serialize_u128(u128),
+        serialize_f32(f32),
+        serialize_f64(f64)
+    );
+
+    fn serialize_bool(self, v: bool) -> Result<()> {
+        self.writer.write_all(&[v as u8])
+    }
+
+    fn serialize_char(self, v: char) -> Result<()> {
+        self.writer.write_all(v.encode_utf8(&mut [0; 4]).as_bytes())
+    }
+
+    fn serialize_str(self, v: &str) -> Result<()> {
+        self.serialize_bytes(v.as_bytes())
+    }
+
+    fn serialize_bytes(self, v: &[u8]) -> Result<()> {
+        self.write_len(Some(v.len()))?;
+        self.writer.write_all(v)
+    }
+
+    fn serialize_none(self) -> Result<()> {
+        self.writer.write_all(&[0])
+    }
+
+    fn serialize_some<T: Serialize + ?Sized>(self, value: &T) -> Result<()> {
+        self.writer.write_all(&[1])?;
+        value.serialize(self)
+    }
+
+    fn serialize_unit(self) -> Result<()> {
+        Ok(())
+    }
+
+    fn serialize_unit_struct(self, _name: &'static str) -> Result<()> {
+        Ok(())
+    }
+
+    fn serialize_unit_variant(self, _name: &'static str, variant_index: u32, _variant: &'static str) -> Result<()> {
+        self.serialize_u32(variant_index)
+    }
+
+    fn serialize_newtype_struct<T: Serialize + ?Sized>(self, _name: &'static str, value: &T) -> Result<()> {
+        value.serialize(self)
+    }
+
+    fn serialize_newtype_variant<T: Serialize + ?Sized>(
+        self,
+        _name: &'static str,
+        variant_index: u32,
+        _variant: &'static str,
+        value: &T,
+    ) -> Result<()> {
+        self.serialize_u32(variant_index)?;
+        value.serialize(self)
+    }
+
+    fn serialize_seq(self, len: Option<usize>) -> Result<Self> {
+        self.write_len(len)?;
+        Ok(self)
+    }
+
+    fn serialize_tuple(self, _len: usize) -> Result<Self> {
+        Ok(self)
+    }
+
+    fn serialize_tuple_struct(self, _name: &'static str, _len: usize) -> Result<Self> {
+        Ok(self)
+    }
+
+    fn serialize_tuple_variant(
+        self,
+        _name: &'static str,
+        variant_index: u32,
+        _variant: &'static str,
+        _len: usize,
+    ) -> Result<Self> {
+        self.serialize_u32(variant_index)?;
+        Ok(self)
+    }
+
+    fn serialize_map(self, len: Option<usize>) -> Result<Self> {
+        self.write_len(len)?;
+        Ok(self)
+    }
+
+    fn serialize_struct(self, _name: &'static str, _len: usize) -> Result<Self> {
+        Ok(self)
+    }
+
+    fn serialize_struct_variant(
+        self,
+        _name: &'static str,
+        variant_index: u32,
+        _variant: &'static str,
+        _len: usize,
+    ) -> Result<Self> {
+        self.serialize_u32(variant_index)?;
+        Ok(self)
+    }
+
+    fn is_human_readable(&self) -> bool {
+        false
+    }
+}
+
+macro_rules! serialize_compound {
+    ($($trait:ident { $($fn:ident$(($key:ident))?),* }),*) => {
+        $(
+            impl<'a, W: Write> ser::$trait for &'a mut Serializer<W> {
+                type Ok = ();
+                type Error = Error;
+
+                $(
+                    fn $fn<T: Serialize + ?Sized>(&mut self, $($key: &'static str,)? value: &T) -> Result<()> {
+                        $(let _ = $key;)?
+                        value.serialize(&mut **self)
+                    }
+                )*
+
+                fn end(self) -> Result<()> {
+                    Ok(())
+                }
+            }
+        )*
+    };
+}
+
+serialize_compound!(
+    SerializeSeq { serialize_element },
+    SerializeTuple { serialize_element },
+    SerializeTupleStruct { serialize_field },
+    SerializeTupleVariant { serialize_field },
+    SerializeMap { serialize_key, serialize_value },
+    SerializeStruct { serialize_field(key) },
+    SerializeStructVariant { serialize_field(key) }
+);
+
+/// Source of the bytes to deserialize
+trait Read {
+    fn read_exact(&mut self, buf: &mut [u8]) -> Result<()>;
+
+    /// Read `len` bytes, without trusting `len` for the allocation
+    fn read_vec(&mut self, len: usize) -> Result<Vec<u8>> {
+        const CHUNK: usize = 0x10000;
+
+        let mut bytes = Vec::with_capacity(len.min(CHUNK));
+        while bytes.len() < len {
+            let start = bytes.len();
+            bytes.resize(start + (len - start).min(CHUNK), 0);
+            self.read_exact(&mut bytes[start..])?;
+        }
+        Ok(bytes)
+    }
+}
+
+fn unexpected_end() -> Error {
+    Error::Encoding("unexpected end of data".to_string())
+}
+
+impl Read for &[u8] {
+    fn read_exact(&mut self, buf: &mut [u8]) -> Result<()> {
+        let (bytes, rest) = self.split_at_checked(buf.len()).ok_or_else(unexpected_end)?;
+        buf.copy_from_slice(bytes);
+        *self = rest;
+        Ok(())
+    }
+
+    fn read_vec(&mut self, len: usize) -> Result<Vec<u8>> {
+        let (bytes, rest) = self.split_at_checked(len).ok_or_else(unexpected_end)?;
+        *self = rest;
+        Ok(bytes.to_vec())
+    }
+}
+
+#[cfg(feature = "std")]
+struct IoReader<R>(R);
+
+#[cfg(feature = "std")]
+impl<R: std::io::Read> Read for IoReader<R> {
+    fn read_exact(&mut self, buf: &mut [u8]) -> Result<()> {
+        self.0.read_exact(buf).map_err(|err| match err.kind() {
+            std::io::ErrorKind::UnexpectedEof => unexpected_end(),
+            _ => Error::Io(err),
+        })
+    }
+}
+
+struct Deserializer<R> {
+    reader: R,
+}
+
+macro_rules! deserialize_le {
+    ($($name:ident($ty:ty) => $visit:ident),*) => {
+        $(
+            fn $name<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+                let mut bytes = [0; core::mem::size_of::<$ty>()];
+                self.reader.read_exact(&mut bytes)?;
+                visitor.$visit(<$ty>::from_le_bytes(bytes))
+            }
+        )*
+    };
+}
+
+impl<R: Read> Deserializer<R> {
+    fn read_u8(&mut self) -> Result<u8> {
+        let mut byte = [0];
+        self.reader.read_exact(&mut byte)?;
+        Ok(byte[0])
+    }
+
+    fn read_u32(&mut self) -> Result<u32> {
+        let mut bytes = [0; 4];
+        self.reader.read_exact(&mut bytes)?;
+        Ok(u32::from_le_bytes(bytes))
+    }
+
+    fn read_len(&mut self) -> Result<usize> {
+        let mut bytes = [0; 8];
+        self.reader.read_exact(&mut bytes)?;
+        usize::try_from(u64::from_le_bytes(bytes)).map_err(|_| Error::Encoding("length too large".to_string()))
+    }
+
+    fn read_string(&mut self) -> Result<String> {
+        let len = self.read_len()?;
+        String::from_utf8(self.reader.read_vec(len)?).map_err(|err| Error::Encoding(err.to_string()))
+    }
+}
+
+impl<'de, 'a, R: Read> de::Deserializer<'de> for &'a mut Deserializer<R> {
+    type Error = Error;
+
+    deserialize_le!(
+        deserialize_i8(i8) => visit_i8, deserialize_i16(i16) => visit_i16, deserialize_i32(i32) => visit_i32,
+        deserialize_i64(i64) => visit_i64, deserialize_i128(i128) => visit_i128,
+        deserialize_u8(u8) => visit_u8, deserialize_u16(u16) => visit_u16, deserialize_u32(u32) => visit_u32,
+        deserialize_u64(u64) => visit_u64, deserialize_u128(u128) => visit_u128,
+        deserialize_f32(f32) => visit_f32, deserialize_f64(f64) => visit_f64
+    );
+
+    fn deserialize_any<V: Visitor<'de>>(self, _visitor: V) -> Result<V::Value> {
+        Err(unsupported("deserializing without a type"))
+    }
+
+    fn deserialize_bool<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        match self.read_u8()? {
+            0 => visitor.visit_bool(false),
+            1 => visitor.visit_bool(true),
+            tag => Err(Error::Encoding(alloc::format!("invalid bool {tag}"))),
+        }
+    }
+
+    fn deserialize_char<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        let mut bytes = [0; 4];
+        bytes[0] = self.read_u8()?;
+        let len = match bytes[0] {
+            0x00..=0x7f => 1,
+            0xc0..=0xdf => 2,
+            0xe0..=0xef => 3,
+            _ => 4,
+        };
+        self.reader.read_exact(&mut bytes[1..len])?;
+
+        let c = core::str::from_utf8(&bytes[..len]).ok().and_then(|s| s.chars().next());
+        visitor.visit_char(c.ok_or_else(|| Error::Encoding("invalid char".to_string()))?)
+    }
+
+    fn deserialize_str<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        visitor.visit_string(self.read_string()?)
+    }
+
+    fn deserialize_string<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        visitor.visit_string(self.read_string()?)
+    }
+
+    fn deserialize_bytes<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        let len = self.read_len()?;
+        visitor.visit_byte_buf(self.reader.read_vec(len)?)
+    }
+
+    fn deserialize_byte_buf<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        self.deserialize_bytes(visitor)
+    }
+
+    fn deserialize_option<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        match self.read_u8()? {
+            0 => visitor.visit_none(),
+            1 => visitor.visit_some(self),
+            tag => Err(Error::Encoding(alloc::format!("invalid option tag {tag}"))),
+        }
+    }
+
+    fn deserialize_unit<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        visitor.visit_unit()
+    }
+
+    fn deserialize_unit_struct<V: Visitor<'de>>(self, _name: &'static str, visitor: V) -> Result<V::Value> {
+        visitor.visit_unit()
+    }
+
+    fn deserialize_newtype_struct<V: Visitor<'de>>(self, _name: &'static str, visitor: V) -> Result<V::Value> {
+        visitor.visit_newtype_struct(self)
+    }
+
+    fn deserialize_seq<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        let len = self.read_len()?;
+        visitor.visit_seq(Access { de: self, len })
+    }
+
+    fn deserialize_tuple<V: Visitor<'de>>(self, len: usize, visitor: V) -> Result<V::Value> {
+        visitor.visit_seq(Access { de: self, len })
+    }
+
+    fn deserialize_tuple_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        len: usize,
+        visitor: V,
+    ) -> Result<V::Value> {
+        visitor.visit_seq(Access { de: self, len })
+    }
+
+    fn deserialize_map<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        let len = self.read_len()?;
+        visitor.visit_map(Access { de: self, len })
+    }
+
+    fn deserialize_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        fields: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value> {
+        visitor.visit_seq(Access { de: self, len: fields.len() })
+    }
+
+    fn deserialize_enum<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        _variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value> {
+        visitor.visit_enum(self)
+    }
+
+    fn deserialize_identifier<V: Visitor<'de>>(self, _visitor: V) -> Result<V::Value> {
+        Err(unsupported("deserializing identifiers"))
+    }
+
+    fn deserialize_ignored_any<V: Visitor<'de>>(self, _visitor: V) -> Result<V::Value> {
+        Err(unsupported("skipping values"))
+    }
+
+    fn is_human_readable(&self) -> bool {
+        false
+    }
+}
+
+/// Elements of a sequence, tuple, struct or map with a known length
+struct Access<'a, R> {
+    de: &'a mut Deserializer<R>,
+    len: usize,
+}
+
+impl<'de, 'a, R: Read> de::SeqAccess<'de> for Access<'a, R> {
+    type Error = Error;
+
+    fn next_element_seed<T: DeserializeSeed<'de>>(&mut self, seed: T) -> Result<Option<T::Value>> {
+        if self.len == 0 {
+            return Ok(None);
+        }
+        self.len -= 1;
+        seed.deserialize(&mut *self.de).map(Some)
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        Some(self.len)
+    }
+}
+
+impl<'de, 'a, R: Read> de::MapAccess<'de> for Access<'a, R> {
+    type Error = Error;
+
+    fn next_key_seed<K: DeserializeSeed<'de>>(&mut self, seed: K) -> Result<Option<K::Value>> {
+        if self.len == 0 {
+            return Ok(None);
+        }
+        self.len -= 1;
+        seed.deserialize(&mut *self.de).map(Some)
+    }
+
+    fn next_value_seed<V: DeserializeSeed<'de>>(&mut self, seed: V) -> Result<V::Value> {
+        seed.deserialize(&mut *self.de)
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        Some(self.len)
+    }
+}
+
+impl<'de, 'a, R: Read> de::EnumAccess<'de> for &'a mut Deserializer<R> {
+    type Error = Error;
+    type Variant = Self;
+
+    fn variant_seed<V: DeserializeSeed<'de>>(self, seed: V) -> Result<(V::Value, Self)> {
+        let variant_index = self.read_u32()?;
+        let value = seed.deserialize(IntoDeserializer::<Error>::into_deserializer(variant_index))?;
+        Ok((value, self))
+    }
+}
+
+impl<'de, 'a, R: Read> de::VariantAccess<'de> for &'a mut Deserializer<R> {
+    type Error = Error;
+
+    fn unit_variant(self) -> Result<()> {
+        Ok(())
+    }
+
+    fn newtype_variant_seed<T: DeserializeSeed<'de>>(self, seed: T) -> Result<T::Value> {
+        seed.deserialize(self)
+    }
+
+    fn tuple_variant<V: Visitor<'de>>(self, len: usize, visitor: V) -> Result<V::Value> {
+        visitor.visit_seq(Access { de: self, len })
+    }
+
+    fn struct_variant<V: Visitor<'de>>(self, fields: &'static [&'static str], visitor: V) -> Result<V::Value> {
+        visitor.visit_seq(Access { de: self, len: fields.len() })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::{boxed::Box, collections::BTreeMap};
+
+    #[derive(Debug, PartialEq, serde::Serialize, serde::Deserialize)]
+    enum Kind {
+        Unit,
+        Newtype(i8),
+        Tuple(u16, f32),
+        Struct { name: Box<str>, tag: char },
+    }
+
+    #[derive(Debug, PartialEq, serde::Serialize, serde::Deserialize)]
+    struct Sample {
+        flag: bool,
+        values: Vec<i64>,
+        kinds: Box<[Kind]>,
+        map: BTreeMap<u32, Option<String>>,
+        pair: (u128, f64),
+        bytes: Vec<u8>,
+    }
+
+    #[test]
+    fn test_bincode_compatible() {
+        let sample = Sample {
+            flag: true,
+            values: vec![-1, 0, i64::MAX],
+            kinds: vec![
+                Kind::Unit,
+                Kind::Newtype(-7),
+                Kind::Tuple(3, 1.5),
+                Kind::Struct { name: "reef".into(), tag: 'ß' },
+            ]
+            .into(),
+            map: [(1, None), (2, Some("two".into()))].into(),
+            pair: (u128::MAX - 1, -0.25),
+            bytes: vec![0, 1, 2, 255],
+        };
+
+        let bytes = serialize(&sample).unwrap();
+        assert_eq!(bytes, bincode::serialize(&sample).unwrap());
+        assert_eq!(deserialize::<Sample>(&bytes).unwrap(), sample);
+        assert_eq!(deserialize_from::<_, Sample>(&bytes[..]).unwrap(), sample);
+
+        assert!(matches!(deserialize::<Sample>(&bytes[..bytes.len() - 1]), Err(Error::Encoding(_))));
+        assert!(matches!(deserialize::<Vec<u8>>(&u64::MAX.to_le_bytes()), Err(Error::Encoding(_))));
+    }
+}
@@ -1,6 +1,9 @@
 //! Errors for this crate
 
-use alloc::string::{String, ToString};
+use alloc::{
+    boxed::Box,
+    string::{String, ToString},
+};
 use core::fmt::Display;
 
 use crate::parser::error::ParseError;
@@ -21,6 +24,11 @@ pub enum Error {
     /// An unknown error occurred
     Other(String),
 
+    /// An error returned by a host function
+    ///
+    /// Use [`Error::downcast_host_ref`] to get the original error back.
+    Host(Box<dyn core::error::Error + Send + Sync>),
+
     /// A function did not return a value
     FuncDidNotReturn,
 
@@ -42,14 +50,31 @@ pub enum Error {
     /// An error returned to pause execution
     PauseExecution,
 
+    /// An error returned by a host function to suspend the call until its results are supplied
+    ///
+    /// See [`CallResult::HostPending`](crate::exec::CallResult::HostPending).
+    SuspendHostCall,
+
     /// An I/O error occurred
+    #[cfg(feature = "std")]
     Io(std::io::Error),
 
     /// A parsing error occurred
     ParseError(ParseError),
 
-    /// Bincode error
-    Bincode(bincode::Error),
+    /// A snapshot or cached module could not be encoded or decoded
+    Encoding(String),
+
+    /// A cached module was written by another version or for another program
+    ///
+    /// See [`Module::from_cache`](crate::Module::from_cache).
+    InvalidCache(String),
+
+    /// A host function failed with this message in the recording being replayed
+    ///
+    /// Displays as the recorded message, see [`Replayer`](crate::record::Replayer).
+    #[cfg(feature = "std")]
+    Replayed(String),
 }
 
 #[derive(Debug)]
@@ -70,6 +95,9 @@ pub enum LinkingError {
         /// The import name
         name: String,
     },
+
+    /// A linked instance refers to an item at an address it does not have
+    InvalidAddress(u32),
 }
 
 impl LinkingError {
@@ -167,6 +195,30 @@ impl LinkingError {
         match self {
             Self::UnknownImport { .. } => "unknown import",
             Self::IncompatibleImportType { .. } => "incompatible import type",
+            Self::InvalidAddress(_) => "invalid address in linked instance",
+        }
+    }
+}
+
+impl Error {
+    /// Create an [`Error::Host`] from any error, this is meant to be returned by host functions
+    pub fn host(err: impl Into<Box<dyn core::error::Error + Send + Sync>>) -> Self {
+        Self::Host(err.into())
+    }
+
+    /// Get a reference to the error of a host function, if it is of type `E`
+    pub fn downcast_host_ref<E: core::error::Error + 'static>(&self) -> Option<&E> {
+        match self {
+            Self::Host(err) => err.downcast_ref(),
+            _ => None,
+        }
+    }
+
+    /// Take the error of a host function, if it is of type `E`
+    pub fn downcast_host<E: core::error::Error + 'static>(self) -> Result<E, Self> {
+        match self {
+            Self::Host(err) => err.downcast().map(|err| *err).map_err(Self::Host),
+            err => Err(err),
         }
     }
 }
@@ -183,6 +235,7 @@ impl From<LinkingError> for Error {
     }
 }
 
+#[cfg(feature = "std")]
 impl From<std::io::Error> for Error {
     fn from(value: std::io::Error) -> Self {
         Self::Io(value)
@@ -193,8 +246,9 @@ impl Display for Error {
     fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
         match self {
             Self::ParseError(err) => write!(f, "error parsing module: {:?}", err),
-            Self::Bincode(err) => write!(f, "bincode error: {:?}", err),
+            Self::Encoding(message) => write!(f, "encoding error: {}", message),
 
+            #[cfg(feature = "std")]
             Self::Io(err) => write!(f, "I/O error: {}", err),
 
             Self::Trap(trap) => write!(f, "trap: {}", trap),
@@ -202,12 +256,18 @@ impl Display for Error {
             Self::CallStackUnderflow => write!(f, "call stack empty"),
             Self::InvalidLabelType => write!(f, "invalid label type"),
             Self::Other(message) => write!(f, "unknown error: {}", message),
+            Self::Host(err) => write!(f, "host error: {}", err),
             Self::UnsupportedFeature(feature) => write!(f, "unsupported feature: {}", feature),
             Self::FuncDidNotReturn => write!(f, "function did not return"),
             Self::BlockStackUnderflow => write!(f, "label stack underflow"),
             Self::ValueStackUnderflow => write!(f, "value stack underflow"),
             Self::InvalidStore => write!(f, "invalid store"),
             Self::PauseExecution => write!(f, "pause execution"),
+            Self::SuspendHostCall => write!(f, "suspend host call"),
+            Self::InvalidCache(message) => write!(f, "invalid module cache: {}", message),
+
+            #[cfg(feature = "std")]
+            Self::Replayed(message) => write!(f, "{}", message),
         }
     }
 }
@@ -221,6 +281,7 @@ impl Display for LinkingError {
             Self::IncompatibleImportType { module, name } => {
                 write!(f, "incompatible import type: {}.{}", module, name)
             }
+            Self::InvalidAddress(addr) => write!(f, "invalid address in linked instance: {}", addr),
         }
     }
 }
@@ -250,7 +311,23 @@ impl Display for Trap {
     }
 }
 
-impl std::error::Error for Error {}
+impl core::error::Error for Error {
+    fn source(&self) -> Option<&(dyn core::error::Error + 'static)> {
+        match self {
+            Self::Trap(trap) => Some(trap),
+            Self::Linker(err) => Some(err),
+            Self::Host(err) => Some(err.as_ref()),
+            #[cfg(feature = "std")]
+            Self::Io(err) => Some(err),
+            Self::ParseError(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+impl core::error::Error for Trap {}
+
+impl core::error::Error for LinkingError {}
 
 impl From<ParseError> for Error {
     fn from(value: ParseError) -> Self {
@@ -258,11 +335,33 @@ impl From<ParseError> for Error {
     }
 }
 
-impl From<bincode::Error> for Error {
-    fn from(value: bincode::Error) -> Self {
-        Self::Bincode(value)
+/// A wrapper around [`core::result::Result`] for this crates operations
+pub type Result<T, E = Error> = core::result::Result<T, E>;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Debug, PartialEq)]
+    struct LimitExceeded(u32);
+
+    impl Display for LimitExceeded {
+        fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+            write!(f, "limit {} exceeded", self.0)
+        }
     }
-}
 
-/// A wrapper around [`core::result::Result`] for this crates operations
-pub type Result<T, E = Error> = std::result::Result<T, E>;
+    impl std::error::Error for LimitExceeded {}
+
+    #[test]
+    fn test_downcast_host_error() {
+        let err = Error::host(LimitExceeded(3));
+        assert_eq!(err.to_string(), "host error: limit 3 exceeded");
+        assert_eq!(err.downcast_host_ref::<LimitExceeded>(), Some(&LimitExceeded(3)));
+        assert!(err.downcast_host_ref::<Trap>().is_none());
+
+        let err = err.downcast_host::<std::io::Error>().unwrap_err();
+        assert_eq!(err.downcast_host::<LimitExceeded>().unwrap(), LimitExceeded(3));
+        assert!(Error::Trap(Trap::Unreachable).downcast_host::<LimitExceeded>().is_err());
+    }
+}
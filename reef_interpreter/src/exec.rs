@@ -1,13 +1,21 @@
 //! Modules for types related to controlling the execution of Wasm
 
-use alloc::vec::Vec;
-use std::io::Write;
+use alloc::{format, vec::Vec};
 
-use crate::error::Result;
+use sha2::{Digest, Sha256};
+
+use crate::encoding;
+use crate::error::{Error, Result};
 use crate::func::{FromWasmValueTuple, FuncHandle};
+use crate::imports::{ExternName, Local, Threading};
+use crate::instance::Instance;
+use crate::io::Write;
 use crate::runtime::{RawWasmValue, Stack};
 use crate::store::memory::MemoryInstance;
-use crate::types::value::WasmValue;
+use crate::types::{
+    value::{ValType, WasmValue},
+    FuncType,
+};
 
 /// Retuened by [`run`](ExecHandle::run) to indicate if the function finished execution with the given max_cycles
 #[derive(Debug)]
@@ -16,54 +24,202 @@ pub enum CallResult {
     Done(Vec<WasmValue>),
     /// Execution has not finished and `run` has to be called again
     Incomplete,
+    /// A host function suspended its call, execution continues once the results were supplied with
+    /// [`ExecHandle::complete_host_call`]
+    HostPending(PendingHostCall),
+}
+
+/// A host function call which was suspended by returning [`Error::SuspendHostCall`]
+///
+/// The pending call is part of the execution state and included in serialized snapshots.
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct PendingHostCall {
+    pub(crate) import: Option<ExternName>,
+    pub(crate) ty: FuncType,
+    pub(crate) params: Vec<RawWasmValue>,
+}
+
+impl PendingHostCall {
+    /// Get the name of the called import
+    ///
+    /// This is `None` for host functions of a linked instance which are not imported directly.
+    pub fn import(&self) -> Option<&ExternName> {
+        self.import.as_ref()
+    }
+
+    /// Get the parameters the host function was called with
+    pub fn params(&self) -> Vec<WasmValue> {
+        self.params.iter().zip(self.ty.params.iter()).map(|(v, ty)| v.attach_type(*ty)).collect()
+    }
+
+    /// Get the types of the results which have to be supplied
+    pub fn result_types(&self) -> &[ValType] {
+        &self.ty.results
+    }
 }
 
 /// Handle to a running execution context of a Wasm function
 #[derive(Debug)]
-pub struct ExecHandle {
+pub struct ExecHandle<T = (), S: Threading = Local> {
+    pub(crate) instance: Instance<T, S>,
     pub(crate) func_handle: FuncHandle,
     pub(crate) stack: Stack,
 }
 
-impl ExecHandle {
+impl<T, S: Threading> ExecHandle<T, S> {
     /// Make progress on the execution of the started Wasm function. `max_cycles` instructions will be executed.
     pub fn run(&mut self, max_cycles: usize) -> Result<CallResult> {
-        let runtime = crate::runtime::interpreter::Interpreter {};
-        if !runtime.exec(&mut self.func_handle.instance, &mut self.stack, max_cycles)? {
-            return Ok(CallResult::Incomplete);
+        if let Some(pending) = &self.stack.pending_host_call {
+            return Ok(CallResult::HostPending(pending.clone()));
+        }
+
+        let runtime = crate::runtime::interpreter::Interpreter { call_base: 0, max_cycles };
+        let mut cycles = 0;
+        let done = runtime.exec(&mut self.instance, &mut self.stack, &mut cycles);
+        self.stack.cycles = self.stack.cycles.saturating_add(cycles as u64);
+        self.stack.total_cycles = self.stack.total_cycles.saturating_add(cycles as u64);
+        if !done? {
+            return Ok(match &self.stack.pending_host_call {
+                Some(pending) => CallResult::HostPending(pending.clone()),
+                None => CallResult::Incomplete,
+            });
+        }
+
+        Ok(CallResult::Done(self.func_handle.results(&self.stack)?))
+    }
+
+    /// Run until the handle has executed `total_cycles` instructions in total
+    ///
+    /// Stops early if the function returns or a host call is suspended. Execution pauses requested by host functions
+    /// are ignored. Cycle counts reported by [`ExecHandle::cycles`] of another handle, which was resumed from the same
+    /// snapshot with the same superinstruction setting, are always reached exactly.
+    pub fn run_to(&mut self, total_cycles: u64) -> Result<CallResult> {
+        loop {
+            let remaining = total_cycles.saturating_sub(self.stack.cycles);
+            if remaining == 0 {
+                return Ok(CallResult::Incomplete);
+            }
+
+            // `run` executes one instruction more than `max_cycles`
+            let max_cycles = usize::try_from(remaining - 1).unwrap_or(usize::MAX);
+            match self.run(max_cycles)? {
+                CallResult::Incomplete => continue,
+                result => return Ok(result),
+            }
         }
+    }
 
-        // Once the function returns:
-        let result_m = self.func_handle.ty.results.len();
+    /// Get the number of instructions executed by this handle
+    ///
+    /// Counting starts at zero when the handle is created, including when it resumes a snapshot.
+    pub fn cycles(&self) -> u64 {
+        self.stack.cycles
+    }
 
-        // 1. Assert: m values are on the top of the stack (Ensured by validation)
-        assert!(self.stack.values.len() >= result_m);
+    /// Get the number of instructions executed since the function was called
+    ///
+    /// Unlike [`ExecHandle::cycles`], this includes the instructions executed before the snapshot the handle resumed.
+    pub fn total_cycles(&self) -> u64 {
+        self.stack.total_cycles
+    }
+
+    /// Get the suspended host function call, if there is one
+    pub fn pending_host_call(&self) -> Option<&PendingHostCall> {
+        self.stack.pending_host_call.as_ref()
+    }
 
-        // 2. Pop m values from the stack
-        let res = self.stack.values.last_n(result_m)?;
+    /// Supply the results of the suspended host function call, so execution can continue with the next `run`
+    pub fn complete_host_call(&mut self, results: &[WasmValue]) -> Result<()> {
+        let Some(pending) = &self.stack.pending_host_call else {
+            return Err(Error::Other("no host call is pending".into()));
+        };
 
-        // The values are returned as the results of the invocation.
-        Ok(CallResult::Done(
-            res.iter().zip(self.func_handle.ty.results.iter()).map(|(v, ty)| v.attach_type(*ty)).collect(),
-        ))
+        let result_types = results.iter().map(|v| v.val_type());
+        if !result_types.eq(pending.ty.results.iter().copied()) {
+            return Err(Error::Other(format!(
+                "invalid results for host call, expected {:?}, got {:?}",
+                pending.ty.results, results
+            )));
+        }
+
+        self.stack.values.extend_from_typed(results);
+        self.stack.pending_host_call = None;
+        Ok(())
     }
 
     /// Take the current execution state and serialize it
     pub fn serialize<W: Write>(&mut self, writer: W, extra_data: &[u8]) -> Result<()> {
-        let encoder = flate2::write::GzEncoder::new(writer, flate2::Compression::best());
-        self.serialize_raw(encoder, extra_data)?;
-        Ok(())
+        encoding::serialize_gzip(writer, &self.state(extra_data))
     }
 
     /// Take the current execution state and serialize it without compression
     pub fn serialize_raw<W: Write>(&mut self, writer: W, extra_data: &[u8]) -> Result<()> {
-        let memory = &self.func_handle.instance.memories[0];
-        let globals = self.func_handle.instance.globals.iter().map(|g| g.value).collect();
-        let data = SerializationState { stack: &self.stack, memory, globals, extra_data };
+        encoding::serialize_into(writer, &self.state(extra_data))
+    }
 
-        bincode::serialize_into(writer, &data)?;
+    /// Compute a SHA-256 hash over the current execution state
+    ///
+    /// The hash covers the memories (except their ignored byte regions), globals and the stack, but not the user data.
+    /// Since the serialization is platform independent, two nodes executing the same program on the same data in
+    /// deterministic mode arrive at the same hash.
+    /// Use [`ExecHandle::state_hash_with_data`] if the user data is part of the result of the program.
+    pub fn state_hash(&self) -> [u8; 32] {
+        self.hash_state(&[])
+    }
 
-        Ok(())
+    fn hash_state(&self, extra_data: &[u8]) -> [u8; 32] {
+        let mut hasher = Sha256::new();
+        encoding::serialize_into(HashWriter(&mut hasher), &self.state(extra_data)).expect("hashing can not fail");
+        hasher.finalize().into()
+    }
+
+    fn state<'a>(&'a self, extra_data: &'a [u8]) -> SerializationState<'a> {
+        let memories = &self.instance.memories;
+        let globals = self.instance.globals.iter().map(|g| g.value).collect();
+        let deterministic = self.instance.deterministic;
+        SerializationState { stack: &self.stack, memories, globals, deterministic, extra_data }
+    }
+
+    /// Get a reference to the instance the function is executed on
+    pub fn instance(&self) -> &Instance<T, S> {
+        &self.instance
+    }
+
+    /// Get a mutable reference to the instance the function is executed on
+    pub fn instance_mut(&mut self) -> &mut Instance<T, S> {
+        &mut self.instance
+    }
+
+    /// Stop execution and take back the instance
+    ///
+    /// Usually called after [`CallResult::Done`] was returned, so other exports can be called on the instance.
+    /// Calling it earlier discards the execution state of the function.
+    pub fn into_instance(self) -> Instance<T, S> {
+        self.instance
+    }
+}
+
+impl<T: serde::Serialize, S: Threading> ExecHandle<T, S> {
+    /// Take the current execution state and serialize it, storing the user data of the instance as extra data
+    ///
+    /// Use [`Instance::instantiate_with_data`] to restore the state.
+    pub fn serialize_with_data<W: Write>(&mut self, writer: W) -> Result<()> {
+        let extra_data = encoding::serialize(&self.instance.user_data)?;
+        self.serialize(writer, &extra_data)
+    }
+
+    /// Like [`ExecHandle::serialize_with_data`], but without compression
+    pub fn serialize_raw_with_data<W: Write>(&mut self, writer: W) -> Result<()> {
+        let extra_data = encoding::serialize(&self.instance.user_data)?;
+        self.serialize_raw(writer, &extra_data)
+    }
+
+    /// Like [`ExecHandle::state_hash`], but the hash also covers the user data
+    ///
+    /// The hashed bytes are the ones written by [`ExecHandle::serialize_raw_with_data`].
+    pub fn state_hash_with_data(&self) -> Result<[u8; 32]> {
+        let extra_data = encoding::serialize(&self.instance.user_data)?;
+        Ok(self.hash_state(&extra_data))
     }
 }
 
@@ -74,16 +230,18 @@ pub enum CallResultTyped<R: FromWasmValueTuple> {
     Done(R),
     /// See [`CallResult::Incomplete`]
     Incomplete,
+    /// See [`CallResult::HostPending`]
+    HostPending(PendingHostCall),
 }
 
 /// [`ExecHandle`] but typed
 #[derive(Debug)]
-pub struct ExecHandleTyped<R: FromWasmValueTuple> {
-    pub(crate) exec_handle: ExecHandle,
+pub struct ExecHandleTyped<R: FromWasmValueTuple, T = (), S: Threading = Local> {
+    pub(crate) exec_handle: ExecHandle<T, S>,
     pub(crate) _marker: core::marker::PhantomData<R>,
 }
 
-impl<R: FromWasmValueTuple> ExecHandleTyped<R> {
+impl<R: FromWasmValueTuple, T, S: Threading> ExecHandleTyped<R, T, S> {
     /// See [`ExecHandle::run`]
     pub fn run(&mut self, max_cycles: usize) -> Result<CallResultTyped<R>> {
         // Call the underlying WASM function
@@ -92,9 +250,41 @@ impl<R: FromWasmValueTuple> ExecHandleTyped<R> {
         Ok(match result {
             CallResult::Done(values) => CallResultTyped::Done(R::from_wasm_value_tuple(&values)?),
             CallResult::Incomplete => CallResultTyped::Incomplete,
+            CallResult::HostPending(pending) => CallResultTyped::HostPending(pending),
+        })
+    }
+
+    /// See [`ExecHandle::run_to`]
+    pub fn run_to(&mut self, total_cycles: u64) -> Result<CallResultTyped<R>> {
+        let result = self.exec_handle.run_to(total_cycles)?;
+
+        Ok(match result {
+            CallResult::Done(values) => CallResultTyped::Done(R::from_wasm_value_tuple(&values)?),
+            CallResult::Incomplete => CallResultTyped::Incomplete,
+            CallResult::HostPending(pending) => CallResultTyped::HostPending(pending),
         })
     }
 
+    /// See [`ExecHandle::cycles`]
+    pub fn cycles(&self) -> u64 {
+        self.exec_handle.cycles()
+    }
+
+    /// See [`ExecHandle::total_cycles`]
+    pub fn total_cycles(&self) -> u64 {
+        self.exec_handle.total_cycles()
+    }
+
+    /// See [`ExecHandle::pending_host_call`]
+    pub fn pending_host_call(&self) -> Option<&PendingHostCall> {
+        self.exec_handle.pending_host_call()
+    }
+
+    /// See [`ExecHandle::complete_host_call`]
+    pub fn complete_host_call(&mut self, results: &[WasmValue]) -> Result<()> {
+        self.exec_handle.complete_host_call(results)
+    }
+
     /// See [`ExecHandle::serialize`]
     pub fn serialize<W: Write>(&mut self, writer: W, extra_data: &[u8]) -> Result<()> {
         self.exec_handle.serialize(writer, extra_data)
@@ -104,20 +294,395 @@ impl<R: FromWasmValueTuple> ExecHandleTyped<R> {
     pub fn serialize_raw<W: Write>(&mut self, writer: W, extra_data: &[u8]) -> Result<()> {
         self.exec_handle.serialize_raw(writer, extra_data)
     }
+
+    /// See [`ExecHandle::state_hash`]
+    pub fn state_hash(&self) -> [u8; 32] {
+        self.exec_handle.state_hash()
+    }
+
+    /// See [`ExecHandle::instance`]
+    pub fn instance(&self) -> &Instance<T, S> {
+        self.exec_handle.instance()
+    }
+
+    /// See [`ExecHandle::instance_mut`]
+    pub fn instance_mut(&mut self) -> &mut Instance<T, S> {
+        self.exec_handle.instance_mut()
+    }
+
+    /// See [`ExecHandle::into_instance`]
+    pub fn into_instance(self) -> Instance<T, S> {
+        self.exec_handle.into_instance()
+    }
+}
+
+impl<R: FromWasmValueTuple, T: serde::Serialize, S: Threading> ExecHandleTyped<R, T, S> {
+    /// See [`ExecHandle::serialize_with_data`]
+    pub fn serialize_with_data<W: Write>(&mut self, writer: W) -> Result<()> {
+        self.exec_handle.serialize_with_data(writer)
+    }
+
+    /// See [`ExecHandle::serialize_raw_with_data`]
+    pub fn serialize_raw_with_data<W: Write>(&mut self, writer: W) -> Result<()> {
+        self.exec_handle.serialize_raw_with_data(writer)
+    }
+
+    /// See [`ExecHandle::state_hash_with_data`]
+    pub fn state_hash_with_data(&self) -> Result<[u8; 32]> {
+        self.exec_handle.state_hash_with_data()
+    }
+}
+
+/// Compute the state hash of a snapshot without restoring it
+///
+/// The hash equals the one of [`ExecHandle::state_hash_with_data`] at the time the snapshot was taken with
+/// [`ExecHandle::serialize_with_data`], or of [`ExecHandle::state_hash`] for [`ExecHandle::serialize`] without extra
+/// data. This binds a snapshot received from elsewhere to the hash claimed for it.
+pub fn snapshot_hash(snapshot: &[u8]) -> Result<[u8; 32]> {
+    let mut hasher = Sha256::new();
+    encoding::decompress_gzip_into(snapshot, HashWriter(&mut hasher))?;
+    Ok(hasher.finalize().into())
+}
+
+/// Feeds serialized bytes into a hasher instead of storing them
+struct HashWriter<'a>(&'a mut Sha256);
+
+impl Write for HashWriter<'_> {
+    fn write_all(&mut self, buf: &[u8]) -> Result<()> {
+        self.0.update(buf);
+        Ok(())
+    }
 }
 
 #[derive(Debug, Clone, PartialEq, Eq, serde::Serialize)]
 pub(crate) struct SerializationState<'a> {
     pub(crate) stack: &'a Stack,
-    pub(crate) memory: &'a MemoryInstance,
+    pub(crate) memories: &'a [MemoryInstance],
     pub(crate) globals: Vec<RawWasmValue>,
+    pub(crate) deterministic: bool,
     pub(crate) extra_data: &'a [u8],
 }
 
 #[derive(Debug, Clone, PartialEq, Eq, serde::Deserialize)]
 pub(crate) struct DeserializationState {
     pub(crate) stack: Stack,
-    pub(crate) memory: MemoryInstance,
+    pub(crate) memories: Vec<MemoryInstance>,
     pub(crate) globals: Vec<RawWasmValue>,
+    pub(crate) deterministic: bool,
     pub(crate) extra_data: Vec<u8>,
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::imports::{Extern, FuncContext, Imports, Shared};
+
+    fn counter_instance() -> Instance {
+        let wasm = wat::parse_str(
+            r#"
+            (module
+                (global $count (mut i32) (i32.const 0))
+                (func (export "add") (param i32) (result i32)
+                    (global.set $count (i32.add (global.get $count) (local.get 0)))
+                    (global.get $count))
+                (func (export "get") (result i32)
+                    (global.get $count)))
+            "#,
+        )
+        .expect("invalid wat");
+        let module = crate::parse_bytes(&wasm).expect("failed to parse module");
+        let (instance, stack, _) =
+            Instance::instantiate(module, Imports::new(), (), None).expect("failed to instantiate");
+        assert!(stack.is_none());
+        instance
+    }
+
+    #[test]
+    fn test_invoke_multiple_exports() {
+        let mut instance = counter_instance();
+        let add = instance.exported_func::<i32, i32>("add").unwrap();
+        let get = instance.exported_func::<(), i32>("get").unwrap();
+
+        assert_eq!(add.invoke(&mut instance, 2).unwrap(), 2);
+        assert_eq!(add.invoke(&mut instance, 3).unwrap(), 5);
+        assert_eq!(get.invoke(&mut instance, ()).unwrap(), 5);
+    }
+
+    #[test]
+    fn test_instance_returned_after_done() {
+        let instance = counter_instance();
+        let add = instance.exported_func::<i32, i32>("add").unwrap();
+
+        let mut exec_handle = add.call(instance, 7, None).unwrap();
+        let res = loop {
+            if let CallResultTyped::Done(res) = exec_handle.run(1).unwrap() {
+                break res;
+            }
+        };
+        assert_eq!(res, 7);
+
+        let mut instance = exec_handle.into_instance();
+        let get = instance.exported_func::<(), i32>("get").unwrap();
+        assert_eq!(get.invoke(&mut instance, ()).unwrap(), 7);
+    }
+
+    #[test]
+    fn test_instance_returned_on_call_error() {
+        let mut instance = counter_instance();
+        let add = instance.exported_func_untyped("add").unwrap();
+        let get = instance.exported_func::<(), i32>("get").unwrap();
+        add.invoke(&mut instance, vec![WasmValue::I32(4)]).unwrap();
+
+        let (instance, err) = *add.call(instance, vec![WasmValue::I64(1)], None).unwrap_err();
+        assert!(matches!(err, Error::Other(_)));
+        let (mut instance, _) = *add.call(instance, vec![], None).unwrap_err();
+
+        assert_eq!(get.invoke(&mut instance, ()).unwrap(), 4);
+    }
+
+    #[test]
+    fn test_user_data_in_snapshot() {
+        let wasm = wat::parse_str(
+            r#"
+            (module
+                (import "env" "tick" (func $tick))
+                (memory (export "memory") 1)
+                (func (export "main")
+                    (call $tick)
+                    (call $tick)))
+            "#,
+        )
+        .expect("invalid wat");
+        let module = crate::parse_bytes(&wasm).expect("failed to parse module");
+
+        let imports = || {
+            let mut imports = Imports::<u32>::new();
+            let tick = Extern::typed_func(|mut ctx: FuncContext<'_, u32>, ()| -> Result<()> {
+                *ctx.data_mut() += 1;
+                Err(crate::Error::PauseExecution)
+            });
+            imports.define("env", "tick", tick).unwrap();
+            imports
+        };
+
+        let (mut instance, stack) = Instance::instantiate_with_data(module.clone(), imports(), 0, None).unwrap();
+        // keep the snapshot small, the memory is not used anyway
+        instance.exported_memory_mut("memory").unwrap().set_ignored_byte_region(0, crate::PAGE_SIZE).unwrap();
+        let main = instance.exported_func::<(), ()>("main").unwrap();
+        let mut exec_handle = main.call(instance, (), stack).unwrap();
+        assert!(matches!(exec_handle.run(100).unwrap(), CallResultTyped::Incomplete));
+        assert_eq!(*exec_handle.instance().data(), 1);
+
+        let mut state = Vec::new();
+        exec_handle.serialize_with_data(&mut state).unwrap();
+        assert_eq!(snapshot_hash(&state).unwrap(), exec_handle.state_hash_with_data().unwrap());
+        assert!(snapshot_hash(&state[..state.len() / 2]).is_err());
+
+        let (instance, stack) = Instance::instantiate_with_data(module, imports(), 0, Some(&state)).unwrap();
+        assert_eq!(*instance.data(), 1);
+
+        let mut exec_handle = main.call(instance, (), stack).unwrap();
+        while let CallResultTyped::Incomplete = exec_handle.run(100).unwrap() {}
+        assert_eq!(exec_handle.into_instance().into_data(), 2);
+    }
+
+    #[test]
+    fn test_ignored_byte_regions_in_snapshot() {
+        let wasm = wat::parse_str(
+            r#"
+            (module
+                (import "env" "pause" (func $pause))
+                (memory (export "memory") 1)
+                (data (i32.const 0) "abcdefghij")
+                (func (export "main") (result i32)
+                    (call $pause)
+                    (i32.load8_u (i32.const 9))))
+            "#,
+        )
+        .expect("invalid wat");
+        let module = crate::parse_bytes(&wasm).expect("failed to parse module");
+        let imports = || {
+            let mut imports = Imports::new();
+            let pause = Extern::typed_func(|_, ()| -> Result<()> { Err(crate::Error::PauseExecution) });
+            imports.define("env", "pause", pause).unwrap();
+            imports
+        };
+
+        let (mut instance, _, _) = Instance::instantiate(module.clone(), imports(), (), None).unwrap();
+        let mut mem = instance.exported_memory_mut("memory").unwrap();
+        mem.add_ignored_byte_region(6, 2).unwrap();
+        mem.add_ignored_byte_region(1, 3).unwrap();
+        mem.add_ignored_byte_region(10, crate::PAGE_SIZE - 10).unwrap();
+        assert!(mem.add_ignored_byte_region(7, 2).is_err());
+        assert!(mem.add_ignored_byte_region(crate::PAGE_SIZE, 1).is_err());
+        assert_eq!(mem.ignored_byte_regions().collect::<Vec<_>>(), [(1, 3), (6, 2), (10, crate::PAGE_SIZE - 10)]);
+
+        let main = instance.exported_func::<(), i32>("main").unwrap();
+        let mut exec_handle = main.call(instance, (), None).unwrap();
+        assert!(matches!(exec_handle.run(100).unwrap(), CallResultTyped::Incomplete));
+        let mut state = Vec::new();
+        exec_handle.serialize(&mut state, &[]).unwrap();
+
+        // only the bytes outside of the ignored regions are restored
+        let (instance, stack, _) = Instance::instantiate(module, imports(), (), Some(&state)).unwrap();
+        let mem = instance.exported_memory("memory").unwrap();
+        assert_eq!(mem.load(0, 10).unwrap(), b"a\0\0\0ef\0\0ij");
+        assert_eq!(mem.ignored_byte_regions().count(), 3);
+
+        let mut exec_handle = main.call(instance, (), stack).unwrap();
+        assert!(matches!(exec_handle.run(100).unwrap(), CallResultTyped::Done(106)));
+    }
+
+    #[test]
+    fn test_resume_on_other_thread() {
+        let wasm = wat::parse_str(
+            r#"
+            (module
+                (import "env" "double" (func $double (param i32) (result i32)))
+                (func (export "add") (param i32) (result i32)
+                    (i32.add (call $double (local.get 0)) (i32.const 1))))
+            "#,
+        )
+        .expect("invalid wat");
+        let module = crate::parse_bytes(&wasm).expect("failed to parse module");
+
+        // only instances with `Shared` host functions can be moved to another thread
+        let factor = std::sync::Arc::new(2);
+        let mut imports = Imports::<(), Shared>::default();
+        imports
+            .define("env", "double", Extern::typed_func(move |_, x: i32| -> Result<i32> { Ok(x * *factor) }))
+            .unwrap();
+        let (instance, _, _) = Instance::instantiate(module, imports, (), None).unwrap();
+        let add = instance.exported_func::<i32, i32>("add").unwrap();
+
+        let mut exec_handle = add.call(instance, 3, None).unwrap();
+        assert!(matches!(exec_handle.run(1).unwrap(), CallResultTyped::Incomplete));
+
+        let res = std::thread::spawn(move || loop {
+            if let CallResultTyped::Done(res) = exec_handle.run(1).unwrap() {
+                break res;
+            }
+        })
+        .join()
+        .unwrap();
+        assert_eq!(res, 7);
+    }
+
+    #[test]
+    fn test_pending_host_call_in_snapshot() {
+        let wasm = wat::parse_str(
+            r#"
+            (module
+                (import "env" "fetch" (func $fetch (param i32) (result i64)))
+                (func (export "main") (result i64)
+                    (i64.add (call $fetch (i32.const 7)) (i64.const 1))))
+            "#,
+        )
+        .expect("invalid wat");
+        let module = crate::parse_bytes(&wasm).expect("failed to parse module");
+
+        let imports = || {
+            let mut imports = Imports::new();
+            let fetch = Extern::typed_func(|_, _: i32| -> Result<i64> { Err(crate::Error::SuspendHostCall) });
+            imports.define("env", "fetch", fetch).unwrap();
+            imports
+        };
+
+        let (instance, _, _) = Instance::instantiate(module.clone(), imports(), (), None).unwrap();
+        let main = instance.exported_func::<(), i64>("main").unwrap();
+        let mut exec_handle = main.call(instance, (), None).unwrap();
+        let CallResultTyped::HostPending(pending) = exec_handle.run(100).unwrap() else { panic!("call not suspended") };
+        assert_eq!(pending.import().map(|i| (i.module(), i.name())), Some(("env", "fetch")));
+        assert_eq!(pending.params(), [WasmValue::I32(7)]);
+
+        let mut state = Vec::new();
+        exec_handle.serialize(&mut state, &[]).unwrap();
+
+        let (instance, stack, _) = Instance::instantiate(module, imports(), (), Some(&state)).unwrap();
+        let mut exec_handle = main.call(instance, (), stack).unwrap();
+        assert!(matches!(exec_handle.run(100).unwrap(), CallResultTyped::HostPending(p) if p == pending));
+        assert!(exec_handle.complete_host_call(&[WasmValue::I32(41)]).is_err());
+
+        exec_handle.complete_host_call(&[WasmValue::I64(41)]).unwrap();
+        assert!(matches!(exec_handle.run(100).unwrap(), CallResultTyped::Done(42)));
+    }
+
+    #[test]
+    fn test_verify_by_cycles() {
+        let wasm = wat::parse_str(
+            r#"
+            (module
+                (memory 1)
+                (global $sum (mut i64) (i64.const 0))
+                (func (export "main") (result i64)
+                    (local $i i32)
+                    (loop $continue
+                        (i32.store (i32.shl (local.get $i) (i32.const 2)) (local.get $i))
+                        (global.set $sum (i64.add (global.get $sum) (i64.extend_i32_u (local.get $i))))
+                        (br_if $continue (i32.lt_u (local.tee $i (i32.add (local.get $i) (i32.const 1))) (i32.const 1000))))
+                    (global.get $sum)))
+            "#,
+        )
+        .expect("invalid wat");
+        let module = crate::parse_bytes(&wasm).expect("failed to parse module");
+
+        let (instance, _, _) = Instance::instantiate(module.clone(), Imports::new(), (), None).unwrap();
+        let main = instance.exported_func::<(), i64>("main").unwrap();
+        let mut exec_handle = main.call(instance, (), None).unwrap();
+        assert!(matches!(exec_handle.run(500).unwrap(), CallResultTyped::Incomplete));
+        let mut start = Vec::new();
+        exec_handle.serialize(&mut start, &[]).unwrap();
+
+        // the worker runs in irregular slices, the verifier only knows the claimed total
+        let resumed = |state: &[u8]| {
+            let (instance, stack, _) = Instance::instantiate(module.clone(), Imports::new(), (), Some(state)).unwrap();
+            main.call(instance, (), stack).unwrap()
+        };
+        let mut worker = resumed(&start);
+        for max_cycles in [3, 100, 17, 1000, 0, 250] {
+            assert!(matches!(worker.run(max_cycles).unwrap(), CallResultTyped::Incomplete));
+        }
+        let (claimed_cycles, claimed_hash) = (worker.cycles(), worker.state_hash());
+
+        let mut verifier = resumed(&start);
+        assert_ne!(verifier.state_hash(), claimed_hash);
+        assert!(matches!(verifier.run_to(claimed_cycles).unwrap(), CallResultTyped::Incomplete));
+        assert_eq!(verifier.cycles(), claimed_cycles);
+        assert_eq!(verifier.state_hash(), claimed_hash);
+
+        assert!(matches!(verifier.run(0).unwrap(), CallResultTyped::Incomplete));
+        assert_ne!(verifier.state_hash(), claimed_hash);
+
+        let sum = (0..1000).sum::<i64>();
+        assert!(matches!(verifier.run_to(u64::MAX).unwrap(), CallResultTyped::Done(res) if res == sum));
+        assert!(matches!(worker.run_to(u64::MAX).unwrap(), CallResultTyped::Done(res) if res == sum));
+        assert_eq!(verifier.state_hash(), worker.state_hash());
+
+        // the total includes the instructions executed before the snapshot
+        assert_eq!(worker.total_cycles(), exec_handle.cycles() + worker.cycles());
+        assert_eq!(verifier.total_cycles(), worker.total_cycles());
+    }
+
+    #[test]
+    fn test_state_hash_with_data() {
+        let wasm = wat::parse_str(r#"(module (func (export "main") (loop $l (br $l))))"#).expect("invalid wat");
+        let module = crate::parse_bytes(&wasm).expect("failed to parse module");
+
+        let (instance, _, _) = Instance::instantiate(module, Imports::new(), 1u32, None).unwrap();
+        let main = instance.exported_func::<(), ()>("main").unwrap();
+        let mut exec_handle = main.call(instance, (), None).unwrap();
+        assert!(matches!(exec_handle.run(10).unwrap(), CallResultTyped::Incomplete));
+        let (hash, hash_with_data) = (exec_handle.state_hash(), exec_handle.state_hash_with_data().unwrap());
+
+        // forging the user data has to change the hash
+        *exec_handle.instance_mut().data_mut() = 2;
+        assert_eq!(exec_handle.state_hash(), hash);
+        assert_ne!(exec_handle.state_hash_with_data().unwrap(), hash_with_data);
+
+        let mut state = Vec::new();
+        exec_handle.serialize_raw_with_data(&mut state).unwrap();
+        let mut hasher = Sha256::new();
+        hasher.update(&state);
+        assert_eq!(<[u8; 32]>::from(hasher.finalize()), exec_handle.state_hash_with_data().unwrap());
+    }
+}
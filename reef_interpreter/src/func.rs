@@ -10,20 +10,23 @@ use alloc::{
 
 use crate::error::{Error, Result};
 use crate::exec::{ExecHandle, ExecHandleTyped};
-use crate::imports::Function;
+use crate::imports::{Function, Threading};
 use crate::instance::Instance;
-use crate::runtime::{CallFrame, RawWasmValue, Stack};
+use crate::runtime::{interpreter::Interpreter, CallFrame, RawWasmValue, Stack};
 use crate::types::{
     value::{ValType, WasmValue},
     FuncType,
 };
 use crate::{unlikely, VecExt};
 
-#[derive(Debug)]
+/// The error of [`FuncHandle::call`], which hands back the instance the call could not be started on
+pub type CallError<T, S> = Box<(Instance<T, S>, Error)>;
+
+#[derive(Debug, Clone)]
 /// A function handle
+///
+/// Handles are not bound to an [`Instance`], so the same handle can be used for several calls.
 pub struct FuncHandle {
-    pub(crate) instance: Instance,
-
     pub(crate) addr: u32,
     pub(crate) ty: FuncType,
 
@@ -33,7 +36,78 @@ pub struct FuncHandle {
 
 impl FuncHandle {
     /// Start or resume execution of function
-    pub fn call(self, params: Vec<WasmValue>, stack: Option<Stack>) -> Result<ExecHandle> {
+    ///
+    /// The instance is moved into the returned [`ExecHandle`] and can be taken back with [`ExecHandle::into_instance`].
+    /// If the call can't be started, for example because the params don't match, the instance is returned with the error.
+    pub fn call<T, S: Threading>(
+        &self,
+        mut instance: Instance<T, S>,
+        params: Vec<WasmValue>,
+        stack: Option<Stack>,
+    ) -> core::result::Result<ExecHandle<T, S>, CallError<T, S>> {
+        let stack = match stack {
+            Some(stack) => stack,
+            None => match self.new_stack(&mut instance, &params) {
+                Ok(stack) => stack,
+                Err(err) => return Err(Box::new((instance, err))),
+            },
+        };
+
+        Ok(ExecHandle { instance, func_handle: self.clone(), stack })
+    }
+
+    /// Call the function on a borrowed instance and run it to completion
+    ///
+    /// Requests to pause execution are ignored, the function is resumed until it returns.
+    pub fn invoke<T, S: Threading>(
+        &self,
+        instance: &mut Instance<T, S>,
+        params: Vec<WasmValue>,
+    ) -> Result<Vec<WasmValue>> {
+        let mut stack = self.new_stack(instance, &params)?;
+
+        let runtime = Interpreter { call_base: 0, max_cycles: usize::MAX };
+        while !runtime.exec(instance, &mut stack, &mut 0)? {
+            if stack.pending_host_call.is_some() {
+                return Err(Error::Other(
+                    "host calls can only be suspended when running through an ExecHandle".to_string(),
+                ));
+            }
+        }
+
+        self.results(&stack)
+    }
+
+    fn new_stack<T, S: Threading>(&self, instance: &mut Instance<T, S>, params: &[WasmValue]) -> Result<Stack> {
+        self.check_params(params)?;
+        instance.translate_func(self.addr)?;
+
+        match instance.funcs.get_or_instance(self.addr, "function")? {
+            Function::Wasm(wasm_func) if wasm_func.ty == self.ty => {
+                let call_frame_params = params.iter().map(|v| RawWasmValue::from(*v));
+                let call_frame = CallFrame::new(self.addr, wasm_func, call_frame_params, 0);
+                Ok(Stack::new(call_frame, instance.module.backend))
+            }
+            Function::Wasm(_) => Err(Error::InvalidStore),
+            Function::Host(_) => Err(Error::Other("Can't call Host function directly".to_string())),
+        }
+    }
+
+    /// Take the results of the returned function from the stack
+    pub(crate) fn results(&self, stack: &Stack) -> Result<Vec<WasmValue>> {
+        let result_m = self.ty.results.len();
+
+        // 1. Assert: m values are on the top of the stack (Ensured by validation)
+        assert!(stack.values.len() >= result_m);
+
+        // 2. Pop m values from the stack
+        let res = stack.values.last_n(result_m)?;
+
+        // The values are returned as the results of the invocation.
+        Ok(res.iter().zip(self.ty.results.iter()).map(|(v, ty)| v.attach_type(*ty)).collect())
+    }
+
+    pub(crate) fn check_params(&self, params: &[WasmValue]) -> Result<()> {
         let func_ty = &self.ty;
 
         if unlikely(func_ty.params.len() != params.len()) {
@@ -44,25 +118,11 @@ impl FuncHandle {
             )));
         }
 
-        if !(func_ty.params.iter().zip(&params).all(|(ty, param)| ty == &param.val_type())) {
+        if !(func_ty.params.iter().zip(params).all(|(ty, param)| ty == &param.val_type())) {
             return Err(Error::Other("Type mismatch".into()));
         }
 
-        let func = self.instance.funcs.get_or_instance(self.addr, "function")?;
-
-        let stack = match stack {
-            Some(stack) => stack,
-            None => match &func {
-                Function::Wasm(wasm_func) => {
-                    let call_frame_params = params.iter().map(|v| RawWasmValue::from(*v));
-                    let call_frame = CallFrame::new(self.addr, wasm_func, call_frame_params, 0);
-                    Stack::new(call_frame)
-                }
-                Function::Host(_) => return Err(Error::Other("Can't call Host function directly".to_string())),
-            },
-        };
-
-        Ok(ExecHandle { func_handle: self, stack })
+        Ok(())
     }
 }
 
@@ -74,6 +134,12 @@ pub struct FuncHandleTyped<P, R> {
     pub(crate) _marker: core::marker::PhantomData<(P, R)>,
 }
 
+impl<P, R> Clone for FuncHandleTyped<P, R> {
+    fn clone(&self) -> Self {
+        Self { func: self.func.clone(), _marker: core::marker::PhantomData }
+    }
+}
+
 /// Things that can be converted to WasmValues
 pub trait IntoWasmValueTuple {
     /// Do the conversion
@@ -90,11 +156,21 @@ pub trait FromWasmValueTuple {
 
 impl<P: IntoWasmValueTuple, R: FromWasmValueTuple> FuncHandleTyped<P, R> {
     /// See [`FuncHandle::call`]
-    pub fn call(self, params: P, stack: Option<Stack>) -> Result<ExecHandleTyped<R>> {
-        let exec_handle = self.func.call(params.into_wasm_value_tuple(), stack)?;
+    pub fn call<T, S: Threading>(
+        &self,
+        instance: Instance<T, S>,
+        params: P,
+        stack: Option<Stack>,
+    ) -> core::result::Result<ExecHandleTyped<R, T, S>, CallError<T, S>> {
+        let exec_handle = self.func.call(instance, params.into_wasm_value_tuple(), stack)?;
 
         Ok(ExecHandleTyped { exec_handle, _marker: Default::default() })
     }
+
+    /// See [`FuncHandle::invoke`]
+    pub fn invoke<T, S: Threading>(&self, instance: &mut Instance<T, S>, params: P) -> Result<R> {
+        R::from_wasm_value_tuple(&self.func.invoke(instance, params.into_wasm_value_tuple())?)
+    }
 }
 
 macro_rules! impl_into_wasm_value_tuple {
@@ -3,34 +3,44 @@
 use alloc::{
     boxed::Box,
     collections::BTreeMap,
-    format,
     string::{String, ToString},
+    sync::Arc,
     vec::Vec,
 };
 use core::fmt::Debug;
 
 use crate::error::{Error, LinkingError, Result};
-use crate::func::{FromWasmValueTuple, IntoWasmValueTuple, ValTypesFromTuple};
-use crate::reference::{MemoryRef, MemoryRefMut};
-use crate::store::memory::MemoryInstance;
+use crate::func::{FromWasmValueTuple, FuncHandle, FuncHandleTyped, IntoWasmValueTuple, ValTypesFromTuple};
+use crate::instance::Instance;
+use crate::link::LinkedInstance;
+use crate::reference::{GlobalRef, GlobalRefMut, MemoryRef, MemoryRefMut, TableRef};
+use crate::runtime::{interpreter::Interpreter, CallFrame, RawWasmValue, Stack};
 use crate::types::{
-    value::WasmValue, ExternalKind, FuncAddr, GlobalAddr, GlobalType, Import, MemAddr, MemoryType, Module, TableAddr,
-    TableType,
+    value::WasmValue, ExternalKind, FuncAddr, GlobalAddr, GlobalType, Import, MemAddr, MemoryType, TableAddr, TableType,
 };
 use crate::types::{FuncType, WasmFunction};
 use crate::VecExt;
 
 /// The internal representation of a function
 #[derive(Debug)]
-pub enum Function {
+pub enum Function<T = (), S: Threading = Local> {
     /// A host function
-    Host(HostFunction),
+    Host(Arc<HostFunction<T, S>>),
 
     /// A pointer to a WebAssembly function
     Wasm(WasmFunction),
 }
 
-impl Function {
+impl<T, S: Threading> Clone for Function<T, S> {
+    fn clone(&self) -> Self {
+        match self {
+            Self::Host(f) => Self::Host(f.clone()),
+            Self::Wasm(f) => Self::Wasm(f.clone()),
+        }
+    }
+}
+
+impl<T, S: Threading> Function<T, S> {
     pub(crate) fn ty(&self) -> &FuncType {
         match self {
             Self::Host(f) => &f.ty,
@@ -40,65 +50,216 @@ impl Function {
 }
 
 /// A host function
-pub struct HostFunction {
+pub struct HostFunction<T = (), S: Threading = Local> {
     pub(crate) ty: FuncType,
-    pub(crate) func: HostFuncInner,
+    pub(crate) func: HostFuncInner<T, S>,
 }
 
-impl HostFunction {
+impl<T, S: Threading> HostFunction<T, S> {
     /// Get the function's type
     pub fn ty(&self) -> &FuncType {
         &self.ty
     }
 
     /// Call the function
-    pub fn call(&self, ctx: FuncContext<'_>, args: &[WasmValue]) -> Result<Vec<WasmValue>> {
+    pub fn call(&self, ctx: FuncContext<'_, T, S>, args: &[WasmValue]) -> Result<Vec<WasmValue>> {
         (self.func)(ctx, args)
     }
 }
 
-pub(crate) type HostFuncInner = Box<dyn Fn(FuncContext<'_>, &[WasmValue]) -> Result<Vec<WasmValue>>>;
+pub(crate) type HostFuncInner<T, S> = Box<<S as Threading>::Func<T>>;
+
+/// Whether the host functions of an instance are thread safe
+///
+/// By default, host functions can capture anything ([`Local`]). Instances with [`Shared`] host functions can be
+/// moved between threads, as long as their user data can.
+pub trait Threading: Debug + Sized + 'static {
+    /// The boxed closure of a host function
+    type Func<T>: ?Sized + Fn(FuncContext<'_, T, Self>, &[WasmValue]) -> Result<Vec<WasmValue>>;
+}
+
+/// Host functions are plain closures, which can capture anything
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Local;
+
+impl Threading for Local {
+    type Func<T> = dyn Fn(FuncContext<'_, T, Self>, &[WasmValue]) -> Result<Vec<WasmValue>>;
+}
+
+/// Host functions are `Send + Sync`, so instances can be moved between threads
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Shared;
+
+impl Threading for Shared {
+    type Func<T> = dyn Fn(FuncContext<'_, T, Self>, &[WasmValue]) -> Result<Vec<WasmValue>> + Send + Sync;
+}
+
+/// Values which can be captured by the host functions of instances with the [`Threading`] `S`
+///
+/// This is every value for [`Local`] and every `Send + Sync` value for [`Shared`].
+pub trait Captured<S: Threading>: Sized + 'static {
+    /// Box a host function, which gets access to the captured value
+    fn into_host_func<T, F>(self, func: F) -> HostFuncInner<T, S>
+    where
+        F: Fn(&Self, FuncContext<'_, T, S>, &[WasmValue]) -> Result<Vec<WasmValue>> + Send + Sync + 'static;
+}
+
+impl<C: 'static> Captured<Local> for C {
+    fn into_host_func<T, F>(self, func: F) -> HostFuncInner<T, Local>
+    where
+        F: Fn(&Self, FuncContext<'_, T, Local>, &[WasmValue]) -> Result<Vec<WasmValue>> + Send + Sync + 'static,
+    {
+        Box::new(move |ctx, args| func(&self, ctx, args))
+    }
+}
+
+impl<C: Send + Sync + 'static> Captured<Shared> for C {
+    fn into_host_func<T, F>(self, func: F) -> HostFuncInner<T, Shared>
+    where
+        F: Fn(&Self, FuncContext<'_, T, Shared>, &[WasmValue]) -> Result<Vec<WasmValue>> + Send + Sync + 'static,
+    {
+        Box::new(move |ctx, args| func(&self, ctx, args))
+    }
+}
 
 /// The context of a host-function call
+///
+/// Besides access to the instance, the context allows calling back into functions of the guest.
 #[derive(Debug)]
-pub struct FuncContext<'i> {
-    pub(crate) module: &'i Module,
-    pub(crate) memories: &'i mut Vec<MemoryInstance>,
+pub struct FuncContext<'i, T = (), S: Threading = Local> {
+    pub(crate) instance: &'i mut Instance<T, S>,
+    pub(crate) stack: &'i mut Stack,
+    pub(crate) cycles: &'i mut usize,
+    pub(crate) max_cycles: usize,
+    pub(crate) pause_requested: &'i mut bool,
 }
 
-impl FuncContext<'_> {
+impl<T, S: Threading> FuncContext<'_, T, S> {
     /// Get a reference to the module instance
     pub fn module(&self) -> &crate::Module {
-        self.module
+        &self.instance.module
+    }
+
+    /// Get the number of instructions executed so far, see [`ExecHandle::cycles`](crate::exec::ExecHandle::cycles)
+    pub fn cycles(&self) -> u64 {
+        self.stack.cycles.saturating_add(*self.cycles as u64)
+    }
+
+    /// Like [`FuncContext::cycles`], but including the instructions executed before the snapshot execution resumed
+    pub fn total_cycles(&self) -> u64 {
+        self.stack.total_cycles.saturating_add(*self.cycles as u64)
+    }
+
+    /// Get a reference to the user data of the instance
+    pub fn data(&self) -> &T {
+        &self.instance.user_data
+    }
+
+    /// Get a mutable reference to the user data of the instance
+    pub fn data_mut(&mut self) -> &mut T {
+        &mut self.instance.user_data
     }
 
     /// Get a reference to an exported memory
     pub fn exported_memory(&self, name: &str) -> Result<MemoryRef<'_>> {
-        Ok(MemoryRef { instance: self.memories.get_or_instance(self.exported_memory_addr(name)?, "memory")? })
+        self.instance.exported_memory(name)
     }
 
     /// Get a reference to an exported memory
     pub fn exported_memory_mut(&mut self, name: &str) -> Result<MemoryRefMut<'_>> {
-        Ok(MemoryRefMut { instance: self.memories.get_mut_or_instance(self.exported_memory_addr(name)?, "memory")? })
+        self.instance.exported_memory_mut(name)
+    }
+
+    /// Get a reference to an exported global
+    pub fn exported_global(&self, name: &str) -> Result<GlobalRef<'_>> {
+        self.instance.exported_global(name)
+    }
+
+    /// Get a reference to an exported global
+    pub fn exported_global_mut(&mut self, name: &str) -> Result<GlobalRefMut<'_>> {
+        self.instance.exported_global_mut(name)
+    }
+
+    /// Get a reference to an exported table
+    pub fn exported_table(&self, name: &str) -> Result<TableRef<'_>> {
+        self.instance.exported_table(name)
+    }
+
+    /// Get an exported function by name, to be called with [`FuncContext::call`]
+    pub fn exported_func_untyped(&self, name: &str) -> Result<FuncHandle> {
+        self.instance.exported_func_untyped(name)
+    }
+
+    /// Get a typed exported function by name, to be called with [`FuncContext::call_typed`]
+    pub fn exported_func<P, R>(&self, name: &str) -> Result<FuncHandleTyped<P, R>>
+    where
+        P: IntoWasmValueTuple,
+        R: FromWasmValueTuple,
+    {
+        self.instance.exported_func(name)
     }
 
-    fn exported_memory_addr(&self, name: &str) -> Result<u32> {
-        let export = self
-            .module
-            .exports
-            .iter()
-            .find(|e| &*e.name == name)
-            .ok_or_else(|| Error::Other(format!("Export not found: {}", name)))?;
+    /// Call a guest function from within the host function and run it to completion
+    ///
+    /// The call is nested on the stack of the current execution and the executed instructions are counted towards its
+    /// cycles. If execution is paused inside the nested call, the pause takes effect once the host function returns.
+    /// The nested call can not be suspended, so it fails if it exceeds the cycles remaining in the current run,
+    /// e.g. of [`ExecHandle::run`](crate::exec::ExecHandle::run).
+    pub fn call(&mut self, func: &FuncHandle, params: Vec<WasmValue>) -> Result<Vec<WasmValue>> {
+        func.check_params(&params)?;
+        self.instance.translate_func(func.addr)?;
+
+        let wasm_func = match self.instance.funcs.get_or_instance(func.addr, "function")? {
+            Function::Wasm(wasm_func) if wasm_func.ty == func.ty => wasm_func,
+            Function::Wasm(_) => return Err(Error::InvalidStore),
+            Function::Host(_) => return Err(Error::Other("Can't call Host function directly".to_string())),
+        };
 
-        if export.kind != ExternalKind::Memory {
-            return Err(Error::Other(format!("Export is not a memory: {}", name)));
+        let call_base = self.stack.call_stack.len();
+        let blocks_base = self.stack.blocks.len();
+        let values_base = self.stack.values.len();
+
+        let params = params.iter().map(|v| RawWasmValue::from(*v));
+        let call_frame = CallFrame::new(func.addr, wasm_func, params, blocks_base as u32);
+        self.stack.call_stack.push(call_frame)?;
+
+        let runtime = Interpreter { call_base, max_cycles: self.max_cycles };
+        let res = loop {
+            match runtime.exec(self.instance, self.stack, self.cycles) {
+                Ok(true) => break self.stack.values.pop_params(&func.ty.results),
+                Ok(false) if *self.cycles > self.max_cycles => {
+                    break Err(Error::Other("nested call exceeded the cycles remaining in the run".to_string()))
+                }
+                Ok(false) if self.stack.pending_host_call.is_some() => {
+                    break Err(Error::Other(
+                        "host calls can only be suspended when running through an ExecHandle".to_string(),
+                    ))
+                }
+                Ok(false) => *self.pause_requested = true,
+                Err(err) => break Err(err),
+            }
         };
 
-        Ok(export.index)
+        // leave the stack as it was before the call, even if it trapped
+        self.stack.pending_host_call = None;
+        self.stack.call_stack.truncate(call_base);
+        self.stack.blocks.truncate(blocks_base as u32);
+        self.stack.values.truncate_keep(values_base as u32, 0);
+
+        res
+    }
+
+    /// Like [`FuncContext::call`], but typed
+    pub fn call_typed<P, R>(&mut self, func: &FuncHandleTyped<P, R>, params: P) -> Result<R>
+    where
+        P: IntoWasmValueTuple,
+        R: FromWasmValueTuple,
+    {
+        R::from_wasm_value_tuple(&self.call(&func.func, params.into_wasm_value_tuple())?)
     }
 }
 
-impl Debug for HostFunction {
+impl<T, S: Threading> Debug for HostFunction<T, S> {
     fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
         f.debug_struct("HostFunction").field("ty", &self.ty).field("func", &"...").finish()
     }
@@ -107,7 +268,7 @@ impl Debug for HostFunction {
 #[derive(Debug)]
 #[non_exhaustive]
 /// An external value
-pub enum Extern {
+pub enum Extern<T = (), S: Threading = Local> {
     /// A global value
     Global {
         /// The type of the global value.
@@ -131,10 +292,10 @@ pub enum Extern {
     },
 
     /// A function
-    Function(Option<Function>),
+    Function(Option<Function<T, S>>),
 }
 
-impl Extern {
+impl<T, S: Threading> Extern<T, S> {
     /// Create a new global import
     pub fn global(val: WasmValue, mutable: bool) -> Self {
         Self::Global { ty: GlobalType { ty: val.val_type(), mutable }, val }
@@ -151,29 +312,57 @@ impl Extern {
     }
 
     /// Create a new function import
-    pub fn func(
+    ///
+    /// With [`Shared`] host functions, the closure has to be `Send + Sync`.
+    pub fn func<F>(ty: &FuncType, func: F) -> Self
+    where
+        F: Fn(FuncContext<'_, T, S>, &[WasmValue]) -> Result<Vec<WasmValue>> + Captured<S>,
+    {
+        Self::func_with(ty, func, |func, ctx, args| func(ctx, args))
+    }
+
+    /// Create a new function import, which gets access to the `captured` value
+    ///
+    /// Only the captured value has to be `Send + Sync` with [`Shared`] host functions, which allows defining the same
+    /// function for any [`Threading`].
+    pub fn func_with<C: Captured<S>>(
         ty: &FuncType,
-        func: impl Fn(FuncContext<'_>, &[WasmValue]) -> Result<Vec<WasmValue>> + 'static,
+        captured: C,
+        func: impl Fn(&C, FuncContext<'_, T, S>, &[WasmValue]) -> Result<Vec<WasmValue>> + Send + Sync + 'static,
     ) -> Self {
-        Self::Function(Some(Function::Host(HostFunction { func: Box::new(func), ty: ty.clone() })))
+        let func = captured.into_host_func(func);
+        Self::Function(Some(Function::Host(Arc::new(HostFunction { func, ty: ty.clone() }))))
     }
 
     /// Create a new typed function import
     // TODO: currently, this is slower than `Extern::func` because of the type conversions.
     //       we should be able to optimize this and make it even faster than `Extern::func`.
-    pub fn typed_func<P, R>(func: impl Fn(FuncContext<'_>, P) -> Result<R> + 'static) -> Self
+    pub fn typed_func<P, R, F>(func: F) -> Self
+    where
+        P: FromWasmValueTuple + ValTypesFromTuple,
+        R: IntoWasmValueTuple + ValTypesFromTuple + Debug,
+        F: Fn(FuncContext<'_, T, S>, P) -> Result<R> + Captured<S>,
+    {
+        Self::typed_func_with(func, |func, ctx, args| func(ctx, args))
+    }
+
+    /// Like [`Extern::func_with`], but typed
+    pub fn typed_func_with<C: Captured<S>, P, R>(
+        captured: C,
+        func: impl Fn(&C, FuncContext<'_, T, S>, P) -> Result<R> + Send + Sync + 'static,
+    ) -> Self
     where
         P: FromWasmValueTuple + ValTypesFromTuple,
         R: IntoWasmValueTuple + ValTypesFromTuple + Debug,
     {
-        let inner_func = move |ctx: FuncContext<'_>, args: &[WasmValue]| -> Result<Vec<WasmValue>> {
+        let inner_func = move |captured: &C, ctx: FuncContext<'_, T, S>, args: &[WasmValue]| {
             let args = P::from_wasm_value_tuple(args)?;
-            let result = func(ctx, args)?;
+            let result = func(captured, ctx, args)?;
             Ok(result.into_wasm_value_tuple().to_vec())
         };
 
         let ty = FuncType { params: P::val_types(), results: R::val_types() };
-        Self::Function(Some(Function::Host(HostFunction { func: Box::new(inner_func), ty })))
+        Self::Function(Some(Function::Host(Arc::new(HostFunction { func: captured.into_host_func(inner_func), ty }))))
     }
 
     /// Get the kind of the external value
@@ -187,30 +376,51 @@ impl Extern {
     }
 }
 
-#[derive(Debug, Clone, PartialEq, Eq, Ord, PartialOrd, Hash)]
+#[derive(Debug, Clone, PartialEq, Eq, Ord, PartialOrd, Hash, serde::Serialize, serde::Deserialize)]
 /// Name of an import
 pub struct ExternName {
     module: String,
     name: String,
 }
 
+impl ExternName {
+    pub(crate) fn new(module: &str, name: &str) -> Self {
+        Self { module: module.to_string(), name: name.to_string() }
+    }
+
+    /// Get the module name
+    pub fn module(&self) -> &str {
+        &self.module
+    }
+
+    /// Get the name inside of the module
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+}
+
 impl From<&Import> for ExternName {
     fn from(import: &Import) -> Self {
         Self { module: import.module.to_string(), name: import.name.to_string() }
     }
 }
 
-#[derive(Debug, Default)]
+#[derive(Debug)]
 /// Imports for a module instance
 ///
-/// This is used to link a module instance to its imports
-///
-/// Note that module instance addresses for [`Imports::link_module`] can be obtained from [`crate::ModuleInstance::id`].
-/// Now, the imports object can be passed to [`crate::ModuleInstance::instantiate`].
+/// This is used to link a module instance to its imports.
+/// Imports can either be defined one by one with [`Imports::define`] or be taken from the exports of
+/// another instance with [`Imports::link_instance`].
+/// Now, the imports object can be passed to [`Instance::instantiate`].
+pub struct Imports<T = (), S: Threading = Local> {
+    pub(crate) values: BTreeMap<ExternName, Extern<T, S>>,
+    pub(crate) linked: BTreeMap<String, LinkedInstance<T, S>>,
+}
 
-// #[derive(Clone)]
-pub struct Imports {
-    values: BTreeMap<ExternName, Extern>,
+impl<T, S: Threading> Default for Imports<T, S> {
+    fn default() -> Self {
+        Imports { values: BTreeMap::new(), linked: BTreeMap::new() }
+    }
 }
 
 pub(crate) struct ResolvedImports {
@@ -226,29 +436,50 @@ impl ResolvedImports {
     }
 }
 
-impl Imports {
-    /// Create a new empty import set
+impl<T> Imports<T> {
+    /// Create a new empty import set with [`Local`] host functions
+    ///
+    /// Use [`Imports::default`] for other kinds of host functions.
     pub fn new() -> Self {
-        Imports { values: BTreeMap::new() }
+        Self::default()
     }
+}
 
+impl<T, S: Threading> Imports<T, S> {
     /// Merge two import sets
     pub fn merge(mut self, other: Self) -> Self {
         self.values.extend(other.values);
+        self.linked.extend(other.linked);
         self
     }
 
     /// Define an import
-    pub fn define(&mut self, module: &str, name: &str, value: Extern) -> Result<&mut Self> {
-        self.values.insert(ExternName { module: module.to_string(), name: name.to_string() }, value);
+    pub fn define(&mut self, module: &str, name: &str, value: Extern<T, S>) -> Result<&mut Self> {
+        self.values.insert(ExternName::new(module, name), value);
+        Ok(self)
+    }
+
+    /// Link the exports of an instance, which are imported under the given module name
+    ///
+    /// The store of the instance is copied into every instance created with these imports, so both instances
+    /// don't share any state afterwards. Host functions of the linked instance are called with the user data of
+    /// the instance they were linked into. Imports defined with [`Imports::define`] take precedence.
+    pub fn link_instance(&mut self, module: &str, instance: &Instance<T, S>) -> Result<&mut Self> {
+        self.linked.insert(module.to_string(), LinkedInstance::new(instance)?);
         Ok(self)
     }
 
-    pub(crate) fn take(&mut self, import: &Import) -> Option<Extern> {
+    pub(crate) fn take(&mut self, import: &Import) -> Option<Extern<T, S>> {
         let name = ExternName::from(import);
         self.values.remove(&name)
     }
 
+    pub(crate) fn contains(&self, import: &Import) -> bool {
+        self.values.contains_key(&ExternName::from(import))
+    }
+}
+
+impl Imports {
     pub(crate) fn compare_types<T: Debug + PartialEq>(import: &Import, actual: &T, expected: &T) -> Result<()> {
         if expected != actual {
             return Err(LinkingError::incompatible_import_type(import).into());
@@ -300,8 +531,194 @@ impl Imports {
 
         Ok(())
     }
+}
 
-    // pub(crate) fn link(mut self, instance: &mut Instance) -> Result<ResolvedImports> {
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::exec::CallResultTyped;
+    use crate::types::Backend;
+
+    #[test]
+    fn test_nested_call_with_pause() {
+        let wasm = wat::parse_str(
+            r#"
+            (module
+                (import "env" "pause" (func $pause))
+                (import "env" "apply" (func $apply (param i32) (result i32)))
+                (func (export "double") (param i32) (result i32)
+                    (call $pause)
+                    (i32.mul (local.get 0) (i32.const 2)))
+                (func (export "main") (result i32)
+                    (i32.add (call $apply (i32.const 20)) (i32.const 2))))
+            "#,
+        )
+        .expect("invalid wat");
+        let module = crate::parse_bytes(&wasm).expect("failed to parse module");
+
+        let mut imports = Imports::new();
+        imports
+            .define("env", "pause", Extern::typed_func(|_, ()| -> Result<()> { Err(Error::PauseExecution) }))
+            .unwrap();
+        imports
+            .define(
+                "env",
+                "apply",
+                Extern::typed_func(|mut ctx: FuncContext<'_>, x: i32| {
+                    let double = ctx.exported_func::<i32, i32>("double")?;
+                    ctx.call_typed(&double, x)
+                }),
+            )
+            .unwrap();
+
+        let (instance, _, _) = Instance::instantiate(module, imports, (), None).expect("failed to instantiate");
+        let main = instance.exported_func::<(), i32>("main").unwrap();
+        let mut exec_handle = main.call(instance, (), None).unwrap();
+
+        // the pause inside `double` only takes effect after `apply` returned
+        assert!(matches!(exec_handle.run(1000).unwrap(), CallResultTyped::Incomplete));
+        assert!(matches!(exec_handle.run(1000).unwrap(), CallResultTyped::Done(42)));
+    }
 
-    // }
+    #[test]
+    fn test_nested_call_budget() {
+        let wasm = wat::parse_str(
+            r#"
+            (module
+                (import "env" "apply" (func $apply (param i32) (result i32)))
+                (func (export "count") (param i32) (result i32)
+                    (loop $continue
+                        (br_if $continue (local.tee 0 (i32.sub (local.get 0) (i32.const 1)))))
+                    (i32.const 7))
+                (func (export "main") (result i32)
+                    (call $apply (i32.const 100))))
+            "#,
+        )
+        .expect("invalid wat");
+
+        for backend in [Backend::Stack, Backend::Slots] {
+            let mut module = crate::parse_bytes(&wasm).expect("failed to parse module");
+            module.set_backend(backend).unwrap();
+            let start = || {
+                let mut imports = Imports::new();
+                let apply = Extern::typed_func(|mut ctx: FuncContext<'_>, x: i32| {
+                    let count = ctx.exported_func::<i32, i32>("count")?;
+                    ctx.call_typed(&count, x)
+                });
+                imports.define("env", "apply", apply).unwrap();
+                let (instance, _, _) = Instance::instantiate(module.clone(), imports, (), None).unwrap();
+                let main = instance.exported_func::<(), i32>("main").unwrap();
+                main.call(instance, (), None).unwrap()
+            };
+
+            // the instructions of the nested call are counted
+            let mut exec_handle = start();
+            assert!(matches!(exec_handle.run(usize::MAX).unwrap(), CallResultTyped::Done(7)));
+            let cycles = exec_handle.cycles();
+            assert!(cycles > 300, "{backend:?}");
+
+            // and can not exceed the budget of the run, since the nested call can not be suspended
+            let mut exec_handle = start();
+            assert!(matches!(exec_handle.run(100), Err(Error::Other(_))), "{backend:?}");
+            let mut exec_handle = start();
+            assert!(exec_handle.run_to(cycles / 2).is_err(), "{backend:?}");
+            assert!(exec_handle.cycles() <= cycles / 2, "{backend:?}");
+
+            let mut exec_handle = start();
+            assert!(matches!(exec_handle.run_to(cycles).unwrap(), CallResultTyped::Done(7)), "{backend:?}");
+            assert_eq!(exec_handle.cycles(), cycles);
+        }
+    }
+
+    #[test]
+    fn test_exported_globals_and_tables() {
+        let wasm = wat::parse_str(
+            r#"
+            (module
+                (import "env" "bump" (func $bump))
+                (global $flag (export "flag") (mut i32) (i32.const 1))
+                (global (export "limit") i64 (i64.const 7))
+                (table (export "table") 2 funcref)
+                (elem (i32.const 1) $bump)
+                (func (export "main") (result i32)
+                    (call $bump)
+                    (global.get $flag)))
+            "#,
+        )
+        .expect("invalid wat");
+        let module = crate::parse_bytes(&wasm).expect("failed to parse module");
+
+        let mut imports = Imports::new();
+        imports
+            .define(
+                "env",
+                "bump",
+                Extern::typed_func(|mut ctx: FuncContext<'_>, ()| {
+                    assert_eq!(ctx.exported_table("table")?.size(), 2);
+                    assert!(ctx.exported_global_mut("limit")?.set(WasmValue::I64(8)).is_err());
+
+                    let mut flag = ctx.exported_global_mut("flag")?;
+                    let WasmValue::I32(val) = flag.get() else { panic!("wrong global type") };
+                    flag.set(WasmValue::I32(val + 41))
+                }),
+            )
+            .unwrap();
+
+        let (mut instance, _, _) = Instance::instantiate(module, imports, (), None).expect("failed to instantiate");
+        let main = instance.exported_func::<(), i32>("main").unwrap();
+        assert_eq!(main.invoke(&mut instance, ()).unwrap(), 42);
+
+        assert_eq!(instance.exported_global("flag").unwrap().get(), WasmValue::I32(42));
+        assert_eq!(instance.exported_global("limit").unwrap().get(), WasmValue::I64(7));
+        assert!(matches!(instance.exported_table("table").unwrap().get(1), Ok(WasmValue::RefFunc(0))));
+    }
+
+    #[test]
+    fn test_local_and_shared_host_funcs() {
+        let wasm = wat::parse_str(
+            r#"
+            (module
+                (import "env" "count" (func $count (result i32)))
+                (func (export "main") (result i32)
+                    (drop (call $count))
+                    (call $count)))
+            "#,
+        )
+        .expect("invalid wat");
+        let module = crate::parse_bytes(&wasm).expect("failed to parse module");
+
+        // local host functions can capture values which are not `Send + Sync`
+        let counter = alloc::rc::Rc::new(core::cell::Cell::new(0));
+        let local_counter = counter.clone();
+        let mut imports = Imports::new();
+        imports
+            .define(
+                "env",
+                "count",
+                Extern::typed_func(move |_, ()| -> Result<i32> {
+                    local_counter.set(local_counter.get() + 1);
+                    Ok(local_counter.get())
+                }),
+            )
+            .unwrap();
+        let (mut instance, _, _) = Instance::instantiate(module.clone(), imports, (), None).unwrap();
+        let main = instance.exported_func::<(), i32>("main").unwrap();
+        assert_eq!(main.invoke(&mut instance, ()).unwrap(), 2);
+        assert_eq!(counter.get(), 2);
+
+        // shared host functions only need the captured value to be `Send + Sync`
+        let mut imports = Imports::<u32, Shared>::default();
+        imports
+            .define(
+                "env",
+                "count",
+                Extern::typed_func_with(Arc::new(10), |step, mut ctx: FuncContext<'_, u32, Shared>, ()| {
+                    *ctx.data_mut() += **step;
+                    Ok(*ctx.data() as i32)
+                }),
+            )
+            .unwrap();
+        let (mut instance, _, _) = Instance::instantiate(module, imports, 0, None).unwrap();
+        assert_eq!(main.invoke(&mut instance, ()).unwrap(), 20);
+    }
 }
@@ -1,10 +1,13 @@
 use alloc::{format, string::ToString, vec::Vec};
 
+use crate::encoding;
 use crate::error::{Error, LinkingError, Result, Trap};
 use crate::exec::DeserializationState;
 use crate::func::{FromWasmValueTuple, FuncHandle, FuncHandleTyped, IntoWasmValueTuple};
-use crate::imports::{Extern, Function, Imports, ResolvedImports};
-use crate::reference::{MemoryRef, MemoryRefMut};
+use crate::imports::{Extern, ExternName, Function, Imports, Local, ResolvedImports, Threading};
+use crate::link::{LinkedExtern, Linker};
+use crate::parser::{slots, Parser};
+use crate::reference::{GlobalRef, GlobalRefMut, MemoryRef, MemoryRefMut, TableRef};
 use crate::runtime::{RawWasmValue, Stack};
 use crate::store::{
     data::DataInstance,
@@ -14,32 +17,54 @@ use crate::store::{
     table::{TableElement, TableInstance},
 };
 use crate::types::{
-    instructions::ConstInstruction, Addr, Data, DataAddr, DataKind, ElementItem, ElementKind, ExternVal, FuncAddr,
-    FuncType, Global, GlobalAddr, ImportKind, MemAddr, MemoryArch, MemoryType, Module, TableAddr, TableType,
-    WasmFunction,
+    instructions::ConstInstruction, Addr, Backend, Data, DataAddr, DataKind, ElementItem, ElementKind, ExternVal,
+    FuncAddr, FuncType, Global, GlobalAddr, Import, ImportKind, MemAddr, MemoryArch, MemoryType, Module, TableAddr,
+    TableType, WasmFunction,
 };
-use crate::{VecExt, CALL_STACK_SIZE};
+use crate::{VecExt, CALL_STACK_SIZE, DETERMINISTIC_MAX_PAGES};
 
 /// An instantiated Wasm module on which function can be called
+///
+/// The instance owns a value of the user data type `T`, which host functions can access through their
+/// [`FuncContext`](crate::imports::FuncContext).
+///
+/// Instances with [`Shared`](crate::imports::Shared) host functions are `Send` whenever `T` is, so a paused execution
+/// can be resumed on another thread.
 #[allow(dead_code)]
-#[derive(Debug, Default)]
-pub struct Instance {
+#[derive(Debug)]
+pub struct Instance<T = (), S: Threading = Local> {
     pub(crate) module: Module,
 
-    pub(crate) funcs: Vec<Function>,
+    pub(crate) funcs: Vec<Function<T, S>>,
     pub(crate) tables: Vec<TableInstance>,
     pub(crate) memories: Vec<MemoryInstance>,
     pub(crate) globals: Vec<GlobalInstance>,
     pub(crate) elements: Vec<ElementInstance>,
     pub(crate) data: Vec<DataInstance>,
+
+    pub(crate) user_data: T,
+
+    /// See [`Instance::set_deterministic`]
+    pub(crate) deterministic: bool,
 }
 
-impl Instance {
+impl<T, S: Threading> Instance<T, S> {
     /// Instantiate the module with the given imports
-    fn instantiate_raw(module: Module, imports: Imports) -> Result<Self> {
-        let mut instance = Instance { module, ..Default::default() };
+    fn instantiate_raw(module: Module, mut imports: Imports<T, S>, user_data: T) -> Result<Self> {
+        let mut instance = Instance {
+            module,
+            funcs: Vec::new(),
+            tables: Vec::new(),
+            memories: Vec::new(),
+            globals: Vec::new(),
+            elements: Vec::new(),
+            data: Vec::new(),
+            user_data,
+            deterministic: false,
+        };
 
-        let mut addrs = instance.resolve_imports(imports)?;
+        let mut linker = Linker::new(&instance.module, &mut imports)?;
+        let mut addrs = instance.resolve_imports(imports, &mut linker)?;
 
         addrs.funcs.extend(instance.init_funcs(instance.module.funcs.clone().into())?);
         addrs.tables.extend(instance.init_tables(instance.module.table_types.clone().into())?);
@@ -58,25 +83,44 @@ impl Instance {
             return Err(Error::Trap(trap));
         }
 
+        linker.append(&mut instance);
+
         Ok(instance)
     }
 
     /// Instantiate the module with the given imports and maybe restore state to resume execution of a function
+    ///
+    /// Fails with [`Error::InvalidStore`] if the state has a different number of memories or globals than the instance.
     pub fn instantiate(
         module: Module,
-        imports: Imports,
+        imports: Imports<T, S>,
+        data: T,
         state: Option<&[u8]>,
     ) -> Result<(Self, Option<Stack>, Vec<u8>)> {
-        let mut instance = Self::instantiate_raw(module, imports)?;
+        let mut instance = Self::instantiate_raw(module, imports, data)?;
 
         match state {
             Some(state) => {
-                let decoder = flate2::read::GzDecoder::new(std::io::Cursor::new(state));
-                let mut state: DeserializationState = bincode::deserialize_from(decoder)?;
+                let mut state: DeserializationState = encoding::deserialize_gzip(state)?;
+                if state.stack.backend != instance.module.backend {
+                    return Err(Error::Other(format!(
+                        "snapshot was taken with the {:?} backend, but the module uses the {:?} backend",
+                        state.stack.backend, instance.module.backend
+                    )));
+                }
                 state.stack.call_stack.0.reserve_exact(CALL_STACK_SIZE);
+                for frame in state.stack.call_stack.0.iter() {
+                    instance.translate_func(frame.func_instance)?;
+                }
 
-                instance.memories[0] = state.memory;
+                // a snapshot of another module, or linked against other instances, can't be restored
+                if state.memories.len() != instance.memories.len() || state.globals.len() != instance.globals.len() {
+                    return Err(Error::InvalidStore);
+                }
+                instance.memories.iter_mut().zip(state.memories).for_each(|(m, state)| *m = state);
                 instance.globals.iter_mut().zip(state.globals.iter()).for_each(|(g, v)| g.value = *v);
+                // The execution has to continue like it started, whichever node resumes it
+                instance.set_deterministic(state.deterministic)?;
 
                 Ok((instance, Some(state.stack), state.extra_data))
             }
@@ -84,6 +128,63 @@ impl Instance {
         }
     }
 
+    /// Enable or disable the deterministic mode, it is disabled by default.
+    ///
+    /// In deterministic mode, every NaN produced by a float instruction is replaced by the positive canonical NaN,
+    /// whose bits don't depend on the platform, and memories can't grow beyond [`DETERMINISTIC_MAX_PAGES`].
+    /// Together, an execution then results in the same memory on every node, so it can be moved between
+    /// native and browser nodes and verified by executing it again.
+    /// The mode is part of snapshots, an instance restored from one continues in the mode the snapshot was taken in.
+    ///
+    /// Enabling it fails if a memory of the instance, including imported ones and those restored from a snapshot,
+    /// is already larger than [`DETERMINISTIC_MAX_PAGES`].
+    pub fn set_deterministic(&mut self, enabled: bool) -> Result<()> {
+        let too_large = self.memories.iter().map(|mem| mem.page_count()).find(|&pages| pages > DETERMINISTIC_MAX_PAGES);
+        if let (true, Some(pages)) = (enabled, too_large) {
+            return Err(Error::UnsupportedFeature(format!(
+                "memory of {pages} pages in deterministic mode, at most {DETERMINISTIC_MAX_PAGES} are supported"
+            )));
+        }
+
+        self.deterministic = enabled;
+        Ok(())
+    }
+
+    /// Whether the deterministic mode is enabled, see [`Instance::set_deterministic`]
+    pub fn is_deterministic(&self) -> bool {
+        self.deterministic
+    }
+
+    /// Execute `memory.grow`, returning the previous page count or -1 if the memory could not grow
+    pub(crate) fn grow_memory(&mut self, addr: MemAddr, pages_delta: i32) -> Result<i32> {
+        let deterministic = self.deterministic;
+        let mem = self.get_mem_mut(addr)?;
+        let prev_size = mem.page_count() as i32;
+
+        if deterministic && prev_size as i64 + pages_delta as i64 > DETERMINISTIC_MAX_PAGES as i64 {
+            return Ok(-1);
+        }
+        Ok(match mem.grow(pages_delta) {
+            Some(_) => prev_size,
+            None => -1,
+        })
+    }
+
+    /// Get a reference to the user data
+    pub fn data(&self) -> &T {
+        &self.user_data
+    }
+
+    /// Get a mutable reference to the user data
+    pub fn data_mut(&mut self) -> &mut T {
+        &mut self.user_data
+    }
+
+    /// Consume the instance and return the user data
+    pub fn into_data(self) -> T {
+        self.user_data
+    }
+
     /// Get a export by name
     pub(crate) fn export_addr(&self, name: &str) -> Option<ExternVal> {
         let export = self.module.exports.iter().find(|e| e.name == name.into())?;
@@ -91,13 +192,53 @@ impl Instance {
         Some(ExternVal::new(export.kind, export.index))
     }
 
+    /// Get the name of the import a function was provided by, if any
+    pub(crate) fn func_import_name(&self, addr: FuncAddr) -> Option<ExternName> {
+        let mut func_imports = self.module.imports.iter().filter(|i| matches!(i.kind, ImportKind::Function(_)));
+        func_imports.nth(addr as usize).map(ExternName::from)
+    }
+
+    /// Translate a lazily parsed function before its first call, see [`parse_bytes_lazy`](crate::parse_bytes_lazy)
+    ///
+    /// The translation is kept in this instance's copy of the module only, the module cache and other instances of the
+    /// module still translate the function on their own. Sharing it would need a lock around the functions of every
+    /// module, which the interpreter can't provide without `std`.
+    #[cold]
+    pub(crate) fn translate_func(&mut self, addr: FuncAddr) -> Result<()> {
+        let Some(Function::Wasm(func)) = self.funcs.get(addr as usize) else { return Ok(()) };
+        let Some(body) = &func.lazy_body else { return Ok(()) };
+
+        let instructions = Parser::translate(body, self.module.superinstructions)?;
+        let mut func = WasmFunction {
+            instructions,
+            locals: func.locals.clone(),
+            ty: func.ty.clone(),
+            slots: None,
+            lazy_body: None,
+        };
+        if self.module.backend == Backend::Slots {
+            let callee_ty = |addr: FuncAddr| self.funcs.get(addr as usize).map(Function::ty);
+            func.slots = Some(slots::lower(&func, &self.module.func_types, callee_ty)?);
+        }
+
+        // functions of the module are placed behind the imported functions
+        let imported = self.module.imports.iter().filter(|i| matches!(i.kind, ImportKind::Function(_))).count();
+        if let Some(cached) = (addr as usize).checked_sub(imported).and_then(|idx| self.module.funcs.get_mut(idx)) {
+            *cached = func.clone();
+        }
+        self.funcs[addr as usize] = Function::Wasm(func);
+        Ok(())
+    }
+
     #[inline]
     pub(crate) fn func_ty(&self, addr: FuncAddr) -> &FuncType {
         self.module.func_types.get(addr as usize).expect("No func type for func, this is a bug")
     }
 
     /// Get an exported function by name
-    pub fn exported_func_untyped(self, name: &str) -> Result<FuncHandle> {
+    ///
+    /// The returned handle does not borrow the instance and can be called multiple times.
+    pub fn exported_func_untyped(&self, name: &str) -> Result<FuncHandle> {
         let export = self.export_addr(name).ok_or_else(|| Error::Other(format!("Export not found: {}", name)))?;
         let ExternVal::Func(func_addr) = export else {
             return Err(Error::Other(format!("Export is not a function: {}", name)));
@@ -106,11 +247,11 @@ impl Instance {
         let func_inst = self.get_func(func_addr)?;
         let ty = func_inst.ty();
 
-        Ok(FuncHandle { addr: func_addr, name: Some(name.to_string()), ty: ty.clone(), instance: self })
+        Ok(FuncHandle { addr: func_addr, name: Some(name.to_string()), ty: ty.clone() })
     }
 
     /// Get a typed exported function by name
-    pub fn exported_func<P, R>(self, name: &str) -> Result<FuncHandleTyped<P, R>>
+    pub fn exported_func<P, R>(&self, name: &str) -> Result<FuncHandleTyped<P, R>>
     where
         P: IntoWasmValueTuple,
         R: FromWasmValueTuple,
@@ -139,6 +280,36 @@ impl Instance {
         self.memory_mut(mem_addr)
     }
 
+    /// Get an exported global by name
+    pub fn exported_global(&self, name: &str) -> Result<GlobalRef<'_>> {
+        let export = self.export_addr(name).ok_or_else(|| Error::Other(format!("Export not found: {}", name)))?;
+        let ExternVal::Global(global_addr) = export else {
+            return Err(Error::Other(format!("Export is not a global: {}", name)));
+        };
+
+        Ok(GlobalRef { instance: self.globals.get_or_instance(global_addr, "global")? })
+    }
+
+    /// Get an exported global by name
+    pub fn exported_global_mut(&mut self, name: &str) -> Result<GlobalRefMut<'_>> {
+        let export = self.export_addr(name).ok_or_else(|| Error::Other(format!("Export not found: {}", name)))?;
+        let ExternVal::Global(global_addr) = export else {
+            return Err(Error::Other(format!("Export is not a global: {}", name)));
+        };
+
+        Ok(GlobalRefMut { instance: self.globals.get_mut_or_instance(global_addr, "global")? })
+    }
+
+    /// Get an exported table by name
+    pub fn exported_table(&self, name: &str) -> Result<TableRef<'_>> {
+        let export = self.export_addr(name).ok_or_else(|| Error::Other(format!("Export not found: {}", name)))?;
+        let ExternVal::Table(table_addr) = export else {
+            return Err(Error::Other(format!("Export is not a table: {}", name)));
+        };
+
+        Ok(TableRef { instance: self.get_table(table_addr)? })
+    }
+
     /// Get a memory by address
     pub(crate) fn memory(&self, addr: MemAddr) -> Result<MemoryRef<'_>> {
         let mem = self.get_mem(addr)?;
@@ -152,52 +323,74 @@ impl Instance {
     }
 }
 
+impl<T: serde::de::DeserializeOwned, S: Threading> Instance<T, S> {
+    /// Like [`Instance::instantiate`], but the user data is restored from the state if there is one
+    ///
+    /// The state has to be created by [`ExecHandle::serialize_with_data`](crate::exec::ExecHandle::serialize_with_data)
+    /// and `data` is only used when starting a new execution.
+    pub fn instantiate_with_data(
+        module: Module,
+        imports: Imports<T, S>,
+        data: T,
+        state: Option<&[u8]>,
+    ) -> Result<(Self, Option<Stack>)> {
+        let (mut instance, stack, extra_data) = Self::instantiate(module, imports, data, state)?;
+        if stack.is_some() {
+            instance.user_data = encoding::deserialize(&extra_data)?;
+        }
+
+        Ok((instance, stack))
+    }
+}
+
 impl Instance {
     #[cold]
     pub(crate) fn not_found_error(name: &str) -> Error {
         Error::Other(format!("{} not found", name))
     }
+}
 
+impl<T, S: Threading> Instance<T, S> {
     /// Get the function at the actual index in the store
     #[inline]
-    pub(crate) fn get_func(&self, addr: FuncAddr) -> Result<&Function> {
-        self.funcs.get(addr as usize).ok_or_else(|| Self::not_found_error("function"))
+    pub(crate) fn get_func(&self, addr: FuncAddr) -> Result<&Function<T, S>> {
+        self.funcs.get(addr as usize).ok_or_else(|| Instance::not_found_error("function"))
     }
 
     /// Get the memory at the actual index in the store
     #[inline]
     pub(crate) fn get_mem(&self, addr: MemAddr) -> Result<&MemoryInstance> {
-        self.memories.get(addr as usize).ok_or_else(|| Self::not_found_error("memory"))
+        self.memories.get(addr as usize).ok_or_else(|| Instance::not_found_error("memory"))
     }
 
     /// Get the mut memory at the actual index in the store
     #[inline]
     pub(crate) fn get_mem_mut(&mut self, addr: MemAddr) -> Result<&mut MemoryInstance> {
-        self.memories.get_mut(addr as usize).ok_or_else(|| Self::not_found_error("memory"))
+        self.memories.get_mut(addr as usize).ok_or_else(|| Instance::not_found_error("memory"))
     }
 
     /// Get the table at the actual index in the store
     #[inline]
     pub(crate) fn get_table(&self, addr: TableAddr) -> Result<&TableInstance> {
-        self.tables.get(addr as usize).ok_or_else(|| Self::not_found_error("table"))
+        self.tables.get(addr as usize).ok_or_else(|| Instance::not_found_error("table"))
     }
 
     /// Get the table at the actual index in the store
     #[inline]
     pub(crate) fn get_table_mut(&mut self, addr: TableAddr) -> Result<&mut TableInstance> {
-        self.tables.get_mut(addr as usize).ok_or_else(|| Self::not_found_error("table"))
+        self.tables.get_mut(addr as usize).ok_or_else(|| Instance::not_found_error("table"))
     }
 
     /// Get the data at the actual index in the store
     #[inline]
     pub(crate) fn get_data_mut(&mut self, addr: DataAddr) -> Result<&mut DataInstance> {
-        self.data.get_mut(addr as usize).ok_or_else(|| Self::not_found_error("data"))
+        self.data.get_mut(addr as usize).ok_or_else(|| Instance::not_found_error("data"))
     }
 
     /// Get the global at the actual index in the store
     #[inline]
-    pub fn get_global_val(&self, addr: MemAddr) -> Result<RawWasmValue> {
-        self.globals.get(addr as usize).ok_or_else(|| Self::not_found_error("global")).map(|global| global.value)
+    pub(crate) fn get_global_val(&self, addr: MemAddr) -> Result<RawWasmValue> {
+        self.globals.get(addr as usize).ok_or_else(|| Instance::not_found_error("global")).map(|global| global.value)
     }
 
     /// Set the global at the actual index in the store
@@ -209,18 +402,26 @@ impl Instance {
     }
 }
 
-impl Instance {
-    pub(crate) fn resolve_imports(&mut self, mut imports: Imports) -> Result<ResolvedImports> {
+impl<T, S: Threading> Instance<T, S> {
+    pub(crate) fn resolve_imports(
+        &mut self,
+        mut imports: Imports<T, S>,
+        linker: &mut Linker<T, S>,
+    ) -> Result<ResolvedImports> {
         let mut addrs = ResolvedImports::new();
 
-        for import in self.module.imports.iter() {
-            let val = imports.take(import).ok_or_else(|| LinkingError::unknown_import(import))?;
+        for import in self.module.imports.clone().iter() {
+            let Some(val) = imports.take(import) else {
+                let linked = linker.take(import).ok_or_else(|| LinkingError::unknown_import(import))??;
+                self.add_linked(import, linked, &mut addrs)?;
+                continue;
+            };
 
             // A link to something that needs to be added to the store
             match (val, &import.kind) {
                 (Extern::Global { ty, val }, ImportKind::Global(import_ty)) => {
                     Imports::compare_types(import, &ty, import_ty)?;
-                    addrs.globals.push(self.globals.add(GlobalInstance::new(val.into())) as u32);
+                    addrs.globals.push(self.globals.add(GlobalInstance::new(ty, val.into())) as u32);
                 }
                 (Extern::Table { ty, .. }, ImportKind::Table(import_ty)) => {
                     Imports::compare_table_types(import, &ty, import_ty)?;
@@ -250,6 +451,38 @@ impl Instance {
         Ok(addrs)
     }
 
+    /// Add an import from a linked instance to the store, its items are already relocated
+    fn add_linked(&mut self, import: &Import, linked: LinkedExtern<T, S>, addrs: &mut ResolvedImports) -> Result<()> {
+        match (linked, &import.kind) {
+            (LinkedExtern::Global(global), ImportKind::Global(import_ty)) => {
+                Imports::compare_types(import, &global.ty, import_ty)?;
+                addrs.globals.push(self.globals.add(global) as u32);
+            }
+            (LinkedExtern::Table(table), ImportKind::Table(import_ty)) => {
+                let ty = TableType { size_initial: table.size() as u32, ..table.kind };
+                Imports::compare_table_types(import, &ty, import_ty)?;
+                addrs.tables.push(self.tables.add(table) as u32);
+            }
+            (LinkedExtern::Memory(memory), ImportKind::Memory(import_ty)) => {
+                Imports::compare_memory_types(import, &memory.kind, import_ty, Some(memory.page_count()))?;
+                addrs.memories.push(self.memories.add(memory) as u32);
+            }
+            (LinkedExtern::Func(func), ImportKind::Function(ty)) => {
+                let import_func_type = self
+                    .module
+                    .func_types
+                    .get(*ty as usize)
+                    .ok_or_else(|| LinkingError::incompatible_import_type(import))?;
+
+                Imports::compare_types(import, func.ty(), import_func_type)?;
+                addrs.funcs.push(self.funcs.add(func) as u32);
+            }
+            _ => return Err(LinkingError::incompatible_import_type(import).into()),
+        }
+
+        Ok(())
+    }
+
     /// Add functions to the store, returning their addresses in the store
     pub(crate) fn init_funcs(&mut self, funcs: Vec<WasmFunction>) -> Result<Vec<FuncAddr>> {
         let func_count = self.funcs.len();
@@ -298,7 +531,8 @@ impl Instance {
         let mut global_addrs = imported_globals;
 
         for (i, global) in new_globals.iter().enumerate() {
-            self.globals.push(GlobalInstance::new(self.eval_const(&global.init, &global_addrs, func_addrs)?));
+            self.globals
+                .push(GlobalInstance::new(global.ty, self.eval_const(&global.init, &global_addrs, func_addrs)?));
             global_addrs.push((i + global_count) as Addr);
         }
 
@@ -0,0 +1,60 @@
+//! Writers used to serialize snapshots and cached modules
+//!
+//! With the `std` feature, every [`std::io::Write`] is a [`Write`]. Without it, [`Write`] is implemented for
+//! `Vec<u8>` and can be implemented for any other sink, like the flash storage or sealed memory of a node.
+
+#[cfg(not(feature = "std"))]
+use alloc::vec::Vec;
+
+use crate::error::Result;
+
+/// A sink for serialized bytes
+pub trait Write {
+    /// Write the whole buffer
+    fn write_all(&mut self, buf: &[u8]) -> Result<()>;
+}
+
+#[cfg(feature = "std")]
+impl<W: std::io::Write + ?Sized> Write for W {
+    fn write_all(&mut self, buf: &[u8]) -> Result<()> {
+        Ok(std::io::Write::write_all(self, buf)?)
+    }
+}
+
+#[cfg(not(feature = "std"))]
+impl Write for Vec<u8> {
+    fn write_all(&mut self, buf: &[u8]) -> Result<()> {
+        self.extend_from_slice(buf);
+        Ok(())
+    }
+}
+
+#[cfg(not(feature = "std"))]
+impl<W: Write + ?Sized> Write for &mut W {
+    fn write_all(&mut self, buf: &[u8]) -> Result<()> {
+        (**self).write_all(buf)
+    }
+}
+
+/// Adapter to pass a [`Write`] to writers of the standard library
+#[cfg(feature = "std")]
+pub(crate) struct StdWriter<W>(pub(crate) W);
+
+#[cfg(feature = "std")]
+impl<W: Write> std::io::Write for StdWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        std::io::Write::write_all(self, buf)?;
+        Ok(buf.len())
+    }
+
+    fn write_all(&mut self, buf: &[u8]) -> std::io::Result<()> {
+        Write::write_all(&mut self.0, buf).map_err(|err| match err {
+            crate::Error::Io(err) => err,
+            err => std::io::Error::other(err),
+        })
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
@@ -1,4 +1,4 @@
-// #![no_std]
+#![cfg_attr(not(feature = "std"), no_std)]
 #![warn(missing_docs, missing_debug_implementations, rust_2018_idioms, unreachable_pub)]
 
 //! A tiny WebAssembly Runtime written in Rust
@@ -9,7 +9,9 @@
 //!
 //! ## Features
 //!- **`std`**\
-//!  Enables the use of `std` and `std::io` for parsing from files and streams. This is enabled by default.
+//!  Enables the use of `std` and `std::io` for parsing from files and streams, and the [`record`] module for
+//!  recording and replaying host calls. This is enabled by default.
+//!  Without it, the crate only needs `alloc` and snapshots are written to any [`io::Write`].
 //!
 //! ## Getting Started
 //! The easiest way to get started is to use the [`Module::parse_bytes`] function to load a
@@ -26,13 +28,18 @@
 
 extern crate alloc;
 
+mod encoding;
 pub mod error;
 pub mod exec;
 pub mod func;
 pub mod imports;
 mod instance;
+pub mod io;
+mod link;
 mod module;
 mod parser;
+#[cfg(feature = "std")]
+pub mod record;
 pub mod reference;
 mod runtime;
 mod store;
@@ -40,7 +47,7 @@ pub mod types;
 
 pub use error::Error;
 pub use instance::Instance;
-pub use module::parse_bytes;
+pub use module::{parse_bytes, parse_bytes_lazy, MODULE_CACHE_VERSION};
 pub use types::Module;
 
 pub(crate) const CALL_STACK_SIZE: usize = 1024;
@@ -50,6 +57,10 @@ pub const PAGE_SIZE: usize = 65536;
 /// Max number of pages for a Wasm module
 pub const MAX_PAGES: usize = 65536;
 const MAX_SIZE: u64 = PAGE_SIZE as u64 * MAX_PAGES as u64;
+/// Max number of pages of a memory in deterministic mode, see [`Instance::set_deterministic`]
+///
+/// Larger memories may not fit into the address space or memory of every node, so growing beyond fails everywhere.
+pub const DETERMINISTIC_MAX_PAGES: usize = 16384;
 
 #[cold]
 pub(crate) fn cold() {}
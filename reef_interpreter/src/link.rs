@@ -0,0 +1,676 @@
+//! Linking the exports of an instance into another instance
+//!
+//! An instance only has a single store, in which the addresses are the indices of its module.
+//! To link an instance, its store is copied into the store of the new instance:
+//! imported memories, tables and globals are placed at the address of the import and all other
+//! items are appended behind the items of the new module. The code of the linked functions is
+//! relocated to the new addresses, so the linked instance keeps working on its own items.
+
+use alloc::{boxed::Box, collections::BTreeMap, format, string::String, vec, vec::Vec};
+
+use crate::error::{Error, LinkingError, Result};
+use crate::imports::{Function, Imports, Threading};
+use crate::instance::Instance;
+use crate::parser::{slots, Parser};
+use crate::runtime::RawWasmValue;
+use crate::store::{
+    data::DataInstance, element::ElementInstance, global::GlobalInstance, memory::MemoryInstance, table::TableInstance,
+};
+use crate::types::{
+    instructions::{BlockArgs, Instruction},
+    value::{ValType, WasmValue},
+    Addr, Backend, Export, ExternVal, ExternalKind, FuncType, Import, ImportKind, Module, TypeAddr,
+};
+
+/// Copy of the store of an instance, created by [`Imports::link_instance`]
+#[derive(Debug)]
+pub(crate) struct LinkedInstance<T, S: Threading> {
+    exports: Box<[Export]>,
+    func_types: Box<[FuncType]>,
+
+    funcs: Vec<Function<T, S>>,
+    tables: Vec<TableInstance>,
+    memories: Vec<MemoryInstance>,
+    globals: Vec<GlobalInstance>,
+    elements: Vec<ElementInstance>,
+    data: Vec<DataInstance>,
+}
+
+impl<T, S: Threading> LinkedInstance<T, S> {
+    /// Copy the store of `instance`, functions which were not translated yet are translated for relocation
+    pub(crate) fn new(instance: &Instance<T, S>) -> Result<Self> {
+        let mut funcs = instance.funcs.clone();
+        for func in funcs.iter_mut() {
+            if let Function::Wasm(func) = func {
+                if let Some(body) = func.lazy_body.take() {
+                    func.instructions = Parser::translate(&body, instance.module.superinstructions)?;
+                }
+            }
+        }
+
+        Ok(Self {
+            exports: instance.module.exports.clone(),
+            func_types: instance.module.func_types.clone(),
+            funcs,
+            tables: instance.tables.clone(),
+            memories: instance.memories.clone(),
+            globals: instance.globals.clone(),
+            elements: instance.elements.clone(),
+            data: instance.data.clone(),
+        })
+    }
+
+    fn export(&self, name: &str) -> Option<ExternVal> {
+        let export = self.exports.iter().find(|e| &*e.name == name)?;
+        Some(ExternVal::new(export.kind, export.index))
+    }
+}
+
+/// An import which is satisfied by a linked instance
+pub(crate) enum LinkedExtern<T, S: Threading> {
+    Func(Function<T, S>),
+    Table(TableInstance),
+    Memory(MemoryInstance),
+    Global(GlobalInstance),
+}
+
+/// Maps the addresses of one kind of item in a linked store to addresses in the new store
+#[derive(Debug)]
+struct AddrMap {
+    /// Address of the import slot, if the item is imported by the new module
+    shared: Vec<Option<Addr>>,
+    addrs: Vec<Addr>,
+}
+
+impl AddrMap {
+    fn new(len: usize) -> Self {
+        Self { shared: vec![None; len], addrs: Vec::new() }
+    }
+
+    /// Place the item at the address of an import slot, returns `false` if it already has one
+    fn share(&mut self, addr: Addr, slot: Addr) -> bool {
+        match self.shared.get_mut(addr as usize) {
+            Some(shared @ None) => {
+                *shared = Some(slot);
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// Assign addresses behind `next` to all items which are not shared
+    fn finalize(&mut self, next: &mut Addr) {
+        self.addrs = self
+            .shared
+            .iter()
+            .map(|shared| {
+                shared.unwrap_or_else(|| {
+                    *next += 1;
+                    *next - 1
+                })
+            })
+            .collect();
+    }
+
+    #[inline]
+    fn get(&self, addr: Addr) -> Result<Addr> {
+        self.addrs.get(addr as usize).copied().ok_or_else(|| LinkingError::InvalidAddress(addr).into())
+    }
+
+    /// Take the items which are appended to the new store
+    fn appended<'a, I: 'a>(&'a self, items: Vec<I>) -> impl Iterator<Item = (Addr, I)> + 'a {
+        items
+            .into_iter()
+            .zip(self.shared.iter().zip(self.addrs.iter()))
+            .filter_map(|(item, (shared, addr))| shared.is_none().then_some((*addr, item)))
+    }
+}
+
+#[derive(Debug)]
+struct Relocation {
+    funcs: AddrMap,
+    tables: AddrMap,
+    memories: AddrMap,
+    globals: AddrMap,
+    elements: AddrMap,
+    data: AddrMap,
+    types: Vec<TypeAddr>,
+}
+
+impl Relocation {
+    fn relocate_instr(&self, instr: &mut Instruction) -> Result<()> {
+        use Instruction::*;
+
+        let block_args = |args: &mut BlockArgs| {
+            if let BlockArgs::FuncType(ty) = args {
+                *ty = self.types[*ty as usize];
+            }
+        };
+
+        match instr {
+            Block(args, _) | Loop(args, _) => block_args(args),
+            If(packed, _, _) => {
+                let mut args = BlockArgs::from(*packed);
+                block_args(&mut args);
+                *packed = args.into();
+            }
+            Call(func) | RefFunc(func) => *func = self.funcs.get(*func)?,
+            CallIndirect(ty, table) => {
+                *ty = self.types[*ty as usize];
+                *table = self.tables.get(*table)?;
+            }
+            GlobalGet(global) | GlobalSet(global) => *global = self.globals.get(*global)?,
+
+            I32Load { mem_addr, .. }
+            | I64Load { mem_addr, .. }
+            | F32Load { mem_addr, .. }
+            | F64Load { mem_addr, .. }
+            | I32Load8S { mem_addr, .. }
+            | I32Load8U { mem_addr, .. }
+            | I32Load16S { mem_addr, .. }
+            | I32Load16U { mem_addr, .. }
+            | I64Load8S { mem_addr, .. }
+            | I64Load8U { mem_addr, .. }
+            | I64Load16S { mem_addr, .. }
+            | I64Load16U { mem_addr, .. }
+            | I64Load32S { mem_addr, .. }
+            | I64Load32U { mem_addr, .. }
+            | I32Store { mem_addr, .. }
+            | I64Store { mem_addr, .. }
+            | F32Store { mem_addr, .. }
+            | F64Store { mem_addr, .. }
+            | I32Store8 { mem_addr, .. }
+            | I32Store16 { mem_addr, .. }
+            | I64Store8 { mem_addr, .. }
+            | I64Store16 { mem_addr, .. }
+            | I64Store32 { mem_addr, .. } => *mem_addr = self.memories.get(*mem_addr)?,
+            I32StoreLocal { mem_addr, .. } => {
+                *mem_addr = u8::try_from(self.memories.get(*mem_addr as Addr)?)
+                    .map_err(|_| Error::UnsupportedFeature("more than 256 linked memories".into()))?;
+            }
+            MemorySize(mem, _) | MemoryGrow(mem, _) | MemoryFill(mem) => *mem = self.memories.get(*mem)?,
+            MemoryCopy(from, to) => {
+                *from = self.memories.get(*from)?;
+                *to = self.memories.get(*to)?;
+            }
+            MemoryInit(data, mem) => {
+                *data = self.data.get(*data)?;
+                *mem = self.memories.get(*mem)?;
+            }
+            DataDrop(data) => *data = self.data.get(*data)?,
+
+            TableGet(table) | TableSet(table) | TableGrow(table) | TableSize(table) | TableFill(table) => {
+                *table = self.tables.get(*table)?
+            }
+            TableCopy { from, to } => {
+                *from = self.tables.get(*from)?;
+                *to = self.tables.get(*to)?;
+            }
+            TableInit(elem, table) => {
+                *elem = self.elements.get(*elem)?;
+                *table = self.tables.get(*table)?;
+            }
+            _ => {}
+        }
+
+        Ok(())
+    }
+
+    /// Rewrite all addresses inside of the linked store
+    ///
+    /// The functions are lowered again for the `backend` of the new module, which has the types `func_types`.
+    fn relocate_store<T, S: Threading>(
+        &self,
+        store: &mut LinkedInstance<T, S>,
+        backend: Backend,
+        func_types: &[FuncType],
+    ) -> Result<()> {
+        for func in store.funcs.iter_mut() {
+            if let Function::Wasm(func) = func {
+                let mut instructions = func.instructions.to_vec();
+                for instr in instructions.iter_mut() {
+                    self.relocate_instr(instr)?;
+                }
+                func.instructions = instructions.into_boxed_slice();
+            }
+        }
+
+        // Relocated calls only reach functions of the same store
+        let callee_types: BTreeMap<Addr, FuncType> = store
+            .funcs
+            .iter()
+            .enumerate()
+            .map(|(addr, func)| Ok((self.funcs.get(addr as Addr)?, func.ty().clone())))
+            .collect::<Result<_>>()?;
+        for func in store.funcs.iter_mut() {
+            if let Function::Wasm(func) = func {
+                func.slots = match backend {
+                    Backend::Stack => None,
+                    Backend::Slots => Some(slots::lower(func, func_types, |addr| callee_types.get(&addr))?),
+                };
+            }
+        }
+
+        for table in store.tables.iter_mut().filter(|t| t.kind.element_type == ValType::RefFunc) {
+            for elem in table.elements.iter_mut() {
+                *elem = elem.addr().map(|addr| self.funcs.get(addr)).transpose()?.into();
+            }
+        }
+
+        for elem in store.elements.iter_mut() {
+            if let Some(items) = &mut elem.items {
+                for item in items.iter_mut() {
+                    *item = item.addr().map(|addr| self.funcs.get(addr)).transpose()?.into();
+                }
+            }
+        }
+
+        for global in store.globals.iter_mut().filter(|g| g.ty.ty == ValType::RefFunc) {
+            if let WasmValue::RefFunc(addr) = global.value.attach_type(ValType::RefFunc) {
+                global.value = RawWasmValue::from(WasmValue::RefFunc(self.funcs.get(addr)?));
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[derive(Debug)]
+struct Link<T, S: Threading> {
+    name: String,
+    store: LinkedInstance<T, S>,
+    relocation: Relocation,
+}
+
+/// Resolves imports from linked instances and appends their stores after instantiation
+#[derive(Debug)]
+pub(crate) struct Linker<T, S: Threading> {
+    links: Vec<Link<T, S>>,
+    new_types: Vec<FuncType>,
+}
+
+impl<T, S: Threading> Linker<T, S> {
+    /// Take the linked instances out of the imports and relocate the ones the module imports from
+    pub(crate) fn new(module: &Module, imports: &mut Imports<T, S>) -> Result<Self> {
+        let mut links: Vec<_> = imports
+            .take_linked()
+            .into_iter()
+            .map(|(name, store)| {
+                let relocation = Relocation {
+                    funcs: AddrMap::new(store.funcs.len()),
+                    tables: AddrMap::new(store.tables.len()),
+                    memories: AddrMap::new(store.memories.len()),
+                    globals: AddrMap::new(store.globals.len()),
+                    elements: AddrMap::new(store.elements.len()),
+                    data: AddrMap::new(store.data.len()),
+                    types: Vec::new(),
+                };
+                (false, Link { name, store, relocation })
+            })
+            .collect();
+
+        // Addresses in the new store, counting the imports first and then the items of the module.
+        let mut next = [0 as Addr; 4];
+        for import in module.imports.iter() {
+            let kind = ExternalKind::from(&import.kind);
+            let slot = next[kind as usize];
+            next[kind as usize] += 1;
+
+            if links.is_empty() || imports.contains(import) {
+                continue;
+            }
+
+            let Some((used, link)) = links.iter_mut().find(|(_, link)| *link.name == *import.module) else {
+                continue;
+            };
+            // Missing or mismatching exports are reported while resolving the imports.
+            let Some(export) = link.store.export(&import.name).filter(|e| e.kind() == kind) else {
+                continue;
+            };
+
+            *used = true;
+            let reloc = &mut link.relocation;
+            let shared = match export {
+                // Functions are immutable, so importing one twice only creates a copy.
+                ExternVal::Func(addr) => {
+                    reloc.funcs.share(addr, slot);
+                    true
+                }
+                ExternVal::Table(addr) => reloc.tables.share(addr, slot),
+                ExternVal::Memory(addr) => reloc.memories.share(addr, slot),
+                ExternVal::Global(addr) => reloc.globals.share(addr, slot),
+            };
+            if !shared {
+                return Err(Error::UnsupportedFeature(format!(
+                    "importing {}.{} which was already imported under another name",
+                    import.module, import.name
+                )));
+            }
+        }
+
+        let mut next_func = next[ExternalKind::Func as usize] + module.funcs.len() as Addr;
+        let mut next_table = next[ExternalKind::Table as usize] + module.table_types.len() as Addr;
+        let mut next_memory = next[ExternalKind::Memory as usize] + module.memory_types.len() as Addr;
+        let mut next_global = next[ExternalKind::Global as usize] + module.globals.len() as Addr;
+        let mut next_element = module.elements.len() as Addr;
+        let mut next_data = module.data.len() as Addr;
+
+        let mut new_types: Vec<FuncType> = Vec::new();
+        let mut links: Vec<_> = links.into_iter().filter_map(|(used, link)| used.then_some(link)).collect();
+        for link in links.iter_mut() {
+            let reloc = &mut link.relocation;
+            reloc.funcs.finalize(&mut next_func);
+            reloc.tables.finalize(&mut next_table);
+            reloc.memories.finalize(&mut next_memory);
+            reloc.globals.finalize(&mut next_global);
+            reloc.elements.finalize(&mut next_element);
+            reloc.data.finalize(&mut next_data);
+
+            reloc.types = link
+                .store
+                .func_types
+                .iter()
+                .map(|ty| {
+                    let addr = module.func_types.iter().chain(new_types.iter()).position(|t| t == ty);
+                    addr.unwrap_or_else(|| {
+                        new_types.push(ty.clone());
+                        module.func_types.len() + new_types.len() - 1
+                    }) as TypeAddr
+                })
+                .collect();
+
+            let func_types: Vec<FuncType> = module.func_types.iter().chain(new_types.iter()).cloned().collect();
+            link.relocation.relocate_store(&mut link.store, module.backend, &func_types)?;
+        }
+
+        Ok(Self { links, new_types })
+    }
+
+    /// Take the item of a linked instance for an import
+    pub(crate) fn take(&mut self, import: &Import) -> Option<Result<LinkedExtern<T, S>>> {
+        let link = self.links.iter_mut().find(|link| *link.name == *import.module)?;
+        let export = link.store.export(&import.name)?;
+
+        let store = &link.store;
+        let item = match (export, &import.kind) {
+            (ExternVal::Func(addr), ImportKind::Function(_)) => {
+                store.funcs.get(addr as usize).cloned().map(LinkedExtern::Func)
+            }
+            (ExternVal::Table(addr), ImportKind::Table(_)) => {
+                store.tables.get(addr as usize).cloned().map(LinkedExtern::Table)
+            }
+            (ExternVal::Memory(addr), ImportKind::Memory(_)) => {
+                store.memories.get(addr as usize).cloned().map(LinkedExtern::Memory)
+            }
+            (ExternVal::Global(addr), ImportKind::Global(_)) => {
+                store.globals.get(addr as usize).cloned().map(LinkedExtern::Global)
+            }
+            _ => return Some(Err(LinkingError::incompatible_import_type(import).into())),
+        };
+
+        Some(item.ok_or_else(|| Instance::not_found_error(&import.name)))
+    }
+
+    /// Append the remaining items of the linked stores, after the module has been instantiated
+    pub(crate) fn append(self, instance: &mut Instance<T, S>) {
+        for link in self.links {
+            let Relocation { funcs, tables, memories, globals, elements, data, .. } = &link.relocation;
+            let store = link.store;
+
+            append(&mut instance.funcs, funcs.appended(store.funcs));
+            append(&mut instance.tables, tables.appended(store.tables));
+            append(&mut instance.memories, memories.appended(store.memories));
+            append(&mut instance.globals, globals.appended(store.globals));
+            append(&mut instance.elements, elements.appended(store.elements));
+            append(&mut instance.data, data.appended(store.data));
+        }
+
+        if !self.new_types.is_empty() {
+            let mut func_types = core::mem::take(&mut instance.module.func_types).into_vec();
+            func_types.extend(self.new_types);
+            instance.module.func_types = func_types.into_boxed_slice();
+        }
+    }
+}
+
+fn append<I>(store: &mut Vec<I>, items: impl Iterator<Item = (Addr, I)>) {
+    for (addr, item) in items {
+        debug_assert_eq!(store.len(), addr as usize, "linked item was relocated to the wrong address");
+        store.push(item);
+    }
+}
+
+impl<T, S: Threading> Imports<T, S> {
+    pub(crate) fn take_linked(&mut self) -> BTreeMap<String, LinkedInstance<T, S>> {
+        core::mem::take(&mut self.linked)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::exec::CallResultTyped;
+    use crate::imports::Extern;
+
+    const LIB: &str = r#"
+        (module
+            (type $unop (func (param i32) (result i32)))
+            (memory (export "memory") 1)
+            (global $hidden (mut i32) (i32.const 100))
+            (global $counter (export "counter") (mut i32) (i32.const 0))
+            (table (export "table") 4 funcref)
+            (elem (i32.const 0) $double $square)
+            (func $double (type $unop) (i32.mul (local.get 0) (i32.const 2)))
+            (func $square (type $unop) (i32.mul (local.get 0) (local.get 0)))
+            (func (export "store") (param i32 i32) (i32.store (local.get 0) (local.get 1)))
+            (func (export "load") (param i32) (result i32) (i32.load (local.get 0)))
+            (func (export "bump") (result i32)
+                (global.set $counter (i32.add (global.get $counter) (i32.const 1)))
+                (global.set $hidden (i32.add (global.get $hidden) (i32.const 1)))
+                (global.get $hidden))
+            (func (export "dispatch") (param i32 i32) (result i32)
+                (call_indirect (type $unop) (local.get 1) (local.get 0))))
+    "#;
+
+    fn parse(wat: &str, backend: Backend) -> Module {
+        let mut module =
+            crate::parse_bytes(&wat::parse_str(wat).expect("invalid wat")).expect("failed to parse module");
+        module.set_backend(backend).unwrap();
+        module
+    }
+
+    fn lib_imports(backend: Backend) -> Imports {
+        let (lib, _, _) = Instance::instantiate(parse(LIB, backend), Imports::new(), (), None).unwrap();
+        let mut imports = Imports::new();
+        imports.link_instance("lib", &lib).unwrap();
+        imports
+    }
+
+    #[test]
+    fn test_link_globals_and_memory() {
+        let wasm = r#"
+            (module
+                (import "lib" "store" (func $store (param i32 i32)))
+                (import "lib" "load" (func $load (param i32) (result i32)))
+                (import "lib" "bump" (func $bump (result i32)))
+                (import "lib" "memory" (memory 1))
+                (import "lib" "counter" (global $counter (mut i32)))
+                (global $own (mut i32) (i32.const 7))
+                (export "counter" (global $counter))
+                (export "own" (global $own))
+                (func (export "write") (i32.store (i32.const 16) (i32.const 5)))
+                (func (export "lib_read") (result i32) (call $load (i32.const 16)))
+                (func (export "lib_write") (call $store (i32.const 32) (i32.const 11)))
+                (func (export "read") (result i32) (i32.load (i32.const 32)))
+                (func (export "count") (global.set $counter (i32.add (global.get $counter) (i32.const 10))))
+                (func (export "bump") (result i32) (call $bump)))
+        "#;
+
+        for backend in [Backend::Stack, Backend::Slots] {
+            let (mut instance, _, _) =
+                Instance::instantiate(parse(wasm, backend), lib_imports(backend), (), None).unwrap();
+
+            // the memory is shared, writes of one side are seen by the other
+            instance.exported_func::<(), ()>("write").unwrap().invoke(&mut instance, ()).unwrap();
+            assert_eq!(instance.exported_func::<(), i32>("lib_read").unwrap().invoke(&mut instance, ()).unwrap(), 5);
+            instance.exported_func::<(), ()>("lib_write").unwrap().invoke(&mut instance, ()).unwrap();
+            assert_eq!(instance.exported_func::<(), i32>("read").unwrap().invoke(&mut instance, ()).unwrap(), 11);
+            assert_eq!(instance.memories.len(), 1, "{backend:?}");
+
+            // the imported global is shared, the hidden one of the library is appended behind the own one
+            instance.exported_func::<(), ()>("count").unwrap().invoke(&mut instance, ()).unwrap();
+            assert_eq!(instance.exported_func::<(), i32>("bump").unwrap().invoke(&mut instance, ()).unwrap(), 101);
+            assert_eq!(instance.exported_global("counter").unwrap().get(), WasmValue::I32(11), "{backend:?}");
+            assert_eq!(instance.exported_global("own").unwrap().get(), WasmValue::I32(7), "{backend:?}");
+            let globals: Vec<_> = instance.globals.iter().map(|g| g.value.attach_type(ValType::I32)).collect();
+            assert_eq!(globals, [WasmValue::I32(11), WasmValue::I32(7), WasmValue::I32(101)], "{backend:?}");
+        }
+    }
+
+    #[test]
+    fn test_link_call_indirect_through_table() {
+        let wasm = r#"
+            (module
+                (type $unop (func (param i32) (result i32)))
+                (import "lib" "table" (table 4 funcref))
+                (import "lib" "dispatch" (func $dispatch (param i32 i32) (result i32)))
+                (elem (table 0) (i32.const 2) func $negate)
+                (func $negate (type $unop) (i32.sub (i32.const 0) (local.get 0)))
+                (func (export "main") (result i32)
+                    (i32.add
+                        (i32.add
+                            ;; a function of the library, called by the module
+                            (call_indirect (type $unop) (i32.const 6) (i32.const 0))
+                            ;; a function of the library, called by the library
+                            (call $dispatch (i32.const 1) (i32.const 6)))
+                        ;; a function of the module, called by the library
+                        (call $dispatch (i32.const 2) (i32.const 6)))))
+        "#;
+
+        for backend in [Backend::Stack, Backend::Slots] {
+            let (mut instance, _, _) =
+                Instance::instantiate(parse(wasm, backend), lib_imports(backend), (), None).unwrap();
+            let main = instance.exported_func::<(), i32>("main").unwrap();
+            assert_eq!(main.invoke(&mut instance, ()).unwrap(), 12 + 36 - 6, "{backend:?}");
+        }
+    }
+
+    #[test]
+    fn test_link_snapshot_keeps_addresses() {
+        let wasm = parse(
+            r#"
+            (module
+                (import "env" "pause" (func $pause))
+                (import "lib" "store" (func $store (param i32 i32)))
+                (import "lib" "bump" (func $bump (result i32)))
+                (import "lib" "memory" (memory 1))
+                (global $own (mut i32) (i32.const 0))
+                (func (export "main") (result i32)
+                    (global.set $own (call $bump))
+                    (call $store (i32.const 8) (i32.const 3))
+                    (call $pause)
+                    (i32.add
+                        (i32.add (call $bump) (global.get $own))
+                        (i32.load (i32.const 8)))))
+            "#,
+            Backend::Stack,
+        );
+
+        let imports = || {
+            let mut imports = lib_imports(Backend::Stack);
+            imports
+                .define("env", "pause", Extern::typed_func(|_, ()| -> Result<()> { Err(Error::PauseExecution) }))
+                .unwrap();
+            imports
+        };
+
+        let (instance, _, _) = Instance::instantiate(wasm.clone(), imports(), (), None).unwrap();
+        let main = instance.exported_func::<(), i32>("main").unwrap();
+        let mut exec_handle = main.call(instance, (), None).unwrap();
+        assert!(matches!(exec_handle.run(1000).unwrap(), CallResultTyped::Incomplete));
+        let globals: Vec<_> = exec_handle.instance().globals.iter().map(|g| g.value).collect();
+
+        let mut state = Vec::new();
+        exec_handle.serialize(&mut state, &[]).unwrap();
+
+        let (instance, stack, _) = Instance::instantiate(wasm, imports(), (), Some(&state)).unwrap();
+        let restored: Vec<_> = instance.globals.iter().map(|g| g.value).collect();
+        assert_eq!(restored, globals);
+
+        // the hidden global of the library continues at 101 and the module sees the memory the library wrote
+        let mut exec_handle = main.call(instance, (), stack).unwrap();
+        assert!(matches!(exec_handle.run(1000).unwrap(), CallResultTyped::Done(res) if res == 102 + 101 + 3));
+    }
+
+    #[test]
+    fn test_link_segments() {
+        // segment 1 of the library is appended behind the segments of the module
+        let lib = r#"
+            (module
+                (memory 1)
+                (table 1 funcref)
+                (data "unused")
+                (data "\2a")
+                (elem func $zero)
+                (elem func $seven)
+                (func $zero (result i32) (i32.const 0))
+                (func $seven (result i32) (i32.const 7))
+                (func (export "init") (result i32)
+                    (memory.init 1 (i32.const 8) (i32.const 0) (i32.const 1))
+                    (table.init 1 (i32.const 0) (i32.const 0) (i32.const 1))
+                    (i32.add (i32.load8_u (i32.const 8)) (call_indirect (result i32) (i32.const 0)))))
+        "#;
+        let wasm = r#"
+            (module
+                (import "lib" "init" (func $init (result i32)))
+                (memory 1)
+                (table 1 funcref)
+                (data "module")
+                (elem func $init)
+                (func (export "main") (result i32) (call $init)))
+        "#;
+
+        for backend in [Backend::Stack, Backend::Slots] {
+            let (lib, _, _) = Instance::instantiate(parse(lib, backend), Imports::new(), (), None).unwrap();
+            let mut imports = Imports::new();
+            imports.link_instance("lib", &lib).unwrap();
+
+            let (mut instance, _, _) = Instance::instantiate(parse(wasm, backend), imports, (), None).unwrap();
+            let main = instance.exported_func::<(), i32>("main").unwrap();
+            assert_eq!(main.invoke(&mut instance, ()).unwrap(), 42 + 7, "{backend:?}");
+        }
+    }
+
+    #[test]
+    fn test_link_snapshot_of_other_instance() {
+        let wasm = r#"
+            (module
+                (import "env" "pause" (func $pause))
+                (import "lib" "bump" (func $bump (result i32)))
+                (memory 1)
+                (func (export "main") (result i32) (call $pause) (call $bump)))
+        "#;
+        let imports = |lib: Option<Imports>| {
+            let mut imports = lib.unwrap_or_default();
+            imports
+                .define("env", "pause", Extern::typed_func(|_, ()| -> Result<()> { Err(Error::PauseExecution) }))
+                .unwrap();
+            imports
+        };
+
+        let (instance, _, _) =
+            Instance::instantiate(parse(wasm, Backend::Stack), imports(Some(lib_imports(Backend::Stack))), (), None)
+                .unwrap();
+        let main = instance.exported_func::<(), i32>("main").unwrap();
+        let mut exec_handle = main.call(instance, (), None).unwrap();
+        assert!(matches!(exec_handle.run(1000).unwrap(), CallResultTyped::Incomplete));
+        let mut state = Vec::new();
+        exec_handle.serialize(&mut state, &[]).unwrap();
+
+        // the snapshot includes the memory and globals of the library, an instance without them can't restore it
+        let mut other = imports(None);
+        other.define("lib", "bump", Extern::typed_func(|_, ()| -> Result<i32> { Ok(0) })).unwrap();
+        let res = Instance::instantiate(parse(wasm, Backend::Stack), other, (), Some(&state));
+        assert!(matches!(res, Err(Error::InvalidStore)));
+    }
+}
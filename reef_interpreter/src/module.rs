@@ -1,7 +1,650 @@
-use crate::{error::Result, parser::Parser, types::Module};
+use alloc::{format, vec::Vec};
+
+use crate::{
+    encoding,
+    error::{Error, Result},
+    io::Write,
+    parser::{optimize, slots, Parser},
+    types::{
+        Backend, ExportType, ExternType, ExternalKind, FuncType, GlobalType, ImportKind, ImportType, MemoryType,
+        Module, TableType, WasmFunction,
+    },
+};
 
 /// Parse a module from bytes. Requires `parser` feature.
 pub fn parse_bytes(wasm: &[u8]) -> Result<Module> {
-    let data = Parser::parse_module_bytes(wasm)?;
+    let data = Parser::parse_module_bytes(wasm, false)?;
+    Ok(data)
+}
+
+/// Parse a module from bytes, but only translate functions on their first call. Requires `parser` feature.
+///
+/// All functions are validated up front, but instructions the interpreter does not support are only found
+/// when a function is translated, its first call fails then without changing the instance.
+/// This shortens the time until the first instruction runs for large modules of which most functions are never called.
+/// Translated functions are cached in the [`Module`] of the instance which called them. Instances own their module,
+/// so the module they were created from and other instances of it don't see these translations and translate the
+/// function again on their first call. Use [`Module::translate_all`] to translate a module before sharing it.
+pub fn parse_bytes_lazy(wasm: &[u8]) -> Result<Module> {
+    let data = Parser::parse_module_bytes(wasm, true)?;
     Ok(data)
 }
+
+/// Magic bytes at the start of every cached module.
+const CACHE_MAGIC: [u8; 4] = *b"RFMC";
+
+/// Version of the cached module format.
+///
+/// Has to be bumped whenever the lowered representation of a [`Module`] changes,
+/// caches of other versions are rejected by [`Module::from_cache`].
+pub const MODULE_CACHE_VERSION: u32 = 6;
+
+impl Module {
+    /// Enable or disable superinstructions, they are enabled for modules returned by [`parse_bytes`].
+    ///
+    /// Superinstructions execute common instruction sequences at once. Instruction pointers are the same either way,
+    /// so snapshots can be resumed with both settings, and a function counts the same cycles until it returns.
+    /// But a superinstruction counts all instructions of its sequence at once, so a run can overshoot its cycle
+    /// budget by the length of a sequence and stops at different cycles depending on the setting. Executions which
+    /// have to stop at the same cycles, like a job and its verification, need the same setting.
+    pub fn set_superinstructions(&mut self, enabled: bool) {
+        self.superinstructions = enabled;
+        for func in self.funcs.iter_mut() {
+            match enabled {
+                true => optimize::fuse(&mut func.instructions),
+                false => optimize::unfuse(&mut func.instructions),
+            }
+        }
+    }
+
+    /// Select the backend the functions of the module are executed with, modules returned by
+    /// [`parse_bytes`] use [`Backend::Stack`].
+    ///
+    /// [`Backend::Slots`] lowers all functions again, so their locals and operands are accessed
+    /// through fixed slots of the frame instead of an operand stack. Both backends count one cycle per executed
+    /// instruction, the slot backend executes fewer instructions for the same function.
+    /// Snapshots and pending host calls work the same way, but can only be resumed with the backend they were taken with.
+    /// Functions which were not translated yet are lowered once they are translated, see [`parse_bytes_lazy`].
+    pub fn set_backend(&mut self, backend: Backend) -> Result<()> {
+        let lowered = match backend {
+            Backend::Stack => self.funcs.iter().map(|_| None).collect(),
+            Backend::Slots => {
+                let imported = self.imports.iter().filter_map(|import| match import.kind {
+                    ImportKind::Function(ty) => self.func_types.get(ty as usize),
+                    _ => None,
+                });
+                let callee_types: Vec<&FuncType> = imported.chain(self.funcs.iter().map(|f| &f.ty)).collect();
+
+                let lower = |func: &WasmFunction| match func.lazy_body {
+                    Some(_) => Ok(None),
+                    None => {
+                        slots::lower(func, &self.func_types, |addr| callee_types.get(addr as usize).copied()).map(Some)
+                    }
+                };
+                self.funcs.iter().map(lower).collect::<Result<Vec<_>, _>>()?
+            }
+        };
+
+        for (func, slots) in self.funcs.iter_mut().zip(lowered) {
+            func.slots = slots;
+        }
+        self.backend = backend;
+        Ok(())
+    }
+
+    /// Translate all functions which were not translated yet, see [`parse_bytes_lazy`].
+    ///
+    /// Modules written to the cache afterwards don't translate any function once they are loaded again.
+    pub fn translate_all(&mut self) -> Result<()> {
+        for func in self.funcs.iter_mut() {
+            if let Some(body) = &func.lazy_body {
+                func.instructions = Parser::translate(body, self.superinstructions)?;
+                func.lazy_body = None;
+            }
+        }
+
+        // lower the functions which were just translated
+        match self.backend {
+            Backend::Stack => Ok(()),
+            Backend::Slots => self.set_backend(Backend::Slots),
+        }
+    }
+
+    /// Parse a module while reading it from `stream`, like [`parse_bytes`] but without the whole program in memory.
+    ///
+    /// Only the section or function body which is currently parsed is buffered,
+    /// so a program can be parsed while it is being downloaded.
+    #[cfg(feature = "std")]
+    pub fn parse_stream<R: std::io::Read>(stream: R) -> Result<Self> {
+        Parser::parse_module_stream(stream, false)
+    }
+
+    /// Like [`Module::parse_stream`], but only translate functions on their first call, see [`parse_bytes_lazy`].
+    #[cfg(feature = "std")]
+    pub fn parse_stream_lazy<R: std::io::Read>(stream: R) -> Result<Self> {
+        Parser::parse_module_stream(stream, true)
+    }
+
+    /// Parse a module from a file, see [`Module::parse_stream`].
+    #[cfg(feature = "std")]
+    pub fn parse_file(path: impl AsRef<std::path::Path>) -> Result<Self> {
+        let file = std::fs::File::open(path)?;
+        Self::parse_stream(std::io::BufReader::new(file))
+    }
+
+    /// Iterate over the imports of the module with their types.
+    pub fn imports(&self) -> impl Iterator<Item = ImportType<'_>> {
+        self.imports.iter().map(|import| {
+            let ty = match &import.kind {
+                ImportKind::Function(ty) => ExternType::Func(&self.func_types[*ty as usize]),
+                ImportKind::Table(ty) => ExternType::Table(ty),
+                ImportKind::Memory(ty) => ExternType::Memory(ty),
+                ImportKind::Global(ty) => ExternType::Global(ty),
+            };
+            ImportType { module: &import.module, name: &import.name, ty }
+        })
+    }
+
+    /// Iterate over the exports of the module with their types.
+    pub fn exports(&self) -> impl Iterator<Item = ExportType<'_>> {
+        self.exports.iter().map(|export| {
+            let ty = match export.kind {
+                ExternalKind::Func => self.func_type(export.index).map(ExternType::Func),
+                ExternalKind::Table => self.tables().nth(export.index as usize).map(ExternType::Table),
+                ExternalKind::Memory => self.memories().nth(export.index as usize).map(ExternType::Memory),
+                ExternalKind::Global => self.global_type(export.index).map(ExternType::Global),
+            };
+            ExportType { name: &export.name, ty: ty.expect("exports are validated") }
+        })
+    }
+
+    /// Get the type of the function at `index`, imported functions come first.
+    pub fn func_type(&self, index: u32) -> Option<&FuncType> {
+        let imported = self.imports.iter().filter_map(|import| match import.kind {
+            ImportKind::Function(ty) => Some(&self.func_types[ty as usize]),
+            _ => None,
+        });
+        imported.chain(self.funcs.iter().map(|func| &func.ty)).nth(index as usize)
+    }
+
+    /// Get the type of the global at `index`, imported globals come first.
+    pub fn global_type(&self, index: u32) -> Option<&GlobalType> {
+        let imported = self.imports.iter().filter_map(|import| match &import.kind {
+            ImportKind::Global(ty) => Some(ty),
+            _ => None,
+        });
+        imported.chain(self.globals.iter().map(|global| &global.ty)).nth(index as usize)
+    }
+
+    /// Iterate over the limits of all tables, imported tables come first.
+    pub fn tables(&self) -> impl Iterator<Item = &TableType> {
+        let imported = self.imports.iter().filter_map(|import| match &import.kind {
+            ImportKind::Table(ty) => Some(ty),
+            _ => None,
+        });
+        imported.chain(self.table_types.iter())
+    }
+
+    /// Iterate over the limits of all memories, imported memories come first.
+    pub fn memories(&self) -> impl Iterator<Item = &MemoryType> {
+        let imported = self.imports.iter().filter_map(|import| match &import.kind {
+            ImportKind::Memory(ty) => Some(ty),
+            _ => None,
+        });
+        imported.chain(self.memory_types.iter())
+    }
+
+    /// Get the contents of the first custom section called `name`.
+    pub fn custom_section(&self, name: &str) -> Option<&[u8]> {
+        self.custom_sections.iter().find(|section| &*section.name == name).map(|section| &*section.data)
+    }
+
+    /// Write the lowered module into a compact, versioned binary form.
+    ///
+    /// `hash` identifies the program the module was parsed from and has to be passed to
+    /// [`Module::from_cache`] again, usually it is a hash of the Wasm bytes.
+    pub fn serialize_cache<W: Write>(&self, mut writer: W, hash: &[u8]) -> Result<()> {
+        writer.write_all(&CACHE_MAGIC)?;
+        writer.write_all(&MODULE_CACHE_VERSION.to_le_bytes())?;
+        writer.write_all(&(hash.len() as u32).to_le_bytes())?;
+        writer.write_all(hash)?;
+        encoding::serialize_into(writer, self)
+    }
+
+    /// Load a module written by [`Module::serialize_cache`] without parsing and validating the program again.
+    ///
+    /// Fails with [`Error::InvalidCache`] if the cache was written by another format version or for another `hash`,
+    /// callers should fall back to [`parse_bytes`] then.
+    /// The cached module is trusted, so it must only be loaded from a source written by this node.
+    pub fn from_cache(cached: &[u8], hash: &[u8]) -> Result<Self> {
+        let (magic, rest) = split_at(cached, CACHE_MAGIC.len())?;
+        if magic != CACHE_MAGIC {
+            return Err(Error::InvalidCache("not a cached module".into()));
+        }
+
+        let (version, rest) = split_at(rest, 4)?;
+        let version = u32::from_le_bytes(version.try_into().expect("split at 4 bytes"));
+        if version != MODULE_CACHE_VERSION {
+            return Err(Error::InvalidCache(format!(
+                "format version {} does not match {}",
+                version, MODULE_CACHE_VERSION
+            )));
+        }
+
+        let (hash_len, rest) = split_at(rest, 4)?;
+        let hash_len = u32::from_le_bytes(hash_len.try_into().expect("split at 4 bytes")) as usize;
+        let (cached_hash, rest) = split_at(rest, hash_len)?;
+        if cached_hash != hash {
+            return Err(Error::InvalidCache("program hash does not match".into()));
+        }
+
+        encoding::deserialize(rest)
+    }
+}
+
+fn split_at(bytes: &[u8], mid: usize) -> Result<(&[u8], &[u8])> {
+    bytes.split_at_checked(mid).ok_or_else(|| Error::InvalidCache("unexpected end of cache".into()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_module_cache_roundtrip() {
+        let wasm = wat::parse_str(
+            r#"
+            (module
+                (memory (export "memory") 1)
+                (data (i32.const 8) "cached")
+                (global $g (mut i32) (i32.const 2))
+                (func (export "double") (param i32) (result i32)
+                    (i32.mul (local.get 0) (global.get $g))))
+            "#,
+        )
+        .expect("invalid wat");
+        let module = parse_bytes(&wasm).expect("failed to parse module");
+
+        let mut cached = Vec::new();
+        module.serialize_cache(&mut cached, b"program-a").unwrap();
+
+        assert_eq!(Module::from_cache(&cached, b"program-a").unwrap(), module);
+        assert!(matches!(Module::from_cache(&cached, b"program-b"), Err(Error::InvalidCache(_))));
+        assert!(matches!(Module::from_cache(&cached[..6], b"program-a"), Err(Error::InvalidCache(_))));
+
+        // Caches of another format version are rejected
+        cached[4..8].copy_from_slice(&(MODULE_CACHE_VERSION + 1).to_le_bytes());
+        assert!(matches!(Module::from_cache(&cached, b"program-a"), Err(Error::InvalidCache(_))));
+    }
+
+    const LAZY_PROGRAM: &str = r#"
+        (module
+            (func $double (param i32) (result i32)
+                (i32.mul (local.get 0) (i32.const 2)))
+            (func $unused (result i32)
+                (i32.const 0))
+            (func (export "main") (param i32) (result i32) (local $i i32)
+                (loop $next
+                    (local.set 0 (call $double (local.get 0)))
+                    (local.set $i (i32.add (local.get $i) (i32.const 1)))
+                    (br_if $next (i32.lt_u (local.get $i) (i32.const 4))))
+                (local.get 0)))
+        "#;
+
+    #[test]
+    fn test_lazy_translation() {
+        use crate::{exec::CallResultTyped, imports::Imports, Instance};
+
+        let wasm = wat::parse_str(LAZY_PROGRAM).expect("invalid wat");
+        let eager = parse_bytes(&wasm).unwrap();
+        let lazy = parse_bytes_lazy(&wasm).unwrap();
+        assert!(lazy.funcs.iter().all(|f| f.instructions.is_empty() && f.lazy_body.is_some()));
+
+        // Invalid function bodies are still rejected up front
+        let invalid = wat::parse_str(r#"(module (func (result i32) (i64.const 1)))"#).unwrap();
+        assert!(matches!(parse_bytes_lazy(&invalid), Err(Error::ParseError(_))));
+
+        for backend in [Backend::Stack, Backend::Slots] {
+            let mut lazy = lazy.clone();
+            lazy.set_backend(backend).unwrap();
+
+            let (mut instance, _, _) = Instance::instantiate(lazy.clone(), Imports::new(), (), None).unwrap();
+            let main = instance.exported_func::<i32, i32>("main").unwrap();
+            assert_eq!(main.invoke(&mut instance, 3).unwrap(), 48);
+
+            // Called functions are translated like the eagerly parsed ones and cached in the module
+            let translated = &instance.module.funcs;
+            assert_eq!(translated[0].instructions, eager.funcs[0].instructions);
+            assert_eq!(translated[2].instructions, eager.funcs[2].instructions);
+            assert!(translated[1].lazy_body.is_some());
+            assert_eq!(translated[0].slots.is_some(), backend == Backend::Slots);
+
+            // Snapshots are resumed on an untranslated module
+            let mut exec_handle = main.call(instance, 5, None).unwrap();
+            assert!(matches!(exec_handle.run(4).unwrap(), CallResultTyped::Incomplete));
+            let mut state = Vec::new();
+            exec_handle.serialize(&mut state, &[]).unwrap();
+
+            let (instance, stack, _) = Instance::instantiate(lazy, Imports::new(), (), Some(&state)).unwrap();
+            let mut exec_handle = main.call(instance, 5, stack).unwrap();
+            assert!(matches!(exec_handle.run(usize::MAX).unwrap(), CallResultTyped::Done(80)));
+        }
+    }
+
+    #[test]
+    fn test_lazy_translation_error() {
+        use crate::{imports::Imports, Instance};
+
+        // `elem.drop` is valid, but not supported by the interpreter
+        let wasm = wat::parse_str(
+            r#"
+            (module
+                (elem $seg func $ok)
+                (func $drop (export "drop") (elem.drop $seg))
+                (func $ok (export "ok") (result i32) (i32.const 7))
+                (func (export "main") (result i32) (call $drop) (call $ok)))
+            "#,
+        )
+        .expect("invalid wat");
+        assert!(matches!(parse_bytes(&wasm), Err(Error::ParseError(_))));
+
+        for backend in [Backend::Stack, Backend::Slots] {
+            let mut lazy = parse_bytes_lazy(&wasm).unwrap();
+            lazy.set_backend(backend).unwrap();
+            let (mut instance, _, _) = Instance::instantiate(lazy, Imports::new(), (), None).unwrap();
+            let drop = instance.exported_func::<(), ()>("drop").unwrap();
+            let ok = instance.exported_func::<(), i32>("ok").unwrap();
+            let main = instance.exported_func::<(), i32>("main").unwrap();
+
+            assert!(matches!(drop.invoke(&mut instance, ()), Err(Error::ParseError(_))));
+            let (instance, err) = *drop.call(instance, (), None).unwrap_err();
+            assert!(matches!(err, Error::ParseError(_)));
+
+            // the failing call inside of the module doesn't break the instance either
+            let mut exec_handle = main.call(instance, (), None).unwrap();
+            assert!(matches!(exec_handle.run(usize::MAX), Err(Error::ParseError(_))));
+            let mut instance = exec_handle.into_instance();
+
+            assert_eq!(ok.invoke(&mut instance, ()).unwrap(), 7, "{backend:?}");
+            assert!(instance.module.funcs[0].lazy_body.is_some());
+        }
+    }
+
+    #[test]
+    fn test_lazy_snapshot_before_translation() {
+        use crate::{
+            exec::CallResultTyped,
+            imports::{Extern, FuncContext, Imports},
+            Instance,
+        };
+
+        let wasm = wat::parse_str(
+            r#"
+            (module
+                (import "env" "pause" (func $pause))
+                (func $double (param i32) (result i32)
+                    (i32.mul (local.get 0) (i32.const 2)))
+                (func (export "main") (param i32) (result i32)
+                    (call $pause)
+                    (call $double (local.get 0))))
+            "#,
+        )
+        .expect("invalid wat");
+        let imports = || {
+            let mut imports = Imports::new();
+            let pause = Extern::typed_func(|_: FuncContext<'_>, ()| -> Result<()> { Err(Error::PauseExecution) });
+            imports.define("env", "pause", pause).unwrap();
+            imports
+        };
+
+        for backend in [Backend::Stack, Backend::Slots] {
+            let mut lazy = parse_bytes_lazy(&wasm).unwrap();
+            lazy.set_backend(backend).unwrap();
+
+            let (instance, _, _) = Instance::instantiate(lazy.clone(), imports(), (), None).unwrap();
+            let main = instance.exported_func::<i32, i32>("main").unwrap();
+            let mut exec_handle = main.call(instance, 21, None).unwrap();
+            assert!(matches!(exec_handle.run(usize::MAX).unwrap(), CallResultTyped::Incomplete));
+            assert!(exec_handle.instance().module.funcs[0].lazy_body.is_some());
+
+            let mut state = Vec::new();
+            exec_handle.serialize(&mut state, &[]).unwrap();
+
+            // $double is translated after resuming, on the instance restored from the snapshot
+            let (instance, stack, _) = Instance::instantiate(lazy, imports(), (), Some(&state)).unwrap();
+            let mut exec_handle = main.call(instance, 21, stack).unwrap();
+            assert!(matches!(exec_handle.run(usize::MAX).unwrap(), CallResultTyped::Done(42)), "{backend:?}");
+            let double = &exec_handle.instance().module.funcs[0];
+            assert!(double.lazy_body.is_none());
+            assert_eq!(double.slots.is_some(), backend == Backend::Slots);
+        }
+    }
+
+    #[test]
+    fn test_cache_untranslated_module() {
+        use crate::{imports::Imports, Instance};
+
+        let wasm = wat::parse_str(LAZY_PROGRAM).expect("invalid wat");
+        for backend in [Backend::Stack, Backend::Slots] {
+            let mut lazy = parse_bytes_lazy(&wasm).unwrap();
+            lazy.set_backend(backend).unwrap();
+
+            let mut cached = Vec::new();
+            lazy.serialize_cache(&mut cached, b"program").unwrap();
+            let cached = Module::from_cache(&cached, b"program").unwrap();
+            assert_eq!(cached, lazy);
+
+            // The bodies are still translated on their first call
+            let (mut instance, _, _) = Instance::instantiate(cached, Imports::new(), (), None).unwrap();
+            let main = instance.exported_func::<i32, i32>("main").unwrap();
+            assert_eq!(main.invoke(&mut instance, 3).unwrap(), 48);
+            assert!(instance.module.funcs[0].lazy_body.is_none());
+            assert!(instance.module.funcs[1].lazy_body.is_some());
+        }
+    }
+
+    #[test]
+    fn test_cache_translated_module() {
+        use crate::{imports::Imports, Instance};
+
+        let wasm = wat::parse_str(LAZY_PROGRAM).expect("invalid wat");
+        for backend in [Backend::Stack, Backend::Slots] {
+            let mut eager = parse_bytes(&wasm).unwrap();
+            eager.set_backend(backend).unwrap();
+            let mut lazy = parse_bytes_lazy(&wasm).unwrap();
+            lazy.set_backend(backend).unwrap();
+
+            lazy.translate_all().unwrap();
+            assert_eq!(lazy, eager);
+
+            let mut cached = Vec::new();
+            lazy.serialize_cache(&mut cached, b"program").unwrap();
+            let cached = Module::from_cache(&cached, b"program").unwrap();
+
+            // A cache hit has nothing left to translate, running it leaves the module as it was loaded
+            assert!(cached.funcs.iter().all(|f| f.lazy_body.is_none()));
+            let (mut instance, _, _) = Instance::instantiate(cached.clone(), Imports::new(), (), None).unwrap();
+            let main = instance.exported_func::<i32, i32>("main").unwrap();
+            assert_eq!(main.invoke(&mut instance, 3).unwrap(), 48);
+            assert_eq!(instance.module, cached);
+        }
+    }
+
+    #[test]
+    fn test_parse_stream() {
+        /// Returns at most 7 bytes per read, like a slow download
+        struct Trickle<'a>(&'a [u8]);
+
+        impl std::io::Read for Trickle<'_> {
+            fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+                let len = buf.len().min(self.0.len()).min(7);
+                buf[..len].copy_from_slice(&self.0[..len]);
+                self.0 = &self.0[len..];
+                Ok(len)
+            }
+        }
+
+        let wasm = wat::parse_str(LAZY_PROGRAM).expect("invalid wat");
+        let module = parse_bytes(&wasm).unwrap();
+        assert_eq!(Module::parse_stream(Trickle(&wasm)).unwrap(), module);
+        assert!(matches!(Module::parse_stream(Trickle(&wasm[..wasm.len() - 3])), Err(Error::ParseError(_))));
+
+        let path = std::env::temp_dir().join(format!("reef-parse-file-{}.wasm", std::process::id()));
+        std::fs::write(&path, &wasm).unwrap();
+        let parsed = Module::parse_file(&path);
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(parsed.unwrap(), module);
+        assert!(matches!(Module::parse_file(&path), Err(Error::Io(_))));
+    }
+
+    /// Returns a single byte per read and is interrupted before every byte
+    struct ByteByByte<'a> {
+        data: &'a [u8],
+        interrupt: bool,
+    }
+
+    impl<'a> ByteByByte<'a> {
+        fn new(data: &'a [u8]) -> Self {
+            Self { data, interrupt: true }
+        }
+    }
+
+    impl std::io::Read for ByteByByte<'_> {
+        fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+            self.interrupt = !self.interrupt;
+            if !self.interrupt {
+                return Err(std::io::ErrorKind::Interrupted.into());
+            }
+
+            let len = buf.len().min(self.data.len()).min(1);
+            buf[..len].copy_from_slice(&self.data[..len]);
+            self.data = &self.data[len..];
+            Ok(len)
+        }
+    }
+
+    #[test]
+    fn test_parse_stream_byte_by_byte() {
+        let wasm = wat::parse_str(LAZY_PROGRAM).expect("invalid wat");
+        assert_eq!(Module::parse_stream(ByteByByte::new(&wasm)).unwrap(), parse_bytes(&wasm).unwrap());
+    }
+
+    #[test]
+    fn test_parse_stream_truncated() {
+        let wasm = wat::parse_str(LAZY_PROGRAM).expect("invalid wat");
+
+        // A prefix ending between two sections is a valid module on its own, all others have to fail
+        for len in 0..wasm.len() {
+            let streamed = Module::parse_stream(ByteByByte::new(&wasm[..len]));
+            match parse_bytes(&wasm[..len]) {
+                Ok(module) => assert_eq!(streamed.unwrap(), module),
+                Err(_) => assert!(streamed.is_err(), "prefix of {len} bytes was parsed"),
+            }
+        }
+        assert!(matches!(Module::parse_stream(ByteByByte::new(&wasm[..wasm.len() - 1])), Err(Error::ParseError(_))));
+    }
+
+    #[test]
+    fn test_parse_stream_lazy() {
+        use crate::{imports::Imports, Instance};
+
+        let wasm = wat::parse_str(LAZY_PROGRAM).expect("invalid wat");
+        let lazy = Module::parse_stream_lazy(ByteByByte::new(&wasm)).unwrap();
+        assert_eq!(lazy, parse_bytes_lazy(&wasm).unwrap());
+        assert!(lazy.funcs.iter().all(|f| f.lazy_body.is_some()));
+
+        for backend in [Backend::Stack, Backend::Slots] {
+            let mut lazy = lazy.clone();
+            lazy.set_backend(backend).unwrap();
+            let (mut instance, _, _) = Instance::instantiate(lazy, Imports::new(), (), None).unwrap();
+            let main = instance.exported_func::<i32, i32>("main").unwrap();
+            assert_eq!(main.invoke(&mut instance, 3).unwrap(), 48, "{backend:?}");
+        }
+    }
+
+    #[test]
+    fn test_custom_section_lookup() {
+        let wasm = wat::parse_str(
+            r#"
+            (module
+                (@custom "reef.meta" (before first) "first")
+                (@custom "reef.other" "other")
+                (func)
+                (@custom "reef.meta" "second")
+                (@custom "reef.empty" ""))
+            "#,
+        )
+        .expect("invalid wat");
+        let module = parse_bytes(&wasm).unwrap();
+
+        // Sections with the same name are kept in order, the lookup returns the first one
+        let meta = module.custom_sections.iter().filter(|s| &*s.name == "reef.meta").map(|s| &*s.data);
+        assert_eq!(meta.collect::<Vec<_>>(), [&b"first"[..], &b"second"[..]]);
+        assert_eq!(module.custom_section("reef.meta"), Some(&b"first"[..]));
+
+        assert_eq!(module.custom_section("reef.other"), Some(&b"other"[..]));
+        assert_eq!(module.custom_section("reef.empty"), Some(&[][..]));
+        assert_eq!(module.custom_section("reef"), None);
+        assert_eq!(module.custom_section("REEF.META"), None);
+        assert_eq!(parse_bytes_lazy(&wasm).unwrap().custom_section("reef.meta"), Some(&b"first"[..]));
+    }
+
+    #[test]
+    fn test_introspection() {
+        use crate::types::{value::ValType, ExternType, Features};
+
+        let wasm = wat::parse_str(
+            r#"
+            (module
+                (import "reef" "log" (func (param i32 i32)))
+                (import "env" "memory" (memory 1 16))
+                (import "env" "seed" (global (mut i64)))
+                (table (export "table") 2 funcref)
+                (global $scale (export "scale") f32 (f32.const 1.5))
+                (func (export "split") (param i64) (result i32 i32)
+                    (i32.wrap_i64 (local.get 0))
+                    (i32.extend8_s (i32.wrap_i64 (i64.shr_u (local.get 0) (i64.const 32)))))
+                (@custom "reef.meta" "v1"))
+            "#,
+        )
+        .expect("invalid wat");
+        let module = parse_bytes(&wasm).unwrap();
+
+        let imports = module.imports().map(|i| (i.module, i.name, i.ty.kind())).collect::<Vec<_>>();
+        assert_eq!(
+            imports,
+            [
+                ("reef", "log", ExternalKind::Func),
+                ("env", "memory", ExternalKind::Memory),
+                ("env", "seed", ExternalKind::Global)
+            ]
+        );
+
+        let exports = module.exports().map(|e| (e.name, e.ty)).collect::<Vec<_>>();
+        assert_eq!(exports[0].0, "table");
+        assert!(matches!(exports[0].1, ExternType::Table(table) if table.size_initial == 2));
+        assert!(matches!(exports[1].1, ExternType::Global(global) if global.ty == ValType::F32 && !global.mutable));
+        let ExternType::Func(split) = exports[2].1 else { panic!("expected a function") };
+        assert_eq!((&*split.params, &*split.results), (&[ValType::I64][..], &[ValType::I32, ValType::I32][..]));
+
+        assert_eq!(module.func_type(0).unwrap().params.len(), 2);
+        assert!(module.func_type(2).is_none());
+        assert!(module.global_type(0).unwrap().mutable);
+        let memory = module.memories().next().unwrap();
+        assert_eq!((memory.page_count_initial, memory.page_count_max), (1, Some(16)));
+
+        assert_eq!(module.custom_section("reef.meta"), Some(&b"v1"[..]));
+        assert!(module.custom_section("name").is_some());
+        assert_eq!(module.custom_section("producers"), None);
+
+        let features = Features {
+            floats: true,
+            mutable_global: true,
+            sign_extension: true,
+            multi_value: true,
+            ..Features::default()
+        };
+        assert_eq!(module.features, features);
+        assert_eq!(parse_bytes(&wat::parse_str(LAZY_PROGRAM).unwrap()).unwrap().features, Features::default());
+    }
+}
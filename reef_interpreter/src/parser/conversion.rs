@@ -2,15 +2,16 @@ use alloc::{boxed::Box, format, string::ToString, vec::Vec};
 
 use crate::parser::{
     error::{ParseError, Result},
+    features,
     module::Code,
-    visit::process_operators,
+    visit::{process_operators, validate_operators},
 };
 use crate::types::{
     self,
     instructions::{BlockArgs, ConstInstruction, MemoryArg},
     value::ValType,
-    ElementItem, Export, ExternalKind, FuncType, Global, GlobalType, Import, ImportKind, MemoryArch, MemoryType,
-    TableType,
+    ElementItem, Export, ExternalKind, Features, FuncType, Global, GlobalType, Import, ImportKind, LazyBody,
+    MemoryArch, MemoryType, TableType,
 };
 
 // use types::*;
@@ -182,6 +183,8 @@ pub(crate) fn convert_module_export(export: wasmparser::Export<'_>) -> Result<Ex
 pub(crate) fn convert_module_code(
     func: wasmparser::FunctionBody<'_>,
     validator: &mut FuncValidator<ValidatorResources>,
+    features: &mut Features,
+    lazy: bool,
 ) -> Result<Code> {
     let locals_reader = func.get_locals_reader()?;
     let count = locals_reader.get_count();
@@ -195,10 +198,17 @@ pub(crate) fn convert_module_code(
             locals.push(convert_valtype(&local.1));
         }
     }
+    let locals = locals.into_boxed_slice();
+    features::scan_body(&func, features)?;
+
+    if lazy {
+        validate_operators(validator, &func)?;
+        let body = LazyBody { offset: func.range().start, bytes: func.as_bytes().into() };
+        return Ok((Box::default(), locals, Some(body)));
+    }
 
     let body = process_operators(Some(validator), func)?;
-    let locals = locals.into_boxed_slice();
-    Ok((body, locals))
+    Ok((body, locals, None))
 }
 
 pub(crate) fn convert_module_type(ty: wasmparser::RecGroup) -> Result<FuncType> {
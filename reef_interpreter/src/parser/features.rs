@@ -0,0 +1,217 @@
+//! Detection of the WebAssembly features a module uses, see [`Features`]
+
+use wasmparser::{BlockType, FunctionBody, Operator};
+
+use crate::parser::Result;
+use crate::types::{value::ValType, DataKind, ElementKind, ExternalKind, Features, ImportKind, Module};
+
+/// Record the features used by the operators of a function body
+pub(crate) fn scan_body(body: &FunctionBody<'_>, features: &mut Features) -> Result<()> {
+    let mut reader = body.get_operators_reader()?;
+    while !reader.eof() {
+        scan_operator(&reader.read()?, features);
+    }
+    Ok(())
+}
+
+fn scan_operator(op: &Operator<'_>, features: &mut Features) {
+    use Operator::*;
+
+    match op {
+        Block { blockty } | Loop { blockty } | If { blockty } => match blockty {
+            BlockType::Empty => {}
+            BlockType::Type(ty) => scan_wasmparser_type(ty, features),
+            BlockType::FuncType(_) => features.multi_value = true,
+        },
+
+        // A float operand has to come from a float value, a load, a constant or a conversion
+        F32Const { .. }
+        | F64Const { .. }
+        | F32Load { .. }
+        | F64Load { .. }
+        | F32ConvertI32S
+        | F32ConvertI32U
+        | F32ConvertI64S
+        | F32ConvertI64U
+        | F64ConvertI32S
+        | F64ConvertI32U
+        | F64ConvertI64S
+        | F64ConvertI64U
+        | F32ReinterpretI32
+        | F64ReinterpretI64 => features.floats = true,
+
+        I32Extend8S | I32Extend16S | I64Extend8S | I64Extend16S | I64Extend32S => features.sign_extension = true,
+
+        I32TruncSatF32S | I32TruncSatF32U | I32TruncSatF64S | I32TruncSatF64U | I64TruncSatF32S | I64TruncSatF32U
+        | I64TruncSatF64S | I64TruncSatF64U => features.saturating_float_to_int = true,
+
+        MemoryInit { .. }
+        | DataDrop { .. }
+        | MemoryCopy { .. }
+        | MemoryFill { .. }
+        | TableInit { .. }
+        | ElemDrop { .. }
+        | TableCopy { .. } => features.bulk_memory = true,
+
+        RefNull { .. }
+        | RefIsNull
+        | RefFunc { .. }
+        | TableGet { .. }
+        | TableSet { .. }
+        | TableGrow { .. }
+        | TableSize { .. }
+        | TableFill { .. }
+        | TypedSelect { .. } => features.reference_types = true,
+        CallIndirect { table_index, .. } if *table_index != 0 => features.reference_types = true,
+
+        _ => {}
+    }
+}
+
+fn scan_wasmparser_type(ty: &wasmparser::ValType, features: &mut Features) {
+    match ty {
+        wasmparser::ValType::F32 | wasmparser::ValType::F64 => features.floats = true,
+        wasmparser::ValType::Ref(_) => features.reference_types = true,
+        _ => {}
+    }
+}
+
+fn scan_type(ty: &ValType, features: &mut Features) {
+    match ty {
+        ValType::F32 | ValType::F64 => features.floats = true,
+        ValType::RefExtern => features.reference_types = true,
+        _ => {}
+    }
+}
+
+/// Record the features used by the types, imports, exports and segments of a module
+pub(crate) fn scan_module(module: &Module, features: &mut Features) {
+    for ty in module.func_types.iter() {
+        ty.params.iter().chain(ty.results.iter()).for_each(|ty| scan_type(ty, features));
+        features.multi_value |= ty.results.len() > 1;
+    }
+    for func in module.funcs.iter() {
+        func.locals.iter().for_each(|ty| scan_type(ty, features));
+    }
+    for global in module.globals.iter() {
+        scan_type(&global.ty.ty, features);
+    }
+
+    let mut tables = module.table_types.len();
+    for import in module.imports.iter() {
+        match &import.kind {
+            ImportKind::Global(global) => {
+                scan_type(&global.ty, features);
+                features.mutable_global |= global.mutable;
+            }
+            ImportKind::Table(_) => tables += 1,
+            ImportKind::Function(_) | ImportKind::Memory(_) => {}
+        }
+    }
+    features.reference_types |= tables > 1;
+    features.reference_types |= module.table_types.iter().any(|table| table.element_type == ValType::RefExtern);
+
+    for export in module.exports.iter().filter(|export| export.kind == ExternalKind::Global) {
+        features.mutable_global |= module.global_type(export.index).is_some_and(|global| global.mutable);
+    }
+
+    features.bulk_memory |= module.data.iter().any(|data| matches!(data.kind, DataKind::Passive));
+    for element in module.elements.iter() {
+        match element.kind {
+            ElementKind::Passive => features.bulk_memory = true,
+            ElementKind::Declared => features.reference_types = true,
+            ElementKind::Active { .. } => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn features(wat: &str) -> Features {
+        crate::parse_bytes(&wat::parse_str(wat).expect("invalid wat")).expect("failed to parse module").features
+    }
+
+    #[test]
+    fn test_floats() {
+        let floats = Features { floats: true, ..Features::default() };
+        assert_eq!(features("(module (func (param f64)))"), floats);
+        assert_eq!(features("(module (func (local f32)))"), floats);
+        assert_eq!(features("(module (global f64 (f64.const 0)))"), floats);
+        assert_eq!(features("(module (func (drop (f32.convert_i32_s (i32.const 1)))))"), floats);
+        assert_eq!(features("(module (memory 1) (func (drop (f64.load (i32.const 0)))))"), floats);
+        assert_eq!(features("(module (func (block (result f32) (unreachable)) (drop)))"), floats);
+    }
+
+    #[test]
+    fn test_mutable_global() {
+        let mutable_global = Features { mutable_global: true, ..Features::default() };
+        assert_eq!(features(r#"(module (import "env" "g" (global (mut i32))))"#), mutable_global);
+        assert_eq!(features(r#"(module (global (export "g") (mut i64) (i64.const 0)))"#), mutable_global);
+
+        // immutable or not shared with the host
+        assert_eq!(features(r#"(module (global (export "g") i32 (i32.const 0)))"#), Features::default());
+        assert_eq!(features(r#"(module (global (mut i32) (i32.const 0)))"#), Features::default());
+    }
+
+    #[test]
+    fn test_sign_extension() {
+        let sign_extension = Features { sign_extension: true, ..Features::default() };
+        assert_eq!(features("(module (func (drop (i32.extend16_s (i32.const 1)))))"), sign_extension);
+        assert_eq!(features("(module (func (drop (i64.extend32_s (i64.const 1)))))"), sign_extension);
+    }
+
+    #[test]
+    fn test_saturating_float_to_int() {
+        let saturating = Features { saturating_float_to_int: true, floats: true, ..Features::default() };
+        assert_eq!(features("(module (func (drop (i32.trunc_sat_f32_u (f32.const 1)))))"), saturating);
+        assert_eq!(features("(module (func (drop (i64.trunc_sat_f64_s (f64.const 1)))))"), saturating);
+    }
+
+    #[test]
+    fn test_multi_value() {
+        let multi_value = Features { multi_value: true, ..Features::default() };
+        assert_eq!(features("(module (func (result i32 i64) (i32.const 0) (i64.const 0)))"), multi_value);
+        assert_eq!(features("(module (func (i32.const 1) (block (param i32) (drop))))"), multi_value);
+    }
+
+    #[test]
+    fn test_bulk_memory() {
+        let bulk_memory = Features { bulk_memory: true, ..Features::default() };
+        assert_eq!(features("(module (memory 1) (data \"passive\"))"), bulk_memory);
+        assert_eq!(features("(module (elem func $f) (func $f))"), bulk_memory);
+        assert_eq!(
+            features("(module (memory 1) (func (memory.copy (i32.const 0) (i32.const 8) (i32.const 4))))"),
+            bulk_memory
+        );
+        assert_eq!(features("(module (memory 1) (data $d \"a\") (func (data.drop $d)))"), bulk_memory);
+
+        // active segments are part of the MVP
+        assert_eq!(features("(module (memory 1) (data (i32.const 0) \"active\"))"), Features::default());
+    }
+
+    #[test]
+    fn test_reference_types() {
+        let reference_types = Features { reference_types: true, ..Features::default() };
+        assert_eq!(features("(module (table 1 funcref) (table 1 funcref))"), reference_types);
+        assert_eq!(features(r#"(module (import "env" "t" (table 1 funcref)) (table 1 funcref))"#), reference_types);
+        assert_eq!(features("(module (table 1 externref))"), reference_types);
+        assert_eq!(features("(module (func $f) (elem declare func $f))"), reference_types);
+        assert_eq!(features("(module (table 1 funcref) (func (drop (table.size 0))))"), reference_types);
+        assert_eq!(features("(module (func (param externref)))"), reference_types);
+        assert_eq!(
+            features(
+                "(module (type $t (func)) (table 1 funcref) (table $second 1 funcref)
+                    (func (call_indirect $second (type $t) (i32.const 0))))"
+            ),
+            reference_types
+        );
+
+        // a single table and `call_indirect` on it are part of the MVP
+        assert_eq!(
+            features("(module (type $t (func)) (table 1 funcref) (func (call_indirect (type $t) (i32.const 0))))"),
+            Features::default()
+        );
+    }
+}
@@ -1,23 +1,26 @@
 //! Parser that translates [`wasmparser`](https://docs.rs/wasmparser) types to types used by this crate.
 
-use alloc::{string::ToString, vec::Vec};
+use alloc::{boxed::Box, string::ToString, vec::Vec};
 
 mod conversion;
 pub(crate) mod error;
+mod features;
 pub(crate) mod module;
+pub(crate) mod optimize;
+pub(crate) mod slots;
 mod visit;
 
-use crate::types::{Module, WasmFunction};
+use crate::types::{instructions::Instruction, Backend, Features, LazyBody, Module, WasmFunction};
 use error::{ParseError, Result};
 use module::ModuleReader;
-use wasmparser::{Validator, WasmFeaturesInflated};
+use wasmparser::{BinaryReader, FunctionBody, Validator, ValidatorResources, WasmFeatures, WasmFeaturesInflated};
 
 /// A WebAssembly parser
 #[derive(Default, Debug)]
 pub(crate) struct Parser {}
 
 impl Parser {
-    fn create_validator() -> Validator {
+    fn features() -> WasmFeatures {
         let features = WasmFeaturesInflated {
             bulk_memory: true,
             floats: true,
@@ -44,14 +47,20 @@ impl Parser {
             custom_page_sizes: false,
             shared_everything_threads: false,
         };
-        Validator::new_with_features(features.into())
+        features.into()
+    }
+
+    fn create_validator() -> Validator {
+        Validator::new_with_features(Self::features())
     }
 
     /// Parse a [`Module`] from bytes
-    pub(crate) fn parse_module_bytes(wasm: impl AsRef<[u8]>) -> Result<Module> {
+    ///
+    /// If `lazy` is set, function bodies are only validated and have to be translated with [`Parser::translate`].
+    pub(crate) fn parse_module_bytes(wasm: impl AsRef<[u8]>, lazy: bool) -> Result<Module> {
         let wasm = wasm.as_ref();
         let mut validator = Self::create_validator();
-        let mut reader = ModuleReader::new();
+        let mut reader = ModuleReader::new(lazy);
 
         for payload in wasmparser::Parser::new(0).parse_all(wasm) {
             reader.process_payload(payload?, &mut validator)?;
@@ -63,6 +72,56 @@ impl Parser {
 
         reader.try_into()
     }
+
+    /// Parse a [`Module`] while reading it from `stream`
+    ///
+    /// Only the section or function body which is currently parsed is buffered.
+    /// If `lazy` is set, function bodies are only validated, like in [`Parser::parse_module_bytes`].
+    #[cfg(feature = "std")]
+    pub(crate) fn parse_module_stream(mut stream: impl std::io::Read, lazy: bool) -> crate::error::Result<Module> {
+        /// Upper bound of a single read, so a corrupted section size doesn't allocate a huge buffer up front
+        const READ_CHUNK: u64 = 0x10000;
+
+        let mut validator = Self::create_validator();
+        let mut reader = ModuleReader::new(lazy);
+        let mut parser = wasmparser::Parser::new(0);
+        let mut buffer = Vec::new();
+        let mut eof = false;
+
+        loop {
+            match parser.parse(&buffer, eof).map_err(ParseError::from)? {
+                wasmparser::Chunk::NeedMoreData(hint) => {
+                    let len = buffer.len();
+                    buffer.resize(len + hint.min(READ_CHUNK) as usize, 0);
+                    let read = loop {
+                        match stream.read(&mut buffer[len..]) {
+                            Err(err) if err.kind() == std::io::ErrorKind::Interrupted => continue,
+                            res => break res?,
+                        }
+                    };
+                    buffer.truncate(len + read);
+                    eof = read == 0;
+                }
+                wasmparser::Chunk::Parsed { consumed, payload } => {
+                    reader.process_payload(payload, &mut validator)?;
+                    buffer.drain(..consumed);
+                    if reader.end_reached {
+                        return Ok(reader.try_into()?);
+                    }
+                }
+            }
+        }
+    }
+
+    /// Translate a function body which was validated by [`Parser::parse_module_bytes`]
+    pub(crate) fn translate(body: &LazyBody, superinstructions: bool) -> Result<Box<[Instruction]>> {
+        let reader = BinaryReader::new(&body.bytes, body.offset, Self::features());
+        let mut instructions = visit::process_operators::<ValidatorResources>(None, FunctionBody::new(reader))?;
+        if !superinstructions {
+            optimize::unfuse(&mut instructions);
+        }
+        Ok(instructions)
+    }
 }
 
 impl TryFrom<ModuleReader> for Module {
@@ -84,17 +143,20 @@ impl TryFrom<ModuleReader> for Module {
             .code
             .into_iter()
             .zip(code_type_addrs)
-            .map(|((instructions, locals), ty_idx)| WasmFunction {
+            .map(|((instructions, locals, lazy_body), ty_idx)| WasmFunction {
                 instructions,
                 locals,
                 ty: reader.func_types.get(ty_idx as usize).expect("No func type for func, this is a bug").clone(),
+                slots: None,
+                lazy_body,
             })
             .collect::<Vec<_>>();
 
         let globals = reader.globals;
         let table_types = reader.table_types;
 
-        Ok(Module {
+        let mut features = reader.features;
+        let mut module = Module {
             funcs: funcs.into_boxed_slice(),
             func_types: reader.func_types.into_boxed_slice(),
             globals: globals.into_boxed_slice(),
@@ -105,6 +167,13 @@ impl TryFrom<ModuleReader> for Module {
             exports: reader.exports.into_boxed_slice(),
             elements: reader.elements.into_boxed_slice(),
             memory_types: reader.memory_types.into_boxed_slice(),
-        })
+            backend: Backend::Stack,
+            superinstructions: true,
+            custom_sections: reader.custom_sections.into_boxed_slice(),
+            features: Features::default(),
+        };
+        features::scan_module(&module, &mut features);
+        module.features = features;
+        Ok(module)
     }
 }
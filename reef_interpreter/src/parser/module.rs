@@ -4,14 +4,17 @@ use wasmparser::{FuncValidatorAllocations, Payload, Validator};
 
 use crate::parser::{conversion, ParseError, Result};
 use crate::types::{
-    instructions::Instruction, value::ValType, Data, Element, Export, FuncType, Global, Import, MemoryType, TableType,
+    self, instructions::Instruction, value::ValType, Data, Element, Export, Features, FuncType, Global, Import,
+    LazyBody, MemoryType, TableType,
 };
 
-pub(crate) type Code = (Box<[Instruction]>, Box<[ValType]>);
+pub(crate) type Code = (Box<[Instruction]>, Box<[ValType]>, Option<LazyBody>);
 
 #[derive(Default)]
 pub(crate) struct ModuleReader {
     func_validator_allocations: Option<FuncValidatorAllocations>,
+    /// Only validate function bodies, they are translated on their first call
+    lazy: bool,
 
     pub(crate) version: Option<u16>,
     pub(crate) start_func: Option<u32>,
@@ -25,12 +28,14 @@ pub(crate) struct ModuleReader {
     pub(crate) imports: Vec<Import>,
     pub(crate) data: Vec<Data>,
     pub(crate) elements: Vec<Element>,
+    pub(crate) custom_sections: Vec<types::CustomSection>,
+    pub(crate) features: Features,
     pub(crate) end_reached: bool,
 }
 
 impl ModuleReader {
-    pub(crate) fn new() -> ModuleReader {
-        Self::default()
+    pub(crate) fn new(lazy: bool) -> ModuleReader {
+        Self { lazy, ..Self::default() }
     }
 
     pub(crate) fn process_payload(&mut self, payload: Payload<'_>, validator: &mut Validator) -> Result<()> {
@@ -126,7 +131,12 @@ impl ModuleReader {
             CodeSectionEntry(function) => {
                 let v = validator.code_section_entry(&function)?;
                 let mut func_validator = v.into_validator(self.func_validator_allocations.take().unwrap_or_default());
-                self.code.push(conversion::convert_module_code(function, &mut func_validator)?);
+                self.code.push(conversion::convert_module_code(
+                    function,
+                    &mut func_validator,
+                    &mut self.features,
+                    self.lazy,
+                )?);
                 self.func_validator_allocations = Some(func_validator.into_allocations());
             }
             ImportSection(reader) => {
@@ -154,8 +164,9 @@ impl ModuleReader {
                 validator.end(offset)?;
                 self.end_reached = true;
             }
-            CustomSection(_reader) => {
-                // debug!("Skipping custom section: {:?}", _reader.name());
+            CustomSection(reader) => {
+                self.custom_sections
+                    .push(types::CustomSection { name: reader.name().into(), data: reader.data().into() });
             }
             UnknownSection { .. } => return Err(ParseError::UnsupportedSection("Unknown section".into())),
             section => return Err(ParseError::UnsupportedSection(format!("Unsupported section: {:?}", section))),
@@ -0,0 +1,118 @@
+//! Peephole pass fusing common instruction sequences into superinstructions.
+//!
+//! A superinstruction replaces the first instruction of its sequence, the remaining instructions are kept
+//! and skipped when executing it. Instruction pointers therefore mean the same with and without fused code,
+//! snapshots taken by an interpreter which stopped in the middle of a sequence can be resumed either way.
+
+use crate::types::instructions::{I32Cmp, Instruction};
+
+/// Fuse superinstructions in place.
+pub(crate) fn fuse(instructions: &mut [Instruction]) {
+    use Instruction::*;
+
+    for idx in 0..instructions.len().saturating_sub(1) {
+        let fused = match instructions[idx..idx + 2] {
+            [LocalGet2(a, b), I32Add] => I32AddLocals(a, b),
+            [I32LocalGetConstAdd(local, val), LocalSet(target)] => I32LocalGetConstAddSet(local, val, target),
+            [cmp, BrIf(label)] => match I32Cmp::from_instruction(cmp) {
+                Some(cmp) => I32CmpBrIf(cmp, label),
+                None => continue,
+            },
+            _ => continue,
+        };
+
+        instructions[idx] = fused;
+    }
+}
+
+/// Restore the first instruction of every fused sequence.
+pub(crate) fn unfuse(instructions: &mut [Instruction]) {
+    use Instruction::*;
+
+    for instr in instructions {
+        *instr = match *instr {
+            I32AddLocals(a, b) => LocalGet2(a, b),
+            I32LocalGetConstAddSet(local, val, _) => I32LocalGetConstAdd(local, val),
+            I32CmpBrIf(cmp, _) => cmp.instruction(),
+            _ => continue,
+        };
+    }
+}
+
+/// Number of instructions covered by a superinstruction.
+pub(crate) const FUSED_LEN: usize = 2;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{exec::CallResultTyped, imports::Imports, Instance, Module};
+
+    fn sum_module() -> Module {
+        let wasm = wat::parse_str(
+            r#"
+            (module
+                (func (export "sum") (param $n i32) (result i32) (local $i i32) (local $acc i32)
+                    (block $done
+                        (loop $next
+                            (br_if $done (i32.ge_s (local.get $i) (local.get $n)))
+                            (local.set $acc (i32.add (local.get $acc) (local.get $i)))
+                            (local.set $i (i32.add (local.get $i) (i32.const 1)))
+                            (br $next)))
+                    (local.get $acc)))
+            "#,
+        )
+        .expect("invalid wat");
+        crate::parse_bytes(&wasm).expect("failed to parse module")
+    }
+
+    #[test]
+    fn test_fuse_unfuse() {
+        let fused = sum_module();
+        let instrs = &fused.funcs[0].instructions;
+        assert!(instrs.iter().any(|i| matches!(i, Instruction::I32AddLocals(..))));
+        assert!(instrs.iter().any(|i| matches!(i, Instruction::I32LocalGetConstAddSet(..))));
+        assert!(instrs.iter().any(|i| matches!(i, Instruction::I32CmpBrIf(I32Cmp::GeS, _))));
+
+        let mut unfused = fused.clone();
+        unfused.set_superinstructions(false);
+        assert_eq!(unfused.funcs[0].instructions.len(), instrs.len());
+        assert!(!unfused.funcs[0].instructions.iter().any(|i| matches!(
+            i,
+            Instruction::I32AddLocals(..) | Instruction::I32LocalGetConstAddSet(..) | Instruction::I32CmpBrIf(..)
+        )));
+
+        unfused.set_superinstructions(true);
+        assert_eq!(unfused, fused);
+    }
+
+    #[test]
+    fn test_resume_unfused_snapshot() {
+        let fused = sum_module();
+        let mut unfused = fused.clone();
+        unfused.set_superinstructions(false);
+
+        // Pause after every possible number of cycles, including in the middle of fused sequences
+        for cycles in 0..64 {
+            let (instance, stack, _) = Instance::instantiate(unfused.clone(), Imports::new(), (), None).unwrap();
+            let sum = instance.exported_func::<i32, i32>("sum").unwrap();
+            let mut exec_handle = sum.call(instance, 5, stack).unwrap();
+            if let CallResultTyped::Done(res) = exec_handle.run(cycles).unwrap() {
+                assert_eq!(res, 10);
+                continue;
+            }
+
+            let mut state = Vec::new();
+            exec_handle.serialize(&mut state, &[]).unwrap();
+
+            let (instance, stack, _) = Instance::instantiate(fused.clone(), Imports::new(), (), Some(&state)).unwrap();
+            let sum = instance.exported_func::<i32, i32>("sum").unwrap();
+            let mut exec_handle = sum.call(instance, 5, stack).unwrap();
+            let res = loop {
+                if let CallResultTyped::Done(res) = exec_handle.run(3).unwrap() {
+                    break res;
+                }
+            };
+            assert_eq!(res, 10);
+        }
+    }
+}
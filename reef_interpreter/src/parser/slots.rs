@@ -0,0 +1,704 @@
+//! Lowering of functions for [`Backend::Slots`](crate::types::Backend::Slots)
+//!
+//! The operand stack of a function is simulated at lowering time, every stack height is assigned a fixed slot
+//! behind the locals of the frame. Values of `local.get` stay in the slot of the local until the local is
+//! overwritten or control flow merges, so most instructions read their operands directly from locals.
+//! Blocks are resolved to jumps, values are moved to the slots of the target label before a branch.
+
+use alloc::{format, string::ToString, vec::Vec};
+
+use crate::parser::{
+    error::{ParseError, Result},
+    optimize,
+};
+use crate::types::{
+    instructions::{BlockArgs, Instruction},
+    slots::{Slot, SlotFunction, SlotInstruction},
+    FuncAddr, FuncType, WasmFunction,
+};
+
+macro_rules! load {
+    ($name:ident) => {
+        |dst, addr, offset, mem| SlotInstruction::$name { dst, addr, offset, mem }
+    };
+}
+
+macro_rules! store {
+    ($name:ident) => {
+        |addr, src, offset, mem| SlotInstruction::$name { addr, src, offset, mem }
+    };
+}
+
+/// Lower `func` to slot code
+///
+/// `func_types` are the types of the module and `callee_ty` resolves the type of called functions.
+pub(crate) fn lower<'a>(
+    func: &WasmFunction,
+    func_types: &'a [FuncType],
+    callee_ty: impl Fn(FuncAddr) -> Option<&'a FuncType>,
+) -> Result<SlotFunction> {
+    // Superinstructions are lowered from the sequences they were fused from
+    let mut instructions = func.instructions.to_vec();
+    optimize::unfuse(&mut instructions);
+
+    let locals = func.ty.params.len() + func.locals.len();
+    let mut lowering = Lowering {
+        func_types,
+        callee_ty,
+        results: func.ty.results.len(),
+        locals,
+        code: Vec::with_capacity(instructions.len()),
+        operands: Vec::new(),
+        max_height: 0,
+        labels: Vec::new(),
+        reachable: true,
+        barrier: 0,
+    };
+
+    let mut pc = 0;
+    while pc < instructions.len() {
+        pc += lowering.lower_instr(&instructions, pc)?;
+    }
+
+    let frame_size = locals + lowering.max_height;
+    if frame_size > Slot::MAX as usize + 1 {
+        return Err(too_many_slots());
+    }
+
+    Ok(SlotFunction { code: lowering.code.into_boxed_slice(), frame_size: frame_size as u32 })
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum LabelKind {
+    Block,
+    Loop,
+    If,
+}
+
+#[derive(Debug)]
+struct Label {
+    kind: LabelKind,
+    /// Height of the operand stack below the parameters of the block
+    height: usize,
+    params: usize,
+    results: usize,
+    /// Start of a loop
+    start: u32,
+    /// Jumps to the end of the block
+    fixups: Vec<usize>,
+    /// Jump to the else branch of an if block
+    else_fixup: Option<usize>,
+    /// Whether the start of the block is reachable
+    reachable: bool,
+}
+
+struct Lowering<'a, F> {
+    func_types: &'a [FuncType],
+    callee_ty: F,
+    results: usize,
+    locals: usize,
+
+    code: Vec<SlotInstruction>,
+    /// Slot the value of each operand is read from
+    operands: Vec<Slot>,
+    max_height: usize,
+    labels: Vec<Label>,
+    reachable: bool,
+    /// Instructions before this offset may be jumped over, so their results can't be redirected
+    barrier: usize,
+}
+
+impl<'a, F: Fn(FuncAddr) -> Option<&'a FuncType>> Lowering<'a, F> {
+    /// Lower the instruction at `pc`, returns the number of consumed instructions
+    fn lower_instr(&mut self, instructions: &[Instruction], pc: usize) -> Result<usize> {
+        use Instruction::*;
+
+        match instructions[pc] {
+            // Custom instructions are lowered like the instructions they replace
+            LocalGet2(a, b) => {
+                self.lower_basic(LocalGet(a), pc)?;
+                self.lower_basic(LocalGet(b), pc)?;
+            }
+            LocalGet3(a, b, c) => {
+                self.lower_basic(LocalGet(a), pc)?;
+                self.lower_basic(LocalGet(b), pc)?;
+                self.lower_basic(LocalGet(c), pc)?;
+            }
+            LocalTeeGet(a, b) => {
+                self.lower_basic(LocalTee(a), pc)?;
+                self.lower_basic(LocalGet(b), pc)?;
+            }
+            LocalGetSet(a, b) => {
+                self.lower_basic(LocalGet(a), pc)?;
+                self.lower_basic(LocalSet(b), pc)?;
+            }
+            I32LocalGetConstAdd(local, val) => {
+                self.lower_basic(LocalGet(local), pc)?;
+                self.lower_basic(I32Const(val), pc)?;
+                self.lower_basic(I32Add, pc)?;
+            }
+            I32StoreLocal { local, const_i32, offset, mem_addr } => {
+                self.lower_basic(LocalGet(local), pc)?;
+                self.lower_basic(I32Const(const_i32), pc)?;
+                self.lower_basic(I32Store { offset: offset as u64, mem_addr: mem_addr as u32 }, pc)?;
+            }
+            I64XorConstRotl(rotate_by) => {
+                self.lower_basic(I64Xor, pc)?;
+                self.lower_basic(I64Const(rotate_by), pc)?;
+                self.lower_basic(I64Rotl, pc)?;
+            }
+            BrTable(default, len) => {
+                let labels = instructions.get(pc + 1..pc + 1 + len as usize).ok_or_else(invalid_br_table)?;
+                if self.reachable {
+                    self.lower_br_table(labels, default)?;
+                }
+                return Ok(1 + len as usize);
+            }
+            instr => self.lower_basic(instr, pc)?,
+        }
+
+        Ok(1)
+    }
+
+    fn lower_basic(&mut self, instr: Instruction, pc: usize) -> Result<()> {
+        use Instruction::*;
+
+        if !self.reachable {
+            return self.lower_unreachable(instr);
+        }
+
+        if let Some(op) = SlotInstruction::unary(instr) {
+            let src = self.pop()?;
+            let dst = self.push_new()?;
+            self.emit(op(dst, src));
+            return Ok(());
+        }
+
+        if let Some(op) = SlotInstruction::binary(instr) {
+            let b = self.pop()?;
+            let a = self.pop()?;
+            let dst = self.push_new()?;
+            self.emit(op(dst, a, b));
+            return Ok(());
+        }
+
+        match instr {
+            Nop => {}
+            Unreachable => {
+                self.emit(SlotInstruction::Unreachable);
+                self.reachable = false;
+            }
+
+            Block(args, _) => {
+                let (params, results) = self.block_arity(args)?;
+                self.materialize(0);
+                self.push_label(LabelKind::Block, params, results, None);
+            }
+            Loop(args, _) => {
+                let (params, results) = self.block_arity(args)?;
+                self.materialize(0);
+                self.barrier = self.code.len();
+                self.push_label(LabelKind::Loop, params, results, None);
+            }
+            If(args, _, _) => {
+                let (params, results) = self.block_arity(args.into())?;
+                let cond = self.pop()?;
+                self.materialize(0);
+                let else_fixup = self.emit(SlotInstruction::JumpIfNot(cond, 0));
+                self.push_label(LabelKind::If, params, results, Some(else_fixup));
+            }
+            Else(_) => self.lower_else()?,
+            EndBlockFrame => self.lower_end()?,
+
+            Br(depth) => {
+                self.branch(depth)?;
+                self.reachable = false;
+            }
+            BrIf(depth) => {
+                let cond = self.pop()?;
+                self.materialize(0);
+                match self.direct_target(depth)? {
+                    Some(target) => self.jump(SlotInstruction::JumpIf(cond, 0), target),
+                    None => {
+                        let skip = self.emit(SlotInstruction::JumpIfNot(cond, 0));
+                        self.branch(depth)?;
+                        self.patch(skip);
+                    }
+                }
+            }
+            Return => {
+                let src = self.top_slots(self.results)?;
+                self.emit(SlotInstruction::Return(src));
+                self.reachable = false;
+            }
+            Call(func) => {
+                let ty = (self.callee_ty)(func).ok_or_else(|| ParseError::Other(format!("unknown function {func}")))?;
+                let base = self.top_slots(ty.params.len())?;
+                self.operands.truncate(self.operands.len() - ty.params.len());
+                self.emit(SlotInstruction::Call(func, base));
+                self.push_results(ty.results.len())?;
+            }
+            CallIndirect(type_addr, table) => {
+                let ty = self.func_types.get(type_addr as usize).ok_or(ParseError::InvalidType)?;
+                let (params, results) = (ty.params.len(), ty.results.len());
+                let idx = self.pop()?;
+                let base = self.top_slots(params)?;
+                self.operands.truncate(self.operands.len() - params);
+                self.emit(SlotInstruction::CallIndirect(type_addr, table, idx, base));
+                self.push_results(results)?;
+            }
+
+            Drop => {
+                self.pop()?;
+            }
+            Select(_) => {
+                let cond = self.pop()?;
+                let b = self.pop()?;
+                let a = self.pop()?;
+                let dst = self.push_new()?;
+                self.emit(SlotInstruction::Select(dst, a, b, cond));
+            }
+
+            LocalGet(local) => self.push(local as Slot)?,
+            LocalSet(local) => self.set_local(local as Slot)?,
+            LocalTee(local) => {
+                self.set_local(local as Slot)?;
+                self.push(local as Slot)?;
+            }
+            GlobalGet(global) => {
+                let dst = self.push_new()?;
+                self.emit(SlotInstruction::GlobalGet(dst, global));
+            }
+            GlobalSet(global) => {
+                let src = self.pop()?;
+                self.emit(SlotInstruction::GlobalSet(global, src));
+            }
+
+            I32Const(val) => self.lower_const(|dst| SlotInstruction::I32Const(dst, val))?,
+            I64Const(val) => self.lower_const(|dst| SlotInstruction::I64Const(dst, val))?,
+            F32Const(val) => self.lower_const(|dst| SlotInstruction::F32Const(dst, val))?,
+            F64Const(val) => self.lower_const(|dst| SlotInstruction::F64Const(dst, val))?,
+
+            I32Load { offset, mem_addr } => self.lower_load(offset, mem_addr, load!(I32Load))?,
+            I64Load { offset, mem_addr } => self.lower_load(offset, mem_addr, load!(I64Load))?,
+            F32Load { offset, mem_addr } => self.lower_load(offset, mem_addr, load!(F32Load))?,
+            F64Load { offset, mem_addr } => self.lower_load(offset, mem_addr, load!(F64Load))?,
+            I32Load8S { offset, mem_addr } => self.lower_load(offset, mem_addr, load!(I32Load8S))?,
+            I32Load8U { offset, mem_addr } => self.lower_load(offset, mem_addr, load!(I32Load8U))?,
+            I32Load16S { offset, mem_addr } => self.lower_load(offset, mem_addr, load!(I32Load16S))?,
+            I32Load16U { offset, mem_addr } => self.lower_load(offset, mem_addr, load!(I32Load16U))?,
+            I64Load8S { offset, mem_addr } => self.lower_load(offset, mem_addr, load!(I64Load8S))?,
+            I64Load8U { offset, mem_addr } => self.lower_load(offset, mem_addr, load!(I64Load8U))?,
+            I64Load16S { offset, mem_addr } => self.lower_load(offset, mem_addr, load!(I64Load16S))?,
+            I64Load16U { offset, mem_addr } => self.lower_load(offset, mem_addr, load!(I64Load16U))?,
+            I64Load32S { offset, mem_addr } => self.lower_load(offset, mem_addr, load!(I64Load32S))?,
+            I64Load32U { offset, mem_addr } => self.lower_load(offset, mem_addr, load!(I64Load32U))?,
+            I32Store { offset, mem_addr } => self.lower_store(offset, mem_addr, store!(I32Store))?,
+            I64Store { offset, mem_addr } => self.lower_store(offset, mem_addr, store!(I64Store))?,
+            F32Store { offset, mem_addr } => self.lower_store(offset, mem_addr, store!(F32Store))?,
+            F64Store { offset, mem_addr } => self.lower_store(offset, mem_addr, store!(F64Store))?,
+            I32Store8 { offset, mem_addr } => self.lower_store(offset, mem_addr, store!(I32Store8))?,
+            I32Store16 { offset, mem_addr } => self.lower_store(offset, mem_addr, store!(I32Store16))?,
+            I64Store8 { offset, mem_addr } => self.lower_store(offset, mem_addr, store!(I64Store8))?,
+            I64Store16 { offset, mem_addr } => self.lower_store(offset, mem_addr, store!(I64Store16))?,
+            I64Store32 { offset, mem_addr } => self.lower_store(offset, mem_addr, store!(I64Store32))?,
+
+            MemorySize(mem, 0) => {
+                let dst = self.push_new()?;
+                self.emit(SlotInstruction::MemorySize(dst, mem));
+            }
+            MemoryGrow(mem, 0) => {
+                let delta = self.pop()?;
+                let dst = self.push_new()?;
+                self.emit(SlotInstruction::MemoryGrow(dst, delta, mem));
+            }
+            MemoryCopy(from, to) => {
+                let base = self.pop_consecutive(3)?;
+                self.emit(SlotInstruction::MemoryCopy(from, to, base));
+            }
+            MemoryFill(mem) => {
+                let base = self.pop_consecutive(3)?;
+                self.emit(SlotInstruction::MemoryFill(mem, base));
+            }
+            MemoryInit(data, mem) => {
+                let base = self.pop_consecutive(3)?;
+                self.emit(SlotInstruction::MemoryInit(mem, data, base));
+            }
+            DataDrop(data) => {
+                self.emit(SlotInstruction::DataDrop(data));
+            }
+
+            TableGet(table) => {
+                let idx = self.pop()?;
+                let dst = self.push_new()?;
+                self.emit(SlotInstruction::TableGet(table, dst, idx));
+            }
+            TableSet(table) => {
+                let base = self.pop_consecutive(2)?;
+                self.emit(SlotInstruction::TableSet(table, base));
+            }
+            TableSize(table) => {
+                let dst = self.push_new()?;
+                self.emit(SlotInstruction::TableSize(table, dst));
+            }
+            TableInit(elem, table) => {
+                let base = self.pop_consecutive(3)?;
+                self.emit(SlotInstruction::TableInit(table, elem, base));
+            }
+
+            // no-op instructions since types are erased at runtime
+            I32ReinterpretF32 | I64ReinterpretF64 | F32ReinterpretI32 | F64ReinterpretI64 => {}
+
+            // Instructions the interpreter does not implement fail once they are executed, like with the stack backend
+            RefNull(_) | RefFunc(_) | MemorySize(..) => self.lower_unsupported(pc, 0, 1)?,
+            RefIsNull => self.lower_unsupported(pc, 1, 1)?,
+            MemoryGrow(..) => self.lower_unsupported(pc, 1, 1)?,
+            TableGrow(_) => self.lower_unsupported(pc, 2, 1)?,
+            TableCopy { .. } | TableFill(_) => self.lower_unsupported(pc, 3, 0)?,
+
+            instr => {
+                return Err(ParseError::UnsupportedOperator(format!("can't lower {:?} to slot code", instr)));
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Only track the nesting of blocks in unreachable code, so the end of the block can be found
+    fn lower_unreachable(&mut self, instr: Instruction) -> Result<()> {
+        match instr {
+            Instruction::Block(..) | Instruction::Loop(..) | Instruction::If(..) => {
+                self.push_label(LabelKind::Block, 0, 0, None);
+                Ok(())
+            }
+            Instruction::Else(_) => self.lower_else(),
+            Instruction::EndBlockFrame => self.lower_end(),
+            _ => Ok(()),
+        }
+    }
+
+    fn lower_else(&mut self) -> Result<()> {
+        let label = self.labels.last().ok_or_else(unbalanced_blocks)?;
+        if !label.reachable {
+            return Ok(());
+        }
+
+        let (height, params) = (label.height, label.params);
+        if self.reachable {
+            self.materialize(0);
+            let fixup = self.emit(SlotInstruction::Jump(0));
+            self.labels.last_mut().ok_or_else(unbalanced_blocks)?.fixups.push(fixup);
+        }
+
+        let else_fixup = self.labels.last_mut().ok_or_else(unbalanced_blocks)?.else_fixup.take();
+        if let Some(else_fixup) = else_fixup {
+            self.patch(else_fixup);
+        }
+
+        // The parameters of the if block are still in their slots, the then branch is not executed
+        self.operands.truncate(height);
+        self.push_results(params)?;
+        self.reachable = true;
+        Ok(())
+    }
+
+    fn lower_end(&mut self) -> Result<()> {
+        if self.reachable {
+            self.materialize(0);
+        }
+
+        let label = self.labels.pop().ok_or_else(unbalanced_blocks)?;
+        if !label.reachable {
+            return Ok(());
+        }
+
+        for fixup in label.fixups.iter().copied().chain(label.else_fixup) {
+            self.patch(fixup);
+        }
+        self.barrier = self.code.len();
+
+        self.operands.truncate(label.height);
+        self.push_results(label.results)?;
+        self.reachable = true;
+        Ok(())
+    }
+
+    fn lower_br_table(&mut self, labels: &[Instruction], default: u32) -> Result<()> {
+        let idx = self.pop()?;
+        self.materialize(0);
+        self.emit(SlotInstruction::BrTable(idx, labels.len() as u32));
+
+        let depths = labels
+            .iter()
+            .map(|label| match label {
+                Instruction::BrLabel(depth) => Ok(*depth),
+                _ => Err(invalid_br_table()),
+            })
+            .chain([Ok(default)])
+            .collect::<Result<Vec<_>>>()?;
+
+        let table = self.code.len();
+        depths.iter().for_each(|_| {
+            self.emit(SlotInstruction::Jump(0));
+        });
+
+        // Targets which need values moved get a stub behind the table
+        for (i, depth) in depths.into_iter().enumerate() {
+            match self.direct_target(depth)? {
+                Some(target) => self.set_target(table + i, target),
+                None => {
+                    self.patch(table + i);
+                    self.branch(depth)?;
+                }
+            }
+        }
+
+        self.reachable = false;
+        Ok(())
+    }
+
+    /// Branch to the label at `depth`, moving its values into place
+    fn branch(&mut self, depth: u32) -> Result<()> {
+        self.materialize(0);
+
+        let Some(label) = self.label(depth)? else {
+            let src = self.top_slots(self.results)?;
+            self.emit(SlotInstruction::Return(src));
+            return Ok(());
+        };
+
+        let (height, values, target) = match label.kind {
+            LabelKind::Loop => (label.height, label.params, Target::Pc(label.start)),
+            _ => (label.height, label.results, Target::Fixup(depth)),
+        };
+
+        let from = self.operands.len().checked_sub(values).ok_or_else(unbalanced_operands)?;
+        if from != height {
+            for i in 0..values {
+                let (dst, src) = (self.slot(height + i)?, self.slot(from + i)?);
+                self.emit(SlotInstruction::Copy(dst, src));
+            }
+        }
+
+        self.jump(SlotInstruction::Jump(0), target);
+        Ok(())
+    }
+
+    /// The target of a branch to `depth`, if the values of the label are already in place
+    fn direct_target(&self, depth: u32) -> Result<Option<Target>> {
+        let Some(label) = self.label(depth)? else {
+            return Ok(None);
+        };
+
+        let (values, target) = match label.kind {
+            LabelKind::Loop => (label.params, Target::Pc(label.start)),
+            _ => (label.results, Target::Fixup(depth)),
+        };
+
+        Ok((values == 0 || self.operands.len().checked_sub(values) == Some(label.height)).then_some(target))
+    }
+
+    /// The label at `depth`, `None` for the function body
+    fn label(&self, depth: u32) -> Result<Option<&Label>> {
+        match self.labels.len().checked_sub(depth as usize) {
+            Some(0) => Ok(None),
+            Some(idx) => Ok(Some(&self.labels[idx - 1])),
+            None => Err(ParseError::Other(format!("branch to unknown label {depth}"))),
+        }
+    }
+
+    fn jump(&mut self, instr: SlotInstruction, target: Target) {
+        let at = self.emit(instr);
+        self.set_target(at, target);
+    }
+
+    fn set_target(&mut self, at: usize, target: Target) {
+        match target {
+            Target::Pc(pc) => set_jump(&mut self.code[at], pc),
+            Target::Fixup(depth) => {
+                let idx = self.labels.len() - 1 - depth as usize;
+                self.labels[idx].fixups.push(at);
+            }
+        }
+    }
+
+    /// Point the jump at `at` to the next instruction
+    fn patch(&mut self, at: usize) {
+        let pc = self.code.len() as u32;
+        set_jump(&mut self.code[at], pc);
+        self.barrier = self.code.len();
+    }
+
+    fn push_label(&mut self, kind: LabelKind, params: usize, results: usize, else_fixup: Option<usize>) {
+        let height = self.operands.len().saturating_sub(params);
+        let start = self.code.len() as u32;
+        let reachable = self.reachable;
+        self.labels.push(Label { kind, height, params, results, start, fixups: Vec::new(), else_fixup, reachable });
+    }
+
+    fn block_arity(&self, args: BlockArgs) -> Result<(usize, usize)> {
+        Ok(match args {
+            BlockArgs::Empty => (0, 0),
+            BlockArgs::Type(_) => (0, 1),
+            BlockArgs::FuncType(t) => {
+                let ty = self.func_types.get(t as usize).ok_or(ParseError::InvalidType)?;
+                (ty.params.len(), ty.results.len())
+            }
+        })
+    }
+
+    fn set_local(&mut self, local: Slot) -> Result<()> {
+        let src = self.pop()?;
+        if src == local {
+            return Ok(());
+        }
+
+        // Values of earlier `local.get`s have to be kept before the local is overwritten
+        let pending = self.operands.contains(&local);
+        if pending {
+            for height in 0..self.operands.len() {
+                if self.operands[height] == local {
+                    let dst = self.slot(height)?;
+                    self.emit(SlotInstruction::Copy(dst, local));
+                    self.operands[height] = dst;
+                }
+            }
+        }
+
+        // Write the result of the last instruction directly into the local
+        if !pending && src == self.slot(self.operands.len())? && self.code.len() > self.barrier {
+            if let Some(dst) = self.code.last_mut().and_then(SlotInstruction::dst_mut).filter(|dst| **dst == src) {
+                *dst = local;
+                return Ok(());
+            }
+        }
+
+        self.emit(SlotInstruction::Copy(local, src));
+        Ok(())
+    }
+
+    fn lower_const(&mut self, instr: impl FnOnce(Slot) -> SlotInstruction) -> Result<()> {
+        let dst = self.push_new()?;
+        self.emit(instr(dst));
+        Ok(())
+    }
+
+    fn lower_load(&mut self, offset: u64, mem: u32, instr: fn(Slot, Slot, u32, u8) -> SlotInstruction) -> Result<()> {
+        let (offset, mem) = mem_arg(offset, mem)?;
+        let addr = self.pop()?;
+        let dst = self.push_new()?;
+        self.emit(instr(dst, addr, offset, mem));
+        Ok(())
+    }
+
+    fn lower_store(&mut self, offset: u64, mem: u32, instr: fn(Slot, Slot, u32, u8) -> SlotInstruction) -> Result<()> {
+        let (offset, mem) = mem_arg(offset, mem)?;
+        let src = self.pop()?;
+        let addr = self.pop()?;
+        self.emit(instr(addr, src, offset, mem));
+        Ok(())
+    }
+
+    fn lower_unsupported(&mut self, pc: usize, params: usize, results: usize) -> Result<()> {
+        self.pop_consecutive(params)?;
+        self.emit(SlotInstruction::Unsupported(pc as u32));
+        self.push_results(results)
+    }
+
+    /// Slot of the operand at `height`
+    fn slot(&self, height: usize) -> Result<Slot> {
+        Slot::try_from(self.locals + height).map_err(|_| too_many_slots())
+    }
+
+    /// Move the operands from `from` up into their own slots
+    fn materialize(&mut self, from: usize) {
+        for height in from..self.operands.len() {
+            let dst = (self.locals + height) as Slot;
+            if self.operands[height] != dst {
+                self.emit(SlotInstruction::Copy(dst, self.operands[height]));
+                self.operands[height] = dst;
+            }
+        }
+    }
+
+    /// The first slot of the top `n` operands, after moving them into their own slots
+    fn top_slots(&mut self, n: usize) -> Result<Slot> {
+        let from = self.operands.len().checked_sub(n).ok_or_else(unbalanced_operands)?;
+        self.materialize(from);
+        self.slot(from)
+    }
+
+    /// Pop `n` operands which are read from consecutive slots, returns the first slot
+    fn pop_consecutive(&mut self, n: usize) -> Result<Slot> {
+        let base = self.top_slots(n)?;
+        self.operands.truncate(self.operands.len() - n);
+        Ok(base)
+    }
+
+    fn push(&mut self, src: Slot) -> Result<()> {
+        self.operands.push(src);
+        self.max_height = self.max_height.max(self.operands.len());
+        // the operand may be moved into its own slot later on
+        self.slot(self.operands.len() - 1).map(|_| ())
+    }
+
+    /// Push an operand in its own slot
+    fn push_new(&mut self) -> Result<Slot> {
+        let dst = self.slot(self.operands.len())?;
+        self.push(dst)?;
+        Ok(dst)
+    }
+
+    fn push_results(&mut self, n: usize) -> Result<()> {
+        (0..n).try_for_each(|_| self.push_new().map(|_| ()))
+    }
+
+    fn pop(&mut self) -> Result<Slot> {
+        self.operands.pop().ok_or_else(unbalanced_operands)
+    }
+
+    fn emit(&mut self, instr: SlotInstruction) -> usize {
+        self.code.push(instr);
+        self.code.len() - 1
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Target {
+    Pc(u32),
+    /// The end of the label at the depth, patched once it is reached
+    Fixup(u32),
+}
+
+fn set_jump(instr: &mut SlotInstruction, target: u32) {
+    match instr {
+        SlotInstruction::Jump(pc) | SlotInstruction::JumpIf(_, pc) | SlotInstruction::JumpIfNot(_, pc) => *pc = target,
+        _ => unreachable!("only jumps are patched"),
+    }
+}
+
+fn mem_arg(offset: u64, mem: u32) -> Result<(u32, u8)> {
+    let offset = u32::try_from(offset).map_err(|_| ParseError::UnsupportedOperator("64-bit memory offsets".into()))?;
+    let mem = u8::try_from(mem).map_err(|_| ParseError::UnsupportedOperator("more than 256 memories".into()))?;
+    Ok((offset, mem))
+}
+
+#[cold]
+fn too_many_slots() -> ParseError {
+    ParseError::UnsupportedOperator(format!("functions with more than {} locals and operands", Slot::MAX as u32 + 1))
+}
+
+#[cold]
+fn unbalanced_operands() -> ParseError {
+    ParseError::Other("operand stack underflow while lowering, this should have been validated".to_string())
+}
+
+#[cold]
+fn unbalanced_blocks() -> ParseError {
+    ParseError::Other("unbalanced blocks while lowering, this should have been validated".to_string())
+}
+
+#[cold]
+fn invalid_br_table() -> ParseError {
+    ParseError::Other("br_table with invalid label".to_string())
+}
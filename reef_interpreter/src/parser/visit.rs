@@ -5,6 +5,7 @@ use wasmparser::{FuncValidator, FunctionBody, VisitOperator, WasmModuleResources
 use crate::parser::{
     conversion::{convert_blocktype, convert_heaptype, convert_memarg, convert_valtype},
     error::{ParseError, Result},
+    optimize,
 };
 use crate::types::instructions::Instruction;
 
@@ -48,7 +49,22 @@ pub(crate) fn process_operators<R: WasmModuleResources>(
         }
     }
 
-    Ok(builder.instructions.into_boxed_slice())
+    let mut instructions = builder.instructions.into_boxed_slice();
+    optimize::fuse(&mut instructions);
+    Ok(instructions)
+}
+
+/// Validate the operators of a function body without translating them
+pub(crate) fn validate_operators<R: WasmModuleResources>(
+    validator: &mut FuncValidator<R>,
+    body: &FunctionBody<'_>,
+) -> Result<()> {
+    let mut reader = body.get_operators_reader()?;
+    while !reader.eof() {
+        reader.visit_operator(&mut validator.visitor(reader.original_position()))??;
+    }
+    validator.finish(reader.original_position())?;
+    Ok(())
 }
 
 macro_rules! define_operands {
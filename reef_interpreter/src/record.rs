@@ -0,0 +1,812 @@
+//! Recording of host function calls and replaying them to reproduce an execution
+//!
+//! A [`Recorder`] wraps the host functions of an [`Imports`] and writes every call with its parameters, outcome and
+//! cycle counter to a [`Write`](std::io::Write). A [`Replayer`] wraps the host functions of the same imports, checks every call
+//! against the recording and answers it with the recorded outcome instead of calling the host function, so a module
+//! can be re-run without the host it was recorded on. The first call which differs from the recording is reported as
+//! a [`Divergence`]. [`Recorder::finish`] adds how the execution ended to the recording, a replay which ends
+//! differently is reported as an [`EndDivergence`]. [`Recorder::sync`] adds the hash of a state the execution
+//! reached, a replay which reaches a different state is reported as a [`SyncDivergence`].
+//!
+//! Cycle counts are relative to the snapshot execution was started or resumed from, so a replay has to start from
+//! the same snapshot as the recording.
+
+use alloc::{boxed::Box, collections::BTreeSet, string::String, sync::Arc, vec::Vec};
+use core::fmt::{self, Debug, Display};
+use std::sync::Mutex;
+
+use crate::encoding;
+use crate::error::{Error, Result};
+use crate::imports::{Captured, Extern, ExternName, Function, HostFunction, Imports, Threading};
+use crate::runtime::RawWasmValue;
+use crate::types::{value::WasmValue, FuncType};
+
+/// Version of the recording format, recordings of other versions are rejected
+pub const RECORDING_VERSION: u32 = 1;
+
+const RECORDING_MAGIC: [u8; 4] = *b"rfhc";
+
+/// A host function call stored in a recording
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct HostCallRecord {
+    cycles: u64,
+    import: ExternName,
+    ty: FuncType,
+    params: Vec<RawWasmValue>,
+    outcome: RecordedOutcome,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+enum RecordedOutcome {
+    Returned(Vec<RawWasmValue>),
+    Paused,
+    Suspended,
+    Failed(String),
+}
+
+/// How a recorded host function call ended
+#[derive(Debug, Clone, PartialEq)]
+pub enum HostCallOutcome {
+    /// The function returned these results
+    Returned(Vec<WasmValue>),
+    /// The function returned [`Error::PauseExecution`]
+    Paused,
+    /// The function returned [`Error::SuspendHostCall`], the results supplied later are not part of the recording
+    Suspended,
+    /// The function failed with this error message
+    Failed(String),
+}
+
+impl HostCallRecord {
+    fn new(cycles: u64, import: ExternName, ty: &FuncType, params: &[WasmValue], res: &Result<Vec<WasmValue>>) -> Self {
+        let outcome = match res {
+            Ok(results) => RecordedOutcome::Returned(results.iter().map(|v| RawWasmValue::from(*v)).collect()),
+            Err(Error::PauseExecution) => RecordedOutcome::Paused,
+            Err(Error::SuspendHostCall) => RecordedOutcome::Suspended,
+            Err(err) => RecordedOutcome::Failed(err.to_string()),
+        };
+        let params = params.iter().map(|v| RawWasmValue::from(*v)).collect();
+        Self { cycles, import, ty: ty.clone(), params, outcome }
+    }
+
+    /// Get the number of instructions executed before the call
+    pub fn cycles(&self) -> u64 {
+        self.cycles
+    }
+
+    /// Get the name of the called import
+    pub fn import(&self) -> &ExternName {
+        &self.import
+    }
+
+    /// Get the parameters the host function was called with
+    pub fn params(&self) -> Vec<WasmValue> {
+        self.params.iter().zip(self.ty.params.iter()).map(|(v, ty)| v.attach_type(*ty)).collect()
+    }
+
+    /// Get how the call ended
+    pub fn outcome(&self) -> HostCallOutcome {
+        match &self.outcome {
+            RecordedOutcome::Returned(results) => HostCallOutcome::Returned(
+                results.iter().zip(self.ty.results.iter()).map(|(v, ty)| v.attach_type(*ty)).collect(),
+            ),
+            RecordedOutcome::Paused => HostCallOutcome::Paused,
+            RecordedOutcome::Suspended => HostCallOutcome::Suspended,
+            RecordedOutcome::Failed(message) => HostCallOutcome::Failed(message.clone()),
+        }
+    }
+
+    fn replay(&self) -> Result<Vec<WasmValue>> {
+        match self.outcome() {
+            HostCallOutcome::Returned(results) => Ok(results),
+            HostCallOutcome::Paused => Err(Error::PauseExecution),
+            HostCallOutcome::Suspended => Err(Error::SuspendHostCall),
+            HostCallOutcome::Failed(message) => Err(Error::Replayed(message)),
+        }
+    }
+}
+
+/// The end of a recorded execution, see [`Recorder::finish`]
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct EndRecord {
+    /// Number of instructions executed until the end
+    pub cycles: u64,
+    /// The result of the execution as encoded by the host, or the message of the error it failed with
+    pub outcome: core::result::Result<Vec<u8>, String>,
+}
+
+impl Display for EndRecord {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.outcome {
+            Ok(result) => write!(f, "result of {} bytes at cycle {}", result.len(), self.cycles),
+            Err(message) => write!(f, "failure `{message}` at cycle {}", self.cycles),
+        }
+    }
+}
+
+/// A state reached by a recorded execution, see [`Recorder::sync`]
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct SyncRecord {
+    /// Number of instructions executed until the state was reached
+    pub cycles: u64,
+    /// Hash of the state, as computed by the host
+    pub state_hash: [u8; 32],
+}
+
+impl Display for SyncRecord {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "state ")?;
+        for byte in &self.state_hash[..8] {
+            write!(f, "{byte:02x}")?;
+        }
+        write!(f, "... at cycle {}", self.cycles)
+    }
+}
+
+#[derive(Debug, serde::Serialize, serde::Deserialize)]
+struct RecordingHeader {
+    magic: [u8; 4],
+    version: u32,
+}
+
+#[derive(Debug, serde::Serialize, serde::Deserialize)]
+enum RecordingEntry {
+    Call(HostCallRecord),
+    Sync(SyncRecord),
+    End(EndRecord),
+}
+
+/// A recording written by a [`Recorder`]
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Recording {
+    /// The recorded host function calls
+    pub calls: Vec<HostCallRecord>,
+    /// The recorded states in the order they were reached
+    pub syncs: Vec<SyncRecord>,
+    /// How execution ended, `None` if the recording was stopped before
+    pub end: Option<EndRecord>,
+}
+
+/// Read a recording written by a [`Recorder`]
+pub fn read_recording<R: std::io::Read>(reader: R) -> Result<Recording> {
+    let mut reader = std::io::BufReader::new(reader);
+
+    let header: RecordingHeader = encoding::deserialize_from(&mut reader)?;
+    if header.magic != RECORDING_MAGIC {
+        return Err(Error::Other("not a host call recording".into()));
+    }
+    if header.version != RECORDING_VERSION {
+        return Err(Error::Other(format!("unsupported recording version {}", header.version)));
+    }
+
+    let mut recording = Recording::default();
+    while !std::io::BufRead::fill_buf(&mut reader)?.is_empty() {
+        if recording.end.is_some() {
+            return Err(Error::Other("host call recorded after the end of execution".into()));
+        }
+        match encoding::deserialize_from(&mut reader)? {
+            RecordingEntry::Call(record) => recording.calls.push(record),
+            RecordingEntry::Sync(sync) => recording.syncs.push(sync),
+            RecordingEntry::End(end) => recording.end = Some(end),
+        }
+    }
+    Ok(recording)
+}
+
+struct RecorderSink {
+    writer: Box<dyn std::io::Write + Send>,
+    error: Option<Error>,
+}
+
+impl RecorderSink {
+    fn write(&mut self, entry: &RecordingEntry) {
+        if self.error.is_none() {
+            // one write per entry, so unbuffered writers are fine
+            self.error = encoding::serialize(entry).and_then(|bytes| Ok(self.writer.write_all(&bytes)?)).err();
+        }
+    }
+}
+
+/// Writes every call to the host functions it wrapped to a [`Write`](std::io::Write)
+///
+/// Cloning the recorder returns a handle to the same recording.
+#[derive(Clone)]
+pub struct Recorder {
+    sink: Arc<Mutex<RecorderSink>>,
+}
+
+impl Debug for Recorder {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Recorder").finish_non_exhaustive()
+    }
+}
+
+impl Recorder {
+    /// Start a new recording
+    pub fn new(mut writer: impl std::io::Write + Send + 'static) -> Result<Self> {
+        let header = RecordingHeader { magic: RECORDING_MAGIC, version: RECORDING_VERSION };
+        writer.write_all(&encoding::serialize(&header)?)?;
+        let writer = Box::new(writer);
+        Ok(Self { sink: Arc::new(Mutex::new(RecorderSink { writer, error: None })) })
+    }
+
+    /// Wrap the host functions defined in `imports`, so their calls are recorded
+    ///
+    /// Host functions of linked instances are not recorded.
+    pub fn wrap<T: 'static, S: Threading>(&self, imports: Imports<T, S>) -> Imports<T, S>
+    where
+        Arc<HostFunction<T, S>>: Captured<S>,
+    {
+        imports.map_host_funcs(|name, host| {
+            let sink = self.sink.clone();
+            host.into_host_func(move |host, ctx, params| {
+                let cycles = ctx.cycles();
+                let res = (host.func)(ctx, params);
+
+                let record = HostCallRecord::new(cycles, name.clone(), &host.ty, params, &res);
+                sink.lock().unwrap_or_else(|err| err.into_inner()).write(&RecordingEntry::Call(record));
+
+                res
+            })
+        })
+    }
+
+    /// Record the hash of a state execution reached, so a replay can check that it reaches the same state
+    ///
+    /// The hash has to be computed the same way on replay, e.g. with
+    /// [`ExecHandle::state_hash_with_data`](crate::exec::ExecHandle::state_hash_with_data).
+    pub fn sync(&self, cycles: u64, state_hash: [u8; 32]) {
+        let sync = SyncRecord { cycles, state_hash };
+        self.sink.lock().unwrap_or_else(|err| err.into_inner()).write(&RecordingEntry::Sync(sync));
+    }
+
+    /// Record how execution ended, so a replay can check that it ends the same way
+    ///
+    /// Leave this out if execution was stopped before it ended, recordings can't be continued after it.
+    pub fn finish(&self, end: EndRecord) {
+        self.sink.lock().unwrap_or_else(|err| err.into_inner()).write(&RecordingEntry::End(end));
+    }
+
+    /// Get the first error which occurred while writing the recording
+    ///
+    /// Failing writes don't affect the execution, but no further calls are recorded after them.
+    pub fn error(&self) -> Option<String> {
+        let sink = self.sink.lock().unwrap_or_else(|err| err.into_inner());
+        sink.error.as_ref().map(|err| err.to_string())
+    }
+}
+
+/// The first host function call which differs from the recording
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Divergence {
+    /// Position of the call in the recording
+    pub index: usize,
+    /// The recorded call, `None` if the recording ended before
+    pub expected: Option<HostCallRecord>,
+    /// The call during the replay, `None` if execution ended before
+    pub actual: Option<HostCallRecord>,
+}
+
+impl Display for Divergence {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let describe = |record: &Option<HostCallRecord>| match record {
+            Some(r) => format!("{}/{}{:?} at cycle {}", r.import.module(), r.import.name(), r.params(), r.cycles),
+            None => "no call".into(),
+        };
+        write!(
+            f,
+            "replay diverged at host call {}: expected {}, got {}",
+            self.index,
+            describe(&self.expected),
+            describe(&self.actual)
+        )
+    }
+}
+
+impl core::error::Error for Divergence {}
+
+/// The end of a replay which differs from the end of the recording
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EndDivergence {
+    /// The recorded end
+    pub expected: EndRecord,
+    /// The end of the replay
+    pub actual: EndRecord,
+}
+
+impl Display for EndDivergence {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "replay ended differently: expected {}, got {}", self.expected, self.actual)
+    }
+}
+
+impl core::error::Error for EndDivergence {}
+
+/// A state reached by the replay which differs from the recorded one
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SyncDivergence {
+    /// The recorded state
+    pub expected: SyncRecord,
+    /// The state of the replay
+    pub actual: SyncRecord,
+}
+
+impl Display for SyncDivergence {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "replay diverged at a sync: expected {}, got {}", self.expected, self.actual)
+    }
+}
+
+impl core::error::Error for SyncDivergence {}
+
+#[derive(Debug)]
+struct ReplayState {
+    records: Vec<HostCallRecord>,
+    end: Option<EndRecord>,
+    next: usize,
+    divergence: Option<Divergence>,
+}
+
+/// Answers calls to the host functions it wrapped from a recording
+///
+/// Once a call differs from the recording in its import, parameters or cycle count, it fails with the
+/// [`Divergence`] as host error, see [`Error::downcast_host_ref`].
+#[derive(Debug, Clone)]
+pub struct Replayer {
+    state: Arc<Mutex<ReplayState>>,
+    passthrough: BTreeSet<ExternName>,
+}
+
+impl Replayer {
+    /// Replay the given recording
+    pub fn new(recording: Recording) -> Self {
+        let Recording { calls: records, syncs: _, end } = recording;
+        Self {
+            state: Arc::new(Mutex::new(ReplayState { records, end, next: 0, divergence: None })),
+            passthrough: BTreeSet::new(),
+        }
+    }
+
+    /// Still call the host function for an import, for functions with side effects like writing to memory
+    ///
+    /// The outcome of the call is checked against the recording as well. Host functions which call back into the
+    /// module always have to be passed through.
+    pub fn passthrough(&mut self, module: &str, name: &str) -> &mut Self {
+        self.passthrough.insert(ExternName::new(module, name));
+        self
+    }
+
+    /// Wrap the host functions defined in `imports`, so their calls are answered from the recording
+    ///
+    /// Must be called after all [`Replayer::passthrough`] imports were added.
+    pub fn wrap<T: 'static, S: Threading>(&self, imports: Imports<T, S>) -> Imports<T, S>
+    where
+        Arc<HostFunction<T, S>>: Captured<S>,
+    {
+        imports.map_host_funcs(|name, host| {
+            let state = self.state.clone();
+            let passthrough = self.passthrough.contains(&name);
+            host.into_host_func(move |host, ctx, params| {
+                let cycles = ctx.cycles();
+                let (index, expected) = {
+                    let mut state = state.lock().unwrap_or_else(|err| err.into_inner());
+                    if let Some(divergence) = &state.divergence {
+                        return Err(Error::host(divergence.clone()));
+                    }
+                    let index = state.next;
+                    state.next += 1;
+                    (index, state.records.get(index).cloned())
+                };
+
+                let res = match (passthrough, &expected) {
+                    (true, _) => (host.func)(ctx, params),
+                    (false, Some(record)) => record.replay(),
+                    (false, None) => Ok(Vec::new()),
+                };
+                let actual = HostCallRecord::new(cycles, name.clone(), &host.ty, params, &res);
+
+                let matches = match &expected {
+                    Some(record) if passthrough => *record == actual,
+                    Some(record) => {
+                        record.cycles == actual.cycles
+                            && record.import == actual.import
+                            && record.params == actual.params
+                    }
+                    None => false,
+                };
+                if matches {
+                    return res;
+                }
+
+                let divergence = Divergence { index, expected, actual: Some(actual) };
+                state.lock().unwrap_or_else(|err| err.into_inner()).divergence = Some(divergence.clone());
+                Err(Error::host(divergence))
+            })
+        })
+    }
+
+    /// Get the first call which differed from the recording
+    pub fn divergence(&self) -> Option<Divergence> {
+        self.state.lock().unwrap_or_else(|err| err.into_inner()).divergence.clone()
+    }
+
+    /// Check that the replay reached a recorded state
+    ///
+    /// Call this once execution ran to the cycles of the sync, with the hash computed like on the recording. Like
+    /// the wrapped calls, it fails with the [`Divergence`] as host error, or with a [`SyncDivergence`] if only the
+    /// state differs.
+    pub fn sync(&self, expected: &SyncRecord, actual: SyncRecord) -> Result<()> {
+        let state = self.state.lock().unwrap_or_else(|err| err.into_inner());
+        if let Some(divergence) = &state.divergence {
+            return Err(Error::host(divergence.clone()));
+        }
+        if *expected != actual {
+            return Err(Error::host(SyncDivergence { expected: expected.clone(), actual }));
+        }
+        Ok(())
+    }
+
+    /// Check that the replay made every recorded call and nothing else, and that it ended like the recording
+    ///
+    /// Call this after execution finished with its end, or with `None` after it ran to the last call or sync of a
+    /// recording which was stopped before execution ended. Like the wrapped calls, it fails with the [`Divergence`]
+    /// as host error, or with an [`EndDivergence`] if only the end differs. The end is only compared if it was
+    /// recorded.
+    pub fn finish(&self, end: Option<EndRecord>) -> Result<()> {
+        let state = self.state.lock().unwrap_or_else(|err| err.into_inner());
+        if let Some(divergence) = &state.divergence {
+            return Err(Error::host(divergence.clone()));
+        }
+        if let Some(record) = state.records.get(state.next) {
+            return Err(Error::host(Divergence { index: state.next, expected: Some(record.clone()), actual: None }));
+        }
+        match (&state.end, end) {
+            (Some(expected), Some(end)) if *expected != end => {
+                Err(Error::host(EndDivergence { expected: expected.clone(), actual: end }))
+            }
+            (Some(expected), None) => Err(Error::Other(format!("replay stopped before the recorded {expected}"))),
+            _ => Ok(()),
+        }
+    }
+}
+
+impl<T: 'static, S: Threading> Imports<T, S> {
+    /// Replace every host function defined in these imports with the function returned by `wrap`
+    fn map_host_funcs(
+        mut self,
+        mut wrap: impl FnMut(ExternName, Arc<HostFunction<T, S>>) -> crate::imports::HostFuncInner<T, S>,
+    ) -> Self {
+        for (name, value) in self.values.iter_mut() {
+            if let Extern::Function(Some(Function::Host(host))) = value {
+                let ty = host.ty.clone();
+                let func = wrap(name.clone(), host.clone());
+                *host = Arc::new(HostFunction { ty, func });
+            }
+        }
+        self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::exec::{CallResultTyped, ExecHandleTyped};
+    use crate::imports::FuncContext;
+    use crate::Instance;
+
+    const WAT: &str = r#"
+        (module
+            (import "env" "next" (func $next (param i32) (result i32)))
+            (import "env" "yield" (func $yield))
+            (func (export "main") (result i32)
+                (local $i i32) (local $sum i32)
+                (loop $continue
+                    (local.set $sum (i32.add (local.get $sum) (call $next (local.get $i))))
+                    (call $yield)
+                    (br_if $continue (i32.lt_u (local.tee $i (i32.add (local.get $i) (i32.const 1))) (i32.const 5))))
+                (local.get $sum)))
+    "#;
+
+    fn imports(offset: i32) -> Imports {
+        let mut imports = Imports::new();
+        let next = Extern::typed_func(move |_: FuncContext<'_>, i: i32| -> Result<i32> { Ok(i * 10 + offset) });
+        imports.define("env", "next", next).unwrap();
+        let pause = Extern::typed_func(|_: FuncContext<'_>, ()| -> Result<()> { Err(Error::PauseExecution) });
+        imports.define("env", "yield", pause).unwrap();
+        imports
+    }
+
+    fn run(imports: Imports) -> Result<i32> {
+        run_to_end(imports, None).map(|(res, _)| res)
+    }
+
+    fn start(imports: Imports) -> Result<ExecHandleTyped<i32>> {
+        let module = crate::parse_bytes(&wat::parse_str(WAT).expect("invalid wat")).unwrap();
+        let (instance, _, _) = Instance::instantiate(module, imports, (), None)?;
+        let main = instance.exported_func::<(), i32>("main")?;
+        main.call(instance, (), None).map_err(|err| err.1)
+    }
+
+    /// Run to the end, syncing the state on every pause
+    fn run_to_end(imports: Imports, recorder: Option<&Recorder>) -> Result<(i32, EndRecord)> {
+        let mut exec_handle = start(imports)?;
+        loop {
+            if let CallResultTyped::Done(res) = exec_handle.run(3)? {
+                let end = EndRecord { cycles: exec_handle.cycles(), outcome: Ok(res.to_le_bytes().to_vec()) };
+                return Ok((res, end));
+            }
+            if let Some(recorder) = recorder {
+                recorder.sync(exec_handle.cycles(), exec_handle.state_hash());
+            }
+        }
+    }
+
+    #[derive(Clone, Default)]
+    struct SharedBuffer(Arc<Mutex<Vec<u8>>>);
+
+    impl std::io::Write for SharedBuffer {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0.lock().unwrap().write(buf)
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_record_replay() {
+        let buffer = SharedBuffer::default();
+        let recorder = Recorder::new(buffer.clone()).unwrap();
+        let (res, end) = run_to_end(recorder.wrap(imports(1)), Some(&recorder)).unwrap();
+        assert_eq!(res, 105);
+        recorder.finish(end.clone());
+        assert!(recorder.error().is_none());
+        let recording = buffer.0.lock().unwrap().clone();
+
+        let Recording { calls: records, syncs, end: recorded_end } = read_recording(&recording[..]).unwrap();
+        assert_eq!(recorded_end.as_ref(), Some(&end));
+        assert!(!syncs.is_empty());
+        assert!(syncs.windows(2).all(|w| w[0].cycles < w[1].cycles));
+        assert_eq!(records.len(), 10);
+        assert_eq!(records[0].import().name(), "next");
+        assert_eq!(records[1].import().name(), "yield");
+        assert_eq!(records[2].params(), [WasmValue::I32(1)]);
+        assert_eq!(records[2].outcome(), HostCallOutcome::Returned(vec![WasmValue::I32(11)]));
+        assert_eq!(records[3].outcome(), HostCallOutcome::Paused);
+        assert!(records.windows(2).all(|w| w[0].cycles() < w[1].cycles()));
+
+        let recording =
+            |calls: Vec<HostCallRecord>| Recording { calls, syncs: syncs.clone(), end: recorded_end.clone() };
+
+        // the host functions are not called, so a different host gives the same result
+        let replayer = Replayer::new(recording(records.clone()));
+        assert_eq!(run(replayer.wrap(imports(2))).unwrap(), 105);
+        replayer.finish(Some(end.clone())).unwrap();
+
+        // the replay reaches the recorded states
+        let replayer = Replayer::new(recording(records.clone()));
+        let mut exec_handle = start(replayer.wrap(imports(2))).unwrap();
+        for sync in &syncs {
+            assert!(matches!(exec_handle.run_to(sync.cycles).unwrap(), CallResultTyped::Incomplete));
+            let actual = SyncRecord { cycles: exec_handle.cycles(), state_hash: exec_handle.state_hash() };
+            replayer.sync(sync, actual.clone()).unwrap();
+
+            let mut other = sync.clone();
+            other.state_hash[0] ^= 1;
+            let divergence = replayer.sync(&other, actual.clone()).unwrap_err().downcast_host::<SyncDivergence>();
+            assert_eq!(divergence.unwrap(), SyncDivergence { expected: other, actual });
+        }
+        // but stopping there is only fine for recordings which were stopped as well
+        let stop = Recording { calls: records.clone(), syncs: syncs.clone(), end: None };
+        let last_sync = syncs.last().unwrap().cycles;
+        assert!(replayer.finish(None).is_err());
+        let replayer = Replayer::new(Recording {
+            calls: records.iter().filter(|call| call.cycles() <= last_sync).cloned().collect(),
+            ..stop
+        });
+        let mut exec_handle = start(replayer.wrap(imports(2))).unwrap();
+        assert!(matches!(exec_handle.run_to(last_sync).unwrap(), CallResultTyped::Incomplete));
+        replayer.finish(None).unwrap();
+
+        // an execution ending differently is detected
+        let mut other_end = end.clone();
+        other_end.outcome = Ok(106i32.to_le_bytes().to_vec());
+        let replayer = Replayer::new(recording(records.clone()));
+        assert_eq!(run(replayer.wrap(imports(2))).unwrap(), 105);
+        let divergence =
+            replayer.finish(Some(other_end.clone())).unwrap_err().downcast_host::<EndDivergence>().unwrap();
+        assert_eq!(divergence, EndDivergence { expected: end.clone(), actual: other_end });
+        other_end = EndRecord { cycles: end.cycles, outcome: Err("trapped".into()) };
+        assert!(replayer.finish(Some(other_end.clone())).is_err());
+        // unless the end was not recorded
+        let replayer = Replayer::new(Recording { calls: records.clone(), syncs: Vec::new(), end: None });
+        assert_eq!(run(replayer.wrap(imports(1))).unwrap(), 105);
+        replayer.finish(Some(other_end)).unwrap();
+
+        // but passing through calls to it reveals the difference
+        let mut replayer = Replayer::new(recording(records.clone()));
+        replayer.passthrough("env", "next");
+        let err = run(replayer.wrap(imports(2))).unwrap_err();
+        let divergence = err.downcast_host_ref::<Divergence>().unwrap();
+        assert_eq!(divergence.index, 0);
+        assert_eq!(divergence.actual.as_ref().unwrap().outcome(), HostCallOutcome::Returned(vec![WasmValue::I32(2)]));
+
+        // a shorter recording is detected
+        let replayer = Replayer::new(recording(records[..4].to_vec()));
+        let err = run(replayer.wrap(imports(1))).unwrap_err();
+        assert_eq!(err.downcast_host_ref::<Divergence>().unwrap().index, 4);
+
+        // as well as calls which were recorded but never made
+        let mut longer = records.clone();
+        longer.push(records[0].clone());
+        let replayer = Replayer::new(recording(longer));
+        assert_eq!(run(replayer.wrap(imports(1))).unwrap(), 105);
+        assert_eq!(replayer.finish(Some(end)).unwrap_err().downcast_host::<Divergence>().unwrap().index, 10);
+
+        let bytes = buffer.0.lock().unwrap().clone();
+        assert!(read_recording(&bytes[1..]).is_err());
+    }
+
+    /// Record a full execution with its syncs and end
+    fn record() -> Recording {
+        let buffer = SharedBuffer::default();
+        let recorder = Recorder::new(buffer.clone()).unwrap();
+        let (_, end) = run_to_end(recorder.wrap(imports(1)), Some(&recorder)).unwrap();
+        recorder.finish(end);
+        let bytes = buffer.0.lock().unwrap().clone();
+        read_recording(&bytes[..]).unwrap()
+    }
+
+    #[test]
+    fn test_replay_diverging_call() {
+        let recording = record();
+
+        // wrong arguments
+        let mut calls = recording.calls.clone();
+        calls[2].params = vec![RawWasmValue::from(WasmValue::I32(7))];
+        let replayer = Replayer::new(Recording { calls, ..recording.clone() });
+        let err = run(replayer.wrap(imports(1))).unwrap_err();
+        let divergence = err.downcast_host_ref::<Divergence>().unwrap();
+        assert_eq!(divergence.index, 2);
+        assert_eq!(divergence.actual.as_ref().unwrap().params(), [WasmValue::I32(1)]);
+        assert_eq!(replayer.divergence().as_ref(), Some(divergence));
+        assert!(replayer.finish(None).unwrap_err().downcast_host_ref::<Divergence>().is_some());
+
+        // wrong import
+        let mut calls = recording.calls.clone();
+        calls[3].import = ExternName::new("env", "sleep");
+        let replayer = Replayer::new(Recording { calls, ..recording.clone() });
+        let err = run(replayer.wrap(imports(1))).unwrap_err();
+        let divergence = err.downcast_host::<Divergence>().unwrap();
+        assert_eq!(divergence.index, 3);
+        assert_eq!(divergence.expected.unwrap().import().name(), "sleep");
+        assert_eq!(divergence.actual.unwrap().import().name(), "yield");
+
+        // calls in a different order, the recorded outcome doesn't fit the import
+        let mut calls = recording.calls.clone();
+        calls.swap(0, 1);
+        let replayer = Replayer::new(Recording { calls, ..recording });
+        let err = run(replayer.wrap(imports(1))).unwrap_err();
+        assert_eq!(err.downcast_host::<Divergence>().unwrap().index, 0);
+    }
+
+    #[test]
+    fn test_replay_without_end() {
+        let buffer = SharedBuffer::default();
+        let recorder = Recorder::new(buffer.clone()).unwrap();
+        let mut exec_handle = start(recorder.wrap(imports(1))).unwrap();
+        for _ in 0..4 {
+            assert!(matches!(exec_handle.run(3).unwrap(), CallResultTyped::Incomplete));
+            recorder.sync(exec_handle.cycles(), exec_handle.state_hash());
+        }
+        let stopped_at = exec_handle.cycles();
+
+        // the recording is read while the recorder is still alive, it just has no end
+        let bytes = buffer.0.lock().unwrap().clone();
+        let recording = read_recording(&bytes[..]).unwrap();
+        assert!(recording.end.is_none());
+        assert_eq!(recording.syncs.len(), 4);
+        assert!(!recording.calls.is_empty());
+        assert!(recording.calls.iter().all(|call| call.cycles() < stopped_at));
+
+        // a replay up to the last sync matches
+        let replayer = Replayer::new(recording.clone());
+        let mut exec_handle = start(replayer.wrap(imports(2))).unwrap();
+        for sync in &recording.syncs {
+            assert!(matches!(exec_handle.run_to(sync.cycles).unwrap(), CallResultTyped::Incomplete));
+            let actual = SyncRecord { cycles: exec_handle.cycles(), state_hash: exec_handle.state_hash() };
+            replayer.sync(sync, actual).unwrap();
+        }
+        replayer.finish(None).unwrap();
+
+        // any end is accepted, since none was recorded
+        let end = EndRecord { cycles: stopped_at, outcome: Err("stopped".into()) };
+        replayer.finish(Some(end)).unwrap();
+
+        // but running past the end of the recording diverges at the first call which was not recorded
+        let err = loop {
+            match exec_handle.run(3) {
+                Ok(CallResultTyped::Done(_)) => panic!("replay ran past the recording"),
+                Ok(_) => continue,
+                Err(err) => break err,
+            }
+        };
+        let divergence = err.downcast_host::<Divergence>().unwrap();
+        assert_eq!((divergence.index, divergence.expected), (recording.calls.len(), None));
+    }
+
+    #[test]
+    fn test_replay_failed_call() {
+        let failing = || {
+            let mut imports = imports(1);
+            let next = Extern::typed_func(|_: FuncContext<'_>, i: i32| -> Result<i32> {
+                if i == 3 {
+                    return Err(Error::host("no value left"));
+                }
+                Ok(i)
+            });
+            imports.define("env", "next", next).unwrap();
+            imports
+        };
+        let run_to_failure = |imports| {
+            let mut exec_handle = start(imports).unwrap();
+            loop {
+                match exec_handle.run(3) {
+                    Ok(CallResultTyped::Done(_)) => panic!("execution did not fail"),
+                    Ok(_) => continue,
+                    Err(err) => break EndRecord { cycles: exec_handle.cycles(), outcome: Err(err.to_string()) },
+                }
+            }
+        };
+
+        let buffer = SharedBuffer::default();
+        let recorder = Recorder::new(buffer.clone()).unwrap();
+        let end = run_to_failure(recorder.wrap(failing()));
+        assert_eq!(end.outcome, Err("host error: no value left".to_string()));
+        recorder.finish(end.clone());
+        let bytes = buffer.0.lock().unwrap().clone();
+        let recording = read_recording(&bytes[..]).unwrap();
+        let failed = recording.calls.last().unwrap();
+        assert_eq!(failed.outcome(), HostCallOutcome::Failed("host error: no value left".into()));
+
+        // the replayed failure reads like the recorded one, so the end matches
+        let replayer = Replayer::new(recording);
+        let replayed_end = run_to_failure(replayer.wrap(imports(2)));
+        assert_eq!(replayed_end, end);
+        replayer.finish(Some(replayed_end)).unwrap();
+    }
+
+    #[test]
+    fn test_replay_across_sync() {
+        let recording = record();
+        let sync = recording.syncs[recording.syncs.len() / 2].clone();
+        assert!(recording.calls.iter().any(|call| call.cycles() < sync.cycles));
+        assert!(recording.calls.iter().any(|call| call.cycles() > sync.cycles));
+
+        let replayer = Replayer::new(recording.clone());
+        let mut exec_handle = start(replayer.wrap(imports(2))).unwrap();
+        assert!(matches!(exec_handle.run_to(sync.cycles).unwrap(), CallResultTyped::Incomplete));
+        let actual = SyncRecord { cycles: exec_handle.cycles(), state_hash: exec_handle.state_hash() };
+        replayer.sync(&sync, actual).unwrap();
+
+        // the calls after the sync are still answered from the recording
+        let res = loop {
+            if let CallResultTyped::Done(res) = exec_handle.run(3).unwrap() {
+                break res;
+            }
+        };
+        assert_eq!(res, 105);
+        let end = EndRecord { cycles: exec_handle.cycles(), outcome: Ok(res.to_le_bytes().to_vec()) };
+        replayer.finish(Some(end)).unwrap();
+
+        // a state which differs from the recorded one is reported at the sync, even though no call diverged
+        let replayer = Replayer::new(recording);
+        let mut exec_handle = start(replayer.wrap(imports(2))).unwrap();
+        assert!(matches!(exec_handle.run_to(sync.cycles).unwrap(), CallResultTyped::Incomplete));
+        let actual = SyncRecord { cycles: exec_handle.cycles() + 1, state_hash: exec_handle.state_hash() };
+        let divergence = replayer.sync(&sync, actual).unwrap_err().downcast_host::<SyncDivergence>().unwrap();
+        assert_eq!(divergence.expected, sync);
+        assert!(replayer.divergence().is_none());
+    }
+}
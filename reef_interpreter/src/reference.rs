@@ -8,7 +8,8 @@ use alloc::{
 use core::ffi::CStr;
 
 use crate::error::{Error, Result};
-use crate::store::memory::MemoryInstance;
+use crate::store::{global::GlobalInstance, memory::MemoryInstance, table::TableInstance};
+use crate::types::{value::WasmValue, GlobalType, TableType};
 
 // This module essentially contains the public APIs to interact with the data stored in the store
 
@@ -48,6 +49,11 @@ impl MemoryRef<'_> {
     pub fn load_vec(&self, offset: usize, len: usize) -> Result<Vec<u8>> {
         self.load(offset, len).map(|x| x.to_vec())
     }
+
+    /// Get the byte regions ignored during serialization as `(offset, len)`, sorted by offset
+    pub fn ignored_byte_regions(&self) -> impl Iterator<Item = (usize, usize)> + '_ {
+        self.instance.ignored_byte_regions.iter().map(|&(start, end)| (start, end - start))
+    }
 }
 
 impl MemoryRefMut<'_> {
@@ -73,7 +79,7 @@ impl MemoryRefMut<'_> {
 
     /// Copy a slice of memory to another place in memory
     pub fn copy_within(&mut self, src: usize, dst: usize, len: usize) -> Result<()> {
-        self.instance.copy_within(src, dst, len)
+        self.instance.copy_within(dst, src, len)
     }
 
     /// Fill a slice of memory with a value
@@ -86,21 +92,95 @@ impl MemoryRefMut<'_> {
         self.instance.store(offset, len, data)
     }
 
-    /// Get byte region ignored during serialization
-    pub fn get_ignored_byte_region(&self) -> (usize, usize) {
-        (self.instance.ignored_byte_region.0, self.instance.ignored_byte_region.1 - self.instance.ignored_byte_region.0)
+    /// Get the byte regions ignored during serialization as `(offset, len)`, sorted by offset
+    pub fn ignored_byte_regions(&self) -> impl Iterator<Item = (usize, usize)> + '_ {
+        self.instance.ignored_byte_regions.iter().map(|&(start, end)| (start, end - start))
+    }
+
+    /// Ignore bytes during serialization, in addition to the regions ignored already
+    ///
+    /// Fails if the region is out of bounds or overlaps a region which is ignored already.
+    pub fn add_ignored_byte_region(&mut self, offset: usize, len: usize) -> Result<()> {
+        self.instance.add_ignored_byte_region(offset, len)
+    }
+
+    /// Set bytes ignored during serialization, replacing all regions ignored before
+    pub fn set_ignored_byte_region(&mut self, offset: usize, len: usize) -> Result<()> {
+        self.instance.ignored_byte_regions.clear();
+        self.instance.add_ignored_byte_region(offset, len)
+    }
+}
+
+/// A reference to a global instance
+#[derive(Debug)]
+pub struct GlobalRef<'g> {
+    pub(crate) instance: &'g GlobalInstance,
+}
+
+/// A borrowed reference to a global instance
+#[derive(Debug)]
+pub struct GlobalRefMut<'g> {
+    pub(crate) instance: &'g mut GlobalInstance,
+}
+
+impl GlobalRef<'_> {
+    /// Get the type of the global
+    pub fn ty(&self) -> GlobalType {
+        self.instance.ty
+    }
+
+    /// Get the current value of the global
+    pub fn get(&self) -> WasmValue {
+        self.instance.value.attach_type(self.instance.ty.ty)
+    }
+}
+
+impl GlobalRefMut<'_> {
+    /// Get the type of the global
+    pub fn ty(&self) -> GlobalType {
+        self.instance.ty
+    }
+
+    /// Get the current value of the global
+    pub fn get(&self) -> WasmValue {
+        self.instance.value.attach_type(self.instance.ty.ty)
+    }
+
+    /// Set the value of the global, which has to be mutable and of the same type
+    pub fn set(&mut self, val: WasmValue) -> Result<()> {
+        if !self.instance.ty.mutable {
+            return Err(Error::Other("Global is immutable".to_string()));
+        }
+
+        if val.val_type() != self.instance.ty.ty {
+            return Err(Error::Other("Type mismatch".to_string()));
+        }
+
+        self.instance.value = val.into();
+        Ok(())
+    }
+}
+
+/// A reference to a table instance
+#[derive(Debug)]
+pub struct TableRef<'t> {
+    pub(crate) instance: &'t TableInstance,
+}
+
+impl TableRef<'_> {
+    /// Get the type of the table
+    pub fn ty(&self) -> &TableType {
+        &self.instance.kind
     }
 
-    /// Set bytes ignored during serialization
-    pub fn set_ignored_byte_region(&mut self, offset: usize, len: usize) {
-        self.instance.ignored_byte_region = (offset, offset + len);
+    /// Get the current number of elements in the table
+    pub fn size(&self) -> usize {
+        self.instance.elements.len()
     }
 
-    /// Copy data into the region ignored by serialization
-    pub fn copy_into_ignored_byte_region(&mut self, data: &[u8]) {
-        let start = self.instance.ignored_byte_region.0;
-        let end = self.instance.ignored_byte_region.1;
-        self.instance.data[start..end].copy_from_slice(data);
+    /// Get the element at the given index
+    pub fn get(&self, index: u32) -> Result<WasmValue> {
+        self.instance.get_wasm_val(index)
     }
 }
 
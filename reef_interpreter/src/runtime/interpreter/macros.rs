@@ -10,13 +10,13 @@
 // This is a bit hard to see from the spec, but it's valid to use breaks to return
 // from a function, so we need to check if the label stack is empty
 macro_rules! break_to {
-    ($cf:ident, $stack:ident, $module:ident, $store:ident, $break_to_relative:expr) => {{
+    ($cf:ident, $stack:ident, $call_base:expr, $break_to_relative:expr) => {{
         if $cf.break_to($break_to_relative, &mut $stack.values, &mut $stack.blocks).is_none() {
-            if $stack.call_stack.is_empty() {
+            if $stack.call_stack.len() <= $call_base {
                 return Ok(true);
             }
 
-            call!($cf, $stack, $module, $store)
+            call!($cf, $stack, module, store)
         }
     }};
 }
@@ -29,8 +29,8 @@ macro_rules! mem_load {
 
     ($load_type:ty, $target_type:ty, $arg:expr, $stack:ident, $module:ident) => {{
         #[inline(always)]
-        fn mem_load_inner(
-            module: &crate::instance::Instance,
+        fn mem_load_inner<T, S: Threading>(
+            module: &crate::instance::Instance<T, S>,
             stack: &mut crate::runtime::Stack,
             mem_addr: crate::types::MemAddr,
             offset: u64,
@@ -67,8 +67,8 @@ macro_rules! mem_store {
 
     ($store_type:ty, $target_type:ty, $arg:expr, $stack:ident, $module:ident) => {{
         #[inline(always)]
-        fn mem_store_inner(
-            module: &mut crate::Instance,
+        fn mem_store_inner<T, S: Threading>(
+            module: &mut crate::Instance<T, S>,
             stack: &mut crate::runtime::Stack,
             mem_addr: crate::types::MemAddr,
             offset: u64,
@@ -181,6 +181,15 @@ macro_rules! arithmetic_single {
     };
 }
 
+/// Replace a NaN on top of the stack with the canonical NaN in deterministic mode
+macro_rules! canonicalize {
+    ($ty:ty, $stack:ident, $deterministic:ident) => {
+        if $deterministic {
+            $stack.values.replace_top(|v| <$ty>::from(v).canonicalize_nan().into())?
+        }
+    };
+}
+
 /// Apply an arithmetic operation to two values on the stack with error checking
 macro_rules! checked_int_arithmetic {
     ($op:ident, $to:ty, $stack:ident) => {
@@ -211,6 +220,14 @@ macro_rules! call {
     }};
 }
 
+/// Skip the rest of the sequence of a superinstruction, counting its cycles like the unfused instructions
+macro_rules! skip_fused {
+    ($cf:ident, $cycles:ident) => {{
+        $cf.instr_ptr += FUSED_LEN - 1;
+        *$cycles = $cycles.saturating_add(FUSED_LEN - 1);
+    }};
+}
+
 macro_rules! skip {
     ($code:expr) => {
         match $code {
@@ -225,6 +242,7 @@ pub(super) use arithmetic;
 pub(super) use arithmetic_single;
 pub(super) use break_to;
 pub(super) use call;
+pub(super) use canonicalize;
 pub(super) use checked_conv_float;
 pub(super) use checked_int_arithmetic;
 pub(super) use comp;
@@ -234,3 +252,4 @@ pub(super) use float_min_max;
 pub(super) use mem_load;
 pub(super) use mem_store;
 pub(super) use skip;
+pub(super) use skip_fused;
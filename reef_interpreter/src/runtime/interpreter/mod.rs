@@ -3,27 +3,61 @@ use alloc::string::ToString;
 use core::ops::{BitAnd, BitOr, BitXor, Neg};
 
 use crate::error::{Error, Result, Trap};
-use crate::imports::{FuncContext, Function};
+use crate::exec::PendingHostCall;
+use crate::imports::{FuncContext, Function, HostFunction, Threading};
 use crate::instance::Instance;
+use crate::parser::optimize::FUSED_LEN;
 use crate::runtime::{BlockFrame, BlockType, CallFrame, RawWasmValue, Stack};
-use crate::types::{instructions::BlockArgs, value::ValType, ElementKind};
+use crate::types::{
+    instructions::{BlockArgs, I32Cmp},
+    value::ValType,
+    Backend, ElementKind, FuncAddr,
+};
 use crate::{cold, unlikely, VecExt};
 
 mod macros;
+#[cfg(not(feature = "std"))]
+mod no_std_floats;
+mod slots;
 mod traits;
 use {macros::*, traits::*};
 
+#[cfg(not(feature = "std"))]
+use no_std_floats::NoStdFloatExt;
+
 /// The Wasm interpreter.
-#[derive(Debug, Default)]
-pub(crate) struct Interpreter {}
+#[derive(Debug)]
+pub(crate) struct Interpreter {
+    /// Depth of the call stack below the executed function, returning to it finishes execution.
+    /// This is only non-zero for nested calls from host functions.
+    pub(crate) call_base: usize,
+    /// Execution stops once `cycles` exceeds it, nested calls from host functions share the budget of their caller.
+    pub(crate) max_cycles: usize,
+}
 
 impl Interpreter {
-    pub(crate) fn exec(&self, mut instance: &mut Instance, stack: &mut Stack, max_cycles: usize) -> Result<bool> {
+    /// Execute until the function returns or `cycles` exceeds `max_cycles`
+    ///
+    /// `cycles` is incremented for every executed instruction, including those of nested calls made by host functions.
+    pub(crate) fn exec<T, S: Threading>(
+        &self,
+        mut instance: &mut Instance<T, S>,
+        stack: &mut Stack,
+        cycles: &mut usize,
+    ) -> Result<bool> {
+        if instance.module.backend == Backend::Slots {
+            return self.exec_slots(instance, stack, cycles);
+        }
+        let max_cycles = self.max_cycles;
+
         let mut cf = stack.call_stack.pop()?;
+        let deterministic = instance.deterministic;
 
-        for _ in 0..=max_cycles {
+        while *cycles <= max_cycles {
             use crate::types::instructions::Instruction::*;
 
+            *cycles = cycles.saturating_add(1);
+
             let curr_instr = cf.fetch_instr(&instance.funcs);
 
             match curr_instr {
@@ -32,9 +66,9 @@ impl Interpreter {
                 Drop => stack.values.pop().map(|_| ())?,
                 Select(_valtype) => self.exec_select(stack)?,
 
-                Call(v) => skip!(self.exec_call(v, stack, &mut cf, instance)),
+                Call(v) => skip!(self.exec_call(v, stack, &mut cf, instance, cycles)),
                 CallIndirect(ty, table) => {
-                    skip!(self.exec_call_indirect(ty, table, stack, &mut cf, instance))
+                    skip!(self.exec_call_indirect(ty, table, stack, &mut cf, instance, cycles))
                 }
                 If(args, el, end) => {
                     skip!(self.exec_if((args).into(), el, end, stack, &mut cf, instance))
@@ -42,10 +76,10 @@ impl Interpreter {
                 Loop(args, end) => self.enter_block(stack, cf.instr_ptr, end, BlockType::Loop, args, instance),
                 Block(args, end) => self.enter_block(stack, cf.instr_ptr, end, BlockType::Block, args, instance),
 
-                Br(v) => break_to!(cf, stack, module, store, v),
+                Br(v) => break_to!(cf, stack, self.call_base, v),
                 BrIf(v) => {
                     if i32::from(stack.values.pop()?) != 0 {
-                        break_to!(cf, stack, module, store, v);
+                        break_to!(cf, stack, self.call_base, v);
                     }
                 }
                 BrTable(default, len) => {
@@ -61,13 +95,13 @@ impl Interpreter {
 
                     let idx: i32 = stack.values.pop()?.into();
                     match cf.instructions(&instance.funcs)[start..end].get(idx as usize) {
-                        None => break_to!(cf, stack, module, store, default),
-                        Some(BrLabel(to)) => break_to!(cf, stack, module, store, *to),
+                        None => break_to!(cf, stack, self.call_base, default),
+                        Some(BrLabel(to)) => break_to!(cf, stack, self.call_base, *to),
                         _ => return Err(Error::Other("br_table with invalid label".to_string())),
                     }
                 }
 
-                Return => match stack.call_stack.is_empty() {
+                Return => match stack.call_stack.len() <= self.call_base {
                     true => return Ok(true),
                     false => call!(cf, stack, module, store),
                 },
@@ -205,21 +239,45 @@ impl Interpreter {
 
                 I64Add => arithmetic!(wrapping_add, i64, stack),
                 I32Add => arithmetic!(wrapping_add, i32, stack),
-                F32Add => arithmetic!(+, f32, stack),
-                F64Add => arithmetic!(+, f64, stack),
+                F32Add => {
+                    arithmetic!(+, f32, stack);
+                    canonicalize!(f32, stack, deterministic)
+                }
+                F64Add => {
+                    arithmetic!(+, f64, stack);
+                    canonicalize!(f64, stack, deterministic)
+                }
 
                 I32Sub => arithmetic!(wrapping_sub, i32, stack),
                 I64Sub => arithmetic!(wrapping_sub, i64, stack),
-                F32Sub => arithmetic!(-, f32, stack),
-                F64Sub => arithmetic!(-, f64, stack),
+                F32Sub => {
+                    arithmetic!(-, f32, stack);
+                    canonicalize!(f32, stack, deterministic)
+                }
+                F64Sub => {
+                    arithmetic!(-, f64, stack);
+                    canonicalize!(f64, stack, deterministic)
+                }
 
-                F32Div => arithmetic!(/, f32, stack),
-                F64Div => arithmetic!(/, f64, stack),
+                F32Div => {
+                    arithmetic!(/, f32, stack);
+                    canonicalize!(f32, stack, deterministic)
+                }
+                F64Div => {
+                    arithmetic!(/, f64, stack);
+                    canonicalize!(f64, stack, deterministic)
+                }
 
                 I32Mul => arithmetic!(wrapping_mul, i32, stack),
                 I64Mul => arithmetic!(wrapping_mul, i64, stack),
-                F32Mul => arithmetic!(*, f32, stack),
-                F64Mul => arithmetic!(*, f64, stack),
+                F32Mul => {
+                    arithmetic!(*, f32, stack);
+                    canonicalize!(f32, stack, deterministic)
+                }
+                F64Mul => {
+                    arithmetic!(*, f64, stack);
+                    canonicalize!(f64, stack, deterministic)
+                }
 
                 // these can trap
                 I32DivS => checked_int_arithmetic!(checked_div, i32, stack),
@@ -273,27 +331,75 @@ impl Interpreter {
                 I64ExtendI32S => conv!(i32, i64, stack),
                 I32WrapI64 => conv!(i64, i32, stack),
 
-                F32DemoteF64 => conv!(f64, f32, stack),
-                F64PromoteF32 => conv!(f32, f64, stack),
+                F32DemoteF64 => {
+                    conv!(f64, f32, stack);
+                    canonicalize!(f32, stack, deterministic)
+                }
+                F64PromoteF32 => {
+                    conv!(f32, f64, stack);
+                    canonicalize!(f64, stack, deterministic)
+                }
 
                 F32Abs => arithmetic_single!(abs, f32, stack),
                 F64Abs => arithmetic_single!(abs, f64, stack),
                 F32Neg => arithmetic_single!(neg, f32, stack),
                 F64Neg => arithmetic_single!(neg, f64, stack),
-                F32Ceil => arithmetic_single!(ceil, f32, stack),
-                F64Ceil => arithmetic_single!(ceil, f64, stack),
-                F32Floor => arithmetic_single!(floor, f32, stack),
-                F64Floor => arithmetic_single!(floor, f64, stack),
-                F32Trunc => arithmetic_single!(trunc, f32, stack),
-                F64Trunc => arithmetic_single!(trunc, f64, stack),
-                F32Nearest => arithmetic_single!(tw_nearest, f32, stack),
-                F64Nearest => arithmetic_single!(tw_nearest, f64, stack),
-                F32Sqrt => arithmetic_single!(sqrt, f32, stack),
-                F64Sqrt => arithmetic_single!(sqrt, f64, stack),
-                F32Min => arithmetic!(tw_minimum, f32, stack),
-                F64Min => arithmetic!(tw_minimum, f64, stack),
-                F32Max => arithmetic!(tw_maximum, f32, stack),
-                F64Max => arithmetic!(tw_maximum, f64, stack),
+                F32Ceil => {
+                    arithmetic_single!(ceil, f32, stack);
+                    canonicalize!(f32, stack, deterministic)
+                }
+                F64Ceil => {
+                    arithmetic_single!(ceil, f64, stack);
+                    canonicalize!(f64, stack, deterministic)
+                }
+                F32Floor => {
+                    arithmetic_single!(floor, f32, stack);
+                    canonicalize!(f32, stack, deterministic)
+                }
+                F64Floor => {
+                    arithmetic_single!(floor, f64, stack);
+                    canonicalize!(f64, stack, deterministic)
+                }
+                F32Trunc => {
+                    arithmetic_single!(trunc, f32, stack);
+                    canonicalize!(f32, stack, deterministic)
+                }
+                F64Trunc => {
+                    arithmetic_single!(trunc, f64, stack);
+                    canonicalize!(f64, stack, deterministic)
+                }
+                F32Nearest => {
+                    arithmetic_single!(tw_nearest, f32, stack);
+                    canonicalize!(f32, stack, deterministic)
+                }
+                F64Nearest => {
+                    arithmetic_single!(tw_nearest, f64, stack);
+                    canonicalize!(f64, stack, deterministic)
+                }
+                F32Sqrt => {
+                    arithmetic_single!(sqrt, f32, stack);
+                    canonicalize!(f32, stack, deterministic)
+                }
+                F64Sqrt => {
+                    arithmetic_single!(sqrt, f64, stack);
+                    canonicalize!(f64, stack, deterministic)
+                }
+                F32Min => {
+                    arithmetic!(tw_minimum, f32, stack);
+                    canonicalize!(f32, stack, deterministic)
+                }
+                F64Min => {
+                    arithmetic!(tw_minimum, f64, stack);
+                    canonicalize!(f64, stack, deterministic)
+                }
+                F32Max => {
+                    arithmetic!(tw_maximum, f32, stack);
+                    canonicalize!(f32, stack, deterministic)
+                }
+                F64Max => {
+                    arithmetic!(tw_maximum, f64, stack);
+                    canonicalize!(f64, stack, deterministic)
+                }
                 F32Copysign => arithmetic!(copysign, f32, stack),
                 F64Copysign => arithmetic!(copysign, f64, stack),
 
@@ -313,7 +419,7 @@ impl Interpreter {
                 TableGet(table_idx) => self.exec_table_get(table_idx, stack, instance)?,
                 TableSet(table_idx) => self.exec_table_set(table_idx, stack, instance)?,
                 TableSize(table_idx) => self.exec_table_size(table_idx, stack, instance)?,
-                TableInit(table_idx, elem_idx) => self.exec_table_init(elem_idx, table_idx, instance)?,
+                TableInit(elem_idx, table_idx) => self.exec_table_init(elem_idx, table_idx, stack, instance)?,
 
                 I32TruncSatF32S => arithmetic_single!(trunc, f32, i32, stack),
                 I32TruncSatF32U => arithmetic_single!(trunc, f32, u32, stack),
@@ -334,6 +440,23 @@ impl Interpreter {
                 I32StoreLocal { local, const_i32: consti32, offset, mem_addr } => {
                     self.exec_i32_store_local(local, consti32, offset, mem_addr, &cf, instance)?
                 }
+
+                // superinstructions, these skip the rest of their sequence
+                I32AddLocals(a, b) => {
+                    self.exec_i32_add_locals(a, b, stack, &cf);
+                    skip_fused!(cf, cycles);
+                }
+                I32LocalGetConstAddSet(local, val, target) => {
+                    self.exec_i32_local_get_const_add_set(local, val, target, &mut cf);
+                    skip_fused!(cf, cycles);
+                }
+                I32CmpBrIf(cmp, v) => {
+                    *cycles = cycles.saturating_add(FUSED_LEN - 1);
+                    match self.exec_i32_cmp(cmp, stack)? {
+                        true => break_to!(cf, stack, self.call_base, v),
+                        false => cf.instr_ptr += FUSED_LEN - 1,
+                    }
+                }
                 i => {
                     cold();
                     return Err(Error::UnsupportedFeature(format!("unimplemented instruction: {:?}", i)));
@@ -376,14 +499,14 @@ impl Interpreter {
 
     #[allow(clippy::too_many_arguments)]
     #[inline(always)]
-    fn exec_i32_store_local(
+    fn exec_i32_store_local<T, S: Threading>(
         &self,
         local: u32,
         const_i32: i32,
         offset: u32,
         mem_addr: u8,
         cf: &CallFrame,
-        instance: &mut Instance,
+        instance: &mut Instance<T, S>,
     ) -> Result<()> {
         let mem = instance.get_mem_mut(mem_addr as u32)?;
         let val = const_i32.to_le_bytes();
@@ -392,6 +515,39 @@ impl Interpreter {
         Ok(())
     }
 
+    #[inline(always)]
+    fn exec_i32_add_locals(&self, a: u32, b: u32, stack: &mut Stack, cf: &CallFrame) {
+        let a: i32 = cf.get_local(a).into();
+        let b: i32 = cf.get_local(b).into();
+        stack.values.push(a.wrapping_add(b).into());
+    }
+
+    #[inline(always)]
+    fn exec_i32_local_get_const_add_set(&self, local: u32, val: i32, target: u32, cf: &mut CallFrame) {
+        let local: i32 = cf.get_local(local).into();
+        cf.set_local(target, local.wrapping_add(val).into());
+    }
+
+    #[inline(always)]
+    fn exec_i32_cmp(&self, cmp: I32Cmp, stack: &mut Stack) -> Result<bool> {
+        let b: i32 = stack.values.pop()?.into();
+        let mut a = || -> Result<i32> { Ok(stack.values.pop()?.into()) };
+
+        Ok(match cmp {
+            I32Cmp::Eqz => b == 0,
+            I32Cmp::Eq => a()? == b,
+            I32Cmp::Ne => a()? != b,
+            I32Cmp::LtS => a()? < b,
+            I32Cmp::LtU => (a()? as u32) < (b as u32),
+            I32Cmp::GtS => a()? > b,
+            I32Cmp::GtU => (a()? as u32) > (b as u32),
+            I32Cmp::LeS => a()? <= b,
+            I32Cmp::LeU => (a()? as u32) <= (b as u32),
+            I32Cmp::GeS => a()? >= b,
+            I32Cmp::GeU => (a()? as u32) >= (b as u32),
+        })
+    }
+
     #[inline(always)]
     fn exec_i32_local_get_const_add(&self, local: u32, val: i32, stack: &mut Stack, cf: &CallFrame) {
         let local: i32 = cf.get_local(local).into();
@@ -454,20 +610,35 @@ impl Interpreter {
     }
 
     #[inline(always)]
-    fn exec_global_get(&self, global_index: u32, stack: &mut Stack, module: &Instance) -> Result<()> {
+    fn exec_global_get<T, S: Threading>(
+        &self,
+        global_index: u32,
+        stack: &mut Stack,
+        module: &Instance<T, S>,
+    ) -> Result<()> {
         let global = module.get_global_val(global_index)?;
         stack.values.push(global);
         Ok(())
     }
 
     #[inline(always)]
-    fn exec_global_set(&self, global_index: u32, stack: &mut Stack, instance: &mut Instance) -> Result<()> {
+    fn exec_global_set<T, S: Threading>(
+        &self,
+        global_index: u32,
+        stack: &mut Stack,
+        instance: &mut Instance<T, S>,
+    ) -> Result<()> {
         instance.set_global_val(global_index, stack.values.pop()?)?;
         Ok(())
     }
 
     #[inline(always)]
-    fn exec_table_get(&self, table_index: u32, stack: &mut Stack, instance: &Instance) -> Result<()> {
+    fn exec_table_get<T, S: Threading>(
+        &self,
+        table_index: u32,
+        stack: &mut Stack,
+        instance: &Instance<T, S>,
+    ) -> Result<()> {
         let table = instance.get_table(table_index)?;
         let idx: u32 = stack.values.pop()?.into();
         let v = table.get_wasm_val(idx)?;
@@ -476,7 +647,12 @@ impl Interpreter {
     }
 
     #[inline(always)]
-    fn exec_table_set(&self, table_index: u32, stack: &mut Stack, instance: &mut Instance) -> Result<()> {
+    fn exec_table_set<T, S: Threading>(
+        &self,
+        table_index: u32,
+        stack: &mut Stack,
+        instance: &mut Instance<T, S>,
+    ) -> Result<()> {
         let table = instance.get_table_mut(table_index)?;
         let val = stack.values.pop()?.into();
         let idx = stack.values.pop()?.into();
@@ -485,27 +661,42 @@ impl Interpreter {
     }
 
     #[inline(always)]
-    fn exec_table_size(&self, table_index: u32, stack: &mut Stack, module: &Instance) -> Result<()> {
+    fn exec_table_size<T, S: Threading>(
+        &self,
+        table_index: u32,
+        stack: &mut Stack,
+        module: &Instance<T, S>,
+    ) -> Result<()> {
         let table = module.get_table(table_index)?;
         stack.values.push(table.size().into());
         Ok(())
     }
 
     #[inline(always)]
-    fn exec_table_init(&self, elem_index: u32, table_index: u32, instance: &mut Instance) -> Result<()> {
-        let table = instance.tables.get_mut_or_instance(table_index, "table")?;
+    fn exec_table_init<T, S: Threading>(
+        &self,
+        elem_index: u32,
+        table_index: u32,
+        stack: &mut Stack,
+        instance: &mut Instance<T, S>,
+    ) -> Result<()> {
+        let size = i32::from(stack.values.pop()?) as usize;
+        let offset = i32::from(stack.values.pop()?) as usize;
+        let dst = i32::from(stack.values.pop()?);
+
         let elem = instance.elements.get_or_instance(elem_index, "element")?;
+        // active and declared segments are dropped after instantiation
+        let items = match (&elem.kind, &elem.items) {
+            (ElementKind::Passive, Some(items)) => items.as_slice(),
+            _ => &[],
+        };
 
-        if let ElementKind::Passive = elem.kind {
-            return Err(Trap::TableOutOfBounds { offset: 0, len: 0, max: 0 }.into());
+        if unlikely(offset.checked_add(size).map_or(true, |end| end > items.len())) {
+            return Err(Trap::TableOutOfBounds { offset, len: size, max: items.len() }.into());
         }
 
-        let Some(items) = elem.items.as_ref() else {
-            return Err(Trap::TableOutOfBounds { offset: 0, len: 0, max: 0 }.into());
-        };
-
-        table.init(0, items)?;
-        Ok(())
+        let table = instance.tables.get_mut_or_instance(table_index, "table")?;
+        table.init(dst, &items[offset..offset + size])
     }
 
     #[inline(always)]
@@ -520,7 +711,13 @@ impl Interpreter {
     }
 
     #[inline(always)]
-    fn exec_memory_size(&self, addr: u32, byte: u8, stack: &mut Stack, module: &Instance) -> Result<()> {
+    fn exec_memory_size<T, S: Threading>(
+        &self,
+        addr: u32,
+        byte: u8,
+        stack: &mut Stack,
+        module: &Instance<T, S>,
+    ) -> Result<()> {
         if unlikely(byte != 0) {
             return Err(Error::UnsupportedFeature("memory.size with byte != 0".to_string()));
         }
@@ -531,24 +728,31 @@ impl Interpreter {
     }
 
     #[inline(always)]
-    fn exec_memory_grow(&self, addr: u32, byte: u8, stack: &mut Stack, instance: &mut Instance) -> Result<()> {
+    fn exec_memory_grow<T, S: Threading>(
+        &self,
+        addr: u32,
+        byte: u8,
+        stack: &mut Stack,
+        instance: &mut Instance<T, S>,
+    ) -> Result<()> {
         if unlikely(byte != 0) {
             return Err(Error::UnsupportedFeature("memory.grow with byte != 0".to_string()));
         }
 
-        let mem = instance.get_mem_mut(addr)?;
-        let prev_size = mem.page_count() as i32;
         let pages_delta = stack.values.last_mut()?;
-        *pages_delta = match mem.grow(i32::from(*pages_delta)) {
-            Some(_) => prev_size.into(),
-            None => (-1).into(),
-        };
+        *pages_delta = instance.grow_memory(addr, i32::from(*pages_delta))?.into();
 
         Ok(())
     }
 
     #[inline(always)]
-    fn exec_memory_copy(&self, from: u32, to: u32, stack: &mut Stack, instance: &mut Instance) -> Result<()> {
+    fn exec_memory_copy<T, S: Threading>(
+        &self,
+        from: u32,
+        to: u32,
+        stack: &mut Stack,
+        instance: &mut Instance<T, S>,
+    ) -> Result<()> {
         let size: i32 = stack.values.pop()?.into();
         let src: i32 = stack.values.pop()?.into();
         let dst: i32 = stack.values.pop()?.into();
@@ -568,7 +772,12 @@ impl Interpreter {
     }
 
     #[inline(always)]
-    fn exec_memory_fill(&self, addr: u32, stack: &mut Stack, instance: &mut Instance) -> Result<()> {
+    fn exec_memory_fill<T, S: Threading>(
+        &self,
+        addr: u32,
+        stack: &mut Stack,
+        instance: &mut Instance<T, S>,
+    ) -> Result<()> {
         let size: i32 = stack.values.pop()?.into();
         let val: i32 = stack.values.pop()?.into();
         let dst: i32 = stack.values.pop()?.into();
@@ -579,12 +788,12 @@ impl Interpreter {
     }
 
     #[inline(always)]
-    fn exec_memory_init(
+    fn exec_memory_init<T, S: Threading>(
         &self,
         data_index: u32,
         mem_index: u32,
         stack: &mut Stack,
-        instance: &mut Instance,
+        instance: &mut Instance<T, S>,
     ) -> Result<()> {
         let size = i32::from(stack.values.pop()?) as usize;
         let offset = i32::from(stack.values.pop()?) as usize;
@@ -606,31 +815,28 @@ impl Interpreter {
     }
 
     #[inline(always)]
-    fn exec_call(&self, v: u32, stack: &mut Stack, cf: &mut CallFrame, instance: &mut Instance) -> Result<()> {
+    fn exec_call<T, S: Threading>(
+        &self,
+        v: u32,
+        stack: &mut Stack,
+        cf: &mut CallFrame,
+        instance: &mut Instance<T, S>,
+        cycles: &mut usize,
+    ) -> Result<()> {
         let func_inst = instance.funcs.get_or_instance(v, "function")?;
         let wasm_func = match &func_inst {
             Function::Wasm(wasm_func) => wasm_func,
             Function::Host(host_func) => {
-                let params = stack.values.pop_params(&host_func.ty.params)?;
-                let res = (host_func.func)(
-                    FuncContext { module: &instance.module, memories: &mut instance.memories },
-                    &params,
-                );
-
-                let res = match res {
-                    Ok(res) => {
-                        stack.values.extend_from_typed(&res);
-                        Ok(())
-                    }
-                    Err(Error::PauseExecution) => Err(Error::PauseExecution),
-                    Err(err) => return Err(err),
-                };
-
-                cf.instr_ptr += 1;
-                return res;
+                let host_func = host_func.clone();
+                return self.exec_call_host(v, &host_func, stack, cf, instance, cycles);
             }
         };
 
+        if unlikely(wasm_func.lazy_body.is_some()) {
+            instance.translate_func(v)?;
+            return self.exec_call(v, stack, cf, instance, cycles);
+        }
+
         let params = stack.values.pop_n_rev(wasm_func.ty.params.len())?;
         let new_call_frame = CallFrame::new(v, wasm_func, params, stack.blocks.len() as u32);
 
@@ -639,14 +845,62 @@ impl Interpreter {
         Ok(())
     }
 
+    fn exec_call_host<T, S: Threading>(
+        &self,
+        func_addr: FuncAddr,
+        host_func: &HostFunction<T, S>,
+        stack: &mut Stack,
+        cf: &mut CallFrame,
+        instance: &mut Instance<T, S>,
+        cycles: &mut usize,
+    ) -> Result<()> {
+        let params = stack.values.pop_params(&host_func.ty.params)?;
+
+        // a pause requested during a nested call can only take effect once the host function returned
+        let mut pause_requested = false;
+        let res = (host_func.func)(
+            FuncContext {
+                instance,
+                stack: &mut *stack,
+                cycles,
+                max_cycles: self.max_cycles,
+                pause_requested: &mut pause_requested,
+            },
+            &params,
+        );
+
+        let res = match res {
+            Ok(res) => {
+                stack.values.extend_from_typed(&res);
+                match pause_requested {
+                    true => Err(Error::PauseExecution),
+                    false => Ok(()),
+                }
+            }
+            Err(Error::PauseExecution) => Err(Error::PauseExecution),
+            // the call is finished once the results are supplied, they are pushed like regular results
+            Err(Error::SuspendHostCall) => {
+                let import = instance.func_import_name(func_addr);
+                let params = params.iter().map(|v| RawWasmValue::from(*v)).collect();
+                stack.pending_host_call = Some(PendingHostCall { import, ty: host_func.ty.clone(), params });
+                Err(Error::PauseExecution)
+            }
+            Err(err) => return Err(err),
+        };
+
+        cf.instr_ptr += 1;
+        res
+    }
+
     #[inline(always)]
-    fn exec_call_indirect(
+    fn exec_call_indirect<T, S: Threading>(
         &self,
         type_addr: u32,
         table_addr: u32,
         stack: &mut Stack,
         cf: &mut CallFrame,
-        instance: &mut Instance,
+        instance: &mut Instance<T, S>,
+        cycles: &mut usize,
     ) -> Result<()> {
         let table = instance.tables.get_or_instance(table_addr, "table")?;
         let table_idx: u32 = stack.values.pop()?.into();
@@ -671,23 +925,8 @@ impl Interpreter {
                     .into());
                 }
 
-                let params = stack.values.pop_params(&host_func.ty.params)?;
-                let res = (host_func.func)(
-                    FuncContext { module: &instance.module, memories: &mut instance.memories },
-                    &params,
-                );
-
-                let res = match res {
-                    Ok(res) => {
-                        stack.values.extend_from_typed(&res);
-                        Ok(())
-                    }
-                    Err(Error::PauseExecution) => Err(Error::PauseExecution),
-                    Err(err) => return Err(err),
-                };
-
-                cf.instr_ptr += 1;
-                return res;
+                let host_func = host_func.clone();
+                return self.exec_call_host(func_ref, &host_func, stack, cf, instance, cycles);
             }
         };
 
@@ -697,6 +936,11 @@ impl Interpreter {
             );
         }
 
+        if unlikely(wasm_func.lazy_body.is_some()) {
+            instance.translate_func(func_ref)?;
+            return self.exec_call(func_ref, stack, cf, instance, cycles);
+        }
+
         let params = stack.values.pop_n_rev(wasm_func.ty.params.len())?;
         let new_call_frame = CallFrame::new(func_ref, wasm_func, params, stack.blocks.len() as u32);
 
@@ -707,14 +951,14 @@ impl Interpreter {
     }
 
     #[inline(always)]
-    fn exec_if(
+    fn exec_if<T, S: Threading>(
         &self,
         args: BlockArgs,
         else_offset: u32,
         end_offset: u32,
         stack: &mut Stack,
         cf: &mut CallFrame,
-        instance: &mut Instance,
+        instance: &mut Instance<T, S>,
     ) -> Result<()> {
         // truthy value is on the top of the stack, so enter the then block
         if i32::from(stack.values.pop()?) != 0 {
@@ -739,14 +983,14 @@ impl Interpreter {
     }
 
     #[inline(always)]
-    fn enter_block(
+    fn enter_block<T, S: Threading>(
         &self,
         stack: &mut super::Stack,
         instr_ptr: usize,
         end_instr_offset: u32,
         ty: BlockType,
         args: BlockArgs,
-        module: &Instance,
+        module: &Instance<T, S>,
     ) {
         let (params, results) = match args {
             BlockArgs::Empty => (0, 0),
@@ -767,3 +1011,165 @@ impl Interpreter {
         });
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::exec::CallResultTyped;
+    use crate::imports::{Extern, Imports};
+    use crate::types::{Backend, Module};
+    use crate::{Error, Instance, DETERMINISTIC_MAX_PAGES};
+
+    /// Stores the results of NaN producing float instructions from offset 0 and some exact results from 256
+    const NAN_PROGRAM: &str = r#"
+        (module
+            (memory (export "memory") 1)
+            (func (export "run")
+                (local $a f32) (local $b f64) (local $inf f32) (local $inf64 f64)
+                (local.set $a (f32.const -nan:0x200001))
+                (local.set $b (f64.const -nan:0x4000000000001))
+                (local.set $inf (f32.const inf))
+                (local.set $inf64 (f64.const inf))
+
+                (f32.store (i32.const 0) (f32.div (f32.const 0) (f32.const 0)))
+                (f32.store (i32.const 4) (f32.sqrt (f32.const -1)))
+                (f32.store (i32.const 8) (f32.add (local.get $a) (f32.const 1)))
+                (f32.store (i32.const 12) (f32.mul (local.get $inf) (f32.const 0)))
+                (f32.store (i32.const 16) (f32.sub (local.get $inf) (local.get $inf)))
+                (f32.store (i32.const 20) (f32.min (local.get $a) (f32.const 1)))
+                (f32.store (i32.const 24) (f32.max (f32.const 1) (local.get $a)))
+                (f32.store (i32.const 28) (f32.ceil (local.get $a)))
+                (f32.store (i32.const 32) (f32.floor (local.get $a)))
+                (f32.store (i32.const 36) (f32.trunc (local.get $a)))
+                (f32.store (i32.const 40) (f32.nearest (local.get $a)))
+                (f32.store (i32.const 44) (f32.demote_f64 (local.get $b)))
+
+                (f64.store (i32.const 48) (f64.div (f64.const 0) (f64.const 0)))
+                (f64.store (i32.const 56) (f64.sqrt (f64.const -1)))
+                (f64.store (i32.const 64) (f64.add (local.get $b) (f64.const 1)))
+                (f64.store (i32.const 72) (f64.mul (local.get $inf64) (f64.const 0)))
+                (f64.store (i32.const 80) (f64.sub (local.get $inf64) (local.get $inf64)))
+                (f64.store (i32.const 88) (f64.min (local.get $b) (f64.const 1)))
+                (f64.store (i32.const 96) (f64.max (f64.const 1) (local.get $b)))
+                (f64.store (i32.const 104) (f64.ceil (local.get $b)))
+                (f64.store (i32.const 112) (f64.floor (local.get $b)))
+                (f64.store (i32.const 120) (f64.trunc (local.get $b)))
+                (f64.store (i32.const 128) (f64.nearest (local.get $b)))
+                (f64.store (i32.const 136) (f64.promote_f32 (local.get $a)))
+
+                (f32.store (i32.const 256) (f32.nearest (f32.const 2.5)))
+                (f64.store (i32.const 264) (f64.sqrt (f64.const 2)))
+                (f64.store (i32.const 272) (f64.div (f64.const 1) (f64.const 3)))
+                ;; sign operations only change the sign bit, also of NaNs
+                (f32.store (i32.const 280) (f32.neg (local.get $a)))
+                (f64.store (i32.const 288) (f64.abs (local.get $b)))))
+    "#;
+
+    /// FNV-1a, so the expected hash doesn't depend on the hasher of the standard library
+    fn hash(bytes: &[u8]) -> u64 {
+        bytes.iter().fold(0xcbf2_9ce4_8422_2325, |hash, &b| (hash ^ b as u64).wrapping_mul(0x0100_0000_01b3))
+    }
+
+    fn run_deterministic(backend: Backend) -> Instance {
+        let wasm = wat::parse_str(NAN_PROGRAM).expect("invalid wat");
+        let mut module = Module::parse_stream(&wasm[..]).expect("failed to parse module");
+        module.set_backend(backend).unwrap();
+
+        let (mut instance, _, _) = Instance::instantiate(module, Imports::new(), (), None).unwrap();
+        instance.set_deterministic(true).unwrap();
+        let run = instance.exported_func::<(), ()>("run").unwrap();
+        run.invoke(&mut instance, ()).unwrap();
+        instance
+    }
+
+    #[test]
+    fn test_deterministic_nans() {
+        for backend in [Backend::Stack, Backend::Slots] {
+            let instance = run_deterministic(backend);
+            let memory = instance.exported_memory("memory").unwrap();
+
+            let f32s = memory.load_vec(0, 48).unwrap();
+            for bits in f32s.chunks(4) {
+                assert_eq!(bits, 0x7fc0_0000_u32.to_le_bytes(), "{backend:?}");
+            }
+            let f64s = memory.load_vec(48, 96).unwrap();
+            for bits in f64s.chunks(8) {
+                assert_eq!(bits, 0x7ff8_0000_0000_0000_u64.to_le_bytes(), "{backend:?}");
+            }
+            assert_eq!(memory.load_vec(280, 4).unwrap(), 0x7fa0_0001_u32.to_le_bytes());
+            assert_eq!(memory.load_vec(288, 8).unwrap(), 0x7ff4_0000_0000_0001_u64.to_le_bytes());
+
+            // The same on every platform, the interpreter workflow also runs this test on wasm32 for browser nodes
+            assert_eq!(hash(&memory.load_vec(0, crate::PAGE_SIZE).unwrap()), 0xbd66_4583_1b28_d20f, "{backend:?}");
+        }
+    }
+
+    #[test]
+    fn test_deterministic_in_snapshot() {
+        let wasm = wat::parse_str(
+            r#"
+            (module
+                (import "env" "pause" (func $pause))
+                (memory (export "memory") 1)
+                (func (export "run") (param $a f32)
+                    (call $pause)
+                    (f32.store (i32.const 0) (f32.sub (local.get $a) (local.get $a)))))
+            "#,
+        )
+        .expect("invalid wat");
+        let imports = || {
+            let mut imports = Imports::new();
+            let pause = Extern::typed_func(|_, ()| -> crate::error::Result<()> { Err(Error::PauseExecution) });
+            imports.define("env", "pause", pause).unwrap();
+            imports
+        };
+
+        for backend in [Backend::Stack, Backend::Slots] {
+            let mut module = crate::parse_bytes(&wasm).unwrap();
+            module.set_backend(backend).unwrap();
+
+            let (mut instance, _, _) = Instance::instantiate(module.clone(), imports(), (), None).unwrap();
+            instance.set_deterministic(true).unwrap();
+            let run = instance.exported_func::<f32, ()>("run").unwrap();
+            let mut exec_handle = run.call(instance, f32::INFINITY, None).unwrap();
+            assert!(matches!(exec_handle.run(100).unwrap(), CallResultTyped::Incomplete));
+            let mut state = Vec::new();
+            exec_handle.serialize(&mut state, &[]).unwrap();
+
+            // the host resuming the snapshot does not enable the mode again
+            let (instance, stack, _) = Instance::instantiate(module, imports(), (), Some(&state)).unwrap();
+            assert!(instance.is_deterministic(), "{backend:?}");
+            let mut exec_handle = run.call(instance, f32::INFINITY, stack).unwrap();
+            assert!(matches!(exec_handle.run(100).unwrap(), CallResultTyped::Done(())));
+            let memory = exec_handle.instance().exported_memory("memory").unwrap();
+            assert_eq!(memory.load_vec(0, 4).unwrap(), 0x7fc0_0000_u32.to_le_bytes(), "{backend:?}");
+        }
+    }
+
+    #[test]
+    fn test_deterministic_memory_limit() {
+        let wasm = wat::parse_str(
+            r#"(module (memory 1) (func (export "grow") (param i32) (result i32) (memory.grow (local.get 0))))"#,
+        )
+        .expect("invalid wat");
+
+        for backend in [Backend::Stack, Backend::Slots] {
+            let mut module = crate::parse_bytes(&wasm).unwrap();
+            module.set_backend(backend).unwrap();
+            let (mut instance, _, _) = Instance::instantiate(module, Imports::new(), (), None).unwrap();
+            instance.set_deterministic(true).unwrap();
+
+            let grow = instance.exported_func::<i32, i32>("grow").unwrap();
+            assert_eq!(grow.invoke(&mut instance, DETERMINISTIC_MAX_PAGES as i32).unwrap(), -1);
+            assert_eq!(grow.invoke(&mut instance, 1).unwrap(), 1);
+            assert_eq!(grow.invoke(&mut instance, DETERMINISTIC_MAX_PAGES as i32 - 1).unwrap(), -1);
+        }
+
+        // memories which are larger from the start are rejected as well
+        let wasm = wat::parse_str(format!("(module (memory {}))", DETERMINISTIC_MAX_PAGES + 1)).expect("invalid wat");
+        let module = crate::parse_bytes(&wasm).unwrap();
+        let (mut instance, _, _) = Instance::instantiate(module, Imports::new(), (), None).unwrap();
+        assert!(matches!(instance.set_deterministic(true), Err(Error::UnsupportedFeature(_))));
+        assert!(!instance.is_deterministic());
+        instance.set_deterministic(false).unwrap();
+    }
+}
@@ -0,0 +1,865 @@
+//! Execution of functions lowered for [`Backend::Slots`](crate::types::Backend::Slots)
+//!
+//! Frames hold the locals followed by the operands, see [`SlotFunction`](crate::types::slots::SlotFunction).
+//! Instructions read and write slots of the current frame directly, the value stack is only used to hand
+//! results to the caller of the interpreter and to receive the results of a completed host call.
+//!
+//! Callers keep their instruction pointer at the call while the callee runs, so the callee finds the slots
+//! to return its results to. Like with the stack backend, a paused frame can be serialized at every instruction.
+
+use alloc::{format, string::ToString, vec::Vec};
+use core::ops::Neg;
+
+#[cfg(not(feature = "std"))]
+use super::no_std_floats::NoStdFloatExt;
+use super::traits::*;
+use super::Interpreter;
+use crate::error::{Error, Result, Trap};
+use crate::exec::PendingHostCall;
+use crate::imports::{FuncContext, Function, HostFunction, Threading};
+use crate::instance::Instance;
+use crate::runtime::{CallFrame, RawWasmValue, Stack};
+use crate::store::memory::MemLoadable;
+use crate::types::{
+    slots::{Slot, SlotInstruction},
+    value::{ValType, WasmValue},
+    FuncAddr, MemAddr,
+};
+use crate::{cold, unlikely, VecExt};
+
+/// Apply an operation to two slots
+macro_rules! binary {
+    ($cf:ident, $dst:expr, $a:expr, $b:expr, $op:ident, $ty:ty) => {{
+        let a = <$ty>::from($cf.locals[$a as usize]);
+        let b = <$ty>::from($cf.locals[$b as usize]);
+        $cf.locals[$dst as usize] = (a.$op(b) as $ty).into();
+    }};
+
+    // also allow operators such as +, -
+    ($cf:ident, $dst:expr, $a:expr, $b:expr, $op:tt, $ty:ty) => {{
+        let a = <$ty>::from($cf.locals[$a as usize]);
+        let b = <$ty>::from($cf.locals[$b as usize]);
+        $cf.locals[$dst as usize] = ((a $op b) as $ty).into();
+    }};
+}
+
+/// Apply an operation to two slots with error checking
+macro_rules! checked_binary {
+    ($cf:ident, $dst:expr, $a:expr, $b:expr, $op:ident, $ty:ty) => {{
+        let a = <$ty>::from($cf.locals[$a as usize]);
+        let b = <$ty>::from($cf.locals[$b as usize]);
+
+        if unlikely(b == 0) {
+            return Err(Error::Trap(Trap::DivisionByZero));
+        }
+
+        let result = a.$op(b).ok_or_else(|| Error::Trap(Trap::IntegerOverflow))?;
+        $cf.locals[$dst as usize] = (result as $ty).into();
+    }};
+}
+
+/// Compare two slots
+macro_rules! comp {
+    ($cf:ident, $dst:expr, $a:expr, $b:expr, $op:tt, $ty:ty) => {{
+        let a = <$ty>::from($cf.locals[$a as usize]);
+        let b = <$ty>::from($cf.locals[$b as usize]);
+        $cf.locals[$dst as usize] = ((a $op b) as i32).into();
+    }};
+}
+
+/// Apply an operation to a slot
+macro_rules! unary {
+    ($cf:ident, $dst:expr, $src:expr, $op:ident, $ty:ty) => {
+        unary!($cf, $dst, $src, $op, $ty, $ty)
+    };
+
+    ($cf:ident, $dst:expr, $src:expr, $op:ident, $from:ty, $to:ty) => {{
+        let v = <$from>::from($cf.locals[$src as usize]);
+        $cf.locals[$dst as usize] = (v.$op() as $to).into();
+    }};
+}
+
+/// Replace a NaN in a slot with the canonical NaN in deterministic mode
+macro_rules! canonicalize {
+    ($cf:ident, $dst:expr, $ty:ty, $deterministic:ident) => {
+        if $deterministic {
+            $cf.locals[$dst as usize] = <$ty>::from($cf.locals[$dst as usize]).canonicalize_nan().into();
+        }
+    };
+}
+
+/// Convert a slot
+macro_rules! conv {
+    ($cf:ident, $dst:expr, $src:expr, $from:ty, $to:ty) => {{
+        let v = <$from>::from($cf.locals[$src as usize]);
+        $cf.locals[$dst as usize] = (v as $to).into();
+    }};
+}
+
+/// Convert a slot with error checking
+macro_rules! checked_conv_float {
+    ($cf:ident, $dst:expr, $src:expr, $from:tt, $to:tt) => {
+        checked_conv_float!($cf, $dst, $src, $from, $to, $to)
+    };
+
+    ($cf:ident, $dst:expr, $src:expr, $from:tt, $intermediate:tt, $to:tt) => {{
+        let (min, max) = super::macros::float_min_max!($from, $intermediate);
+        let a = <$from>::from($cf.locals[$src as usize]);
+
+        if unlikely(a.is_nan()) {
+            return Err(Error::Trap(Trap::InvalidConversionToInt));
+        }
+
+        if unlikely(a <= min || a >= max) {
+            return Err(Error::Trap(Trap::IntegerOverflow));
+        }
+
+        $cf.locals[$dst as usize] = (a as $intermediate as $to).into();
+    }};
+}
+
+/// Load a value from memory into a slot
+macro_rules! load {
+    ($cf:ident, $instance:ident, $dst:ident, $addr:ident, $offset:ident, $mem:ident, $ty:ty) => {
+        load!($cf, $instance, $dst, $addr, $offset, $mem, $ty, $ty)
+    };
+
+    ($cf:ident, $instance:ident, $dst:ident, $addr:ident, $offset:ident, $mem:ident, $load:ty, $to:ty) => {{
+        let val: $load = exec_load(&$instance, &$cf, $addr, $offset, $mem)?;
+        $cf.locals[$dst as usize] = (val as $to).into();
+    }};
+}
+
+/// Store a slot to memory
+macro_rules! store {
+    ($cf:ident, $instance:ident, $addr:ident, $src:ident, $offset:ident, $mem:ident, $ty:ty) => {
+        store!($cf, $instance, $addr, $src, $offset, $mem, $ty, $ty)
+    };
+
+    ($cf:ident, $instance:ident, $addr:ident, $src:ident, $offset:ident, $mem:ident, $store:ty, $from:ty) => {{
+        let val = <$from>::from($cf.locals[$src as usize]) as $store;
+        exec_store(&mut $instance, &$cf, $addr, $offset, $mem, &val.to_le_bytes())?;
+    }};
+}
+
+impl Interpreter {
+    /// Execute functions lowered to slot code, see [`Interpreter::exec`]
+    pub(super) fn exec_slots<T, S: Threading>(
+        &self,
+        mut instance: &mut Instance<T, S>,
+        stack: &mut Stack,
+        cycles: &mut usize,
+    ) -> Result<bool> {
+        let max_cycles = self.max_cycles;
+        let mut cf = stack.call_stack.pop()?;
+        let deterministic = instance.deterministic;
+
+        // results of a completed host call, the call was already skipped
+        if !stack.values.is_empty() {
+            let results = stack.values.len();
+            let base = match cf.instr_ptr.checked_sub(1).map(|pc| self.call_base_slot(&cf, pc, instance)) {
+                Some(Ok(base)) => base as usize,
+                _ => return Err(Error::Other("host call results without a host call".to_string())),
+            };
+            for (slot, value) in cf.locals[base..base + results].iter_mut().zip(stack.values.pop_n_rev(results)?) {
+                *slot = value;
+            }
+        }
+
+        while *cycles <= max_cycles {
+            use SlotInstruction::*;
+
+            *cycles = cycles.saturating_add(1);
+
+            match cf.fetch_slot_instr(&instance.funcs) {
+                Unreachable => return Err(Error::Trap(Trap::Unreachable)),
+                Unsupported(pc) => {
+                    cold();
+                    let instr = cf.instructions(&instance.funcs)[pc as usize];
+                    return Err(Error::UnsupportedFeature(format!("unimplemented instruction: {:?}", instr)));
+                }
+
+                Jump(pc) => {
+                    cf.instr_ptr = pc as usize;
+                    continue;
+                }
+                JumpIf(cond, pc) => {
+                    if i32::from(cf.locals[cond as usize]) != 0 {
+                        cf.instr_ptr = pc as usize;
+                        continue;
+                    }
+                }
+                JumpIfNot(cond, pc) => {
+                    if i32::from(cf.locals[cond as usize]) == 0 {
+                        cf.instr_ptr = pc as usize;
+                        continue;
+                    }
+                }
+                BrTable(idx, len) => {
+                    let idx = u32::from(cf.locals[idx as usize]).min(len) as usize;
+                    match cf.fetch_slot_instr_at(&instance.funcs, cf.instr_ptr + 1 + idx) {
+                        Jump(pc) => cf.instr_ptr = pc as usize,
+                        _ => return Err(Error::Other("br_table with invalid label".to_string())),
+                    }
+                    continue;
+                }
+
+                Return(src) => {
+                    let results = cf.wasm_func(&instance.funcs).ty.results.len();
+                    let src = src as usize..src as usize + results;
+
+                    if stack.call_stack.len() <= self.call_base {
+                        stack.values.extend_from_slice(&cf.locals[src]);
+                        return Ok(true);
+                    }
+
+                    let callee = core::mem::replace(&mut cf, stack.call_stack.pop()?);
+                    let base = self.call_base_slot(&cf, cf.instr_ptr, instance)? as usize;
+                    cf.locals[base..base + results].copy_from_slice(&callee.locals[src]);
+                    cf.instr_ptr += 1;
+                    continue;
+                }
+                Call(func, base) => match self.exec_slot_call(func, base, stack, &mut cf, instance, cycles) {
+                    Ok(()) => continue,
+                    Err(Error::PauseExecution) => break,
+                    Err(e) => return Err(e),
+                },
+                CallIndirect(ty, table, idx, base) => {
+                    let func = self.resolve_indirect(ty, table, idx, &cf, instance)?;
+                    match self.exec_slot_call(func, base, stack, &mut cf, instance, cycles) {
+                        Ok(()) => continue,
+                        Err(Error::PauseExecution) => break,
+                        Err(e) => return Err(e),
+                    }
+                }
+
+                Copy(dst, src) => cf.locals[dst as usize] = cf.locals[src as usize],
+                Select(dst, a, b, cond) => {
+                    let src = if i32::from(cf.locals[cond as usize]) != 0 { a } else { b };
+                    cf.locals[dst as usize] = cf.locals[src as usize];
+                }
+                GlobalGet(dst, global) => cf.locals[dst as usize] = instance.get_global_val(global)?,
+                GlobalSet(global, src) => instance.set_global_val(global, cf.locals[src as usize])?,
+
+                I32Const(dst, val) => cf.locals[dst as usize] = val.into(),
+                I64Const(dst, val) => cf.locals[dst as usize] = val.into(),
+                F32Const(dst, val) => cf.locals[dst as usize] = val.into(),
+                F64Const(dst, val) => cf.locals[dst as usize] = val.into(),
+
+                I32Load { dst, addr, offset, mem } => load!(cf, instance, dst, addr, offset, mem, i32),
+                I64Load { dst, addr, offset, mem } => load!(cf, instance, dst, addr, offset, mem, i64),
+                F32Load { dst, addr, offset, mem } => load!(cf, instance, dst, addr, offset, mem, f32),
+                F64Load { dst, addr, offset, mem } => load!(cf, instance, dst, addr, offset, mem, f64),
+                I32Load8S { dst, addr, offset, mem } => load!(cf, instance, dst, addr, offset, mem, i8, i32),
+                I32Load8U { dst, addr, offset, mem } => load!(cf, instance, dst, addr, offset, mem, u8, i32),
+                I32Load16S { dst, addr, offset, mem } => load!(cf, instance, dst, addr, offset, mem, i16, i32),
+                I32Load16U { dst, addr, offset, mem } => load!(cf, instance, dst, addr, offset, mem, u16, i32),
+                I64Load8S { dst, addr, offset, mem } => load!(cf, instance, dst, addr, offset, mem, i8, i64),
+                I64Load8U { dst, addr, offset, mem } => load!(cf, instance, dst, addr, offset, mem, u8, i64),
+                I64Load16S { dst, addr, offset, mem } => load!(cf, instance, dst, addr, offset, mem, i16, i64),
+                I64Load16U { dst, addr, offset, mem } => load!(cf, instance, dst, addr, offset, mem, u16, i64),
+                I64Load32S { dst, addr, offset, mem } => load!(cf, instance, dst, addr, offset, mem, i32, i64),
+                I64Load32U { dst, addr, offset, mem } => load!(cf, instance, dst, addr, offset, mem, u32, i64),
+                I32Store { addr, src, offset, mem } => store!(cf, instance, addr, src, offset, mem, i32),
+                I64Store { addr, src, offset, mem } => store!(cf, instance, addr, src, offset, mem, i64),
+                F32Store { addr, src, offset, mem } => store!(cf, instance, addr, src, offset, mem, f32),
+                F64Store { addr, src, offset, mem } => store!(cf, instance, addr, src, offset, mem, f64),
+                I32Store8 { addr, src, offset, mem } => store!(cf, instance, addr, src, offset, mem, i8, i32),
+                I32Store16 { addr, src, offset, mem } => store!(cf, instance, addr, src, offset, mem, i16, i32),
+                I64Store8 { addr, src, offset, mem } => store!(cf, instance, addr, src, offset, mem, i8, i64),
+                I64Store16 { addr, src, offset, mem } => store!(cf, instance, addr, src, offset, mem, i16, i64),
+                I64Store32 { addr, src, offset, mem } => store!(cf, instance, addr, src, offset, mem, i32, i64),
+
+                MemorySize(dst, mem) => {
+                    cf.locals[dst as usize] = (instance.get_mem(mem)?.page_count() as i32).into();
+                }
+                MemoryGrow(dst, delta, mem) => {
+                    cf.locals[dst as usize] = instance.grow_memory(mem, i32::from(cf.locals[delta as usize]))?.into();
+                }
+
+                // Rare instructions run on the value stack, like with the stack backend
+                MemoryCopy(from, to, base) => {
+                    stack.values.extend_from_slice(&cf.locals[base as usize..base as usize + 3]);
+                    self.exec_memory_copy(from, to, stack, instance)?;
+                }
+                MemoryFill(mem, base) => {
+                    stack.values.extend_from_slice(&cf.locals[base as usize..base as usize + 3]);
+                    self.exec_memory_fill(mem, stack, instance)?;
+                }
+                MemoryInit(mem, data, base) => {
+                    stack.values.extend_from_slice(&cf.locals[base as usize..base as usize + 3]);
+                    self.exec_memory_init(data, mem, stack, instance)?;
+                }
+                DataDrop(data) => instance.get_data_mut(data)?.drop(),
+                TableGet(table, dst, idx) => {
+                    stack.values.push(cf.locals[idx as usize]);
+                    self.exec_table_get(table, stack, instance)?;
+                    cf.locals[dst as usize] = stack.values.pop()?;
+                }
+                TableSet(table, base) => {
+                    stack.values.extend_from_slice(&cf.locals[base as usize..base as usize + 2]);
+                    self.exec_table_set(table, stack, instance)?;
+                }
+                TableSize(table, dst) => cf.locals[dst as usize] = instance.get_table(table)?.size().into(),
+                TableInit(table, elem, base) => {
+                    stack.values.extend_from_slice(&cf.locals[base as usize..base as usize + 3]);
+                    self.exec_table_init(elem, table, stack, instance)?;
+                }
+
+                I32Eqz(dst, src) => cf.locals[dst as usize] = ((i32::from(cf.locals[src as usize]) == 0) as i32).into(),
+                I64Eqz(dst, src) => cf.locals[dst as usize] = ((i64::from(cf.locals[src as usize]) == 0) as i32).into(),
+
+                I32Eq(d, a, b) => comp!(cf, d, a, b, ==, i32),
+                I64Eq(d, a, b) => comp!(cf, d, a, b, ==, i64),
+                F32Eq(d, a, b) => comp!(cf, d, a, b, ==, f32),
+                F64Eq(d, a, b) => comp!(cf, d, a, b, ==, f64),
+
+                I32Ne(d, a, b) => comp!(cf, d, a, b, !=, i32),
+                I64Ne(d, a, b) => comp!(cf, d, a, b, !=, i64),
+                F32Ne(d, a, b) => comp!(cf, d, a, b, !=, f32),
+                F64Ne(d, a, b) => comp!(cf, d, a, b, !=, f64),
+
+                I32LtS(d, a, b) => comp!(cf, d, a, b, <, i32),
+                I64LtS(d, a, b) => comp!(cf, d, a, b, <, i64),
+                I32LtU(d, a, b) => comp!(cf, d, a, b, <, u32),
+                I64LtU(d, a, b) => comp!(cf, d, a, b, <, u64),
+                F32Lt(d, a, b) => comp!(cf, d, a, b, <, f32),
+                F64Lt(d, a, b) => comp!(cf, d, a, b, <, f64),
+
+                I32LeS(d, a, b) => comp!(cf, d, a, b, <=, i32),
+                I64LeS(d, a, b) => comp!(cf, d, a, b, <=, i64),
+                I32LeU(d, a, b) => comp!(cf, d, a, b, <=, u32),
+                I64LeU(d, a, b) => comp!(cf, d, a, b, <=, u64),
+                F32Le(d, a, b) => comp!(cf, d, a, b, <=, f32),
+                F64Le(d, a, b) => comp!(cf, d, a, b, <=, f64),
+
+                I32GeS(d, a, b) => comp!(cf, d, a, b, >=, i32),
+                I64GeS(d, a, b) => comp!(cf, d, a, b, >=, i64),
+                I32GeU(d, a, b) => comp!(cf, d, a, b, >=, u32),
+                I64GeU(d, a, b) => comp!(cf, d, a, b, >=, u64),
+                F32Ge(d, a, b) => comp!(cf, d, a, b, >=, f32),
+                F64Ge(d, a, b) => comp!(cf, d, a, b, >=, f64),
+
+                I32GtS(d, a, b) => comp!(cf, d, a, b, >, i32),
+                I64GtS(d, a, b) => comp!(cf, d, a, b, >, i64),
+                I32GtU(d, a, b) => comp!(cf, d, a, b, >, u32),
+                I64GtU(d, a, b) => comp!(cf, d, a, b, >, u64),
+                F32Gt(d, a, b) => comp!(cf, d, a, b, >, f32),
+                F64Gt(d, a, b) => comp!(cf, d, a, b, >, f64),
+
+                I32Add(d, a, b) => binary!(cf, d, a, b, wrapping_add, i32),
+                I64Add(d, a, b) => binary!(cf, d, a, b, wrapping_add, i64),
+                F32Add(d, a, b) => {
+                    binary!(cf, d, a, b, +, f32);
+                    canonicalize!(cf, d, f32, deterministic)
+                }
+                F64Add(d, a, b) => {
+                    binary!(cf, d, a, b, +, f64);
+                    canonicalize!(cf, d, f64, deterministic)
+                }
+
+                I32Sub(d, a, b) => binary!(cf, d, a, b, wrapping_sub, i32),
+                I64Sub(d, a, b) => binary!(cf, d, a, b, wrapping_sub, i64),
+                F32Sub(d, a, b) => {
+                    binary!(cf, d, a, b, -, f32);
+                    canonicalize!(cf, d, f32, deterministic)
+                }
+                F64Sub(d, a, b) => {
+                    binary!(cf, d, a, b, -, f64);
+                    canonicalize!(cf, d, f64, deterministic)
+                }
+
+                F32Div(d, a, b) => {
+                    binary!(cf, d, a, b, /, f32);
+                    canonicalize!(cf, d, f32, deterministic)
+                }
+                F64Div(d, a, b) => {
+                    binary!(cf, d, a, b, /, f64);
+                    canonicalize!(cf, d, f64, deterministic)
+                }
+
+                I32Mul(d, a, b) => binary!(cf, d, a, b, wrapping_mul, i32),
+                I64Mul(d, a, b) => binary!(cf, d, a, b, wrapping_mul, i64),
+                F32Mul(d, a, b) => {
+                    binary!(cf, d, a, b, *, f32);
+                    canonicalize!(cf, d, f32, deterministic)
+                }
+                F64Mul(d, a, b) => {
+                    binary!(cf, d, a, b, *, f64);
+                    canonicalize!(cf, d, f64, deterministic)
+                }
+
+                // these can trap
+                I32DivS(d, a, b) => checked_binary!(cf, d, a, b, checked_div, i32),
+                I64DivS(d, a, b) => checked_binary!(cf, d, a, b, checked_div, i64),
+                I32DivU(d, a, b) => checked_binary!(cf, d, a, b, checked_div, u32),
+                I64DivU(d, a, b) => checked_binary!(cf, d, a, b, checked_div, u64),
+
+                I32RemS(d, a, b) => checked_binary!(cf, d, a, b, checked_wrapping_rem, i32),
+                I64RemS(d, a, b) => checked_binary!(cf, d, a, b, checked_wrapping_rem, i64),
+                I32RemU(d, a, b) => checked_binary!(cf, d, a, b, checked_wrapping_rem, u32),
+                I64RemU(d, a, b) => checked_binary!(cf, d, a, b, checked_wrapping_rem, u64),
+
+                I32And(d, a, b) => binary!(cf, d, a, b, &, i32),
+                I64And(d, a, b) => binary!(cf, d, a, b, &, i64),
+                I32Or(d, a, b) => binary!(cf, d, a, b, |, i32),
+                I64Or(d, a, b) => binary!(cf, d, a, b, |, i64),
+                I32Xor(d, a, b) => binary!(cf, d, a, b, ^, i32),
+                I64Xor(d, a, b) => binary!(cf, d, a, b, ^, i64),
+                I32Shl(d, a, b) => binary!(cf, d, a, b, wasm_shl, i32),
+                I64Shl(d, a, b) => binary!(cf, d, a, b, wasm_shl, i64),
+                I32ShrS(d, a, b) => binary!(cf, d, a, b, wasm_shr, i32),
+                I64ShrS(d, a, b) => binary!(cf, d, a, b, wasm_shr, i64),
+                I32ShrU(d, a, b) => binary!(cf, d, a, b, wasm_shr, u32),
+                I64ShrU(d, a, b) => binary!(cf, d, a, b, wasm_shr, u64),
+                I32Rotl(d, a, b) => binary!(cf, d, a, b, wasm_rotl, i32),
+                I64Rotl(d, a, b) => binary!(cf, d, a, b, wasm_rotl, i64),
+                I32Rotr(d, a, b) => binary!(cf, d, a, b, wasm_rotr, i32),
+                I64Rotr(d, a, b) => binary!(cf, d, a, b, wasm_rotr, i64),
+
+                I32Clz(d, s) => unary!(cf, d, s, leading_zeros, i32),
+                I64Clz(d, s) => unary!(cf, d, s, leading_zeros, i64),
+                I32Ctz(d, s) => unary!(cf, d, s, trailing_zeros, i32),
+                I64Ctz(d, s) => unary!(cf, d, s, trailing_zeros, i64),
+                I32Popcnt(d, s) => unary!(cf, d, s, count_ones, i32),
+                I64Popcnt(d, s) => unary!(cf, d, s, count_ones, i64),
+
+                F32ConvertI32S(d, s) => conv!(cf, d, s, i32, f32),
+                F32ConvertI64S(d, s) => conv!(cf, d, s, i64, f32),
+                F64ConvertI32S(d, s) => conv!(cf, d, s, i32, f64),
+                F64ConvertI64S(d, s) => conv!(cf, d, s, i64, f64),
+                F32ConvertI32U(d, s) => conv!(cf, d, s, u32, f32),
+                F32ConvertI64U(d, s) => conv!(cf, d, s, u64, f32),
+                F64ConvertI32U(d, s) => conv!(cf, d, s, u32, f64),
+                F64ConvertI64U(d, s) => conv!(cf, d, s, u64, f64),
+                I32Extend8S(d, s) => conv!(cf, d, s, i8, i32),
+                I32Extend16S(d, s) => conv!(cf, d, s, i16, i32),
+                I64Extend8S(d, s) => conv!(cf, d, s, i8, i64),
+                I64Extend16S(d, s) => conv!(cf, d, s, i16, i64),
+                I64Extend32S(d, s) => conv!(cf, d, s, i32, i64),
+                I64ExtendI32U(d, s) => conv!(cf, d, s, u32, i64),
+                I64ExtendI32S(d, s) => conv!(cf, d, s, i32, i64),
+                I32WrapI64(d, s) => conv!(cf, d, s, i64, i32),
+
+                F32DemoteF64(d, s) => {
+                    conv!(cf, d, s, f64, f32);
+                    canonicalize!(cf, d, f32, deterministic)
+                }
+                F64PromoteF32(d, s) => {
+                    conv!(cf, d, s, f32, f64);
+                    canonicalize!(cf, d, f64, deterministic)
+                }
+
+                F32Abs(d, s) => unary!(cf, d, s, abs, f32),
+                F64Abs(d, s) => unary!(cf, d, s, abs, f64),
+                F32Neg(d, s) => unary!(cf, d, s, neg, f32),
+                F64Neg(d, s) => unary!(cf, d, s, neg, f64),
+                F32Ceil(d, s) => {
+                    unary!(cf, d, s, ceil, f32);
+                    canonicalize!(cf, d, f32, deterministic)
+                }
+                F64Ceil(d, s) => {
+                    unary!(cf, d, s, ceil, f64);
+                    canonicalize!(cf, d, f64, deterministic)
+                }
+                F32Floor(d, s) => {
+                    unary!(cf, d, s, floor, f32);
+                    canonicalize!(cf, d, f32, deterministic)
+                }
+                F64Floor(d, s) => {
+                    unary!(cf, d, s, floor, f64);
+                    canonicalize!(cf, d, f64, deterministic)
+                }
+                F32Trunc(d, s) => {
+                    unary!(cf, d, s, trunc, f32);
+                    canonicalize!(cf, d, f32, deterministic)
+                }
+                F64Trunc(d, s) => {
+                    unary!(cf, d, s, trunc, f64);
+                    canonicalize!(cf, d, f64, deterministic)
+                }
+                F32Nearest(d, s) => {
+                    unary!(cf, d, s, tw_nearest, f32);
+                    canonicalize!(cf, d, f32, deterministic)
+                }
+                F64Nearest(d, s) => {
+                    unary!(cf, d, s, tw_nearest, f64);
+                    canonicalize!(cf, d, f64, deterministic)
+                }
+                F32Sqrt(d, s) => {
+                    unary!(cf, d, s, sqrt, f32);
+                    canonicalize!(cf, d, f32, deterministic)
+                }
+                F64Sqrt(d, s) => {
+                    unary!(cf, d, s, sqrt, f64);
+                    canonicalize!(cf, d, f64, deterministic)
+                }
+                F32Min(d, a, b) => {
+                    binary!(cf, d, a, b, tw_minimum, f32);
+                    canonicalize!(cf, d, f32, deterministic)
+                }
+                F64Min(d, a, b) => {
+                    binary!(cf, d, a, b, tw_minimum, f64);
+                    canonicalize!(cf, d, f64, deterministic)
+                }
+                F32Max(d, a, b) => {
+                    binary!(cf, d, a, b, tw_maximum, f32);
+                    canonicalize!(cf, d, f32, deterministic)
+                }
+                F64Max(d, a, b) => {
+                    binary!(cf, d, a, b, tw_maximum, f64);
+                    canonicalize!(cf, d, f64, deterministic)
+                }
+                F32Copysign(d, a, b) => binary!(cf, d, a, b, copysign, f32),
+                F64Copysign(d, a, b) => binary!(cf, d, a, b, copysign, f64),
+
+                I32TruncF32S(d, s) => checked_conv_float!(cf, d, s, f32, i32),
+                I32TruncF64S(d, s) => checked_conv_float!(cf, d, s, f64, i32),
+                I32TruncF32U(d, s) => checked_conv_float!(cf, d, s, f32, u32, i32),
+                I32TruncF64U(d, s) => checked_conv_float!(cf, d, s, f64, u32, i32),
+                I64TruncF32S(d, s) => checked_conv_float!(cf, d, s, f32, i64),
+                I64TruncF64S(d, s) => checked_conv_float!(cf, d, s, f64, i64),
+                I64TruncF32U(d, s) => checked_conv_float!(cf, d, s, f32, u64, i64),
+                I64TruncF64U(d, s) => checked_conv_float!(cf, d, s, f64, u64, i64),
+
+                I32TruncSatF32S(d, s) => unary!(cf, d, s, trunc, f32, i32),
+                I32TruncSatF32U(d, s) => unary!(cf, d, s, trunc, f32, u32),
+                I32TruncSatF64S(d, s) => unary!(cf, d, s, trunc, f64, i32),
+                I32TruncSatF64U(d, s) => unary!(cf, d, s, trunc, f64, u32),
+                I64TruncSatF32S(d, s) => unary!(cf, d, s, trunc, f32, i64),
+                I64TruncSatF32U(d, s) => unary!(cf, d, s, trunc, f32, u64),
+                I64TruncSatF64S(d, s) => unary!(cf, d, s, trunc, f64, i64),
+                I64TruncSatF64U(d, s) => unary!(cf, d, s, trunc, f64, u64),
+            };
+
+            cf.instr_ptr += 1;
+        }
+
+        stack.call_stack.push(cf)?;
+
+        Ok(false)
+    }
+
+    /// The slot a call at `pc` passes its parameters in and receives its results
+    fn call_base_slot<T, S: Threading>(&self, cf: &CallFrame, pc: usize, instance: &Instance<T, S>) -> Result<Slot> {
+        match cf.fetch_slot_instr_at(&instance.funcs, pc) {
+            SlotInstruction::Call(_, base) | SlotInstruction::CallIndirect(_, _, _, base) => Ok(base),
+            _ => Err(Error::Other("returned to a frame which is not at a call".to_string())),
+        }
+    }
+
+    /// Resolve the function of an indirect call and check its type
+    fn resolve_indirect<T, S: Threading>(
+        &self,
+        type_addr: u32,
+        table_addr: u32,
+        idx: Slot,
+        cf: &CallFrame,
+        instance: &Instance<T, S>,
+    ) -> Result<FuncAddr> {
+        let table = instance.tables.get_or_instance(table_addr, "table")?;
+        let table_idx: u32 = cf.locals[idx as usize].into();
+
+        // verify that the table is of the right type, this should be validated by the parser already
+        let func_ref = {
+            assert!(table.kind.element_type == ValType::RefFunc, "table is not of type funcref");
+            table.get(table_idx)?.addr().ok_or(Trap::UninitializedElement { index: table_idx as usize })?
+        };
+
+        let func_ty = instance.funcs.get_or_instance(func_ref, "function")?.ty();
+        let call_ty = instance.func_ty(type_addr);
+        if unlikely(func_ty != call_ty) {
+            return Err(Trap::IndirectCallTypeMismatch { actual: func_ty.clone(), expected: call_ty.clone() }.into());
+        }
+
+        Ok(func_ref)
+    }
+
+    /// Call a function with the parameters starting at `base`, the caller stays at the call until the callee returns
+    #[inline(always)]
+    fn exec_slot_call<T, S: Threading>(
+        &self,
+        addr: FuncAddr,
+        base: Slot,
+        stack: &mut Stack,
+        cf: &mut CallFrame,
+        instance: &mut Instance<T, S>,
+        cycles: &mut usize,
+    ) -> Result<()> {
+        let wasm_func = match instance.funcs.get_or_instance(addr, "function")? {
+            Function::Wasm(wasm_func) => wasm_func,
+            Function::Host(host_func) => {
+                let host_func = host_func.clone();
+                return self.exec_slot_call_host(addr, &host_func, base, stack, cf, instance, cycles);
+            }
+        };
+
+        if unlikely(wasm_func.lazy_body.is_some()) {
+            instance.translate_func(addr)?;
+            return self.exec_slot_call(addr, base, stack, cf, instance, cycles);
+        }
+
+        let params = &cf.locals[base as usize..base as usize + wasm_func.ty.params.len()];
+        let new_call_frame = CallFrame::new(addr, wasm_func, params.iter().copied(), 0);
+        stack.call_stack.push(core::mem::replace(cf, new_call_frame))?;
+        Ok(())
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    fn exec_slot_call_host<T, S: Threading>(
+        &self,
+        func_addr: FuncAddr,
+        host_func: &HostFunction<T, S>,
+        base: Slot,
+        stack: &mut Stack,
+        cf: &mut CallFrame,
+        instance: &mut Instance<T, S>,
+        cycles: &mut usize,
+    ) -> Result<()> {
+        let slots = &mut cf.locals[base as usize..];
+        let params: Vec<WasmValue> =
+            slots.iter().zip(host_func.ty.params.iter()).map(|(v, ty)| v.attach_type(*ty)).collect();
+
+        // a pause requested during a nested call can only take effect once the host function returned
+        let mut pause_requested = false;
+        let res = (host_func.func)(
+            FuncContext {
+                instance,
+                stack: &mut *stack,
+                cycles,
+                max_cycles: self.max_cycles,
+                pause_requested: &mut pause_requested,
+            },
+            &params,
+        );
+
+        let res = match res {
+            Ok(res) => {
+                slots.iter_mut().zip(res).for_each(|(slot, v)| *slot = v.into());
+                match pause_requested {
+                    true => Err(Error::PauseExecution),
+                    false => Ok(()),
+                }
+            }
+            Err(Error::PauseExecution) => Err(Error::PauseExecution),
+            // the results are placed by the next run, once they are supplied
+            Err(Error::SuspendHostCall) => {
+                let import = instance.func_import_name(func_addr);
+                let params = params.iter().map(|v| RawWasmValue::from(*v)).collect();
+                stack.pending_host_call = Some(PendingHostCall { import, ty: host_func.ty.clone(), params });
+                Err(Error::PauseExecution)
+            }
+            Err(err) => return Err(err),
+        };
+
+        cf.instr_ptr += 1;
+        res
+    }
+}
+
+#[inline(always)]
+fn exec_load<T, S: Threading, const LEN: usize, V: MemLoadable<LEN>>(
+    instance: &Instance<T, S>,
+    cf: &CallFrame,
+    addr: Slot,
+    offset: u32,
+    mem: u8,
+) -> Result<V> {
+    let mem = instance.get_mem(mem as MemAddr)?;
+    let addr = u32::from(cf.locals[addr as usize]) as u64 + offset as u64;
+    match usize::try_from(addr) {
+        Ok(addr) => mem.load_as::<LEN, V>(addr),
+        Err(_) => {
+            cold();
+            Err(Error::Trap(Trap::MemoryOutOfBounds { offset: offset as usize, len: LEN, max: mem.max_pages() }))
+        }
+    }
+}
+
+#[inline(always)]
+fn exec_store<T, S: Threading>(
+    instance: &mut Instance<T, S>,
+    cf: &CallFrame,
+    addr: Slot,
+    offset: u32,
+    mem: u8,
+    val: &[u8],
+) -> Result<()> {
+    let mem = instance.get_mem_mut(mem as MemAddr)?;
+    let addr = u32::from(cf.locals[addr as usize]) as u64 + offset as u64;
+    mem.store(addr as usize, val.len(), val)
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::vec;
+
+    use crate::exec::{CallResult, CallResultTyped, ExecHandle};
+    use crate::imports::{Extern, Imports};
+    use crate::types::{value::WasmValue, Backend, Module};
+    use crate::{Error, Instance};
+
+    const PROGRAM: &str = r#"
+        (module
+            (import "env" "square" (func $square (param i64) (result i64)))
+            (memory (export "memory") 1)
+            (table 2 funcref)
+            (elem (i32.const 0) $fib $sum)
+            (type $unary (func (param i32) (result i32)))
+
+            (func $fib (param i32) (result i32)
+                (if (result i32) (i32.lt_u (local.get 0) (i32.const 2))
+                    (then (local.get 0))
+                    (else
+                        (i32.add
+                            (call $fib (i32.sub (local.get 0) (i32.const 1)))
+                            (call $fib (i32.sub (local.get 0) (i32.const 2)))))))
+
+            (func $sum (param i32) (result i32) (local $acc i32)
+                (block $done
+                    (loop $next
+                        (br_if $done (i32.eqz (local.get 0)))
+                        (local.set $acc (i32.add (local.get $acc) (local.get 0)))
+                        (local.set 0 (i32.sub (local.get 0) (i32.const 1)))
+                        (br $next)))
+                (local.get $acc))
+
+            (func (export "fib") (param i32) (result i32) (call $fib (local.get 0)))
+            (func (export "sum") (param i32) (result i32) (call $sum (local.get 0)))
+            (func (export "indirect") (param i32 i32) (result i32)
+                (call_indirect (type $unary) (local.get 1) (local.get 0)))
+
+            (func (export "classify") (param i32) (result i32)
+                (block $c (block $b (block $a
+                    (br_table $a $b $c (local.get 0)))
+                    (return (i32.const 10)))
+                    (return (i32.const 20)))
+                (i32.const 30))
+
+            (func (export "store") (param i32) (result i64) (local $i i32)
+                (loop $fill
+                    (i64.store8 offset=16 (local.get $i) (i64.extend_i32_u (local.get $i)))
+                    (local.set $i (i32.add (local.get $i) (i32.const 1)))
+                    (br_if $fill (i32.lt_u (local.get $i) (local.get 0))))
+                (i64.add (i64.load offset=16 (i32.const 0)) (i64.load16_s (i32.const 17))))
+
+            (func (export "select") (param i32 f64 f64) (result f64)
+                (f64.min
+                    (select (local.get 1) (local.get 2) (local.get 0))
+                    (f64.sqrt (local.get 2))))
+
+            (func (export "square") (param i64) (result i64)
+                (i64.add (call $square (local.get 0)) (i64.const 1)))
+
+            (func (export "div") (param i32 i32) (result i32)
+                (i32.div_s (local.get 0) (local.get 1))))
+    "#;
+
+    fn module(backend: Backend) -> Module {
+        let wasm = wat::parse_str(PROGRAM).expect("invalid wat");
+        let mut module = crate::parse_bytes(&wasm).expect("failed to parse module");
+        module.set_backend(backend).unwrap();
+        module
+    }
+
+    fn imports(suspend: bool) -> Imports {
+        let mut imports = Imports::new();
+        let square = Extern::typed_func(move |_, v: i64| match suspend {
+            true => Err(Error::SuspendHostCall),
+            false => Ok(v * v),
+        });
+        imports.define("env", "square", square).unwrap();
+        imports
+    }
+
+    fn instance(backend: Backend) -> Instance {
+        Instance::instantiate(module(backend), imports(false), (), None).unwrap().0
+    }
+
+    fn snapshot(exec_handle: &mut ExecHandle) -> Vec<u8> {
+        // keep the snapshot small, the memory is not used by these tests
+        exec_handle
+            .instance_mut()
+            .exported_memory_mut("memory")
+            .unwrap()
+            .set_ignored_byte_region(0, crate::PAGE_SIZE)
+            .unwrap();
+        let mut state = Vec::new();
+        exec_handle.serialize(&mut state, &[]).unwrap();
+        state
+    }
+
+    #[test]
+    fn test_backends_agree() {
+        let calls = [
+            ("fib", vec![WasmValue::I32(15)]),
+            ("sum", vec![WasmValue::I32(100)]),
+            ("indirect", vec![WasmValue::I32(10), WasmValue::I32(0)]),
+            ("indirect", vec![WasmValue::I32(10), WasmValue::I32(1)]),
+            ("indirect", vec![WasmValue::I32(10), WasmValue::I32(2)]),
+            ("classify", vec![WasmValue::I32(0)]),
+            ("classify", vec![WasmValue::I32(1)]),
+            ("classify", vec![WasmValue::I32(7)]),
+            ("store", vec![WasmValue::I32(12)]),
+            ("select", vec![WasmValue::I32(0), WasmValue::F64(1.5), WasmValue::F64(16.0)]),
+            ("select", vec![WasmValue::I32(1), WasmValue::F64(1.5), WasmValue::F64(16.0)]),
+            ("square", vec![WasmValue::I64(9)]),
+            ("div", vec![WasmValue::I32(-7), WasmValue::I32(2)]),
+            ("div", vec![WasmValue::I32(1), WasmValue::I32(0)]),
+            ("div", vec![WasmValue::I32(i32::MIN), WasmValue::I32(-1)]),
+        ];
+
+        let (mut stack, mut slots) = (instance(Backend::Stack), instance(Backend::Slots));
+        for (name, params) in calls {
+            let expected = stack.exported_func_untyped(name).unwrap().invoke(&mut stack, params.clone());
+            let actual = slots.exported_func_untyped(name).unwrap().invoke(&mut slots, params.clone());
+            assert_eq!(format!("{actual:?}"), format!("{expected:?}"), "{name}({params:?})");
+        }
+        assert_eq!(slots.exported_func::<i32, i32>("fib").unwrap().invoke(&mut slots, 15).unwrap(), 610);
+        assert_eq!(
+            slots.exported_memory("memory").unwrap().load(16, 12).unwrap(),
+            stack.exported_memory("memory").unwrap().load(16, 12).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_slots_snapshot_resume() {
+        let module = module(Backend::Slots);
+        let (instance, _, _) = Instance::instantiate(module.clone(), imports(false), (), None).unwrap();
+        let fib = instance.exported_func::<i32, i32>("fib").unwrap();
+        let mut exec_handle = fib.call(instance, 6, None).unwrap();
+
+        // serialize after every few instructions and resume from the snapshot
+        let res = loop {
+            if let CallResultTyped::Done(res) = exec_handle.run(5).unwrap() {
+                break res;
+            }
+
+            let state = snapshot(&mut exec_handle.exec_handle);
+            let (instance, stack, _) = Instance::instantiate(module.clone(), imports(false), (), Some(&state)).unwrap();
+            exec_handle = fib.call(instance, 6, stack).unwrap();
+        };
+        assert_eq!(res, 8);
+    }
+
+    #[test]
+    fn test_slots_pending_host_call() {
+        let (instance, _, _) = Instance::instantiate(module(Backend::Slots), imports(true), (), None).unwrap();
+        let square = instance.exported_func_untyped("square").unwrap();
+        let mut exec_handle = square.call(instance, vec![WasmValue::I64(6)], None).unwrap();
+        let CallResult::HostPending(pending) = exec_handle.run(100).unwrap() else { panic!("call not suspended") };
+        assert_eq!(pending.params(), [WasmValue::I64(6)]);
+
+        let state = snapshot(&mut exec_handle);
+
+        // snapshots can only be resumed with the backend they were taken with
+        assert!(Instance::instantiate(module(Backend::Stack), imports(true), (), Some(&state)).is_err());
+
+        let (instance, stack, _) =
+            Instance::instantiate(module(Backend::Slots), imports(true), (), Some(&state)).unwrap();
+        let mut exec_handle = square.call(instance, vec![WasmValue::I64(6)], stack).unwrap();
+        assert!(matches!(exec_handle.run(100).unwrap(), CallResult::HostPending(_)));
+        exec_handle.complete_host_call(&[WasmValue::I64(36)]).unwrap();
+        assert!(matches!(exec_handle.run(100).unwrap(), CallResult::Done(res) if res == [WasmValue::I64(37)]));
+    }
+}
@@ -1,3 +1,6 @@
+#[cfg(not(feature = "std"))]
+use super::no_std_floats::NoStdFloatExt;
+
 pub(crate) trait CheckedWrappingRem
 where
     Self: Sized,
@@ -5,6 +8,31 @@ where
     fn checked_wrapping_rem(self, rhs: Self) -> Option<Self>;
 }
 
+pub(crate) trait CanonicalizeNan {
+    /// Replace any NaN with the positive canonical NaN, whose bits are the same on every platform
+    fn canonicalize_nan(self) -> Self;
+}
+
+impl CanonicalizeNan for f32 {
+    #[inline]
+    fn canonicalize_nan(self) -> Self {
+        match self.is_nan() {
+            true => f32::from_bits(0x7fc0_0000),
+            false => self,
+        }
+    }
+}
+
+impl CanonicalizeNan for f64 {
+    #[inline]
+    fn canonicalize_nan(self) -> Self {
+        match self.is_nan() {
+            true => f64::from_bits(0x7ff8_0000_0000_0000),
+            false => self,
+        }
+    }
+}
+
 pub(crate) trait WasmFloatExt {
     fn tw_minimum(self, other: Self) -> Self;
     fn tw_maximum(self, other: Self) -> Self;
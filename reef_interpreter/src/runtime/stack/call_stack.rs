@@ -2,9 +2,9 @@ use alloc::{boxed::Box, vec::Vec};
 use core::hint::unreachable_unchecked;
 
 use crate::error::{Error, Result, Trap};
-use crate::imports::Function;
+use crate::imports::{Function, Threading};
 use crate::runtime::{BlockType, RawWasmValue};
-use crate::types::{instructions::Instruction, FuncAddr, LocalAddr, WasmFunction};
+use crate::types::{instructions::Instruction, slots::SlotInstruction, FuncAddr, LocalAddr, WasmFunction};
 use crate::{cold, unlikely, CALL_STACK_SIZE};
 
 #[derive(Debug, Clone, PartialEq, Eq, Default, serde::Serialize, serde::Deserialize)]
@@ -19,9 +19,14 @@ impl CallStack {
         Self(stack)
     }
 
+    #[inline(always)]
+    pub(crate) fn len(&self) -> usize {
+        self.0.len()
+    }
+
     #[inline]
-    pub(crate) fn is_empty(&self) -> bool {
-        self.0.is_empty()
+    pub(crate) fn truncate(&mut self, len: usize) {
+        self.0.truncate(len);
     }
 
     #[inline(always)]
@@ -56,7 +61,7 @@ pub(crate) struct CallFrame {
 
 impl CallFrame {
     #[inline(always)]
-    pub(crate) fn fetch_instr(&self, funcs: &[Function]) -> Instruction {
+    pub(crate) fn fetch_instr<T, S: Threading>(&self, funcs: &[Function<T, S>]) -> Instruction {
         // SAFETY: this is verified by the parser/validator
         let func = unsafe { funcs.get_unchecked(self.func_instance as usize) };
         let wasm_func = match func {
@@ -69,7 +74,7 @@ impl CallFrame {
             }
         };
         match wasm_func.instructions.get(self.instr_ptr) {
-            Some(instr) => instr.clone(),
+            Some(instr) => *instr,
             None => {
                 cold();
                 panic!("Instruction pointer out of bounds");
@@ -77,6 +82,33 @@ impl CallFrame {
         }
     }
 
+    #[inline(always)]
+    pub(crate) fn fetch_slot_instr<T, S: Threading>(&self, funcs: &[Function<T, S>]) -> SlotInstruction {
+        self.fetch_slot_instr_at(funcs, self.instr_ptr)
+    }
+
+    #[inline(always)]
+    pub(crate) fn fetch_slot_instr_at<T, S: Threading>(&self, funcs: &[Function<T, S>], pc: usize) -> SlotInstruction {
+        match self.wasm_func(funcs).slots.as_ref().and_then(|slots| slots.code.get(pc)) {
+            Some(instr) => *instr,
+            None => {
+                cold();
+                panic!("Instruction pointer out of bounds");
+            }
+        }
+    }
+
+    #[inline(always)]
+    pub(crate) fn wasm_func<'a, T, S: Threading>(&self, funcs: &'a [Function<T, S>]) -> &'a WasmFunction {
+        // SAFETY: this is verified by the parser/validator
+        let func = unsafe { funcs.get_unchecked(self.func_instance as usize) };
+        match func {
+            Function::Wasm(wasm_func) => wasm_func,
+            // SAFETY: frames are only created for Wasm functions
+            Function::Host(_) => unsafe { unreachable_unchecked() },
+        }
+    }
+
     /// Break to a block at the given index (relative to the current frame)
     /// Returns `None` if there is no block at the given index (e.g. if we need to return, this is handled by the caller)
     pub(crate) fn break_to(
@@ -129,7 +161,11 @@ impl CallFrame {
         block_ptr: u32,
     ) -> Self {
         let locals = {
-            let total_size = wasm_func.locals.len() + params.len();
+            // frames of the slot backend also hold the operands
+            let total_size = match &wasm_func.slots {
+                Some(slots) => slots.frame_size as usize,
+                None => wasm_func.locals.len() + params.len(),
+            };
             let mut locals = Vec::new();
             locals.reserve_exact(total_size);
             locals.extend(params);
@@ -151,7 +187,7 @@ impl CallFrame {
     }
 
     #[inline(always)]
-    pub(crate) fn instructions<'a>(&self, funcs: &'a [Function]) -> &'a [Instruction] {
+    pub(crate) fn instructions<'a, T, S: Threading>(&self, funcs: &'a [Function<T, S>]) -> &'a [Instruction] {
         // SAFETY: this is verified by the parser/validator
         let func = unsafe { funcs.get_unchecked(self.func_instance as usize) };
         &match func {
@@ -6,16 +6,35 @@ pub(crate) use block_stack::{BlockFrame, BlockStack, BlockType};
 pub(crate) use call_stack::{CallFrame, CallStack};
 pub(crate) use value_stack::ValueStack;
 
+use crate::exec::PendingHostCall;
+use crate::types::Backend;
+
 /// A WebAssembly Stack
 #[derive(Debug, Clone, PartialEq, Eq, Default, serde::Serialize, serde::Deserialize)]
 pub struct Stack {
     pub(crate) values: ValueStack,
     pub(crate) blocks: BlockStack,
     pub(crate) call_stack: CallStack,
+    pub(crate) pending_host_call: Option<PendingHostCall>,
+    /// The backend the frames were created for
+    pub(crate) backend: Backend,
+    /// Instructions executed before the current run, counted from when execution was started or resumed
+    #[serde(skip)]
+    pub(crate) cycles: u64,
+    /// Instructions executed before the current run, counted from when execution was started and kept in snapshots
+    pub(crate) total_cycles: u64,
 }
 
 impl Stack {
-    pub(crate) fn new(call_frame: CallFrame) -> Self {
-        Self { values: ValueStack::default(), blocks: BlockStack::new(), call_stack: CallStack::new(call_frame) }
+    pub(crate) fn new(call_frame: CallFrame, backend: Backend) -> Self {
+        Self {
+            values: ValueStack::default(),
+            blocks: BlockStack::new(),
+            call_stack: CallStack::new(call_frame),
+            pending_host_call: None,
+            backend,
+            cycles: 0,
+            total_cycles: 0,
+        }
     }
 }
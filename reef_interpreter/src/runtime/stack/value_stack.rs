@@ -22,6 +22,16 @@ impl ValueStack {
         self.0.extend(values.iter().map(|v| RawWasmValue::from(*v)));
     }
 
+    #[inline]
+    pub(crate) fn extend_from_slice(&mut self, values: &[RawWasmValue]) {
+        self.0.extend_from_slice(values);
+    }
+
+    #[inline(always)]
+    pub(crate) fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
     #[inline(always)]
     pub(crate) fn replace_top(&mut self, func: fn(RawWasmValue) -> RawWasmValue) -> Result<()> {
         let v = self.last_mut()?;
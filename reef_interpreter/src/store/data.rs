@@ -3,7 +3,7 @@ use alloc::vec::Vec;
 /// A WebAssembly Data Instance
 ///
 /// See <https://webassembly.github.io/spec/core/exec/runtime.html#data-instances>
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub(crate) struct DataInstance {
     pub(crate) data: Option<Vec<u8>>,
 }
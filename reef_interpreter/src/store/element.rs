@@ -6,7 +6,7 @@ use crate::types::ElementKind;
 /// A WebAssembly Element Instance
 ///
 /// See <https://webassembly.github.io/spec/core/exec/runtime.html#element-instances>
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub(crate) struct ElementInstance {
     pub(crate) kind: ElementKind,
     pub(crate) items: Option<Vec<TableElement>>, // none is the element was dropped
@@ -1,15 +1,17 @@
 use crate::runtime::RawWasmValue;
+use crate::types::GlobalType;
 
 /// A WebAssembly Global Instance
 ///
 /// See <https://webassembly.github.io/spec/core/exec/runtime.html#global-instances>
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub(crate) struct GlobalInstance {
     pub(crate) value: RawWasmValue,
+    pub(crate) ty: GlobalType,
 }
 
 impl GlobalInstance {
-    pub(crate) fn new(value: RawWasmValue) -> Self {
-        Self { value }
+    pub(crate) fn new(ty: GlobalType, value: RawWasmValue) -> Self {
+        Self { value, ty }
     }
 }
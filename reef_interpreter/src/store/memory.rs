@@ -1,4 +1,4 @@
-use alloc::{vec, vec::Vec};
+use alloc::{format, vec, vec::Vec};
 
 use crate::error::{Error, Result, Trap};
 use crate::types::MemoryType;
@@ -11,8 +11,8 @@ use crate::{MAX_PAGES, MAX_SIZE, PAGE_SIZE};
 pub(crate) struct MemoryInstance {
     pub(crate) kind: MemoryType,
     pub(crate) page_count: usize,
-    /// ignored during serialization
-    pub(crate) ignored_byte_region: (usize, usize),
+    /// Byte ranges `start..end` ignored during serialization, sorted and not overlapping
+    pub(crate) ignored_byte_regions: Vec<(usize, usize)>,
     pub(crate) data: Vec<u8>,
 }
 
@@ -24,10 +24,63 @@ impl MemoryInstance {
             kind,
             data: vec![0; PAGE_SIZE * kind.page_count_initial as usize],
             page_count: kind.page_count_initial as usize,
-            ignored_byte_region: (0, 0),
+            ignored_byte_regions: Vec::new(),
         }
     }
 
+    /// Rebuild a memory from the chunks of data between its ignored byte regions, see [`Self::serialized_chunks`]
+    fn from_chunks(
+        kind: MemoryType,
+        page_count: usize,
+        ignored_byte_regions: Vec<(usize, usize)>,
+        data_chunks: Vec<Vec<u8>>,
+    ) -> core::result::Result<Self, &'static str> {
+        let len = page_count.checked_mul(PAGE_SIZE).ok_or("memory too large")?;
+        if data_chunks.len() != ignored_byte_regions.len() + 1 {
+            return Err("number of data chunks does not match the ignored byte regions");
+        }
+
+        let mut data = vec![0; len];
+        let mut start = 0;
+        for (i, chunk) in data_chunks.iter().enumerate() {
+            let end = ignored_byte_regions.get(i).map_or(len, |region| region.0);
+            if end < start || end > len || chunk.len() != end - start {
+                return Err("data chunks do not match the ignored byte regions");
+            }
+
+            data[start..end].copy_from_slice(chunk);
+            start = ignored_byte_regions.get(i).map_or(len, |region| region.1);
+        }
+
+        Ok(Self { kind, page_count, ignored_byte_regions, data })
+    }
+
+    /// Data outside of the ignored byte regions, which is serialized
+    fn serialized_chunks(&self) -> impl Iterator<Item = &[u8]> {
+        let starts = core::iter::once(0).chain(self.ignored_byte_regions.iter().map(|region| region.1));
+        let ends = self.ignored_byte_regions.iter().map(|region| region.0).chain(core::iter::once(self.data.len()));
+        starts.zip(ends).map(|(start, end)| &self.data[start..end])
+    }
+
+    /// Ignore `addr..addr + len` during serialization, in addition to the regions ignored already
+    pub(crate) fn add_ignored_byte_region(&mut self, addr: usize, len: usize) -> Result<()> {
+        let end = addr.checked_add(len).ok_or_else(|| self.trap_oob(addr, len))?;
+        if end > self.data.len() {
+            return Err(self.trap_oob(addr, len));
+        }
+        if len == 0 {
+            return Ok(());
+        }
+
+        let index = self.ignored_byte_regions.partition_point(|region| region.1 <= addr);
+        if self.ignored_byte_regions.get(index).is_some_and(|region| region.0 < end) {
+            return Err(Error::Other(format!("ignored byte region {addr:#x}..{end:#x} overlaps another one")));
+        }
+
+        self.ignored_byte_regions.insert(index, (addr, end));
+        Ok(())
+    }
+
     #[inline(never)]
     #[cold]
     fn trap_oob(&self, addr: usize, len: usize) -> Error {
@@ -180,12 +233,22 @@ impl serde::Serialize for MemoryInstance {
     {
         use serde::ser::SerializeStruct;
 
+        struct Chunks<'a>(&'a MemoryInstance);
+
+        impl serde::Serialize for Chunks<'_> {
+            fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+            where
+                S: serde::Serializer,
+            {
+                serializer.collect_seq(self.0.serialized_chunks())
+            }
+        }
+
         let mut state = serializer.serialize_struct("MemoryInstance", 4)?;
         state.serialize_field("kind", &self.kind)?;
         state.serialize_field("page_count", &self.page_count)?;
-        state.serialize_field("ignored_byte_region", &self.ignored_byte_region)?;
-        state.serialize_field("data_before_ignore", &self.data[..self.ignored_byte_region.0])?;
-        state.serialize_field("data_after_ignore", &self.data[self.ignored_byte_region.1..])?;
+        state.serialize_field("ignored_byte_regions", &self.ignored_byte_regions)?;
+        state.serialize_field("data_chunks", &Chunks(self))?;
 
         state.end()
     }
@@ -196,15 +259,14 @@ impl<'de> serde::Deserialize<'de> for MemoryInstance {
     where
         D: serde::Deserializer<'de>,
     {
+        use core::fmt;
         use serde::de::{self, Deserialize, Deserializer, MapAccess, SeqAccess, Visitor};
-        use std::fmt;
 
         enum Field {
             Kind,
             PageCount,
-            IgnoredPageRegion,
-            DataBeforeIgnore,
-            DataAfterIgnore,
+            IgnoredByteRegions,
+            DataChunks,
         }
 
         impl<'de> Deserialize<'de> for Field {
@@ -218,9 +280,7 @@ impl<'de> serde::Deserialize<'de> for MemoryInstance {
                     type Value = Field;
 
                     fn expecting(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
-                        formatter.write_str(
-                            "`kind`, `page_count`, `ignored_byte_region`, `data_before_ignore` or `data_after_ignore`",
-                        )
+                        formatter.write_str("`kind`, `page_count`, `ignored_byte_regions` or `data_chunks`")
                     }
 
                     fn visit_str<E>(self, value: &str) -> Result<Field, E>
@@ -230,9 +290,8 @@ impl<'de> serde::Deserialize<'de> for MemoryInstance {
                         match value {
                             "kind" => Ok(Field::Kind),
                             "page_count" => Ok(Field::PageCount),
-                            "ignored_byte_region" => Ok(Field::IgnoredPageRegion),
-                            "data_before_ignore" => Ok(Field::DataBeforeIgnore),
-                            "data_after_ignore" => Ok(Field::DataAfterIgnore),
+                            "ignored_byte_regions" => Ok(Field::IgnoredByteRegions),
+                            "data_chunks" => Ok(Field::DataChunks),
                             _ => Err(de::Error::unknown_field(value, FIELDS)),
                         }
                     }
@@ -257,20 +316,13 @@ impl<'de> serde::Deserialize<'de> for MemoryInstance {
             {
                 let kind = seq.next_element()?.ok_or_else(|| de::Error::invalid_length(0, &self))?;
                 let page_count = seq.next_element()?.ok_or_else(|| de::Error::invalid_length(1, &self))?;
-                let ignored_byte_region: (usize, usize) =
-                    seq.next_element()?.ok_or_else(|| de::Error::invalid_length(2, &self))?;
-
+                let ignored_byte_regions = seq.next_element()?.ok_or_else(|| de::Error::invalid_length(2, &self))?;
                 // TODO: avoid allocation
-                let data_before_ignore: Vec<u8> =
+                let data_chunks: Vec<Vec<u8>> =
                     seq.next_element()?.ok_or_else(|| de::Error::invalid_length(3, &self))?;
-                let data_after_ignore: Vec<u8> =
-                    seq.next_element()?.ok_or_else(|| de::Error::invalid_length(4, &self))?;
 
-                let mut data = vec![0; page_count * PAGE_SIZE];
-                data[..ignored_byte_region.0].copy_from_slice(&data_before_ignore);
-                data[ignored_byte_region.1..].copy_from_slice(&data_after_ignore);
-
-                Ok(MemoryInstance { kind, page_count, ignored_byte_region, data })
+                MemoryInstance::from_chunks(kind, page_count, ignored_byte_regions, data_chunks)
+                    .map_err(de::Error::custom)
             }
 
             fn visit_map<V>(self, mut map: V) -> Result<Self::Value, V::Error>
@@ -279,8 +331,8 @@ impl<'de> serde::Deserialize<'de> for MemoryInstance {
             {
                 let mut kind = None;
                 let mut page_count = None;
-                let mut ignored_byte_region: Option<(usize, usize)> = None;
-                let mut data = None;
+                let mut ignored_byte_regions = None;
+                let mut data_chunks: Option<Vec<Vec<u8>>> = None;
                 while let Some(key) = map.next_key()? {
                     match key {
                         Field::Kind => {
@@ -294,48 +346,34 @@ impl<'de> serde::Deserialize<'de> for MemoryInstance {
                                 return Err(de::Error::duplicate_field("page_count"));
                             }
                             page_count = Some(map.next_value()?);
-                            data = Some(vec![0u8; page_count.unwrap() * PAGE_SIZE]);
                         }
-                        Field::IgnoredPageRegion => {
-                            if ignored_byte_region.is_some() {
-                                return Err(de::Error::duplicate_field("ignored_byte_region"));
+                        Field::IgnoredByteRegions => {
+                            if ignored_byte_regions.is_some() {
+                                return Err(de::Error::duplicate_field("ignored_byte_regions"));
                             }
-                            ignored_byte_region = Some(map.next_value()?);
-                        }
-                        Field::DataBeforeIgnore => {
-                            let Some(data) = &mut data else {
-                                return Err(de::Error::missing_field("page_count"));
-                            };
-                            let Some(ignored_byte_region) = ignored_byte_region else {
-                                return Err(de::Error::missing_field("ignored_byte_region"));
-                            };
-                            // TODO: avoid allocation
-                            data[..ignored_byte_region.0].copy_from_slice(&map.next_value::<Vec<u8>>()?);
+                            ignored_byte_regions = Some(map.next_value()?);
                         }
-                        Field::DataAfterIgnore => {
-                            let Some(data) = &mut data else {
-                                return Err(de::Error::missing_field("page_count"));
-                            };
-                            let Some(ignored_byte_region) = ignored_byte_region else {
-                                return Err(de::Error::missing_field("ignored_byte_region"));
-                            };
+                        Field::DataChunks => {
+                            if data_chunks.is_some() {
+                                return Err(de::Error::duplicate_field("data_chunks"));
+                            }
                             // TODO: avoid allocation
-                            data[ignored_byte_region.1..].copy_from_slice(&map.next_value::<Vec<u8>>()?);
+                            data_chunks = Some(map.next_value()?);
                         }
                     }
                 }
                 let kind = kind.ok_or_else(|| de::Error::missing_field("kind"))?;
                 let page_count = page_count.ok_or_else(|| de::Error::missing_field("page_count"))?;
-                let ignored_byte_region: (usize, usize) =
-                    ignored_byte_region.ok_or_else(|| de::Error::missing_field("ignored_byte_region"))?;
-                let data = data.ok_or_else(|| de::Error::missing_field("page_count"))?;
+                let ignored_byte_regions =
+                    ignored_byte_regions.ok_or_else(|| de::Error::missing_field("ignored_byte_regions"))?;
+                let data_chunks = data_chunks.ok_or_else(|| de::Error::missing_field("data_chunks"))?;
 
-                Ok(MemoryInstance { kind, page_count, ignored_byte_region, data })
+                MemoryInstance::from_chunks(kind, page_count, ignored_byte_regions, data_chunks)
+                    .map_err(de::Error::custom)
             }
         }
 
-        const FIELDS: &[&str] =
-            &["kind", "page_count", "ignored_byte_region", "data_before_ignore", "data_after_ignore"];
+        const FIELDS: &[&str] = &["kind", "page_count", "ignored_byte_regions", "data_chunks"];
         deserializer.deserialize_struct("MemoryInstance", FIELDS, MemoryInstanceVisitor)
     }
 }
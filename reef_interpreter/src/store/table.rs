@@ -12,7 +12,7 @@ const MAX_TABLE_SIZE: u32 = 10000000;
 /// A WebAssembly Table Instance
 ///
 /// See <https://webassembly.github.io/spec/core/exec/runtime.html#table-instances>
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub(crate) struct TableInstance {
     pub(crate) elements: Vec<TableElement>,
     pub(crate) kind: TableType,
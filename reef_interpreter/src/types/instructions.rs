@@ -71,6 +71,59 @@ pub enum ConstInstruction {
     RefFunc(FuncAddr),
 }
 
+/// An i32 comparison fused into a [`Instruction::I32CmpBrIf`]
+#[derive(Debug, Copy, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub enum I32Cmp {
+    Eqz,
+    Eq,
+    Ne,
+    LtS,
+    LtU,
+    GtS,
+    GtU,
+    LeS,
+    LeU,
+    GeS,
+    GeU,
+}
+
+impl I32Cmp {
+    /// The comparison performed by `instr`, if it is an i32 comparison
+    pub fn from_instruction(instr: Instruction) -> Option<Self> {
+        Some(match instr {
+            Instruction::I32Eqz => Self::Eqz,
+            Instruction::I32Eq => Self::Eq,
+            Instruction::I32Ne => Self::Ne,
+            Instruction::I32LtS => Self::LtS,
+            Instruction::I32LtU => Self::LtU,
+            Instruction::I32GtS => Self::GtS,
+            Instruction::I32GtU => Self::GtU,
+            Instruction::I32LeS => Self::LeS,
+            Instruction::I32LeU => Self::LeU,
+            Instruction::I32GeS => Self::GeS,
+            Instruction::I32GeU => Self::GeU,
+            _ => return None,
+        })
+    }
+
+    /// The unfused comparison instruction
+    pub fn instruction(self) -> Instruction {
+        match self {
+            Self::Eqz => Instruction::I32Eqz,
+            Self::Eq => Instruction::I32Eq,
+            Self::Ne => Instruction::I32Ne,
+            Self::LtS => Instruction::I32LtS,
+            Self::LtU => Instruction::I32LtU,
+            Self::GtS => Instruction::I32GtS,
+            Self::GtU => Instruction::I32GtU,
+            Self::LeS => Instruction::I32LeS,
+            Self::LeU => Instruction::I32LeU,
+            Self::GeS => Instruction::I32GeS,
+            Self::GeU => Instruction::I32GeU,
+        }
+    }
+}
+
 /// A WebAssembly Instruction
 ///
 /// These are our own internal bytecode instructions so they may not match the spec exactly.
@@ -82,7 +135,7 @@ pub enum ConstInstruction {
 ///   This makes it easier to implement the label stack iteratively.
 ///
 /// See <https://webassembly.github.io/spec/core/binary/instructions.html>
-#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+#[derive(Debug, Clone, Copy, PartialEq, serde::Serialize, serde::Deserialize)]
 
 // should be kept as small as possible (16 bytes max)
 #[rustfmt::skip]
@@ -106,6 +159,16 @@ pub enum Instruction {
     LocalGet3(LocalAddr, LocalAddr, LocalAddr),
     LocalGetSet(LocalAddr, LocalAddr),
 
+    // > Superinstructions
+    // Fused by `parser::optimize` in place of the first instruction of a sequence,
+    // the rest of the sequence is kept behind it and skipped, so every instruction pointer stays valid.
+    // LocalGet2 + I32Add
+    I32AddLocals(LocalAddr, LocalAddr),
+    // I32LocalGetConstAdd + LocalSet
+    I32LocalGetConstAddSet(LocalAddr, i32, LocalAddr),
+    // I32 comparison + BrIf
+    I32CmpBrIf(I32Cmp, LabelAddr),
+
     // > Control Instructions
     // See <https://webassembly.github.io/spec/core/binary/instructions.html#control-instructions>
     Unreachable,
@@ -199,7 +262,7 @@ pub enum Instruction {
     I64TruncSatF32S, I64TruncSatF32U, I64TruncSatF64S, I64TruncSatF64U,
 
     // > Table Instructions
-    TableInit(TableAddr, ElemAddr),
+    TableInit(ElemAddr, TableAddr),
     TableGet(TableAddr),
     TableSet(TableAddr),
     TableCopy { from: TableAddr, to: TableAddr },
@@ -208,12 +271,14 @@ pub enum Instruction {
     TableFill(TableAddr),
 
     // > Bulk Memory Instructions
-    MemoryInit(MemAddr, DataAddr),
+    MemoryInit(DataAddr, MemAddr),
     MemoryCopy(MemAddr, MemAddr),
     MemoryFill(MemAddr),
     DataDrop(DataAddr),
 }
 
+const _: () = assert!(core::mem::size_of::<Instruction>() <= 16, "Instruction should be at most 16 bytes");
+
 #[cfg(test)]
 mod test_blockargs_packed {
     use super::*;
@@ -5,9 +5,11 @@ use alloc::boxed::Box;
 use core::{fmt::Debug, ops::Range};
 
 pub mod instructions;
+pub mod slots;
 pub mod value;
 
 use instructions::{ConstInstruction, Instruction};
+use slots::SlotFunction;
 use value::ValType;
 
 /// A WebAssembly Module
@@ -66,6 +68,61 @@ pub struct Module {
     ///
     /// Corresponds to the `elem` section of the original WebAssembly module.
     pub elements: Box<[Element]>,
+
+    /// The backend the functions are executed with, see [`Module::set_backend`].
+    pub backend: Backend,
+
+    /// Whether functions are translated with superinstructions, see [`Module::set_superinstructions`].
+    pub superinstructions: bool,
+
+    /// Custom sections of the WebAssembly module, in the order they appeared.
+    ///
+    /// See [`Module::custom_section`].
+    pub custom_sections: Box<[CustomSection]>,
+
+    /// WebAssembly features beyond the MVP which the module uses.
+    pub features: Features,
+}
+
+/// A custom section of a WebAssembly module, like `name` or `producers`
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct CustomSection {
+    pub name: Box<str>,
+    pub data: Box<[u8]>,
+}
+
+/// WebAssembly features used by a [`Module`]
+///
+/// Only the features this crate supports are reported, modules using others fail to parse.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct Features {
+    /// Floating point types or instructions, whose NaN results may differ between platforms.
+    pub floats: bool,
+    /// Imports or exports of mutable globals.
+    pub mutable_global: bool,
+    /// Sign extension instructions like `i32.extend8_s`.
+    pub sign_extension: bool,
+    /// Non-trapping float to integer conversions like `i32.trunc_sat_f32_s`.
+    pub saturating_float_to_int: bool,
+    /// Functions or blocks with multiple results, or blocks with parameters.
+    pub multi_value: bool,
+    /// Passive segments and bulk memory and table instructions like `memory.copy`.
+    pub bulk_memory: bool,
+    /// Reference types, table instructions and multiple tables.
+    pub reference_types: bool,
+}
+
+/// How the functions of a [`Module`] are executed
+///
+/// Snapshots can only be resumed with the backend they were taken with.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub enum Backend {
+    /// Execute the instructions on an operand stack, the default.
+    #[default]
+    Stack,
+    /// Execute functions lowered to a register-machine style code, where locals and operands are
+    /// resolved to fixed slots of the frame at parse time.
+    Slots,
 }
 
 /// A WebAssembly External Kind.
@@ -84,6 +141,42 @@ pub enum ExternalKind {
     Global,
 }
 
+/// The type of an import or export, see [`Module::imports`] and [`Module::exports`]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ExternType<'a> {
+    Func(&'a FuncType),
+    Table(&'a TableType),
+    Memory(&'a MemoryType),
+    Global(&'a GlobalType),
+}
+
+impl ExternType<'_> {
+    /// Get the kind of the extern
+    pub fn kind(&self) -> ExternalKind {
+        match self {
+            Self::Func(_) => ExternalKind::Func,
+            Self::Table(_) => ExternalKind::Table,
+            Self::Memory(_) => ExternalKind::Memory,
+            Self::Global(_) => ExternalKind::Global,
+        }
+    }
+}
+
+/// An import of a [`Module`] with its type
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ImportType<'a> {
+    pub module: &'a str,
+    pub name: &'a str,
+    pub ty: ExternType<'a>,
+}
+
+/// An export of a [`Module`] with its type
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ExportType<'a> {
+    pub name: &'a str,
+    pub ty: ExternType<'a>,
+}
+
 /// A WebAssembly Address.
 ///
 /// These are indexes into the respective stores.
@@ -141,7 +234,7 @@ impl ExternVal {
 /// The type of a WebAssembly Function.
 ///
 /// See <https://webassembly.github.io/spec/core/syntax/types.html#function-types>
-#[derive(Debug, Clone, PartialEq, Default, serde::Serialize, serde::Deserialize)]
+#[derive(Debug, Clone, PartialEq, Eq, Default, serde::Serialize, serde::Deserialize)]
 
 pub struct FuncType {
     pub params: Box<[ValType]>,
@@ -154,6 +247,20 @@ pub struct WasmFunction {
     pub instructions: Box<[Instruction]>,
     pub locals: Box<[ValType]>,
     pub ty: FuncType,
+    /// The lowered code, only present if the module uses [`Backend::Slots`]
+    pub slots: Option<SlotFunction>,
+    /// The validated body, only present until a lazily parsed function is translated on its first call
+    ///
+    /// `instructions` are empty until then, see [`parse_bytes_lazy`](crate::parse_bytes_lazy).
+    pub lazy_body: Option<LazyBody>,
+}
+
+/// The body of a function in the binary format, kept to translate the function on its first call
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct LazyBody {
+    /// Offset of the body in the Wasm binary, used for error messages
+    pub offset: usize,
+    pub bytes: Box<[u8]>,
 }
 
 /// A WebAssembly Module Export
@@ -0,0 +1,214 @@
+//! Instructions of the slot backend, see [`Backend::Slots`](super::Backend::Slots).
+
+use alloc::boxed::Box;
+
+use crate::types::{instructions::Instruction, DataAddr, ElemAddr, FuncAddr, GlobalAddr, MemAddr, TableAddr, TypeAddr};
+
+/// Index of a value in the frame of a function lowered for the slot backend
+pub type Slot = u16;
+
+/// Code of a function lowered for the slot backend
+///
+/// Locals and operands are resolved to fixed slots of the frame at parse time:
+/// the parameters and locals come first, followed by one slot for every height of the operand stack.
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct SlotFunction {
+    pub code: Box<[SlotInstruction]>,
+    /// Number of slots of a frame of the function
+    pub frame_size: u32,
+}
+
+macro_rules! slot_instructions {
+    (
+        $(#[$meta:meta])*
+        pub enum SlotInstruction { $($variants:tt)* }
+        unary { $($unary:ident),* }
+        binary { $($binary:ident),* }
+    ) => {
+        $(#[$meta])*
+        pub enum SlotInstruction {
+            $($variants)*
+
+            // > Numeric Instructions
+            // Operands are `(dst, src)` and `(dst, a, b)`, named like the instruction they were lowered from.
+            $($unary(Slot, Slot),)*
+            $($binary(Slot, Slot, Slot),)*
+        }
+
+        impl SlotInstruction {
+            /// Constructor of the unary numeric instruction `instr` is lowered to
+            pub(crate) fn unary(instr: Instruction) -> Option<fn(Slot, Slot) -> Self> {
+                match instr {
+                    $(Instruction::$unary => Some(Self::$unary),)*
+                    _ => None,
+                }
+            }
+
+            /// Constructor of the binary numeric instruction `instr` is lowered to
+            pub(crate) fn binary(instr: Instruction) -> Option<fn(Slot, Slot, Slot) -> Self> {
+                match instr {
+                    $(Instruction::$binary => Some(Self::$binary),)*
+                    _ => None,
+                }
+            }
+
+            fn numeric_dst_mut(&mut self) -> Option<&mut Slot> {
+                match self {
+                    $(Self::$unary(dst, _) => Some(dst),)*
+                    $(Self::$binary(dst, ..) => Some(dst),)*
+                    _ => None,
+                }
+            }
+        }
+    };
+}
+
+slot_instructions! {
+    /// An instruction of the slot backend
+    ///
+    /// Unlike [`Instruction`], operands and results are read from and written to slots of the frame,
+    /// so there is no operand stack and no block stack at runtime.
+    /// Blocks are lowered to jumps to absolute instruction offsets.
+    #[derive(Debug, Clone, Copy, PartialEq, serde::Serialize, serde::Deserialize)]
+    // should be kept as small as possible (16 bytes max)
+    #[non_exhaustive]
+    pub enum SlotInstruction {
+        // > Control Instructions
+        Unreachable,
+        // An instruction the interpreter does not implement, the operand is its offset in `WasmFunction::instructions`
+        Unsupported(u32),
+        Jump(u32),
+        JumpIf(Slot, u32),
+        JumpIfNot(Slot, u32),
+        // (index, len), has to be followed by len + 1 `Jump` instructions, the last one is the default
+        BrTable(Slot, u32),
+        // Returns the results starting at the slot
+        Return(Slot),
+        // Parameters start at the slot, the results are written back to it
+        Call(FuncAddr, Slot),
+        // (type, table, index, parameters)
+        CallIndirect(TypeAddr, TableAddr, Slot, Slot),
+
+        // > Parametric and Variable Instructions
+        Copy(Slot, Slot),
+        // (dst, a, b, condition)
+        Select(Slot, Slot, Slot, Slot),
+        GlobalGet(Slot, GlobalAddr),
+        GlobalSet(GlobalAddr, Slot),
+
+        // > Constants
+        I32Const(Slot, i32),
+        I64Const(Slot, i64),
+        F32Const(Slot, f32),
+        F64Const(Slot, f64),
+
+        // > Memory Instructions
+        I32Load { dst: Slot, addr: Slot, offset: u32, mem: u8 },
+        I64Load { dst: Slot, addr: Slot, offset: u32, mem: u8 },
+        F32Load { dst: Slot, addr: Slot, offset: u32, mem: u8 },
+        F64Load { dst: Slot, addr: Slot, offset: u32, mem: u8 },
+        I32Load8S { dst: Slot, addr: Slot, offset: u32, mem: u8 },
+        I32Load8U { dst: Slot, addr: Slot, offset: u32, mem: u8 },
+        I32Load16S { dst: Slot, addr: Slot, offset: u32, mem: u8 },
+        I32Load16U { dst: Slot, addr: Slot, offset: u32, mem: u8 },
+        I64Load8S { dst: Slot, addr: Slot, offset: u32, mem: u8 },
+        I64Load8U { dst: Slot, addr: Slot, offset: u32, mem: u8 },
+        I64Load16S { dst: Slot, addr: Slot, offset: u32, mem: u8 },
+        I64Load16U { dst: Slot, addr: Slot, offset: u32, mem: u8 },
+        I64Load32S { dst: Slot, addr: Slot, offset: u32, mem: u8 },
+        I64Load32U { dst: Slot, addr: Slot, offset: u32, mem: u8 },
+        I32Store { addr: Slot, src: Slot, offset: u32, mem: u8 },
+        I64Store { addr: Slot, src: Slot, offset: u32, mem: u8 },
+        F32Store { addr: Slot, src: Slot, offset: u32, mem: u8 },
+        F64Store { addr: Slot, src: Slot, offset: u32, mem: u8 },
+        I32Store8 { addr: Slot, src: Slot, offset: u32, mem: u8 },
+        I32Store16 { addr: Slot, src: Slot, offset: u32, mem: u8 },
+        I64Store8 { addr: Slot, src: Slot, offset: u32, mem: u8 },
+        I64Store16 { addr: Slot, src: Slot, offset: u32, mem: u8 },
+        I64Store32 { addr: Slot, src: Slot, offset: u32, mem: u8 },
+        MemorySize(Slot, MemAddr),
+        // (dst, delta, memory)
+        MemoryGrow(Slot, Slot, MemAddr),
+
+        // > Bulk Memory and Table Instructions
+        // Operands are read from consecutive slots starting at the last one
+        MemoryCopy(MemAddr, MemAddr, Slot),
+        MemoryFill(MemAddr, Slot),
+        MemoryInit(MemAddr, DataAddr, Slot),
+        DataDrop(DataAddr),
+        // (table, dst, index)
+        TableGet(TableAddr, Slot, Slot),
+        TableSet(TableAddr, Slot),
+        TableSize(TableAddr, Slot),
+        TableInit(TableAddr, ElemAddr, Slot),
+    }
+
+    unary {
+        I32Eqz, I64Eqz,
+        I32Clz, I32Ctz, I32Popcnt, I64Clz, I64Ctz, I64Popcnt,
+        F32Abs, F32Neg, F32Ceil, F32Floor, F32Trunc, F32Nearest, F32Sqrt,
+        F64Abs, F64Neg, F64Ceil, F64Floor, F64Trunc, F64Nearest, F64Sqrt,
+        I32WrapI64, I32TruncF32S, I32TruncF32U, I32TruncF64S, I32TruncF64U, I32Extend8S, I32Extend16S,
+        I64Extend8S, I64Extend16S, I64Extend32S, I64ExtendI32S, I64ExtendI32U,
+        I64TruncF32S, I64TruncF32U, I64TruncF64S, I64TruncF64U,
+        F32ConvertI32S, F32ConvertI32U, F32ConvertI64S, F32ConvertI64U, F32DemoteF64,
+        F64ConvertI32S, F64ConvertI32U, F64ConvertI64S, F64ConvertI64U, F64PromoteF32,
+        I32TruncSatF32S, I32TruncSatF32U, I32TruncSatF64S, I32TruncSatF64U,
+        I64TruncSatF32S, I64TruncSatF32U, I64TruncSatF64S, I64TruncSatF64U
+    }
+
+    binary {
+        I32Eq, I32Ne, I32LtS, I32LtU, I32GtS, I32GtU, I32LeS, I32LeU, I32GeS, I32GeU,
+        I64Eq, I64Ne, I64LtS, I64LtU, I64GtS, I64GtU, I64LeS, I64LeU, I64GeS, I64GeU,
+        F32Eq, F32Ne, F32Lt, F32Gt, F32Le, F32Ge,
+        F64Eq, F64Ne, F64Lt, F64Gt, F64Le, F64Ge,
+        I32Add, I32Sub, I32Mul, I32DivS, I32DivU, I32RemS, I32RemU,
+        I64Add, I64Sub, I64Mul, I64DivS, I64DivU, I64RemS, I64RemU,
+        I32And, I32Or, I32Xor, I32Shl, I32ShrS, I32ShrU, I32Rotl, I32Rotr,
+        I64And, I64Or, I64Xor, I64Shl, I64ShrS, I64ShrU, I64Rotl, I64Rotr,
+        F32Add, F32Sub, F32Mul, F32Div, F32Min, F32Max, F32Copysign,
+        F64Add, F64Sub, F64Mul, F64Div, F64Min, F64Max, F64Copysign
+    }
+}
+
+const _: () = assert!(core::mem::size_of::<SlotInstruction>() <= 16, "SlotInstruction should be at most 16 bytes");
+
+impl SlotInstruction {
+    /// The slot the instruction writes its only result to, if it has exactly one
+    pub(crate) fn dst_mut(&mut self) -> Option<&mut Slot> {
+        use SlotInstruction::*;
+
+        if self.numeric_dst_mut().is_some() {
+            return self.numeric_dst_mut();
+        }
+
+        match self {
+            Copy(dst, _)
+            | Select(dst, ..)
+            | GlobalGet(dst, _)
+            | I32Const(dst, _)
+            | I64Const(dst, _)
+            | F32Const(dst, _)
+            | F64Const(dst, _)
+            | MemorySize(dst, _)
+            | MemoryGrow(dst, ..)
+            | TableGet(_, dst, _)
+            | TableSize(_, dst) => Some(dst),
+            I32Load { dst, .. }
+            | I64Load { dst, .. }
+            | F32Load { dst, .. }
+            | F64Load { dst, .. }
+            | I32Load8S { dst, .. }
+            | I32Load8U { dst, .. }
+            | I32Load16S { dst, .. }
+            | I32Load16U { dst, .. }
+            | I64Load8S { dst, .. }
+            | I64Load8U { dst, .. }
+            | I64Load16S { dst, .. }
+            | I64Load16U { dst, .. }
+            | I64Load32S { dst, .. }
+            | I64Load32U { dst, .. } => Some(dst),
+            _ => None,
+        }
+    }
+}
@@ -14,7 +14,9 @@ const jobIDUrlParam = "job_id"
 type JobSubmission struct {
 	Name string `json:"name"`
 	// Attaching a dataset to a job submission is non-optional.
-	DatasetID  string                       `json:"datasetId"`
+	DatasetID string `json:"datasetId"`
+	// Optional named datasets in the order the program sees them, if set they replace `DatasetID`.
+	Datasets   []database.JobDataset        `json:"datasets"`
 	SourceCode string                       `json:"sourceCode"`
 	Language   logic.JobProgrammingLanguage `json:"language"`
 }
@@ -80,14 +82,14 @@ func SubmitJob(ctx *gin.Context) {
 	// Validate additional constraints, like validity of the dataset and language.
 	//
 
-	found, err := logic.DatasetManager.DoesDatasetExist(submission.DatasetID)
+	problem, err := validateDatasets(submission)
 	if err != nil {
 		serverErr(ctx, err.Error())
 		return
 	}
 
-	if !found {
-		badRequest(ctx, fmt.Sprintf("dataset with id `%s` not found", submission.DatasetID))
+	if problem != "" {
+		badRequest(ctx, problem)
 		return
 	}
 
@@ -105,6 +107,7 @@ func SubmitJob(ctx *gin.Context) {
 		submission.SourceCode,
 		submission.Name,
 		submission.DatasetID,
+		submission.Datasets,
 		session.ID,
 	)
 
@@ -127,6 +130,35 @@ func SubmitJob(ctx *gin.Context) {
 	)
 }
 
+// Checks that every dataset of a submission exists and that the named datasets have unique names.
+// Returns what is wrong with the datasets, empty if nothing is.
+func validateDatasets(submission JobSubmission) (problem string, err error) {
+	ids := []string{submission.DatasetID}
+	names := make(map[string]bool, len(submission.Datasets))
+
+	for _, dataset := range submission.Datasets {
+		if names[dataset.Name] {
+			return fmt.Sprintf("duplicate dataset name `%s`", dataset.Name), nil
+		}
+
+		names[dataset.Name] = true
+		ids = append(ids, dataset.DatasetId)
+	}
+
+	for _, id := range ids {
+		found, err := logic.DatasetManager.DoesDatasetExist(id)
+		if err != nil {
+			return "", err
+		}
+
+		if !found {
+			return fmt.Sprintf("dataset with id `%s` not found", id), nil
+		}
+	}
+
+	return "", nil
+}
+
 //
 // Job cancellation and abortion.
 //
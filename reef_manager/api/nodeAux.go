@@ -94,3 +94,16 @@ func setLastPing(nodeID logic.NodeId) {
 		)
 	}
 }
+
+//
+// Claims.
+//
+
+func decodeWallClock(list capnp.UInt64List) []uint64 {
+	readings := make([]uint64, list.Len())
+	for idx := range readings {
+		readings[idx] = list.At(idx)
+	}
+
+	return readings
+}
@@ -156,7 +156,9 @@ func performHandshake(conn *logic.WSConn, endpointIP string) (logic.Node, error)
 	switch kind {
 	case node.MessageFromNodeKind_handshakeResponse:
 		log.Tracef("Received handshakeResponse")
-	case node.MessageFromNodeKind_jobStateSync, node.MessageFromNodeKind_jobResult:
+	case node.MessageFromNodeKind_jobStateSync,
+		node.MessageFromNodeKind_jobResult,
+		node.MessageFromNodeKind_verifyResult:
 		fallthrough
 	default:
 		return logic.Node{}, fmt.Errorf("received illegal/unexpected message kind from node during handshake: %d", kind)
@@ -180,14 +182,23 @@ func performHandshake(conn *logic.WSConn, endpointIP string) (logic.Node, error)
 		return logic.Node{}, fmt.Errorf("could not read node name: %s", err.Error())
 	}
 
+	// Nodes which predate the versioning of the reef ABI do not send a range and only run version 1.
+	abiVersionMin, abiVersionMax := handshakeResponse.AbiVersionMin(), handshakeResponse.AbiVersionMax()
+	if abiVersionMax == 0 {
+		abiVersionMin, abiVersionMax = 1, 1
+	}
+
 	//
 	// Adding the node.
 	//
 
 	newNode := logic.JobManager.ConnectNode(logic.NodeInfo{
-		EndpointIP: endpointIP,
-		Name:       nodeName,
-		NumWorkers: numWorkers,
+		EndpointIP:    endpointIP,
+		Name:          nodeName,
+		NumWorkers:    numWorkers,
+		CanVerify:     handshakeResponse.CanVerify(),
+		AbiVersionMin: abiVersionMin,
+		AbiVersionMax: abiVersionMax,
 	}, conn)
 
 	assignIDMsg, err := createAssignIDMsg(newNode.Id)
@@ -71,6 +71,8 @@ func handleGenericIncoming(nodeData logic.Node, message []byte) error {
 		return processStateSyncFromNode(nodeData.Id, decodedEnclosingMsg)
 	case node.MessageFromNodeKind_jobResult:
 		return processJobResultFromNode(nodeData.Id, decodedEnclosingMsg)
+	case node.MessageFromNodeKind_verifyResult:
+		return processVerifyResultFromNode(nodeData.Id, decodedEnclosingMsg)
 	default:
 		return fmt.Errorf("received illegal message kind from node: %d", kind)
 	}
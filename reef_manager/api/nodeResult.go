@@ -70,11 +70,24 @@ func parseJobResultFromNode(nodeID logic.NodeId, message node.MessageFromNode) (
 		return logic.JobResult{}, fmt.Errorf("could not decode result content bytes: %s", err.Error())
 	}
 
+	stateHash, err := result.StateHash()
+	if err != nil {
+		return logic.JobResult{}, fmt.Errorf("could not decode state hash: %s", err.Error())
+	}
+
+	wallClock, err := result.WallClock()
+	if err != nil {
+		return logic.JobResult{}, fmt.Errorf("could not decode wall clock readings: %s", err.Error())
+	}
+
 	return logic.JobResult{
 		JobID:       *jobID,
 		WorkerIndex: workerIndex,
 		Success:     result.Success(),
 		ContentType: result.ContentType(),
 		Contents:    contents,
+		Cycles:      result.Cycles(),
+		StateHash:   stateHash,
+		WallClock:   decodeWallClock(wallClock),
 	}, nil
 }
@@ -76,6 +76,16 @@ func parseStateSync(nodeId logic.NodeId, message node.MessageFromNode) (logic.St
 
 	progress := result.Progress()
 
+	stateHash, err := result.StateHash()
+	if err != nil {
+		return logic.StateSync{}, fmt.Errorf("could not decode state hash: %s", err.Error())
+	}
+
+	wallClock, err := result.WallClock()
+	if err != nil {
+		return logic.StateSync{}, fmt.Errorf("could not decode wall clock readings: %s", err.Error())
+	}
+
 	logs, err := result.Logs()
 	if err != nil {
 		return logic.StateSync{}, fmt.Errorf("could not decode interpreter state bytes: %s", err.Error())
@@ -112,5 +122,8 @@ func parseStateSync(nodeId logic.NodeId, message node.MessageFromNode) (logic.St
 		Progress:         progress,
 		Logs:             logsOutput,
 		InterpreterState: interpreterState,
+		Cycles:           result.Cycles(),
+		StateHash:        stateHash,
+		WallClock:        decodeWallClock(wallClock),
 	}, nil
 }
@@ -0,0 +1,45 @@
+package api
+
+import (
+	"fmt"
+
+	"github.com/reef-runtime/reef/reef_manager/logic"
+	node "github.com/reef-runtime/reef/reef_protocol_node"
+)
+
+func processVerifyResultFromNode(nodeID logic.NodeId, message node.MessageFromNode) error {
+	result, err := parseVerifyResultFromNode(message)
+	if err != nil {
+		return fmt.Errorf("parse verify result: %s", err.Error())
+	}
+
+	if err := logic.JobManager.ProcessVerifyResult(nodeID, result); err != nil {
+		return err
+	}
+
+	return nil
+}
+
+func parseVerifyResultFromNode(message node.MessageFromNode) (logic.VerifyResult, error) {
+	if message.Body().Which() != node.MessageFromNode_body_Which_verifyResult {
+		panic("assertion failed: expected body type is verify result, got something different")
+	}
+
+	result, err := message.Body().VerifyResult()
+	if err != nil {
+		return logic.VerifyResult{}, fmt.Errorf("could not parse verify result: %s", err.Error())
+	}
+
+	errorMessage, err := result.Error()
+	if err != nil {
+		return logic.VerifyResult{}, fmt.Errorf("could not decode error message: %s", err.Error())
+	}
+
+	return logic.VerifyResult{
+		WorkerIndex: result.WorkerIndex(),
+		Matches:     result.Matches(),
+		Cycles:      result.Cycles(),
+		FailureKind: result.FailureKind(),
+		Error:       errorMessage,
+	}, nil
+}
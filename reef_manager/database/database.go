@@ -124,7 +124,7 @@ func Init(pLogger *logrus.Logger, config DatabaseConfig, migrations embed.FS, em
 
 // // Solely used for testing purposes.
 func deleteAllTables() error {
-	tables := []string{LogTableName, ResultTableName, JobTableName, DSTableName}
+	tables := []string{LogTableName, ResultTableName, JobDatasetTableName, JobTableName, DSTableName}
 
 	for _, table := range tables {
 		if _, err := db.builder.Delete(table).Exec(); err != nil {
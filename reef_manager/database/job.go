@@ -9,6 +9,7 @@ import (
 
 const JobTableName = "job"
 const ResultTableName = "job_result"
+const JobDatasetTableName = "job_dataset"
 
 type ContentType uint16
 
@@ -29,10 +30,18 @@ type JobTableData struct {
 	WasmId string `json:"wasmId"`
 	// Dataset Id of the job.
 	DatasetId string `json:"datasetId"`
+	// Named datasets of the job in the order the program sees them.
+	// If empty, `DatasetId` is the only dataset of the job and has an empty name.
+	Datasets []JobDataset `json:"datasets"`
 	// Owner session ID of the job.
 	Owner string `json:"owner"`
 }
 
+type JobDataset struct {
+	Name      string `json:"name"`
+	DatasetId string `json:"datasetId"`
+}
+
 type JobWithResult struct {
 	// Normal data of this job.
 	Job JobTableData `json:"job"`
@@ -65,6 +74,18 @@ func AddJob(
 		return err
 	}
 
+	for position, dataset := range data.Datasets {
+		if _, err := db.builder.Insert(JobDatasetTableName).Values(
+			data.Id,
+			position,
+			dataset.Name,
+			dataset.DatasetId,
+		).Exec(); err != nil {
+			log.Errorf("Could not add job dataset to database: executing query failed: %s", err.Error())
+			return err
+		}
+	}
+
 	return nil
 }
 
@@ -78,6 +99,10 @@ func DeleteJob(jobId string) (found bool, err error) {
 		return false, err
 	}
 
+	if err := deleteJobDatasets(jobId); err != nil {
+		return false, err
+	}
+
 	res, err := db.builder.Delete(JobTableName).Where("job.id=?", jobId).Exec()
 	if err != nil {
 		log.Errorf("Could not delete job: job entry: %s", err.Error())
@@ -185,9 +210,77 @@ func ListJobs(idFilter *string, usernameFilter *string) ([]JobWithResult, error)
 		jobs = append(jobs, jobWithResult)
 	}
 
+	datasets, err := listJobDatasets(idFilter)
+	if err != nil {
+		return nil, err
+	}
+
+	for idx := range jobs {
+		jobDatasets, found := datasets[jobs[idx].Job.Id]
+		if !found {
+			jobDatasets = make([]JobDataset, 0)
+		}
+
+		jobs[idx].Job.Datasets = jobDatasets
+	}
+
 	return jobs, nil
 }
 
+// Returns the named datasets of all jobs, or of a single one, by job ID.
+func listJobDatasets(idFilter *string) (map[string][]JobDataset, error) {
+	baseQuery := db.builder.
+		Select("job_id", "name", "dataset_id").
+		From(JobDatasetTableName).
+		OrderBy("job_id ASC", "position ASC")
+
+	if idFilter != nil {
+		baseQuery = baseQuery.Where("job_id=?", *idFilter)
+	}
+
+	res, err := baseQuery.Query()
+	if err != nil {
+		log.Errorf("Could not list job datasets: executing query failed: %s", err.Error())
+		return nil, err
+	}
+
+	if res.Err() != nil {
+		log.Errorf("Could not list job datasets: getting rows failed: %s", res.Err())
+		return nil, err
+	}
+
+	defer res.Close()
+
+	datasets := make(map[string][]JobDataset)
+
+	for res.Next() {
+		var jobID string
+		var dataset JobDataset
+
+		if err := res.Scan(&jobID, &dataset.Name, &dataset.DatasetId); err != nil {
+			log.Errorf("Could not list job datasets: scanning results failed: %s", err.Error())
+			return nil, err
+		}
+
+		datasets[jobID] = append(datasets[jobID], dataset)
+	}
+
+	return datasets, nil
+}
+
+func deleteJobDatasets(jobId string) error {
+	_, err := db.builder.
+		Delete(JobDatasetTableName).
+		Where("job_dataset.job_id=?", jobId).Exec()
+
+	if err != nil {
+		log.Errorf("Could not delete job datasets from database: executing query failed: %s", err.Error())
+		return err
+	}
+
+	return nil
+}
+
 func JobHasOwner(jobID string, owner string) (isTrue bool, err error) {
 	_, found, err := GetJob(jobID, &owner)
 	if err != nil {
@@ -35,6 +35,7 @@ func TestJobResults(t *testing.T) {
 		Submitted: now,
 		WasmId:    "",
 		DatasetId: datasets[0].Id,
+		Datasets:  nil,
 		Owner:     "",
 	}
 
@@ -60,3 +61,43 @@ func TestJobResults(t *testing.T) {
 	assert.Equal(t, result.Content, []byte{1, 2, 3})
 	assert.Equal(t, result.JobID, jobID)
 }
+
+func TestJobDatasets(t *testing.T) {
+	AddDummyDS(t)
+
+	const jobID = "testid-datasets"
+
+	jobDatasets := []JobDataset{
+		{Name: "second", DatasetId: dummyDS.Id},
+		{Name: "first", DatasetId: dummyDS.Id},
+	}
+
+	job := JobTableData{
+		Id:        jobID,
+		Name:      "",
+		Submitted: time.Now(),
+		WasmId:    "",
+		DatasetId: dummyDS.Id,
+		Datasets:  jobDatasets,
+		Owner:     "",
+	}
+
+	err := AddJob(job)
+	assert.NoError(t, err)
+
+	// The datasets are kept in the order of the job.
+	stored, found, err := GetJob(jobID, nil)
+	assert.NoError(t, err)
+	assert.True(t, found)
+	assert.Equal(t, jobDatasets, stored.Job.Datasets)
+
+	// Jobs without named datasets list none.
+	err = deleteJobDatasets(jobID)
+	assert.NoError(t, err)
+
+	stored, found, err = GetJob(jobID, nil)
+	assert.NoError(t, err)
+	assert.True(t, found)
+	assert.Empty(t, stored.Job.Datasets)
+	assert.NotNil(t, stored.Job.Datasets)
+}
@@ -0,0 +1,11 @@
+CREATE TABLE
+IF NOT EXISTS
+job_dataset(
+    -- Foreign key: job ID.
+    job_id      VARCHAR(64) NOT NULL REFERENCES job(id),
+    -- Position of the dataset in the list of datasets the program sees.
+    position    INT NOT NULL,
+    name        TEXT NOT NULL,
+    dataset_id  VARCHAR(64) NOT NULL,
+    PRIMARY KEY (job_id, position)
+);
@@ -0,0 +1,72 @@
+package logic
+
+import (
+	"bytes"
+	"encoding/binary"
+	"errors"
+	"fmt"
+)
+
+//
+// Versioning of the reef imports.
+// Programs declare the ABI version they were built against in the `reef_abi` custom section, as a little endian u32.
+// Programs without the section use ABI version 1.
+// Nodes advertise the range of versions they can run during the handshake, jobs only go to nodes covering theirs.
+//
+
+const reefAbiSection = "reef_abi"
+
+// ABI version of programs without a `reef_abi` section, also assumed for nodes which do not advertise a range.
+const defaultAbiVersion uint32 = 1
+
+const wasmSectionCustom = 0
+
+var wasmHeader = []byte{0x00, 'a', 's', 'm', 0x01, 0x00, 0x00, 0x00}
+
+// Reads the ABI version declared by a Wasm program.
+// If there are multiple `reef_abi` sections, the first one is used, like the nodes do.
+func programAbiVersion(wasm []byte) (uint32, error) {
+	if !bytes.HasPrefix(wasm, wasmHeader) {
+		return 0, errors.New("not a Wasm module")
+	}
+
+	rest := wasm[len(wasmHeader):]
+	for len(rest) > 0 {
+		id := rest[0]
+
+		size, sizeLen := binary.Uvarint(rest[1:])
+		if sizeLen <= 0 || size > uint64(len(rest)-1-sizeLen) {
+			return 0, errors.New("truncated Wasm section")
+		}
+
+		content := rest[1+sizeLen : 1+sizeLen+int(size)]
+		rest = rest[1+sizeLen+int(size):]
+
+		if id != wasmSectionCustom {
+			continue
+		}
+
+		nameLen, nameLenLen := binary.Uvarint(content)
+		if nameLenLen <= 0 || nameLen > uint64(len(content)-nameLenLen) {
+			return 0, errors.New("truncated Wasm custom section name")
+		}
+
+		if string(content[nameLenLen:nameLenLen+int(nameLen)]) != reefAbiSection {
+			continue
+		}
+
+		payload := content[nameLenLen+int(nameLen):]
+		if len(payload) != 4 {
+			return 0, fmt.Errorf("invalid `%s` section, expected a u32 ABI version", reefAbiSection)
+		}
+
+		return binary.LittleEndian.Uint32(payload), nil
+	}
+
+	return defaultAbiVersion, nil
+}
+
+// Whether the node can run programs built against the given ABI version.
+func (i NodeInfo) supportsAbi(version uint32) bool {
+	return i.AbiVersionMin <= version && version <= i.AbiVersionMax
+}
@@ -0,0 +1,115 @@
+package logic
+
+import (
+	"os"
+	"testing"
+
+	"github.com/sirupsen/logrus"
+	"github.com/stretchr/testify/assert"
+)
+
+func TestMain(m *testing.M) {
+	log = logrus.New()
+	log.SetLevel(logrus.TraceLevel)
+
+	os.Exit(m.Run())
+}
+
+func customSection(name string, payload []byte) []byte {
+	content := append([]byte{byte(len(name))}, []byte(name)...)
+	content = append(content, payload...)
+	return append([]byte{wasmSectionCustom, byte(len(content))}, content...)
+}
+
+func wasmModule(sections ...[]byte) []byte {
+	module := append([]byte{}, wasmHeader...)
+	for _, section := range sections {
+		module = append(module, section...)
+	}
+	return module
+}
+
+func TestProgramAbiVersion(t *testing.T) {
+	// Type section with no types, followed by an unrelated custom section.
+	typeSection := []byte{0x01, 0x01, 0x00}
+	nameSection := customSection("name", []byte{0x00, 0x00})
+
+	version, err := programAbiVersion(wasmModule(typeSection, nameSection))
+	assert.NoError(t, err)
+	assert.Equal(t, defaultAbiVersion, version)
+
+	version, err = programAbiVersion(wasmModule(typeSection, customSection(reefAbiSection, []byte{7, 0, 0, 0})))
+	assert.NoError(t, err)
+	assert.Equal(t, uint32(7), version)
+
+	// The first section wins, like on the nodes.
+	version, err = programAbiVersion(wasmModule(
+		nameSection,
+		customSection(reefAbiSection, []byte{2, 0, 0, 0}),
+		customSection(reefAbiSection, []byte{5, 0, 0, 0}),
+	))
+	assert.NoError(t, err)
+	assert.Equal(t, uint32(2), version)
+
+	_, err = programAbiVersion(wasmModule(customSection(reefAbiSection, []byte{2})))
+	assert.Error(t, err)
+
+	_, err = programAbiVersion(wasmModule([]byte{0x01, 0x05, 0x00}))
+	assert.Error(t, err)
+
+	_, err = programAbiVersion([]byte("not wasm"))
+	assert.Error(t, err)
+}
+
+func addTestNode(m *JobManagerT, name string, abiVersionMin uint32, abiVersionMax uint32) NodeId {
+	id := NodeId{byte(len(m.Nodes.Map) + 1)}
+
+	m.Nodes.Insert(id, NewLockedValue(Node{
+		Info: NodeInfo{
+			EndpointIP:    "",
+			Name:          name,
+			NumWorkers:    1,
+			CanVerify:     true,
+			AbiVersionMin: abiVersionMin,
+			AbiVersionMax: abiVersionMax,
+		},
+		Id:            id,
+		WorkerState:   make([]*JobId, 1),
+		Verifications: make([]*JobId, 1),
+	}))
+
+	return id
+}
+
+func TestNodeSelectionRespectsAbi(t *testing.T) {
+	m := JobManagerT{Nodes: newLockedMap[NodeId, LockedValue[Node]]()}
+
+	oldNode := addTestNode(&m, "old", 1, 1)
+
+	nodeID, _, found := m.findSuitableNode(1)
+	assert.True(t, found)
+	assert.Equal(t, oldNode, nodeID)
+
+	_, _, found = m.findSuitableNode(7)
+	assert.False(t, found)
+
+	newNode := addTestNode(&m, "new", 1, 7)
+
+	nodeID, _, found = m.findSuitableNode(7)
+	assert.True(t, found)
+	assert.Equal(t, newNode, nodeID)
+
+	// Only the old node could verify claims of the new one, but it cannot run the program.
+	_, _, found = m.findVerifyingNode(newNode, 7)
+	assert.False(t, found)
+	assert.False(t, m.canBeVerified(newNode, 7))
+
+	nodeID, _, found = m.findVerifyingNode(newNode, 1)
+	assert.True(t, found)
+	assert.Equal(t, oldNode, nodeID)
+	assert.True(t, m.canBeVerified(newNode, 1))
+
+	nodeID, _, found = m.findVerifyingNode(oldNode, 7)
+	assert.True(t, found)
+	assert.Equal(t, newNode, nodeID)
+}
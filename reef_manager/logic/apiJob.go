@@ -36,6 +36,7 @@ func (j *APIJob) MarshalJSON() ([]byte, error) {
 		"submitted": j.Job.Job.Submitted,
 		"wasmId":    j.Job.Job.WasmId,
 		"datasetId": j.Job.Job.DatasetId,
+		"datasets":  j.Job.Job.Datasets,
 		"owner":     j.Job.Job.Owner,
 		"progress":  j.Progress,
 		"status":    j.Status,
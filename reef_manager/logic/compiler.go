@@ -169,6 +169,8 @@ func parseLanguage(from JobProgrammingLanguage) compiler.Language {
 type CompileArtifact struct {
 	Wasm []byte
 	Hash string
+	// Reef ABI version the program was built against.
+	AbiVersion uint32
 }
 
 // nolint:funlen
@@ -187,9 +189,15 @@ func (c *CompilerManager) Compile(
 
 	// Success, there is already a cached version available.
 	if cachedBytes != nil {
+		abiVersion, err := programAbiVersion(cachedBytes)
+		if err != nil {
+			return artifact, nil, fmt.Errorf("cached artifact: %s", err.Error())
+		}
+
 		return CompileArtifact{
-			Wasm: cachedBytes,
-			Hash: hash,
+			Wasm:       cachedBytes,
+			Hash:       hash,
+			AbiVersion: abiVersion,
 		}, nil, nil
 	}
 
@@ -225,6 +233,12 @@ func (c *CompilerManager) Compile(
 			return artifact, nil, err
 		}
 
+		abiVersion, err := programAbiVersion(file)
+		if err != nil {
+			compileErr := err.Error()
+			return artifact, &compileErr, nil
+		}
+
 		if err := c.writeCached(hash, file); err != nil {
 			return artifact, nil, err
 		}
@@ -233,8 +247,9 @@ func (c *CompilerManager) Compile(
 		copy(fileBuf, file)
 
 		return CompileArtifact{
-			Wasm: fileBuf,
-			Hash: hash,
+			Wasm:       fileBuf,
+			Hash:       hash,
+			AbiVersion: abiVersion,
 		}, nil, nil
 	case compiler.CompilerResponse_Which_systemError:
 		fs, err := r.SystemError()
@@ -28,6 +28,9 @@ type JobManagerT struct {
 	MaxJobRuntimeSecs uint64
 	// Any nodes with a name contained in that slice will not receive any jobs.
 	NodesBlackList []string
+	// If no other node can verify the claim of a node, it is accepted instead of waiting for such a node.
+	// Enabled by default, so setups without a second node which can verify claims still finish jobs.
+	TrustUnverifiedClaims bool
 }
 
 var JobManager JobManagerT
@@ -76,6 +79,13 @@ type Job struct {
 	LastRuntimeIncrement time.Time
 	RuntimeSeconds       uint64
 	IsBeingAborted       bool
+	// Claim which is currently being verified, at most one per job.
+	Verification *VerifyClaim
+	// Claims waiting for their verification, oldest first.
+	// Each one starts from the state claimed by the one before.
+	PendingClaims []VerifyClaim
+	// Reef ABI version of the program, the job only runs on nodes which support it.
+	AbiVersion uint32
 }
 
 func (m *JobManagerT) SubmitJob(
@@ -83,6 +93,7 @@ func (m *JobManagerT) SubmitJob(
 	sourceCode string,
 	name string,
 	datasetID string,
+	datasets []database.JobDataset,
 	ownerID string,
 ) (idString string, compilerErr *string, backendErr error) {
 	now := time.Now()
@@ -94,12 +105,14 @@ func (m *JobManagerT) SubmitJob(
 		SourceCode string
 		Name       string
 		DatasetID  string
+		Datasets   []database.JobDataset
 		OwnerID    string
 	}{
 		Language:   language,
 		SourceCode: sourceCode,
 		Name:       name,
 		DatasetID:  datasetID,
+		Datasets:   datasets,
 		OwnerID:    ownerID,
 	}); err != nil {
 		return "", nil, err
@@ -137,6 +150,7 @@ func (m *JobManagerT) SubmitJob(
 		Submitted: now,
 		WasmId:    artifact.Hash,
 		DatasetId: datasetID,
+		Datasets:  datasets,
 		Owner:     ownerID,
 	}
 
@@ -154,6 +168,9 @@ func (m *JobManagerT) SubmitJob(
 		LastRuntimeIncrement: time.Now(),
 		RuntimeSeconds:       0,
 		IsBeingAborted:       false,
+		Verification:         nil,
+		PendingClaims:        nil,
+		AbiVersion:           artifact.AbiVersion,
 	}
 
 	m.NonFinishedJobs.Insert(idString, NewLockedValue(job))
@@ -245,6 +262,15 @@ func (m *JobManagerT) Init() error {
 
 		log.Tracef("Loaded saved job from DB as queued: `%s`", dbJob.Job.Id)
 
+		// A missing artifact is reported once the job is started, which loads it again.
+		abiVersion := defaultAbiVersion
+		if wasm, err := m.Compiler.getCached(dbJob.Job.WasmId); err == nil && len(wasm) > 0 {
+			if abiVersion, err = programAbiVersion(wasm); err != nil {
+				log.Warnf("Could not read ABI version of job `%s`: %s", dbJob.Job.Id, err.Error())
+				abiVersion = defaultAbiVersion
+			}
+		}
+
 		// Put job back in queued state.
 		job := Job{
 			Data: database.JobTableData{
@@ -252,6 +278,7 @@ func (m *JobManagerT) Init() error {
 				Name:      dbJob.Job.Name,
 				WasmId:    dbJob.Job.WasmId,
 				DatasetId: dbJob.Job.DatasetId,
+				Datasets:  dbJob.Job.Datasets,
 				Submitted: dbJob.Job.Submitted,
 				Owner:     dbJob.Job.Owner,
 			},
@@ -265,6 +292,9 @@ func (m *JobManagerT) Init() error {
 			LastRuntimeIncrement: time.Now(),
 			RuntimeSeconds:       0,
 			IsBeingAborted:       false,
+			Verification:         nil,
+			PendingClaims:        nil,
+			AbiVersion:           abiVersion,
 		}
 
 		m.NonFinishedJobs.Insert(dbJob.Job.Id, NewLockedValue(job))
@@ -46,7 +46,7 @@ func (m *JobManagerT) QueuedJobs() (first LockedValue[Job], irstExists bool) {
 }
 
 // Does critical housekeeping and management on the job manager.
-// Starts queued jobs, manages maximum allowed job runtime.
+// Starts queued jobs and verifications, manages maximum allowed job runtime.
 // Error is a critical error, like a database fault.
 func (m *JobManagerT) JobManagerMainLoopIteration() error {
 	// Try to start all queued jobs.
@@ -59,6 +59,9 @@ func (m *JobManagerT) JobManagerMainLoopIteration() error {
 		return err
 	}
 
+	// Retry the verification of claims which found no free node.
+	m.startPendingVerifications()
+
 	return nil
 }
 
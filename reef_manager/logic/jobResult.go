@@ -16,6 +16,10 @@ type JobResult struct {
 	Success     bool
 	ContentType node.ResultContentType
 	Contents    []byte
+	// Claim on the final state of a successful job, like the one of a state sync.
+	Cycles    uint64
+	StateHash []byte
+	WallClock []uint64
 }
 
 const intResByteCount = 4
@@ -51,23 +55,39 @@ func (r JobResult) String() string {
 	return fmt.Sprintf("[%s] on %s@%d (%s): %s", outcome, r.JobID, r.WorkerIndex, contentTypeStr, content)
 }
 
+//
+// Processes the result of a job.
+// Failures are stored right away, a successful result only once its claim was verified.
+// Until then, the worker which ran the job stays occupied.
+//
+
 func (m *JobManagerT) ProcessResult(nodeID NodeId, result JobResult) error {
-	jobID, err := m.processResultWithLockingOps(nodeID, result)
+	jobID, claim, err := m.processResultWithLockingOps(nodeID, result)
 	if err != nil {
 		return err
 	}
 
+	if claim != nil {
+		if err := m.StartVerification(*claim); err != nil {
+			log.Errorf("Could not verify result of job `%s`: %s", jobID, err.Error())
+		}
+	}
+
 	m.updateSingleJobState(jobID)
 	m.updateNodeState()
 
 	return nil
 }
 
-func (m *JobManagerT) processResultWithLockingOps(nodeId NodeId, result JobResult) (jobId JobId, err error) {
+// nolint:funlen
+func (m *JobManagerT) processResultWithLockingOps(
+	nodeId NodeId,
+	result JobResult,
+) (jobId JobId, claim *VerifyClaim, err error) {
 	thisNode, found := m.Nodes.Get(nodeId)
 
 	if !found {
-		return "", fmt.Errorf("process result: node Id is illegal: `%s`", IdToString(nodeId))
+		return "", nil, fmt.Errorf("process result: node Id is illegal: `%s`", IdToString(nodeId))
 	}
 
 	thisNode.Lock.RLock()
@@ -75,18 +95,57 @@ func (m *JobManagerT) processResultWithLockingOps(nodeId NodeId, result JobResul
 	thisNode.Lock.RUnlock()
 
 	if result.WorkerIndex >= numWorkers {
-		return "", fmt.Errorf("process result: worker index is illegal: %d", result.WorkerIndex)
+		return "", nil, fmt.Errorf("process result: worker index is illegal: %d", result.WorkerIndex)
 	}
 
 	_, exists, err := database.GetResult(result.JobID)
 	if err != nil {
-		return "", err
+		return "", nil, err
 	}
 
 	if exists {
-		return "", fmt.Errorf("result for job `%s` already exists in database", result.JobID)
+		return "", nil, fmt.Errorf("result for job `%s` already exists in database", result.JobID)
+	}
+
+	if !result.Success {
+		return result.JobID, nil, m.finishJob(nodeId, result)
+	}
+
+	if len(result.StateHash) == 0 {
+		return "", nil, fmt.Errorf("process result: successful result of job `%s` makes no claim", result.JobID)
+	}
+
+	job, found := m.NonFinishedJobs.Get(result.JobID)
+	if !found {
+		return "", nil, fmt.Errorf("illegal job id in result: `%s`", result.JobID)
 	}
 
+	// The last synced state is where the verification of the result starts from.
+	job.Lock.Lock()
+	if job.Data.hasPendingResult() {
+		job.Lock.Unlock()
+		return "", nil, fmt.Errorf("result for job `%s` is already being verified", result.JobID)
+	}
+
+	job.Data.queueClaim(VerifyClaim{
+		JobId:            result.JobID,
+		ClaimedBy:        nodeId,
+		InterpreterState: job.Data.InterpreterState,
+		Cycles:           result.Cycles,
+		StateHash:        result.StateHash,
+		WallClock:        result.WallClock,
+		ClaimedState:     nil,
+		Result:           &result,
+		AbiVersion:       job.Data.AbiVersion,
+	})
+	claim = job.Data.nextClaim()
+	job.Lock.Unlock()
+
+	return result.JobID, claim, nil
+}
+
+// Stores the result of a job, removes the job from the non-finished jobs and frees the worker which ran it.
+func (m *JobManagerT) finishJob(nodeId NodeId, result JobResult) error {
 	// If the result is truncated, pad it.
 	if result.ContentType == node.ResultContentType_i32 && len(result.Contents) < intResByteCount {
 		log.Warnf("[node] Got truncated response for 32-bit integer, using zero...")
@@ -100,25 +159,29 @@ func (m *JobManagerT) processResultWithLockingOps(nodeId NodeId, result JobResul
 		ContentType: database.ContentType(result.ContentType),
 		Created:     time.Now(),
 	}); err != nil {
-		return "", fmt.Errorf("process result: DB: %s", err.Error())
+		return fmt.Errorf("process result: DB: %s", err.Error())
 	}
 
-	thisNode.Lock.Lock()
-	jobId = *thisNode.Data.WorkerState[result.WorkerIndex]
 	// Finally, delete the job from the worker.
-	thisNode.Data.WorkerState[result.WorkerIndex] = nil
-	thisNode.Lock.Unlock()
+	// The node may have been dropped while the result was being verified.
+	if thisNode, found := m.Nodes.Get(nodeId); found {
+		thisNode.Lock.Lock()
+		if jobId := thisNode.Data.WorkerState[result.WorkerIndex]; jobId != nil && *jobId == result.JobID {
+			thisNode.Data.WorkerState[result.WorkerIndex] = nil
+		}
+		thisNode.Lock.Unlock()
+	}
 
-	job, found := m.NonFinishedJobs.Delete(jobId)
+	job, found := m.NonFinishedJobs.Delete(result.JobID)
 	if !found {
-		return "", fmt.Errorf("illegal job id in result: `%s`", jobId)
+		return fmt.Errorf("illegal job id in result: `%s`", result.JobID)
 	}
 
 	job.Lock.Lock()
 	for _, log := range job.Data.Logs {
 		if err := database.AddLog(log); err != nil {
 			job.Lock.Unlock()
-			return "", fmt.Errorf("save log: %s", err.Error())
+			return fmt.Errorf("save log: %s", err.Error())
 		}
 	}
 
@@ -128,5 +191,5 @@ func (m *JobManagerT) processResultWithLockingOps(nodeId NodeId, result JobResul
 	job.Data.Progress = 1.0
 	job.Lock.Unlock()
 
-	return jobId, nil
+	return nil
 }
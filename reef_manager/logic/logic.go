@@ -150,6 +150,7 @@ func Init(
 	templatesDirPath string,
 	maxJobRuntimeSecs uint64,
 	nodesBlackList []string,
+	trustUnverifiedClaims bool,
 ) error {
 	log = logger
 	log.Trace("Initializing logic package...")
@@ -185,6 +186,7 @@ func Init(
 		UIManager.TriggerDataSourceChan,
 		maxJobRuntimeSecs,
 		nodesBlackList,
+		trustUnverifiedClaims,
 	)
 	if err := JobManager.Init(); err != nil {
 		return err
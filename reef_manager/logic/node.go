@@ -22,6 +22,11 @@ type NodeInfo struct {
 	// TODO: maybe worker descriptions
 	// TODO: maybe the current state of the node?
 	NumWorkers uint16 `json:"numWorkers"`
+	// Whether the node accepts verifications of the state claimed by other nodes.
+	CanVerify bool `json:"canVerify"`
+	// Range of reef ABI versions of programs the node can run.
+	AbiVersionMin uint32 `json:"abiVersionMin"`
+	AbiVersionMax uint32 `json:"abiVersionMax"`
 }
 
 type WSConn struct {
@@ -104,6 +109,13 @@ type Node struct {
 	// Therefore maps every worker to a possible jobId.
 	// If the mapped jobId is `nil`, the worker is free and can start a job.
 	WorkerState []*JobId
+	// Maps every worker to the job it currently verifies, see `VerifyClaim`.
+	// A worker is only free if it neither runs nor verifies a job.
+	Verifications []*JobId
+}
+
+func (n *Node) isWorkerFree(workerIdx int) bool {
+	return n.WorkerState[workerIdx] == nil && n.Verifications[workerIdx] == nil
 }
 
 type NodeWeb struct {
@@ -146,29 +158,44 @@ type StateSync struct {
 	Progress         float32
 	Logs             []database.JobLog
 	InterpreterState []byte
+	// Instructions executed since the previous state sync and the hash of `InterpreterState`.
+	// The hash is empty if the sync makes no claim.
+	Cycles    uint64
+	StateHash []byte
+	// Readings of the wall clock during these instructions.
+	WallClock []uint64
 }
 
 //
 // Processes a job state sync from a node.
 // If this is the first state sync from that job on that node, put the job into the `running state`
 // since it was previously in `starting`.
+// Only syncs with a claim replace the state of the job, the claim is verified by another node once the previous
+// claims of the job were verified.
 //
 
 func (m *JobManagerT) StateSync(nodeId NodeId, state StateSync) error {
-	jobId, err := m.StateSyncWithLockingOps(nodeId, state)
+	jobId, claim, err := m.StateSyncWithLockingOps(nodeId, state)
 	if err != nil {
 		return err
 	}
 
+	if claim != nil {
+		if err := m.StartVerification(*claim); err != nil {
+			log.Errorf("Could not verify state sync of job `%s`: %s", jobId, err.Error())
+		}
+	}
+
 	m.updateSingleJobState(jobId)
 
 	return nil
 }
 
-func (m *JobManagerT) StateSyncWithLockingOps(nodeId NodeId, state StateSync) (JobId, error) {
+// nolint:funlen
+func (m *JobManagerT) StateSyncWithLockingOps(nodeId NodeId, state StateSync) (JobId, *VerifyClaim, error) {
 	node, found := m.Nodes.Get(nodeId)
 	if !found {
-		return "", fmt.Errorf("state sync: node `%s` was not found", IdToString(nodeId))
+		return "", nil, fmt.Errorf("state sync: node `%s` was not found", IdToString(nodeId))
 	}
 
 	node.Lock.RLock()
@@ -176,14 +203,18 @@ func (m *JobManagerT) StateSyncWithLockingOps(nodeId NodeId, state StateSync) (J
 	node.Lock.RUnlock()
 
 	if state.WorkerIndex >= numWorkers {
-		return "", fmt.Errorf("state sync: worker %d is illegal", state.WorkerIndex)
+		return "", nil, fmt.Errorf("state sync: worker %d is illegal", state.WorkerIndex)
 	}
 
 	node.Lock.RLock()
 	jobId := node.Data.WorkerState[state.WorkerIndex]
 	node.Lock.RUnlock()
 	if jobId == nil {
-		return "", fmt.Errorf("state sync: worker %d on node `%s` is idle", state.WorkerIndex, IdToString(nodeId))
+		return "", nil, fmt.Errorf(
+			"state sync: worker %d on node `%s` is idle",
+			state.WorkerIndex,
+			IdToString(nodeId),
+		)
 	}
 
 	m.NonFinishedJobs.Lock.Lock()
@@ -197,7 +228,7 @@ func (m *JobManagerT) StateSyncWithLockingOps(nodeId NodeId, state StateSync) (J
 			IdToString(nodeId),
 			state.WorkerIndex,
 		)
-		return "", nil
+		return "", nil, nil
 	}
 
 	//
@@ -208,7 +239,23 @@ func (m *JobManagerT) StateSyncWithLockingOps(nodeId NodeId, state StateSync) (J
 	job.Data.Logs = append(job.Data.Logs, state.Logs...)
 	job.Data.Progress = state.Progress
 
-	job.Data.InterpreterState = state.InterpreterState
+	// The previous state is where the verification of the claim starts from.
+	var claim *VerifyClaim
+	if len(state.StateHash) > 0 {
+		job.Data.queueClaim(VerifyClaim{
+			JobId:            *jobId,
+			ClaimedBy:        nodeId,
+			InterpreterState: job.Data.InterpreterState,
+			Cycles:           state.Cycles,
+			StateHash:        state.StateHash,
+			WallClock:        state.WallClock,
+			ClaimedState:     state.InterpreterState,
+			Result:           nil,
+			AbiVersion:       job.Data.AbiVersion,
+		})
+		job.Data.InterpreterState = state.InterpreterState
+		claim = job.Data.nextClaim()
+	}
 
 	jobStatus := job.Data.Status
 
@@ -225,7 +272,7 @@ func (m *JobManagerT) StateSyncWithLockingOps(nodeId NodeId, state StateSync) (J
 
 	log.Debugf("State sync job `%s` worker %d, progress %3f%%", *jobId, state.WorkerIndex, state.Progress)
 
-	return *jobId, nil
+	return *jobId, claim, nil
 }
 
 func (m *JobManagerT) ConnectNode(node NodeInfo, conn *WSConn) (nodeObj Node) {
@@ -238,21 +285,24 @@ func (m *JobManagerT) ConnectNode(node NodeInfo, conn *WSConn) (nodeObj Node) {
 
 	now := time.Now()
 	nodeObj = Node{
-		Info:        node,
-		LastPing:    now,
-		Conn:        conn,
-		Id:          newID,
-		WorkerState: make([]*string, node.NumWorkers),
+		Info:          node,
+		LastPing:      now,
+		Conn:          conn,
+		Id:            newID,
+		WorkerState:   make([]*string, node.NumWorkers),
+		Verifications: make([]*string, node.NumWorkers),
 	}
 
 	m.Nodes.Insert(newID, NewLockedValue(nodeObj))
 
 	log.Infof(
-		"[node] Handshake success: connected to new node `%s` ip=`%s` name=`%s` with %d workers",
+		"[node] Handshake success: connected to new node `%s` ip=`%s` name=`%s` with %d workers, ABI %d..=%d",
 		newIDString,
 		node.EndpointIP,
 		node.Name,
 		node.NumWorkers,
+		node.AbiVersionMin,
+		node.AbiVersionMax,
 	)
 
 	m.updateNodeState()
@@ -292,6 +342,13 @@ func (m *JobManagerT) DropNode(id NodeId) bool {
 			log.Errorf("Could not park job: %s", err.Error())
 		}
 	}
+
+	// Verifications of this node will never report back, verify their claims on another node.
+	for _, verifiedJob := range node.Data.Verifications {
+		if verifiedJob != nil {
+			m.retryVerification(*verifiedJob)
+		}
+	}
 	node.Lock.RUnlock()
 
 	m.Nodes.Delete(id)
@@ -327,20 +384,26 @@ func newJobManager(
 	refreshData chan WebSocketTopic,
 	maxJobRuntimeSecs uint64,
 	nodesBlackList []string,
+	trustUnverifiedClaims bool,
 ) JobManagerT {
 	if len(nodesBlackList) > 0 {
 		log.Warnf("Node blacklist contains candidates: [%s]", strings.Join(nodesBlackList, ", "))
 	}
 
+	if !trustUnverifiedClaims {
+		log.Warn("Claims which no other node can verify are held until a verifying node connects")
+	}
+
 	return JobManagerT{
-		Templates:            templates,
-		Compiler:             compiler,
-		Nodes:                newLockedMap[NodeId, LockedValue[Node]](),
-		NonFinishedJobs:      newLockedMap[JobId, LockedValue[Job]](),
-		SendUIUpdatesTo:      triggerUIUpdates,
-		RequestToRefreshData: refreshData,
-		MaxJobRuntimeSecs:    maxJobRuntimeSecs,
-		NodesBlackList:       nodesBlackList,
+		Templates:             templates,
+		Compiler:              compiler,
+		Nodes:                 newLockedMap[NodeId, LockedValue[Node]](),
+		NonFinishedJobs:       newLockedMap[JobId, LockedValue[Job]](),
+		SendUIUpdatesTo:       triggerUIUpdates,
+		RequestToRefreshData:  refreshData,
+		MaxJobRuntimeSecs:     maxJobRuntimeSecs,
+		NodesBlackList:        nodesBlackList,
+		TrustUnverifiedClaims: trustUnverifiedClaims,
 	}
 }
 
@@ -21,6 +21,7 @@ func toNodeJobInitializationMessage(
 	workerIndex uint32,
 	jobID string,
 	datasetID string,
+	datasets []database.JobDataset,
 	progress float32,
 	interpreterState []byte,
 	programByteCode []byte,
@@ -60,6 +61,15 @@ func toNodeJobInitializationMessage(
 		return nil, err
 	}
 
+	datasetsList, err := nestedBody.NewDatasets(int32(len(datasets)))
+	if err != nil {
+		return nil, err
+	}
+
+	if err := setJobDatasets(datasetsList, datasets); err != nil {
+		return nil, err
+	}
+
 	// Progress.
 	nestedBody.SetProgress(progress)
 
@@ -75,6 +85,23 @@ func toNodeJobInitializationMessage(
 	return msg.Marshal()
 }
 
+// Fills the list of named datasets of a start or verify message.
+func setJobDatasets(list node.JobDataset_List, datasets []database.JobDataset) error {
+	for idx, dataset := range datasets {
+		item := list.At(idx)
+
+		if err := item.SetName(dataset.Name); err != nil {
+			return err
+		}
+
+		if err := item.SetDatasetId(dataset.DatasetId); err != nil {
+			return err
+		}
+	}
+
+	return nil
+}
+
 func (m *JobManagerT) StartJobOnNode(
 	node LockedValue[Node],
 	job LockedValue[Job],
@@ -85,6 +112,7 @@ func (m *JobManagerT) StartJobOnNode(
 		uint32(workerIdx),
 		job.Data.Data.Id,
 		job.Data.Data.DatasetId,
+		job.Data.Data.Datasets,
 		job.Data.Progress,
 		job.Data.InterpreterState,
 		programByteCode,
@@ -131,6 +159,7 @@ func (m *JobManagerT) StartJobOnFreeNode(job LockedValue[Job]) (couldStart bool,
 	job.Lock.RLock()
 	wasmID := job.Data.Data.WasmId
 	jobID := job.Data.Data.Id
+	abiVersion := job.Data.AbiVersion
 	job.Lock.RUnlock()
 
 	wasmCode, wasmError := m.Compiler.getCached(wasmID)
@@ -175,7 +204,7 @@ func (m *JobManagerT) StartJobOnFreeNode(job LockedValue[Job]) (couldStart bool,
 		panic("This case is excluded")
 	}
 
-	nodeID, workerIndex, nodeFound := m.findSuitableNode()
+	nodeID, workerIndex, nodeFound := m.findSuitableNode(abiVersion)
 	if !nodeFound {
 		return false, nil
 	}
@@ -215,9 +244,10 @@ func (m *JobManagerT) StartJobOnFreeNode(job LockedValue[Job]) (couldStart bool,
 // Additionally, this function has the job of balancing the job distribution across the nodes.
 // For this, each node is assigned a "suitability score".
 // The node with the highest score is elected to run the job.
+// Only nodes supporting the ABI version of the job's program are considered.
 //
 
-func (m *JobManagerT) findSuitableNode() (nodeID NodeId, workerIdx uint16, found bool) {
+func (m *JobManagerT) findSuitableNode(abiVersion uint32) (nodeID NodeId, workerIdx uint16, found bool) {
 	m.Nodes.Lock.RLock()
 	defer m.Nodes.Lock.RUnlock()
 
@@ -227,7 +257,7 @@ func (m *JobManagerT) findSuitableNode() (nodeID NodeId, workerIdx uint16, found
 
 	for _, node := range m.Nodes.Map {
 		node.Lock.RLock()
-		score, isPossible := m.calculateNodeSuitabilityScore(node.Data)
+		score, isPossible := m.calculateNodeSuitabilityScore(node.Data, abiVersion)
 		node.Lock.RUnlock()
 
 		if isPossible {
@@ -250,8 +280,8 @@ func (m *JobManagerT) findSuitableNode() (nodeID NodeId, workerIdx uint16, found
 
 	// Return one free worker of the elected node.
 	maxNode.Lock.RLock()
-	for workerIdx, jobID := range maxNode.Data.WorkerState {
-		if jobID == nil {
+	for workerIdx := range maxNode.Data.WorkerState {
+		if maxNode.Data.isWorkerFree(workerIdx) {
 			maxNode.Lock.RUnlock()
 			return maxNode.Data.Id, uint16(workerIdx), true
 		}
@@ -261,7 +291,7 @@ func (m *JobManagerT) findSuitableNode() (nodeID NodeId, workerIdx uint16, found
 	return nodeID, 0, false
 }
 
-func (m *JobManagerT) calculateNodeSuitabilityScore(node *Node) (score uint8, isPossible bool) {
+func (m *JobManagerT) calculateNodeSuitabilityScore(node *Node, abiVersion uint32) (score uint8, isPossible bool) {
 	//
 	// Check that this node is not blacklisted.
 	//
@@ -275,11 +305,26 @@ func (m *JobManagerT) calculateNodeSuitabilityScore(node *Node) (score uint8, is
 		return 0, false
 	}
 
+	//
+	// Check that this node can run the program.
+	//
+
+	if !node.Info.supportsAbi(abiVersion) {
+		log.Tracef(
+			"Node `%s` does not support ABI version %d (supports %d..=%d); excluded from candidates",
+			IdToString(node.Id),
+			abiVersion,
+			node.Info.AbiVersionMin,
+			node.Info.AbiVersionMax,
+		)
+		return 0, false
+	}
+
 	const oneHundred = 100
 
 	amountFreeWorkers := 0
-	for _, worker := range node.WorkerState {
-		if worker == nil {
+	for workerIdx := range node.WorkerState {
+		if node.isWorkerFree(workerIdx) {
 			amountFreeWorkers++
 		}
 	}
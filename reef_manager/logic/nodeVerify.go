@@ -0,0 +1,583 @@
+package logic
+
+import (
+	"fmt"
+	"slices"
+	"time"
+
+	"capnproto.org/go/capnp/v3"
+	"github.com/gorilla/websocket"
+	"github.com/reef-runtime/reef/reef_manager/database"
+	node "github.com/reef-runtime/reef/reef_protocol_node"
+)
+
+//
+// Verification of the state claimed by nodes.
+// A node claims to have reached the state hash `StateHash` after executing `Cycles` instructions from the previous
+// state sync of the job.
+// Another node re-executes the job from that state and reports whether it arrives at the same hash.
+// The claims of a job are verified one after another, so every state the job can resume from and its result are
+// covered.
+//
+
+type VerifyClaim struct {
+	JobId     JobId
+	ClaimedBy NodeId
+	// State of the sync before the claim, the verification starts from here.
+	InterpreterState []byte
+	Cycles           uint64
+	StateHash        []byte
+	// Readings of the wall clock during the claimed instructions, the verifying node returns them to the program.
+	WallClock []uint64
+	// State synced with the claim, the verifying node checks that it hashes to `StateHash` as well.
+	// Empty for the claim on a result.
+	ClaimedState []byte
+	// Result reported with the claim, `nil` for the claim of a state sync.
+	// The result is only stored once its claim was verified.
+	Result *JobResult
+	// Reef ABI version of the job's program, only nodes supporting it can verify the claim.
+	AbiVersion uint32
+	// Whether it was already reported that the claim waits for a node which can verify it.
+	WaitLogged bool
+}
+
+// Appends a claim to the ones waiting for their verification.
+// Consecutive state sync claims of the same node are merged, so the pending claims of a job do not pile up while
+// one is being verified.
+// Nodes clear the wall clock readings in the state at every sync, but a verification only starts from one.
+// So a claim is only merged into one which did not read the wall clock, otherwise the merged state would differ.
+func (j *Job) queueClaim(claim VerifyClaim) {
+	last := len(j.PendingClaims) - 1
+	if last >= 0 && claim.Result == nil && j.PendingClaims[last].Result == nil &&
+		j.PendingClaims[last].ClaimedBy == claim.ClaimedBy && len(j.PendingClaims[last].WallClock) == 0 {
+		j.PendingClaims[last].Cycles += claim.Cycles
+		j.PendingClaims[last].StateHash = claim.StateHash
+		j.PendingClaims[last].WallClock = claim.WallClock
+		j.PendingClaims[last].ClaimedState = claim.ClaimedState
+		return
+	}
+
+	j.PendingClaims = append(j.PendingClaims, claim)
+}
+
+// Whether the job has reported a result which is waiting for its verification.
+func (j *Job) hasPendingResult() bool {
+	if j.Verification != nil && j.Verification.Result != nil {
+		return true
+	}
+
+	for _, claim := range j.PendingClaims {
+		if claim.Result != nil {
+			return true
+		}
+	}
+
+	return false
+}
+
+// Takes the oldest pending claim for its verification, unless a claim of the job is being verified already.
+func (j *Job) nextClaim() *VerifyClaim {
+	if j.Verification != nil || len(j.PendingClaims) == 0 {
+		return nil
+	}
+
+	claim := j.PendingClaims[0]
+	j.PendingClaims = j.PendingClaims[1:]
+	j.Verification = &claim
+
+	return &claim
+}
+
+type VerifyResult struct {
+	WorkerIndex uint16
+	Matches     bool
+	Cycles      uint64
+	FailureKind node.JobFailureKind
+	Error       string
+}
+
+// nolint:funlen
+func toNodeJobVerifyMessage(
+	workerIndex uint32,
+	jobID string,
+	datasetID string,
+	datasets []database.JobDataset,
+	programByteCode []byte,
+	claim VerifyClaim,
+) ([]byte, error) {
+	msg, seg, err := capnp.NewMessage(capnp.SingleSegment(nil))
+	if err != nil {
+		return nil, err
+	}
+
+	toNodeMsg, err := node.NewRootMessageToNode(seg)
+	if err != nil {
+		return nil, err
+	}
+
+	toNodeMsg.SetKind(node.MessageToNodeKind_verifyJob)
+
+	nestedBody, err := node.NewJobVerifyMessage(seg)
+	if err != nil {
+		return nil, err
+	}
+
+	// Worker index.
+	nestedBody.SetWorkerIndex(workerIndex)
+
+	// Job Id.
+	if err := nestedBody.SetJobId(jobID); err != nil {
+		return nil, err
+	}
+
+	// Program Byte Code.
+	if err := nestedBody.SetProgramByteCode(programByteCode); err != nil {
+		return nil, err
+	}
+
+	// Dataset.
+	if err := nestedBody.SetDatasetId(datasetID); err != nil {
+		return nil, err
+	}
+
+	datasetsList, err := nestedBody.NewDatasets(int32(len(datasets)))
+	if err != nil {
+		return nil, err
+	}
+
+	if err := setJobDatasets(datasetsList, datasets); err != nil {
+		return nil, err
+	}
+
+	// Interpreter State.
+	if err := nestedBody.SetInterpreterState(claim.InterpreterState); err != nil {
+		return nil, err
+	}
+
+	// Claim.
+	nestedBody.SetCycles(claim.Cycles)
+	if err := nestedBody.SetStateHash(claim.StateHash); err != nil {
+		return nil, err
+	}
+
+	if err := nestedBody.SetClaimedState(claim.ClaimedState); err != nil {
+		return nil, err
+	}
+
+	wallClock, err := nestedBody.NewWallClock(int32(len(claim.WallClock)))
+	if err != nil {
+		return nil, err
+	}
+
+	for idx, reading := range claim.WallClock {
+		wallClock.Set(idx, reading)
+	}
+
+	if claim.Result != nil {
+		nestedBody.SetClaimsResult(true)
+		nestedBody.SetResultContentType(claim.Result.ContentType)
+		if err := nestedBody.SetResultContents(claim.Result.Contents); err != nil {
+			return nil, err
+		}
+	}
+
+	if err := toNodeMsg.Body().SetVerifyJob(nestedBody); err != nil {
+		return nil, err
+	}
+
+	return msg.Marshal()
+}
+
+//
+// Starts the verification of a claim on a free worker of another node.
+// If all of them are busy, the claim is retried by the job daemon.
+// If no other node can verify claims at all, the claim is trusted, so setups with a single node still finish jobs.
+// If `TrustUnverifiedClaims` is disabled, such a claim stays pending until a verifying node connects instead.
+//
+
+func (m *JobManagerT) StartVerification(claim VerifyClaim) error {
+	started, err := m.startVerificationWithLockingOps(claim)
+	if err != nil {
+		m.retryVerification(claim.JobId)
+		return err
+	}
+
+	if !started {
+		if m.canBeVerified(claim.ClaimedBy, claim.AbiVersion) {
+			m.retryVerification(claim.JobId)
+			return nil
+		}
+
+		if !m.TrustUnverifiedClaims {
+			return m.holdUnverifiableClaim(claim)
+		}
+
+		log.Debugf("Found no node to verify claim of job `%s`, trusting it", claim.JobId)
+
+		if claim.Result != nil {
+			if err := database.AddLog(database.JobLog{
+				Kind:    database.LogKindSystem,
+				Created: time.Now(),
+				Content: "No other node could verify the result, it was accepted without verification.",
+				JobId:   claim.JobId,
+			}); err != nil {
+				m.retryVerification(claim.JobId)
+				return err
+			}
+		}
+
+		return m.acceptClaim(claim)
+	}
+
+	m.updateNodeState()
+
+	return nil
+}
+
+// Starts the verification of the next pending claim of the job, if there is one.
+func (m *JobManagerT) startNextVerification(jobID JobId) error {
+	job, found := m.NonFinishedJobs.Get(jobID)
+	if !found {
+		return nil
+	}
+
+	job.Lock.Lock()
+	claim := job.Data.nextClaim()
+	job.Lock.Unlock()
+
+	if claim == nil {
+		return nil
+	}
+
+	return m.StartVerification(*claim)
+}
+
+// Starts the verification of the next pending claim of every job, called periodically by the job daemon.
+func (m *JobManagerT) startPendingVerifications() {
+	claims := make([]VerifyClaim, 0)
+
+	m.NonFinishedJobs.Lock.RLock()
+	for _, job := range m.NonFinishedJobs.Map {
+		job.Lock.Lock()
+		if claim := job.Data.nextClaim(); claim != nil {
+			claims = append(claims, *claim)
+		}
+		job.Lock.Unlock()
+	}
+	m.NonFinishedJobs.Lock.RUnlock()
+
+	for _, claim := range claims {
+		if err := m.StartVerification(claim); err != nil {
+			log.Errorf("Could not verify claim of job `%s`: %s", claim.JobId, err.Error())
+		}
+	}
+}
+
+func (m *JobManagerT) startVerificationWithLockingOps(claim VerifyClaim) (started bool, err error) {
+	job, found := m.NonFinishedJobs.Get(claim.JobId)
+	if !found {
+		return false, nil
+	}
+
+	job.Lock.RLock()
+	wasmID := job.Data.Data.WasmId
+	datasetID := job.Data.Data.DatasetId
+	datasets := job.Data.Data.Datasets
+	job.Lock.RUnlock()
+
+	nodeID, workerIndex, found := m.findVerifyingNode(claim.ClaimedBy, claim.AbiVersion)
+	if !found {
+		log.Debugf("Found no free node to verify claim of job `%s`", claim.JobId)
+		return false, nil
+	}
+
+	verifyingNode, found := m.Nodes.Get(nodeID)
+	if !found {
+		return false, nil
+	}
+
+	wasmCode, err := m.Compiler.getCached(wasmID)
+	if err != nil {
+		return false, fmt.Errorf("could not load job's Wasm from cache: %s", err.Error())
+	}
+
+	msg, err := toNodeJobVerifyMessage(uint32(workerIndex), claim.JobId, datasetID, datasets, wasmCode, claim)
+	if err != nil {
+		return false, err
+	}
+
+	verifyingNode.Lock.Lock()
+	defer verifyingNode.Lock.Unlock()
+
+	if err := verifyingNode.Data.Conn.WriteMessage(websocket.BinaryMessage, msg); err != nil {
+		return false, err
+	}
+
+	verifyingNode.Data.Verifications[workerIndex] = &claim.JobId
+
+	log.Debugf(
+		"[node] Verifying %d cycles of job `%s` on node `%s`",
+		claim.Cycles,
+		claim.JobId,
+		IdToString(nodeID),
+	)
+
+	return true, nil
+}
+
+// Finds the most suitable node which can verify the claim of the node `claimedBy`.
+func (m *JobManagerT) findVerifyingNode(
+	claimedBy NodeId,
+	abiVersion uint32,
+) (nodeID NodeId, workerIdx uint16, found bool) {
+	m.Nodes.Lock.RLock()
+	defer m.Nodes.Lock.RUnlock()
+
+	maxScore := uint8(0)
+	var maxNode LockedValue[Node]
+
+	for id, node := range m.Nodes.Map {
+		if id == claimedBy {
+			continue
+		}
+
+		node.Lock.RLock()
+		score, isPossible := m.calculateNodeSuitabilityScore(node.Data, abiVersion)
+		canVerify := node.Data.Info.CanVerify
+		node.Lock.RUnlock()
+
+		if isPossible && canVerify && (maxNode.Data == nil || score > maxScore) {
+			maxScore = score
+			maxNode = node
+		}
+	}
+
+	if maxNode.Data == nil {
+		return nodeID, 0, false
+	}
+
+	maxNode.Lock.RLock()
+	defer maxNode.Lock.RUnlock()
+
+	for workerIdx := range maxNode.Data.WorkerState {
+		if maxNode.Data.isWorkerFree(workerIdx) {
+			return maxNode.Data.Id, uint16(workerIdx), true
+		}
+	}
+
+	return nodeID, 0, false
+}
+
+// Whether any node other than `claimedBy` can verify claims of programs with the given ABI version, even if all of
+// its workers are busy right now.
+func (m *JobManagerT) canBeVerified(claimedBy NodeId, abiVersion uint32) bool {
+	m.Nodes.Lock.RLock()
+	defer m.Nodes.Lock.RUnlock()
+
+	for id, node := range m.Nodes.Map {
+		node.Lock.RLock()
+		canVerify := node.Data.Info.CanVerify && node.Data.Info.supportsAbi(abiVersion) &&
+			!slices.Contains(m.NodesBlackList, node.Data.Info.Name)
+		node.Lock.RUnlock()
+
+		if id != claimedBy && canVerify {
+			return true
+		}
+	}
+
+	return false
+}
+
+// Keeps a claim which no other node can verify pending until a verifying node connects.
+// This is reported once per claim, for the claim of a result in the job log as well.
+func (m *JobManagerT) holdUnverifiableClaim(claim VerifyClaim) error {
+	if claim.WaitLogged {
+		m.retryVerification(claim.JobId)
+		return nil
+	}
+
+	log.Warnf("Found no node to verify claim of job `%s`, holding it until one connects", claim.JobId)
+
+	if claim.Result != nil {
+		if err := database.AddLog(database.JobLog{
+			Kind:    database.LogKindSystem,
+			Created: time.Now(),
+			Content: "The result is held back until a node connects which can verify it.",
+			JobId:   claim.JobId,
+		}); err != nil {
+			m.retryVerification(claim.JobId)
+			return err
+		}
+	}
+
+	if job, found := m.NonFinishedJobs.Get(claim.JobId); found {
+		job.Lock.Lock()
+		if job.Data.Verification != nil {
+			job.Data.Verification.WaitLogged = true
+		}
+		job.Lock.Unlock()
+	}
+
+	m.retryVerification(claim.JobId)
+
+	return nil
+}
+
+// Puts the claim being verified back in front of the pending claims of the job, so it is verified again later.
+func (m *JobManagerT) retryVerification(jobID JobId) {
+	job, found := m.NonFinishedJobs.Get(jobID)
+	if !found {
+		return
+	}
+
+	job.Lock.Lock()
+	if claim := job.Data.Verification; claim != nil {
+		job.Data.PendingClaims = append([]VerifyClaim{*claim}, job.Data.PendingClaims...)
+		job.Data.Verification = nil
+	}
+	job.Lock.Unlock()
+}
+
+// Accepts a verified claim, or one which no other node can verify.
+// The claim on a result finishes the job, unless the job has moved on to another node in the meantime.
+func (m *JobManagerT) acceptClaim(claim VerifyClaim) error {
+	job, found := m.NonFinishedJobs.Get(claim.JobId)
+	if !found {
+		return nil
+	}
+
+	job.Lock.Lock()
+	job.Data.Verification = nil
+	workerNodeID := job.Data.WorkerNodeID
+	job.Lock.Unlock()
+
+	if claim.Result == nil {
+		return m.startNextVerification(claim.JobId)
+	}
+
+	if workerNodeID != nil && *workerNodeID != claim.ClaimedBy {
+		log.Warnf("Job `%s` has moved to another node before its result could be accepted", claim.JobId)
+		return nil
+	}
+
+	if err := m.finishJob(claim.ClaimedBy, *claim.Result); err != nil {
+		return err
+	}
+
+	m.updateSingleJobState(claim.JobId)
+	m.updateNodeState()
+
+	return nil
+}
+
+//
+// Processes the result of a verification.
+// If the claim turns out to be wrong, the claiming node is disconnected and the job resumes from the state before
+// the claim.
+//
+
+func (m *JobManagerT) ProcessVerifyResult(nodeID NodeId, result VerifyResult) error {
+	thisNode, found := m.Nodes.Get(nodeID)
+	if !found {
+		return fmt.Errorf("process verify result: node Id is illegal: `%s`", IdToString(nodeID))
+	}
+
+	thisNode.Lock.Lock()
+	if int(result.WorkerIndex) >= len(thisNode.Data.Verifications) {
+		thisNode.Lock.Unlock()
+		return fmt.Errorf("process verify result: worker index is illegal: %d", result.WorkerIndex)
+	}
+
+	jobID := thisNode.Data.Verifications[result.WorkerIndex]
+	thisNode.Data.Verifications[result.WorkerIndex] = nil
+	thisNode.Lock.Unlock()
+
+	if jobID == nil {
+		return fmt.Errorf("process verify result: worker %d is not verifying", result.WorkerIndex)
+	}
+
+	m.updateNodeState()
+
+	job, found := m.NonFinishedJobs.Get(*jobID)
+	if !found {
+		log.Debugf("Verify result: job `%s` has finished in the meantime", *jobID)
+		return nil
+	}
+
+	job.Lock.RLock()
+	claim := job.Data.Verification
+	job.Lock.RUnlock()
+
+	if claim == nil {
+		return nil
+	}
+
+	switch result.FailureKind {
+	case node.JobFailureKind_none:
+		if result.Matches {
+			log.Debugf("Verified claim of job `%s` by node `%s`", *jobID, IdToString(claim.ClaimedBy))
+			return m.acceptClaim(*claim)
+		}
+	case node.JobFailureKind_trap, node.JobFailureKind_hostLimit, node.JobFailureKind_invalidProgram:
+		// The claimed state can not be reached.
+	case node.JobFailureKind_aborted, node.JobFailureKind_internal:
+		log.Warnf("Verification of job `%s` did not finish, retrying later: %s", *jobID, result.Error)
+		m.retryVerification(*jobID)
+		return nil
+	}
+
+	job.Lock.Lock()
+	job.Data.Verification = nil
+	job.Lock.Unlock()
+
+	return m.rejectClaim(job, *claim, result)
+}
+
+func (m *JobManagerT) rejectClaim(job LockedValue[Job], claim VerifyClaim, result VerifyResult) error {
+	const rejectMsg = "State synced by node `%s` could not be verified (%d of %d cycles), " +
+		"resuming from the state before."
+
+	log.Warnf(
+		"[node] Rejecting state sync of job `%s` by node `%s`: verification ran %d of %d cycles: %s",
+		claim.JobId,
+		IdToString(claim.ClaimedBy),
+		result.Cycles,
+		claim.Cycles,
+		result.Error,
+	)
+
+	if err := database.AddLog(database.JobLog{
+		Kind:    database.LogKindSystem,
+		Created: time.Now(),
+		Content: fmt.Sprintf(rejectMsg, IdToString(claim.ClaimedBy), result.Cycles, claim.Cycles),
+		JobId:   claim.JobId,
+	}); err != nil {
+		return err
+	}
+
+	job.Lock.Lock()
+	if job.Data.WorkerNodeID == nil || *job.Data.WorkerNodeID == claim.ClaimedBy {
+		job.Data.InterpreterState = claim.InterpreterState
+		// The pending claims build on the rejected state.
+		job.Data.PendingClaims = nil
+	} else {
+		log.Warnf("Job `%s` has moved to another node before its state could be rolled back", claim.JobId)
+	}
+	job.Lock.Unlock()
+
+	// The node is not trusted anymore, its jobs are parked once the connection is dropped.
+	claimingNode, found := m.Nodes.Get(claim.ClaimedBy)
+	if !found {
+		return nil
+	}
+
+	claimingNode.Lock.Lock()
+	err := claimingNode.Data.Conn.Close()
+	claimingNode.Lock.Unlock()
+
+	if err != nil {
+		log.Warnf("[node] Could not close connection to node `%s`: %s", IdToString(claim.ClaimedBy), err.Error())
+	}
+
+	return nil
+}
@@ -0,0 +1,105 @@
+package logic
+
+import (
+	"testing"
+
+	"github.com/stretchr/testify/assert"
+)
+
+func syncClaim(claimedBy NodeId, cycles uint64, wallClock []uint64) VerifyClaim {
+	return VerifyClaim{
+		JobId:            "job",
+		ClaimedBy:        claimedBy,
+		InterpreterState: nil,
+		Cycles:           cycles,
+		StateHash:        []byte{byte(cycles)},
+		WallClock:        wallClock,
+		ClaimedState:     []byte{byte(cycles)},
+		Result:           nil,
+		AbiVersion:       defaultAbiVersion,
+		WaitLogged:       false,
+	}
+}
+
+func TestQueueClaimMergesSyncs(t *testing.T) {
+	job := Job{}
+
+	job.queueClaim(syncClaim(NodeId{1}, 10, nil))
+	job.queueClaim(syncClaim(NodeId{1}, 20, nil))
+	// The merged claim did not read the wall clock, so the readings of the next one can be appended.
+	job.queueClaim(syncClaim(NodeId{1}, 30, []uint64{7}))
+
+	assert.Len(t, job.PendingClaims, 1)
+	assert.Equal(t, uint64(60), job.PendingClaims[0].Cycles)
+	assert.Equal(t, []byte{30}, job.PendingClaims[0].StateHash)
+	assert.Equal(t, []uint64{7}, job.PendingClaims[0].WallClock)
+
+	// Claims of another node are never merged.
+	job.queueClaim(syncClaim(NodeId{2}, 40, nil))
+	assert.Len(t, job.PendingClaims, 2)
+}
+
+func TestQueueClaimKeepsWallClockSyncsApart(t *testing.T) {
+	job := Job{}
+
+	job.queueClaim(syncClaim(NodeId{1}, 10, []uint64{1, 2}))
+	job.queueClaim(syncClaim(NodeId{1}, 20, []uint64{3}))
+
+	// The node cleared the readings at the sync between the claims, each one is verified on its own.
+	assert.Len(t, job.PendingClaims, 2)
+	assert.Equal(t, []uint64{1, 2}, job.PendingClaims[0].WallClock)
+	assert.Equal(t, uint64(10), job.PendingClaims[0].Cycles)
+	assert.Equal(t, []uint64{3}, job.PendingClaims[1].WallClock)
+	assert.Equal(t, uint64(20), job.PendingClaims[1].Cycles)
+
+	// A claim without readings is not merged into one with readings either.
+	job.queueClaim(syncClaim(NodeId{1}, 30, nil))
+	assert.Len(t, job.PendingClaims, 3)
+}
+
+func singleNodeManager(trustUnverifiedClaims bool) (*JobManagerT, NodeId, LockedValue[Job]) {
+	m := &JobManagerT{
+		Nodes:                 newLockedMap[NodeId, LockedValue[Node]](),
+		NonFinishedJobs:       newLockedMap[JobId, LockedValue[Job]](),
+		TrustUnverifiedClaims: trustUnverifiedClaims,
+	}
+	nodeID := addTestNode(m, "single", 1, 1)
+
+	job := NewLockedValue(Job{})
+	job.Data.Data.Id = "job"
+	m.NonFinishedJobs.Insert("job", job)
+
+	return m, nodeID, job
+}
+
+func TestSingleNodeClaimsAreTrusted(t *testing.T) {
+	m, nodeID, job := singleNodeManager(true)
+
+	job.Data.queueClaim(syncClaim(nodeID, 10, nil))
+	claim := job.Data.nextClaim()
+	assert.NotNil(t, claim)
+
+	// The only node claimed the state itself, so it is accepted without a verification.
+	assert.NoError(t, m.StartVerification(*claim))
+	assert.Nil(t, job.Data.Verification)
+	assert.Empty(t, job.Data.PendingClaims)
+}
+
+func TestSingleNodeClaimsAreHeld(t *testing.T) {
+	m, nodeID, job := singleNodeManager(false)
+
+	job.Data.queueClaim(syncClaim(nodeID, 10, nil))
+	claim := job.Data.nextClaim()
+	assert.NotNil(t, claim)
+
+	assert.NoError(t, m.StartVerification(*claim))
+	assert.Nil(t, job.Data.Verification)
+	assert.Len(t, job.Data.PendingClaims, 1)
+	assert.True(t, job.Data.PendingClaims[0].WaitLogged)
+
+	// Retries by the job daemon keep the claim pending.
+	m.startPendingVerifications()
+	assert.Nil(t, job.Data.Verification)
+	assert.Len(t, job.Data.PendingClaims, 1)
+	assert.Equal(t, uint64(10), job.Data.PendingClaims[0].Cycles)
+}
@@ -31,6 +31,8 @@ type Config struct {
 	NodeNameBlacklist string `env:"REEF_NODES_BLACKLIST"      env-required:"true"`
 	Database          database.DatabaseConfig
 	CompilerConfig    logic.CompilerConfig
+	// Accept claims of nodes if no other node can verify them, instead of waiting for one to connect.
+	TrustUnverifiedClaims bool `env:"REEF_TRUST_UNVERIFIED_CLAIMS" env-default:"true"`
 }
 
 //go:embed database/migrations/*.sql
@@ -143,6 +145,7 @@ func sail(logger *logrus.Logger) error {
 		config.TemplatesDirPath,
 		config.MaxJobRuntime,
 		nodesBlackList,
+		config.TrustUnverifiedClaims,
 	); err != nil {
 		logger.Fatalf("Initializing logic package failed: %s", err.Error())
 		return errors.New("system error")
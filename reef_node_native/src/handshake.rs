@@ -4,6 +4,7 @@ use log::debug;
 use tungstenite::Message;
 
 use reef_protocol_node::message_capnp::{self, message_to_node, MessageFromNodeKind, MessageToNodeKind};
+use reef_wasm_interface::{REEF_ABI_MIN_VERSION, REEF_ABI_VERSION};
 
 use crate::WSConn;
 
@@ -107,6 +108,9 @@ fn ack_handshake_message(num_workers: u16, node_name: &str) -> Result<Message> {
 
     handshake_response.set_num_workers(num_workers);
     handshake_response.set_node_name(node_name);
+    handshake_response.set_abi_version_min(REEF_ABI_MIN_VERSION);
+    handshake_response.set_abi_version_max(REEF_ABI_VERSION);
+    handshake_response.set_can_verify(true);
 
     let mut buffer = vec![];
     capnp::serialize::write_message(&mut buffer, &message).with_context(|| "could not encode message")?;
@@ -1,4 +1,5 @@
 use std::fmt::Display;
+use std::path::{Path, PathBuf};
 use std::sync::atomic::Ordering;
 use std::thread;
 use std::time::{Duration, Instant};
@@ -8,25 +9,34 @@ use std::{
 };
 
 use anyhow::{bail, Context, Result};
-use capnp::{message::ReaderOptions, serialize};
+use capnp::{message::ReaderOptions, serialize, struct_list, text};
 use clap::Parser;
 use log::{debug, error, info, trace, warn};
-use reef_protocol_node::message_capnp::{MessageFromNodeKind, ResultContentType};
+use reef_protocol_node::message_capnp::{JobFailureKind, MessageFromNodeKind, ResultContentType};
 use tungstenite::{stream::MaybeTlsStream, Message, WebSocket};
 use url::Url;
 
 use reef_protocol_node::message_capnp::{
+    job_dataset,
     message_to_node::{self, body},
     MessageToNodeKind,
 };
+use reef_wasm_interface::ReefDataset;
 
 mod handshake;
+mod module_cache;
+mod pool;
+mod record;
+mod verify;
 mod worker;
-use worker::{FromWorkerMessage, Job, WorkerData};
+use module_cache::ModuleCache;
+use pool::WorkerPool;
+use verify::{Verification, VerifyClaim, VerifyOutcome, VerifyRequest};
+use worker::{FromWorkerMessage, HostCalls, Job, JobTask, WorkerData};
 
 type WSConn = WebSocket<MaybeTlsStream<TcpStream>>;
 
-use crate::worker::{spawn_worker_thread, JobResult, WorkerSignal};
+use crate::worker::{JobResult, WorkerSignal};
 
 const MAIN_THREAD_SLEEP: Duration = Duration::from_millis(10);
 
@@ -34,8 +44,9 @@ const MAIN_THREAD_SLEEP: Duration = Duration::from_millis(10);
 #[derive(Parser, Debug)]
 #[command(version, about, long_about = None)]
 struct Args {
+    #[arg(required_unless_present = "replay")]
     // Base url of the manager.
-    manager_url: Url,
+    manager_url: Option<Url>,
 
     #[arg(short, long)]
     // Name to be sent to the manager (default is the hostname + extra infos)
@@ -52,22 +63,53 @@ struct Args {
     #[arg(short = 'w', long)]
     // How many concurrent workers to offer, default is the number of CPUs.
     num_workers: Option<usize>,
+
+    #[arg(long)]
+    // Directory of the cache of parsed programs, default is `reef/module_cache` in the cache directory of the user.
+    module_cache_dir: Option<PathBuf>,
+
+    #[arg(long)]
+    // Parse every program on job start instead of using the module cache.
+    no_module_cache: bool,
+
+    #[arg(long)]
+    // Record the host calls of every job into this directory, to reproduce them with `--replay`.
+    record_dir: Option<PathBuf>,
+
+    #[arg(long, requires_all = ["program", "dataset"])]
+    // Instead of connecting to a manager, replay a recording of host calls and check that execution matches.
+    replay: Option<PathBuf>,
+
+    #[arg(long, requires = "replay")]
+    // Program to replay.
+    program: Option<PathBuf>,
+
+    #[arg(long, requires = "replay", value_name = "[NAME=]PATH")]
+    // Dataset of the replayed job, repeated for every dataset in order. Without a name the dataset is unnamed.
+    dataset: Vec<String>,
+
+    #[arg(long, requires = "replay")]
+    // Snapshot the recording was started from, if the job was resumed.
+    state: Option<PathBuf>,
 }
 
 const SYNC_DELAY_MILLIS: u64 = 1337;
 const PING_DELAY_MILLIS: u64 = 10000;
 
-struct NodeState(Vec<Job>);
+struct NodeState {
+    jobs: Vec<Job>,
+    verifications: Vec<Verification>,
+}
 
 impl Display for NodeState {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(f, "[{}]", self.0.iter().map(|w| { w.job_id.clone() }).collect::<Vec<String>>().join(", "))
+        write!(f, "[{}]", self.jobs.iter().map(|w| { w.job_id.clone() }).collect::<Vec<String>>().join(", "))
     }
 }
 
 impl NodeState {
     fn new(num_workers: usize) -> Self {
-        Self(Vec::with_capacity(num_workers))
+        Self { jobs: Vec::with_capacity(num_workers), verifications: Vec::new() }
     }
 }
 
@@ -76,15 +118,20 @@ fn main() -> anyhow::Result<()> {
 
     env_logger::builder().filter_level(log::LevelFilter::Debug).parse_default_env().init();
 
+    if let (Some(recording), Some(program)) = (&args.replay, &args.program) {
+        return record::replay(recording, program, &args.dataset, args.state.as_deref());
+    }
+    let manager_url = args.manager_url.expect("clap requires the manager url without --replay");
+
     //
     // Create connection.
     //
-    let scheme = match args.manager_url.scheme() {
+    let scheme = match manager_url.scheme() {
         "https" => "wss",
         _ => "ws",
     };
 
-    let mut connect_url = args.manager_url.clone();
+    let mut connect_url = manager_url.clone();
     connect_url.set_path(reef_wasm_interface::NODE_REGISTER_PATH);
     connect_url.set_scheme(scheme).unwrap();
 
@@ -128,6 +175,19 @@ fn main() -> anyhow::Result<()> {
     }
 
     let mut state = NodeState::new(num_workers);
+    let pool = WorkerPool::new(num_workers);
+
+    let module_cache_dir = match args.no_module_cache {
+        true => None,
+        false => args.module_cache_dir.or_else(|| {
+            let dir = dirs::cache_dir().map(|dir| dir.join("reef").join("module_cache"));
+            if dir.is_none() {
+                warn!("Module cache disabled, no cache directory found for the current user");
+            }
+            dir
+        }),
+    };
+    let module_cache = Arc::new(ModuleCache::new(module_cache_dir));
 
     let sync_wait_duration = Duration::from_millis(args.sync_delay_millis.unwrap_or(SYNC_DELAY_MILLIS));
 
@@ -146,7 +206,7 @@ fn main() -> anyhow::Result<()> {
         match socket.read() {
             Ok(msg) => {
                 state
-                    .handle_websocket(msg, args.manager_url.as_str())
+                    .handle_websocket(msg, manager_url.as_str(), &pool, &module_cache, args.record_dir.as_deref())
                     .with_context(|| "evaluating incoming message")?;
                 worked = true;
             }
@@ -167,7 +227,7 @@ fn main() -> anyhow::Result<()> {
         // Worker channels communications.
         //
         let mut finished_worker_indices = vec![];
-        for job in state.0.iter_mut() {
+        for job in state.jobs.iter_mut() {
             // read channel until empty
             loop {
                 let msg = job.channel_from_worker.try_recv();
@@ -190,8 +250,8 @@ fn main() -> anyhow::Result<()> {
                     Ok(FromWorkerMessage::Progress(new)) => {
                         job.progress = new;
                     }
-                    Ok(FromWorkerMessage::State(interpreter_state)) => {
-                        job.flush_state(&interpreter_state, &mut socket)?;
+                    Ok(FromWorkerMessage::State(interpreter_state, claim)) => {
+                        job.flush_state(&interpreter_state, claim.as_ref(), &mut socket)?;
                         job.last_sync = Instant::now();
                         job.sync_running = false;
                     }
@@ -214,30 +274,39 @@ fn main() -> anyhow::Result<()> {
 
         // Remove all finished jobs.
         for job_idx in finished_worker_indices {
-            let idx_in_vec = state.0.iter().position(|w| w.worker_index == job_idx).unwrap();
+            let idx_in_vec = state.jobs.iter().position(|w| w.worker_index == job_idx).unwrap();
 
-            let mut job = state.0.remove(idx_in_vec);
+            let mut job = state.jobs.remove(idx_in_vec);
             job.progress = 1.0;
 
             // Transfer any logs and the final progress reading to the manager.
             // State can be empty since it is not required anymore.
-            job.flush_state(&[], &mut socket)?;
+            job.flush_state(&[], None, &mut socket)?;
 
             let worker_index = job.worker_index as u16;
 
-            let thread_res = job.handle.join().expect("worker thread panic'ed, this is a bug");
+            let thread_res = job.result_receiver.recv().expect("worker thread panic'ed, this is a bug");
 
             let job_result = match thread_res {
-                Ok((content_type, contents)) => {
+                Ok(((content_type, contents), claim)) => {
                     info!("==> Job has executed successfully!");
-                    JobResult { success: true, content_type, contents }
+                    JobResult {
+                        success: true,
+                        failure_kind: JobFailureKind::None,
+                        content_type,
+                        contents,
+                        claim: Some(claim),
+                    }
                 }
                 Err(err) => {
-                    info!("==> Job failed: {err:?}");
+                    let failure_kind = reef_wasm_interface::failure_kind(&err);
+                    info!("==> Job failed ({failure_kind:?}): {err}");
                     JobResult {
                         success: false,
+                        failure_kind,
                         content_type: ResultContentType::StringPlain,
-                        contents: format!("{:?}", err).into_bytes(),
+                        contents: err.to_string().into_bytes(),
+                        claim: None,
                     }
                 }
             };
@@ -246,6 +315,36 @@ fn main() -> anyhow::Result<()> {
                 .with_context(|| "could not send final job result to manager")?;
         }
 
+        // Report finished verifications.
+        let mut idx = 0;
+        while idx < state.verifications.len() {
+            let verification = &state.verifications[idx];
+            let res = match verification.result_receiver.try_recv() {
+                Ok(res) => res,
+                Err(mpsc::TryRecvError::Empty) => {
+                    idx += 1;
+                    continue;
+                }
+                // The thread died without reporting, the manager retries internal failures on another node.
+                Err(mpsc::TryRecvError::Disconnected) => {
+                    warn!("Verification thread of job '{}' exited without a result", verification.job_id);
+                    Err(reef_interpreter::Error::Other(
+                        "internal error, verification thread exited without a result".to_string(),
+                    ))
+                }
+            };
+            let verification = state.verifications.remove(idx);
+            worked = true;
+
+            match &res {
+                Ok(outcome) => info!("==> Verified job '{}': matches={}", verification.job_id, outcome.matches),
+                Err(err) => info!("==> Verification of job '{}' failed: {err}", verification.job_id),
+            }
+
+            send_verify_result(verification.worker_index as u16, &res, &mut socket)
+                .with_context(|| "could not send verification result to manager")?;
+        }
+
         flush_nonblocking_ws(&mut socket)?;
 
         if !worked {
@@ -265,8 +364,48 @@ fn send_job_result(worker_index: u16, res: &JobResult, socket: &mut WSConn) -> a
 
     state_result.set_worker_index(worker_index);
     state_result.set_success(res.success);
+    state_result.set_failure_kind(res.failure_kind);
     state_result.set_contents(&res.contents);
     state_result.set_content_type(res.content_type);
+    if let Some(claim) = &res.claim {
+        state_result.set_cycles(claim.cycles);
+        state_result.set_state_hash(&claim.state_hash);
+        state_result.set_wall_clock(&claim.wall_clock[..])?;
+    }
+
+    let mut buffer = vec![];
+
+    capnp::serialize::write_message(&mut buffer, &message).with_context(|| "could not encode message")?;
+
+    write_nonblocking_ws(socket, Message::Binary(buffer))?;
+
+    Ok(())
+}
+
+fn send_verify_result(
+    worker_index: u16,
+    res: &Result<VerifyOutcome, reef_interpreter::Error>,
+    socket: &mut WSConn,
+) -> anyhow::Result<()> {
+    let mut message = capnp::message::Builder::new_default();
+    let mut encapsulating_message: reef_protocol_node::message_capnp::message_from_node::Builder = message.init_root();
+    encapsulating_message.set_kind(MessageFromNodeKind::VerifyResult);
+    let mut verify_result = encapsulating_message.get_body().init_verify_result();
+
+    verify_result.set_worker_index(worker_index);
+    match res {
+        Ok(outcome) => {
+            verify_result.set_matches(outcome.matches);
+            verify_result.set_cycles(outcome.cycles);
+            verify_result.set_state_hash(&outcome.state_hash);
+            verify_result.set_failure_kind(JobFailureKind::None);
+        }
+        Err(err) => {
+            verify_result.set_matches(false);
+            verify_result.set_failure_kind(reef_wasm_interface::failure_kind(err));
+            verify_result.set_error(err.to_string().as_str());
+        }
+    }
 
     let mut buffer = vec![];
 
@@ -279,10 +418,18 @@ fn send_job_result(worker_index: u16, res: &JobResult, socket: &mut WSConn) -> a
 
 impl NodeState {
     fn worker_exists(&self, worker_index: usize) -> bool {
-        self.0.iter().any(|w| w.worker_index == worker_index)
+        self.jobs.iter().any(|w| w.worker_index == worker_index)
+            || self.verifications.iter().any(|v| v.worker_index == worker_index)
     }
 
-    fn handle_websocket(&mut self, msg: tungstenite::Message, manager_url: &str) -> Result<()> {
+    fn handle_websocket(
+        &mut self,
+        msg: tungstenite::Message,
+        manager_url: &str,
+        pool: &WorkerPool,
+        module_cache: &Arc<ModuleCache>,
+        record_dir: Option<&Path>,
+    ) -> Result<()> {
         let action = match msg {
             Message::Text(_) => bail!("received a text message, this should never happen"),
             Message::Binary(bin) => handle_binary(&bin)?,
@@ -299,10 +446,15 @@ impl NodeState {
 
         match action {
             Action::StartJob(request) => {
-                if let Err(err) = self.start_job(request, manager_url) {
+                if let Err(err) = self.start_job(request, manager_url, pool, module_cache, record_dir) {
                     warn!("Failed to start job: {err}");
                 }
             }
+            Action::VerifyJob(request) => {
+                if let Err(err) = self.start_verification(request, manager_url, module_cache) {
+                    warn!("Failed to start verification: {err}");
+                }
+            }
             Action::AbortJob(job_id) => {
                 if let Err(e) = self.abort_job(&job_id) {
                     error!("could not abort job: {e}")
@@ -319,16 +471,27 @@ impl NodeState {
     }
 
     fn abort_job(&mut self, job_id: &str) -> Result<()> {
-        let Some(job) = self.0.iter().find(|j| j.job_id == job_id) else {
-            bail!("job to be aborted with ID {job_id} not found on this node")
-        };
+        let signal = self
+            .jobs
+            .iter()
+            .map(|j| (&j.job_id, &j.signal_to_worker))
+            .chain(self.verifications.iter().map(|v| (&v.job_id, &v.signal_to_worker)))
+            .find_map(|(id, signal)| (id == job_id).then_some(signal));
+        let Some(signal) = signal else { bail!("job to be aborted with ID {job_id} not found on this node") };
 
-        job.signal_to_worker.store(WorkerSignal::ABORT, Ordering::Relaxed);
+        signal.store(WorkerSignal::ABORT, Ordering::Relaxed);
 
         Ok(())
     }
 
-    fn start_job(&mut self, request: StartJobRequest, manager_url: &str) -> Result<()> {
+    fn start_job(
+        &mut self,
+        request: StartJobRequest,
+        manager_url: &str,
+        pool: &WorkerPool,
+        module_cache: &Arc<ModuleCache>,
+        record_dir: Option<&Path>,
+    ) -> Result<()> {
         // 1. Check if the worker exists and is available.
         if self.worker_exists(request.worker_index) {
             bail!("requested illegal worker index");
@@ -348,17 +511,28 @@ impl NodeState {
 
         let state = if request.interpreter_state.is_empty() { None } else { Some(request.interpreter_state) };
 
-        debug!("Fetching dataset '{}'...", request.dataset_id);
+        let datasets = fetch_datasets(manager_url, &request.datasets)?;
 
-        let url = format!("{}api/dataset/{}", manager_url, request.dataset_id);
-        let resp = reqwest::blocking::get(url)?;
-        let dataset = resp.bytes()?.to_vec();
+        let host_calls = match record_dir {
+            Some(dir) => HostCalls::Record(record::start_recording(dir, &request.job_id, state.as_deref())?),
+            None => HostCalls::Live,
+        };
+
+        let (result_sender, result_receiver) = mpsc::channel();
 
-        let handle = spawn_worker_thread(
+        pool.submit(JobTask::new(
             signal.clone(),
-            request.job_id.clone(),
-            WorkerData { sender: to_master_sender, program: request.program_byte_code, state, dataset },
-        );
+            WorkerData {
+                job_id: request.job_id.clone(),
+                sender: to_master_sender,
+                module_cache: module_cache.clone(),
+                program: request.program_byte_code,
+                state,
+                datasets,
+                host_calls,
+            },
+            result_sender,
+        ));
 
         let job = Job {
             last_sync: Instant::now(),
@@ -370,22 +544,77 @@ impl NodeState {
             signal_to_worker: signal.clone(),
             channel_from_worker: from_worker_receiver,
 
-            handle,
+            result_receiver,
 
             logs_to_be_flushed: Vec::new(),
             progress: request.progress,
         };
 
-        self.0.push(job);
+        self.jobs.push(job);
 
         Ok(())
     }
+
+    fn start_verification(
+        &mut self,
+        request: VerifyRequest,
+        manager_url: &str,
+        module_cache: &Arc<ModuleCache>,
+    ) -> Result<()> {
+        if self.worker_exists(request.worker_index) {
+            bail!("requested illegal worker index");
+        }
+
+        info!(
+            "==> Verifying {} cycles of job '{}' on worker {}...",
+            request.claim.cycles, request.job_id, request.worker_index
+        );
+
+        let datasets = fetch_datasets(manager_url, &request.datasets)?;
+        self.verifications.push(Verification::spawn(request, module_cache.clone(), datasets));
+
+        Ok(())
+    }
+}
+
+/// Fetch the datasets of a job, given as pairs of name and dataset ID.
+fn fetch_datasets(manager_url: &str, datasets: &[(String, String)]) -> Result<Vec<ReefDataset>> {
+    datasets
+        .iter()
+        .map(|(name, dataset_id)| {
+            debug!("Fetching dataset '{dataset_id}' as '{name}'...");
+
+            let url = format!("{manager_url}api/dataset/{dataset_id}");
+            let resp = reqwest::blocking::get(url)?;
+            Ok(ReefDataset::new(name.as_str(), resp.bytes()?.to_vec()))
+        })
+        .collect()
+}
+
+/// Read the names and IDs of the datasets of a job.
+///
+/// Managers which don't send a list of datasets only give the job a single unnamed one.
+fn read_datasets(
+    dataset_id: text::Reader<'_>,
+    datasets: struct_list::Reader<'_, job_dataset::Owned>,
+) -> Result<Vec<(String, String)>> {
+    let read_text =
+        |text: text::Reader<'_>| String::from_utf8(text.0.to_vec()).with_context(|| "illegal dataset encoding");
+
+    if datasets.is_empty() {
+        return Ok(vec![(String::new(), read_text(dataset_id)?)]);
+    }
+    datasets
+        .iter()
+        .map(|dataset| Ok((read_text(dataset.get_name()?)?, read_text(dataset.get_dataset_id()?)?)))
+        .collect()
 }
 
 struct StartJobRequest {
     worker_index: usize,
     job_id: String,
-    dataset_id: String,
+    /// Name and ID of every dataset, in the order the program sees them.
+    datasets: Vec<(String, String)>,
     progress: f32,
 
     program_byte_code: Vec<u8>,
@@ -395,6 +624,7 @@ struct StartJobRequest {
 enum Action {
     Pong,
     StartJob(StartJobRequest),
+    VerifyJob(VerifyRequest),
     AbortJob(String),
     Disconnect,
 }
@@ -420,19 +650,44 @@ fn handle_binary(bin_slice: &[u8]) -> Result<Action> {
             let body = body?;
             let job_id = String::from_utf8(body.get_job_id()?.0.to_vec()).with_context(|| "illegal job ID encoding")?;
 
-            let dataset_id =
-                String::from_utf8(body.get_dataset_id()?.0.to_vec()).with_context(|| "illegal dataset ID encoding")?;
+            let datasets = read_datasets(body.get_dataset_id()?, body.get_datasets()?)?;
 
             Ok(Action::StartJob(StartJobRequest {
                 worker_index: body.get_worker_index() as usize,
                 job_id,
-                dataset_id,
+                datasets,
                 progress: body.get_progress(),
 
                 program_byte_code: body.get_program_byte_code()?.to_vec(),
                 interpreter_state: body.get_interpreter_state()?.to_vec(),
             }))
         }
+        (MessageToNodeKind::VerifyJob, body::Which::VerifyJob(body)) => {
+            let body = body?;
+            let job_id = String::from_utf8(body.get_job_id()?.0.to_vec()).with_context(|| "illegal job ID encoding")?;
+
+            let datasets = read_datasets(body.get_dataset_id()?, body.get_datasets()?)?;
+
+            Ok(Action::VerifyJob(VerifyRequest {
+                worker_index: body.get_worker_index() as usize,
+                job_id,
+                datasets,
+
+                program_byte_code: body.get_program_byte_code()?.to_vec(),
+                interpreter_state: body.get_interpreter_state()?.to_vec(),
+                claim: VerifyClaim {
+                    cycles: body.get_cycles(),
+                    state_hash: body.get_state_hash()?.to_vec(),
+                    state: body.get_claimed_state()?.to_vec(),
+                    wall_clock: body.get_wall_clock()?.iter().collect(),
+                    result: if body.get_claims_result() {
+                        Some((body.get_result_content_type()?, body.get_result_contents()?.to_vec()))
+                    } else {
+                        None
+                    },
+                },
+            }))
+        }
         (MessageToNodeKind::AbortJob, body::Which::AbortJob(body)) => {
             let body = body?;
             let job_id = String::from_utf8(body.get_job_id()?.0.to_vec()).with_context(|| "illegal job ID encoding")?;
@@ -0,0 +1,118 @@
+use std::collections::HashSet;
+use std::fs;
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+use log::{debug, warn};
+use reef_interpreter::{parse_bytes_lazy, Module};
+use sha2::{Digest, Sha256};
+
+/// On-disk cache of parsed and validated modules, keyed by the SHA-256 hash of the program.
+///
+/// Resumed jobs usually run a program this node has seen before,
+/// loading the cached module skips validation and translation of the Wasm bytes.
+/// On a miss functions are translated on their first call, so large programs start quickly either way,
+/// while a fully translated copy is written to the cache in the background.
+#[derive(Debug)]
+pub(crate) struct ModuleCache {
+    dir: Option<PathBuf>,
+    /// Hashes of the programs currently translated and written in the background.
+    in_flight: Arc<Mutex<HashSet<[u8; 32]>>>,
+}
+
+impl ModuleCache {
+    /// Cache modules in `dir`, `None` disables the cache.
+    ///
+    /// Cached modules are not validated again, so `dir` is restricted to the user running the node.
+    pub(crate) fn new(dir: Option<PathBuf>) -> Self {
+        let dir = dir.and_then(|dir| match create_private_dir(&dir) {
+            Ok(()) => Some(dir),
+            Err(err) => {
+                warn!("Module cache disabled, could not create private directory {}: {err}", dir.display());
+                None
+            }
+        });
+
+        Self { dir, in_flight: Arc::default() }
+    }
+
+    /// Load the module of `program` from the cache or parse it and store it in the cache.
+    pub(crate) fn load(&self, program: &[u8]) -> Result<Module, reef_interpreter::Error> {
+        let Some(dir) = &self.dir else {
+            return parse_bytes_lazy(program);
+        };
+
+        let hash = Sha256::digest(program);
+        let path = dir.join(format!("{}.module", hex::encode(hash)));
+
+        if let Ok(cached) = fs::read(&path) {
+            match Module::from_cache(&cached, &hash) {
+                Ok(module) => {
+                    debug!("Loaded module from cache {}", path.display());
+                    return Ok(module);
+                }
+                Err(err) => debug!("Ignoring cached module {}: {err}", path.display()),
+            }
+        }
+
+        let module = parse_bytes_lazy(program)?;
+
+        // Workers starting the same program at once share a single background write.
+        if !self.in_flight.lock().unwrap().insert(hash.into()) {
+            return Ok(module);
+        }
+
+        let mut translated = module.clone();
+        let in_flight = self.in_flight.clone();
+        thread::spawn(move || {
+            let res = translated.translate_all().and_then(|_| write_cache(&translated, &hash, &path));
+            if let Err(err) = res {
+                warn!("Failed to cache module {}: {err}", path.display());
+            }
+            in_flight.lock().unwrap().remove(&<[u8; 32]>::from(hash));
+        });
+
+        Ok(module)
+    }
+}
+
+/// Create `dir` and make it accessible to its owner only.
+///
+/// This fails if the directory already exists and belongs to another user.
+fn create_private_dir(dir: &Path) -> std::io::Result<()> {
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::{DirBuilderExt, PermissionsExt};
+
+        fs::DirBuilder::new().recursive(true).mode(0o700).create(dir)?;
+        // Only the owner can change the permissions, which also covers directories created before.
+        fs::set_permissions(dir, fs::Permissions::from_mode(0o700))
+    }
+
+    #[cfg(not(unix))]
+    fs::create_dir_all(dir)
+}
+
+fn write_cache(module: &Module, hash: &[u8], path: &Path) -> Result<(), reef_interpreter::Error> {
+    static TMP_COUNTER: AtomicUsize = AtomicUsize::new(0);
+
+    // Write to a temporary file first, other workers may load the same program concurrently.
+    let tmp_id = TMP_COUNTER.fetch_add(1, Ordering::Relaxed);
+    let tmp_path = path.with_extension(format!("{}-{tmp_id}.tmp", std::process::id()));
+
+    let res = (|| {
+        let mut writer = BufWriter::new(fs::File::create(&tmp_path)?);
+        module.serialize_cache(&mut writer, hash)?;
+        writer.flush()?;
+        fs::rename(&tmp_path, path)?;
+        Ok(())
+    })();
+
+    if res.is_err() {
+        let _ = fs::remove_file(&tmp_path);
+    }
+    res
+}
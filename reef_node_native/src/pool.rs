@@ -0,0 +1,119 @@
+use std::collections::VecDeque;
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::{Arc, Mutex};
+use std::thread::{self, Thread};
+use std::time::Duration;
+
+use crate::worker::{JobTask, StepResult};
+
+/// How long an idle pool thread waits before it looks for work to steal again.
+const IDLE_PARK: Duration = Duration::from_millis(10);
+
+/// Longest a thread waits when all of its jobs are sleeping, so it still notices newly queued or stolen work.
+const MAX_SLEEP_PARK: Duration = Duration::from_millis(100);
+
+/// Work-stealing thread pool executing the jobs of this node.
+///
+/// Every thread owns a queue of jobs and runs them round-robin, one slice of cycles at a time.
+/// Paused jobs are plain values, so an idle thread steals them from the back of another thread's queue.
+#[derive(Debug)]
+pub(crate) struct WorkerPool {
+    shared: Arc<Shared>,
+    threads: Vec<Thread>,
+}
+
+#[derive(Debug)]
+struct Shared {
+    queues: Vec<Mutex<VecDeque<JobTask>>>,
+}
+
+impl WorkerPool {
+    pub(crate) fn new(num_threads: usize) -> Self {
+        let num_threads = num_threads.max(1);
+        let shared = Arc::new(Shared { queues: (0..num_threads).map(|_| Mutex::new(VecDeque::new())).collect() });
+
+        let threads = (0..num_threads)
+            .map(|idx| {
+                let shared = shared.clone();
+                thread::Builder::new()
+                    .name(format!("reef-worker-{idx}"))
+                    .spawn(move || shared.worker_loop(idx))
+                    .expect("failed to spawn worker thread")
+                    .thread()
+                    .clone()
+            })
+            .collect();
+
+        Self { shared, threads }
+    }
+
+    /// Queue a job on the thread with the least queued jobs.
+    pub(crate) fn submit(&self, task: JobTask) {
+        let (idx, queue) = self
+            .shared
+            .queues
+            .iter()
+            .enumerate()
+            .min_by_key(|(_, queue)| queue.lock().unwrap().len())
+            .expect("pool has at least one thread");
+
+        queue.lock().unwrap().push_back(task);
+        self.threads[idx].unpark();
+    }
+}
+
+impl Shared {
+    fn worker_loop(&self, own: usize) {
+        // Number of consecutive steps of sleeping jobs and the shortest remaining sleep among them.
+        let mut sleeping_steps = 0;
+        let mut shortest_sleep = MAX_SLEEP_PARK;
+
+        loop {
+            let Some(mut task) = self.pop(own) else {
+                thread::park_timeout(IDLE_PARK);
+                continue;
+            };
+
+            // A panicking job must not take the thread and the other jobs queued on it down.
+            let step = match panic::catch_unwind(AssertUnwindSafe(|| task.step())) {
+                Ok(step) => step,
+                Err(panic) => {
+                    task.fail_panicked(panic);
+                    continue;
+                }
+            };
+
+            match step {
+                StepResult::Runnable => {
+                    sleeping_steps = 0;
+                    shortest_sleep = MAX_SLEEP_PARK;
+                    self.queues[own].lock().unwrap().push_back(task);
+                }
+                StepResult::Sleeping(remaining) => {
+                    self.queues[own].lock().unwrap().push_back(task);
+
+                    sleeping_steps += 1;
+                    shortest_sleep = shortest_sleep.min(remaining);
+
+                    // All jobs of this thread are sleeping, wait instead of spinning.
+                    if sleeping_steps >= self.queues[own].lock().unwrap().len() {
+                        thread::park_timeout(shortest_sleep);
+                        sleeping_steps = 0;
+                        shortest_sleep = MAX_SLEEP_PARK;
+                    }
+                }
+                StepResult::Done => {}
+            }
+        }
+    }
+
+    /// Take the next job of this thread or steal one from the other threads.
+    fn pop(&self, own: usize) -> Option<JobTask> {
+        if let Some(task) = self.queues[own].lock().unwrap().pop_front() {
+            return Some(task);
+        }
+
+        let len = self.queues.len();
+        (1..len).map(|offset| (own + offset) % len).find_map(|idx| self.queues[idx].lock().unwrap().pop_back())
+    }
+}
@@ -0,0 +1,172 @@
+use std::fs::{self, File};
+use std::path::Path;
+use std::sync::{mpsc, Arc};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use anyhow::{bail, Context, Result};
+use log::{debug, info};
+
+use reef_interpreter::exec::CallResultTyped;
+use reef_interpreter::record::{
+    read_recording, Divergence, HostCallOutcome, HostCallRecord, Recorder, Replayer, SyncRecord,
+};
+use reef_interpreter::types::value::WasmValue;
+use reef_wasm_interface::{
+    end_record, ReefDataset, REEF_CLOCK_WALL, REEF_DATASET_NAME_NAME, REEF_DATASET_READ_NAME, REEF_DATASET_WRITE_NAME,
+    REEF_DEFER_SYNC_NAME, REEF_MODULE_NAME, REEF_RANDOM_FILL_NAME, REEF_RESULT_NAME, REEF_TIME_NOW_NAME,
+};
+
+use crate::module_cache::ModuleCache;
+use crate::worker::{setup_interpreter, HostCalls, WorkerData};
+
+/// Start recording the host calls of a job in `dir`.
+///
+/// Every start of a job gets its own recording `<job>-<millis>.calls`,
+/// next to the snapshot it was started from as `<job>-<millis>.state` if it was resumed.
+pub(crate) fn start_recording(dir: &Path, job_id: &str, state: Option<&[u8]>) -> Result<Recorder> {
+    let millis = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_millis();
+    let base = dir.join(format!("{job_id}-{millis}"));
+
+    fs::create_dir_all(dir).with_context(|| format!("could not create {}", dir.display()))?;
+    if let Some(state) = state {
+        fs::write(base.with_extension("state"), state).with_context(|| "could not write starting state")?;
+    }
+
+    let file = File::create(base.with_extension("calls")).with_context(|| "could not create recording")?;
+    debug!("Recording host calls of '{job_id}' to {}", base.with_extension("calls").display());
+
+    Ok(Recorder::new(file)?)
+}
+
+/// Re-run a job against a recording of its host calls and check that it makes the same calls, reaches the same
+/// synced states and ends the same way.
+///
+/// Datasets are given as `[NAME=]PATH`, in the order the job had them.
+/// The ID of the job, which seeds its random numbers, is taken from the name of the recording.
+pub(crate) fn replay(recording: &Path, program: &Path, datasets: &[String], state: Option<&Path>) -> Result<()> {
+    let file = File::open(recording).with_context(|| format!("could not open {}", recording.display()))?;
+    let records = read_recording(file)?;
+    let num_calls = records.calls.len();
+    let syncs = records.syncs.clone();
+    let wall_clock: Vec<u64> = records.calls.iter().filter_map(wall_clock_reading).collect();
+
+    // Without an end, execution was stopped at some point after the last recorded call or sync.
+    let stop_cycles = match &records.end {
+        Some(_) => u64::MAX,
+        None => {
+            let last_call = records.calls.last().map_or(0, HostCallRecord::cycles);
+            let stop_cycles = last_call.max(syncs.last().map_or(0, |sync| sync.cycles));
+            info!("The recording was stopped before the job ended, replaying its first {stop_cycles} cycles");
+            stop_cycles
+        }
+    };
+
+    let mut replayer = Replayer::new(records);
+    // Imports writing into memory, like copying datasets, have side effects the program depends on.
+    replayer.passthrough(REEF_MODULE_NAME, REEF_DATASET_WRITE_NAME);
+    replayer.passthrough(REEF_MODULE_NAME, REEF_DATASET_READ_NAME);
+    replayer.passthrough(REEF_MODULE_NAME, REEF_DATASET_NAME_NAME);
+    replayer.passthrough(REEF_MODULE_NAME, REEF_RANDOM_FILL_NAME);
+    // So do imports changing the state of the reef imports, which is part of the synced states.
+    // The wall clock returns the recorded readings for this.
+    replayer.passthrough(REEF_MODULE_NAME, REEF_RESULT_NAME);
+    replayer.passthrough(REEF_MODULE_NAME, REEF_DEFER_SYNC_NAME);
+    replayer.passthrough(REEF_MODULE_NAME, REEF_TIME_NOW_NAME);
+
+    let job_id = recording
+        .file_stem()
+        .and_then(|stem| stem.to_str()?.rsplit_once('-'))
+        .map(|(job_id, _millis)| job_id.to_owned())
+        .with_context(|| "recording is not named `<job>-<millis>.calls`")?;
+
+    let datasets = datasets
+        .iter()
+        .map(|dataset| {
+            let (name, path) = dataset.split_once('=').unwrap_or(("", dataset));
+            let data = fs::read(path).with_context(|| format!("could not read dataset {path}"))?;
+            Ok(ReefDataset::new(name, data))
+        })
+        .collect::<Result<_>>()?;
+
+    let state = state.map(fs::read).transpose().with_context(|| "could not read starting state")?;
+    let (sender, _discard) = mpsc::channel();
+    let data = WorkerData {
+        job_id,
+        sender,
+        module_cache: Arc::new(ModuleCache::new(None)),
+        program: fs::read(program).with_context(|| "could not read program")?,
+        state,
+        datasets,
+        host_calls: HostCalls::Replay(replayer.clone()),
+    };
+
+    let (mut exec_handle, host) = setup_interpreter(data)?;
+    host.replay_wall_clock(&wall_clock);
+
+    let mut res = Ok(CallResultTyped::Incomplete);
+    for sync in &syncs {
+        res = exec_handle.run_to(sync.cycles);
+        if !matches!(res, Ok(CallResultTyped::Incomplete)) {
+            break;
+        }
+
+        let actual = SyncRecord { cycles: exec_handle.cycles(), state_hash: exec_handle.state_hash_with_data()? };
+        replayer.sync(sync, actual)?;
+        // Like on the node, the readings of the wall clock are sent with the sync and leave the state.
+        exec_handle.instance_mut().data_mut().wall_clock_readings.clear();
+    }
+    if matches!(res, Ok(CallResultTyped::Incomplete)) {
+        res = exec_handle.run_to(stop_cycles);
+    }
+    let cycles = exec_handle.cycles();
+
+    let output = match res {
+        Ok(CallResultTyped::Done(_)) => Ok(exec_handle.into_instance().into_data().output),
+        Ok(CallResultTyped::Incomplete) => {
+            replayer.finish(None)?;
+            info!(
+                "==> Replay matches the recording: {num_calls} host calls and {} syncs in {cycles} cycles",
+                syncs.len()
+            );
+            return Ok(());
+        }
+        Ok(CallResultTyped::HostPending(_)) => bail!("unexpected pending host call"),
+        Err(err) if err.downcast_host_ref::<Divergence>().is_some() => bail!("{err}"),
+        Err(err) => {
+            // The recording can end with a failed call, which ends execution on replay as well.
+            info!("Execution failed: {err}");
+            Err(err)
+        }
+    };
+    let end = end_record(cycles, output.as_ref()).with_context(|| "replay was aborted")?;
+
+    match replayer.finish(Some(end)) {
+        Ok(()) => {
+            info!(
+                "==> Replay matches the recording: {num_calls} host calls and {} syncs in {cycles} cycles",
+                syncs.len()
+            );
+            Ok(())
+        }
+        Err(err) => bail!("{err}"),
+    }
+}
+
+/// Get the reading of a recorded call to the wall clock.
+fn wall_clock_reading(call: &HostCallRecord) -> Option<u64> {
+    let import = call.import();
+    if import.module() != REEF_MODULE_NAME
+        || import.name() != REEF_TIME_NOW_NAME
+        || call.params() != [WasmValue::I32(REEF_CLOCK_WALL)]
+    {
+        return None;
+    }
+
+    match call.outcome() {
+        HostCallOutcome::Returned(results) => match results[..] {
+            [WasmValue::I64(millis)] => Some(millis as u64),
+            _ => None,
+        },
+        _ => None,
+    }
+}
@@ -0,0 +1,156 @@
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::{
+    atomic::{AtomicU8, Ordering},
+    mpsc, Arc,
+};
+use std::thread;
+
+use log::{debug, warn};
+
+use reef_interpreter::exec::{snapshot_hash, CallResultTyped};
+use reef_wasm_interface::{ReefDataset, ReefError};
+
+use crate::module_cache::ModuleCache;
+use crate::worker::{
+    panic_message, setup_interpreter, FromWorkerMessage, HostCalls, JobOutput, WorkerData, WorkerSignal,
+};
+
+/// Instructions executed between two checks for an abort signal.
+const ITERATION_CYCLES: u64 = 0x10000;
+
+/// A running re-execution of a job, started by the manager to check the state claimed by another node.
+#[derive(Debug)]
+pub(crate) struct Verification {
+    pub(crate) worker_index: usize,
+    pub(crate) job_id: String,
+
+    pub(crate) signal_to_worker: Arc<AtomicU8>,
+    pub(crate) result_receiver: VerifyResultReceiver,
+}
+
+#[derive(Debug)]
+pub(crate) struct VerifyOutcome {
+    /// Whether the cycle count, the state hash and the claimed state or result all match the claim.
+    pub(crate) matches: bool,
+    /// Cycles executed, less than claimed if the program returned earlier.
+    pub(crate) cycles: u64,
+    pub(crate) state_hash: [u8; 32],
+}
+
+/// Sent by the manager: the claim of a node which ran the job from `interpreter_state`.
+#[derive(Debug)]
+pub(crate) struct VerifyRequest {
+    pub(crate) worker_index: usize,
+    pub(crate) job_id: String,
+    /// Name and ID of every dataset, in the order the program sees them.
+    pub(crate) datasets: Vec<(String, String)>,
+
+    pub(crate) program_byte_code: Vec<u8>,
+    pub(crate) interpreter_state: Vec<u8>,
+    pub(crate) claim: VerifyClaim,
+}
+
+/// The state a node claims to have reached after running `cycles` instructions.
+#[derive(Debug)]
+pub(crate) struct VerifyClaim {
+    pub(crate) cycles: u64,
+    pub(crate) state_hash: Vec<u8>,
+    /// State synced by the node, it has to hash to `state_hash` as well.
+    pub(crate) state: Vec<u8>,
+    /// Readings of the wall clock by the node, returned to the program again.
+    pub(crate) wall_clock: Vec<u64>,
+    /// Set for the claim on the result of the job, the program has to return it after exactly `cycles` instructions.
+    pub(crate) result: Option<JobOutput>,
+}
+
+pub(crate) type VerifyResultReceiver = mpsc::Receiver<Result<VerifyOutcome, reef_interpreter::Error>>;
+
+impl Verification {
+    /// Re-execute the job from `state` on a separate thread until `cycles` instructions have run.
+    ///
+    /// Logs and progress reports of the program are discarded, sleeps are skipped.
+    pub(crate) fn spawn(request: VerifyRequest, module_cache: Arc<ModuleCache>, datasets: Vec<ReefDataset>) -> Self {
+        let signal = Arc::new(AtomicU8::new(WorkerSignal::CONTINUE));
+        let (result_sender, result_receiver) = mpsc::channel();
+
+        let thread_signal = signal.clone();
+        let job_id = request.job_id.clone();
+        thread::Builder::new()
+            .name(format!("reef-verify-{}", request.worker_index))
+            .spawn(move || {
+                let (sender, discard) = mpsc::channel();
+                let state = if request.interpreter_state.is_empty() { None } else { Some(request.interpreter_state) };
+                let program = request.program_byte_code;
+                let job_id = request.job_id.clone();
+                let data =
+                    WorkerData { job_id, sender, module_cache, program, state, datasets, host_calls: HostCalls::Live };
+
+                // Like a job, a panicking verification must not take the node down, it is reported as internal error.
+                let res = panic::catch_unwind(AssertUnwindSafe(|| {
+                    verify(&request.job_id, data, &discard, &thread_signal, &request.claim)
+                }))
+                .unwrap_or_else(|panic| {
+                    let message = panic_message(&*panic);
+                    warn!("Verification of '{}' panicked: {message}", request.job_id);
+                    Err(reef_interpreter::Error::Other(format!("internal error, verification panicked: {message}")))
+                });
+                result_sender.send(res).unwrap();
+            })
+            .expect("failed to spawn verification thread");
+
+        Self { worker_index: request.worker_index, job_id, signal_to_worker: signal, result_receiver }
+    }
+}
+
+fn verify(
+    job_id: &str,
+    data: WorkerData,
+    discard: &mpsc::Receiver<FromWorkerMessage>,
+    signal: &AtomicU8,
+    claim: &VerifyClaim,
+) -> Result<VerifyOutcome, reef_interpreter::Error> {
+    let (mut exec_handle, host) = setup_interpreter(data)?;
+    host.replay_wall_clock(&claim.wall_clock);
+    let cycles = claim.cycles;
+
+    debug!("Verifying {cycles} cycles of '{job_id}'...");
+
+    let mut done = false;
+    while exec_handle.cycles() < cycles {
+        discard.try_iter().for_each(drop);
+        if signal.load(Ordering::Relaxed) == WorkerSignal::ABORT {
+            return Err(reef_interpreter::Error::host(ReefError::Aborted));
+        }
+
+        let target = cycles.min(exec_handle.cycles().saturating_add(ITERATION_CYCLES));
+        match exec_handle.run_to(target)? {
+            CallResultTyped::Done(_) => {
+                done = true;
+                break;
+            }
+            CallResultTyped::Incomplete => {}
+            // None of the reef imports suspend their calls.
+            CallResultTyped::HostPending(_) => {
+                return Err(reef_interpreter::Error::Other("unexpected pending host call".into()))
+            }
+        }
+    }
+
+    // The hash includes the state of the reef imports, so the output of the job can not be forged.
+    let actual_hash = exec_handle.state_hash_with_data()?;
+    // The hash alone does not bind the state the manager resumes the job from, or the result it stores.
+    let claimed_matches = match &claim.result {
+        None => snapshot_hash(&claim.state).is_ok_and(|hash| hash == actual_hash),
+        Some((content_type, contents)) => {
+            let output = &exec_handle.instance().data().output;
+            done && output.content_type == reef_wasm_interface::content_type_to_num(*content_type)
+                && output.data == *contents
+        }
+    };
+
+    Ok(VerifyOutcome {
+        matches: exec_handle.cycles() == cycles && actual_hash[..] == *claim.state_hash && claimed_matches,
+        cycles: exec_handle.cycles(),
+        state_hash: actual_hash,
+    })
+}
@@ -1,36 +1,32 @@
-use std::cell::{Cell, RefCell};
+use std::any::Any;
+use std::collections::VecDeque;
 use std::mem;
-use std::rc::Rc;
 use std::sync::{
-    atomic::{AtomicU8, Ordering},
-    mpsc, Arc,
+    atomic::{AtomicBool, AtomicU8, Ordering},
+    mpsc, Arc, Mutex,
 };
-use std::thread::{self, JoinHandle};
-use std::time::{Duration, Instant};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
 
 use anyhow::Context;
-use log::debug;
+use log::{debug, warn};
 use tungstenite::Message;
 
 use reef_interpreter::{
     exec::CallResultTyped,
-    imports::{Extern, Imports},
-    parse_bytes,
-    reference::MemoryStringExt,
-    Instance, PAGE_SIZE,
+    imports::Shared,
+    record::{Recorder, Replayer},
+    PAGE_SIZE,
 };
-use reef_protocol_node::message_capnp::{MessageFromNodeKind, ResultContentType};
+use reef_protocol_node::message_capnp::{JobFailureKind, MessageFromNodeKind, ResultContentType};
 use reef_wasm_interface::*;
 
-use crate::{write_nonblocking_ws, WSConn};
+use crate::{module_cache::ModuleCache, write_nonblocking_ws, WSConn};
 
 // TODO: use a shared constant for this.
 const LOG_KIND_DEFAULT: u16 = 0;
 
 const ITERATION_CYCLES: usize = 0x10000;
 
-const MAX_CONTINUES_SLEEP: Duration = Duration::from_millis(100);
-
 #[derive(Debug)]
 pub(crate) struct ReefLog {
     pub(crate) content: String,
@@ -48,7 +44,7 @@ pub(crate) struct Job {
     pub(crate) signal_to_worker: Arc<AtomicU8>,
     pub(crate) channel_from_worker: mpsc::Receiver<FromWorkerMessage>,
 
-    pub(crate) handle: JobThreadHandle,
+    pub(crate) result_receiver: JobResultReceiver,
 
     pub(crate) logs_to_be_flushed: Vec<ReefLog>,
     pub(crate) progress: f32,
@@ -57,12 +53,20 @@ pub(crate) struct Job {
 #[derive(Debug)]
 pub(crate) struct JobResult {
     pub(crate) success: bool,
+    pub(crate) failure_kind: JobFailureKind,
     pub(crate) content_type: ResultContentType,
     pub(crate) contents: Vec<u8>,
+    /// Claim on the final state, only made by successful jobs.
+    pub(crate) claim: Option<StateClaim>,
 }
 
 impl Job {
-    pub(crate) fn flush_state(&mut self, state: &[u8], socket: &mut WSConn) -> anyhow::Result<()> {
+    pub(crate) fn flush_state(
+        &mut self,
+        state: &[u8],
+        claim: Option<&StateClaim>,
+        socket: &mut WSConn,
+    ) -> anyhow::Result<()> {
         let mut message = capnp::message::Builder::new_default();
         let mut encapsulating_message: reef_protocol_node::message_capnp::message_from_node::Builder =
             message.init_root();
@@ -73,6 +77,11 @@ impl Job {
         state_sync.set_worker_index(self.worker_index as u16);
         state_sync.set_progress(self.progress);
         state_sync.set_interpreter(state);
+        if let Some(claim) = claim {
+            state_sync.set_cycles(claim.cycles);
+            state_sync.set_state_hash(&claim.state_hash);
+            state_sync.set_wall_clock(&claim.wall_clock[..])?;
+        }
 
         // Logs.
         let mut logs = state_sync.init_logs(self.logs_to_be_flushed.len() as u32);
@@ -94,8 +103,19 @@ impl Job {
     }
 }
 
+/// Claim of a state sync or of the result of a job, which the manager can have checked by the verification of another
+/// node.
+#[derive(Debug)]
+pub(crate) struct StateClaim {
+    /// Instructions executed since the previous state sync.
+    pub(crate) cycles: u64,
+    pub(crate) state_hash: [u8; 32],
+    /// Readings of the wall clock during these instructions, see [`ReefState::wall_clock_readings`].
+    pub(crate) wall_clock: Vec<u64>,
+}
+
 pub(crate) enum FromWorkerMessage {
-    State(Vec<u8>),
+    State(Vec<u8>, Option<StateClaim>),
     Log(ReefLog),
     Progress(f32),
     Done,
@@ -105,14 +125,27 @@ pub(crate) type WorkerSender = mpsc::Sender<FromWorkerMessage>;
 
 #[derive(Debug)]
 pub(crate) struct WorkerData {
+    pub(crate) job_id: String,
     pub(crate) sender: WorkerSender,
+    pub(crate) module_cache: Arc<ModuleCache>,
     pub(crate) program: Vec<u8>,
     pub(crate) state: Option<Vec<u8>>,
-    pub(crate) dataset: Vec<u8>,
+    pub(crate) datasets: Vec<ReefDataset>,
+    pub(crate) host_calls: HostCalls,
 }
 
-type ReefJobOutput = (ResultContentType, Vec<u8>);
-pub(crate) type JobThreadHandle = JoinHandle<Result<ReefJobOutput, reef_interpreter::Error>>;
+/// How calls to the reef imports are handled, see [`reef_interpreter::record`].
+#[derive(Debug, Default)]
+pub(crate) enum HostCalls {
+    #[default]
+    Live,
+    Record(Recorder),
+    Replay(Replayer),
+}
+
+pub(crate) type JobOutput = (ResultContentType, Vec<u8>);
+pub(crate) type JobResultSender = mpsc::Sender<Result<(JobOutput, StateClaim), reef_interpreter::Error>>;
+pub(crate) type JobResultReceiver = mpsc::Receiver<Result<(JobOutput, StateClaim), reef_interpreter::Error>>;
 
 #[non_exhaustive]
 pub(crate) struct WorkerSignal;
@@ -123,223 +156,310 @@ impl WorkerSignal {
     pub(crate) const ABORT: u8 = 2;
 }
 
-pub(crate) fn spawn_worker_thread(signal: Arc<AtomicU8>, job_id: String, data: WorkerData) -> JobThreadHandle {
-    thread::spawn(move || -> Result<ReefJobOutput, reef_interpreter::Error> {
-        debug!("Instantiating WASM interpreter...");
+/// Host of the reef imports of one job.
+#[derive(Debug)]
+pub(crate) struct NativeHost {
+    job_id: String,
+    sender: WorkerSender,
+    datasets: Vec<ReefDataset>,
+    /// Set by `reef/sleep`, execution continues once the deadline has passed.
+    sleep_until: Mutex<Option<Instant>>,
+    /// Set by `reef/checkpoint` and reset once the state has been synced.
+    checkpoint: AtomicBool,
+    /// Readings of the wall clock returned instead of the current time, see [`NativeHost::replay_wall_clock`].
+    wall_clock_replay: Mutex<VecDeque<u64>>,
+}
 
-        let sleep_until = Rc::new(Cell::new(Instant::now()));
-        let job_output = Rc::new(RefCell::new((ResultContentType::Bytes, Vec::new())));
+impl NativeHost {
+    fn new(job_id: String, sender: WorkerSender, datasets: Vec<ReefDataset>) -> Self {
+        Self {
+            job_id,
+            sender,
+            datasets,
+            sleep_until: Mutex::new(None),
+            checkpoint: AtomicBool::new(false),
+            wall_clock_replay: Mutex::default(),
+        }
+    }
 
-        let sender = data.sender.clone();
-        // send initial state sync to move job from starting to running
-        sender.send(FromWorkerMessage::State(data.state.clone().unwrap_or_default())).unwrap();
+    /// Return the claimed readings of the wall clock to the program, for the verification of a claim.
+    ///
+    /// Once they are used up, the current time is returned again, which won't match the claim.
+    pub(crate) fn replay_wall_clock(&self, readings: &[u64]) {
+        self.wall_clock_replay.lock().unwrap().extend(readings);
+    }
 
-        let mut exec_handle = match setup_interpreter(data, sleep_until.clone(), job_output.clone()) {
-            Ok(handle) => handle,
-            Err(err) => {
-                sender.send(FromWorkerMessage::Done).unwrap();
-                return Err(err);
-            }
-        };
+    /// Whether the job asked for a state sync since the last call.
+    fn take_checkpoint(&self) -> bool {
+        self.checkpoint.swap(false, Ordering::Relaxed)
+    }
 
-        // This is not being re-allocated inside the hotloop for performance gains.
-        let mut serialized_state = Vec::with_capacity(PAGE_SIZE * 2);
-
-        debug!("Executing '{job_id}'...");
-
-        let res = loop {
-            // Check for signal from manager thread.
-            match signal.swap(WorkerSignal::CONTINUE, Ordering::Relaxed) {
-                // No signal, perform normal execution.
-                WorkerSignal::CONTINUE => (),
-                // Perform a state sync.
-                WorkerSignal::SAVE_STATE => {
-                    serialized_state.clear();
-                    let mut writer = std::io::Cursor::new(&mut serialized_state);
-
-                    // this clone should generally be cheap because job output is either not set or
-                    // very short during execution.
-                    let mut extra_data = job_output.borrow().1.clone();
-                    extra_data.push(reef_wasm_interface::content_type_to_num(job_output.borrow().0));
-
-                    exec_handle.serialize(&mut writer, &job_output.borrow().1)?;
-
-                    debug!("Serialized {} bytes for state of {}.", serialized_state.len(), job_id);
-
-                    sender.send(FromWorkerMessage::State(serialized_state.clone())).unwrap();
-                }
-                // Kill the worker.
-                WorkerSignal::ABORT => break Err(reef_interpreter::Error::Other("job was aborted".into())),
-                other => {
-                    unreachable!("internal bug: master thread has sent invalid signal: {other}")
-                }
-            }
+    /// How long the job still sleeps.
+    fn sleep_remaining(&self) -> Duration {
+        match *self.sleep_until.lock().unwrap() {
+            Some(sleep_until) => sleep_until.duration_since(Instant::now()),
+            None => Duration::ZERO,
+        }
+    }
+}
 
-            let sleep_remaining = sleep_until.get().duration_since(Instant::now());
-            if sleep_remaining != Duration::ZERO {
-                let dur = sleep_remaining.min(MAX_CONTINUES_SLEEP);
-                thread::sleep(dur);
-                continue;
-            }
+impl ReefHost for NativeHost {
+    fn log(&self, message: String) -> Result<(), reef_interpreter::Error> {
+        self.sender.send(FromWorkerMessage::Log(ReefLog { content: message, kind: LOG_KIND_DEFAULT })).unwrap();
+        Ok(())
+    }
 
-            // Execute Wasm.
-            let run_res = exec_handle.run(ITERATION_CYCLES);
-            match run_res {
-                Ok(CallResultTyped::Done(_)) => {
-                    break Ok(());
-                }
-                Ok(CallResultTyped::Incomplete) => {}
-                Err(err) => break Err(err),
-            }
-        };
+    fn progress(&self, done: f32) -> Result<(), reef_interpreter::Error> {
+        self.sender.send(FromWorkerMessage::Progress(done)).unwrap();
+        Ok(())
+    }
 
-        sender.send(FromWorkerMessage::Done).unwrap();
-        drop(exec_handle);
+    fn sleep(&self, duration: Duration) -> Result<(), reef_interpreter::Error> {
+        let sleep_until = Instant::now()
+            .checked_add(duration)
+            .ok_or_else(|| reef_interpreter::Error::host(ReefError::InvalidSleep(duration.as_secs_f32())))?;
+        *self.sleep_until.lock().unwrap() = Some(sleep_until);
+        Ok(())
+    }
 
-        let job_output = Rc::try_unwrap(job_output).unwrap().into_inner();
-        res.map(|_| job_output)
-    })
+    fn checkpoint(&self) -> Result<(), reef_interpreter::Error> {
+        self.checkpoint.store(true, Ordering::Relaxed);
+        Ok(())
+    }
+
+    fn datasets(&self) -> &[ReefDataset] {
+        &self.datasets
+    }
+
+    fn wall_time(&self) -> Duration {
+        match self.wall_clock_replay.lock().unwrap().pop_front() {
+            Some(millis) => Duration::from_millis(millis),
+            None => SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default(),
+        }
+    }
+
+    fn job_id(&self) -> &str {
+        &self.job_id
+    }
 }
 
-fn setup_interpreter(
-    data: WorkerData,
-    sleep_until: Rc<Cell<Instant>>,
-    job_output: Rc<RefCell<(ResultContentType, Vec<u8>)>>,
-) -> Result<ReefMainHandle, reef_interpreter::Error> {
-    let dataset = Rc::new(data.dataset);
+/// Outcome of running one slice of a job.
+#[derive(Debug)]
+pub(crate) enum StepResult {
+    /// The job can continue immediately.
+    Runnable,
+    /// The job sleeps for at least the given duration.
+    Sleeping(Duration),
+    /// The job has finished, its result has been sent.
+    Done,
+}
 
-    let module = parse_bytes(&data.program)?;
-    let imports = reef_imports(data.sender, sleep_until, job_output.clone(), dataset.clone())?;
+/// A job which is executed on the worker pool in slices of [`ITERATION_CYCLES`].
+///
+/// Between slices the task can be moved to any thread of the pool.
+#[derive(Debug)]
+pub(crate) struct JobTask {
+    job_id: String,
+    signal: Arc<AtomicU8>,
+    sender: WorkerSender,
+    result_sender: JobResultSender,
+
+    /// Present until the interpreter has been set up on the first step.
+    setup: Option<WorkerData>,
+    exec_handle: Option<ReefMainHandle<ReefState, Shared>>,
+    host: Option<Arc<NativeHost>>,
+    recorder: Option<Recorder>,
+    /// When the manager thread asked for a state sync which has not been performed yet.
+    sync_requested: Option<Instant>,
+    /// Instructions executed by the interpreter up to the last state sync.
+    synced_cycles: u64,
+
+    // This is not being re-allocated for every sync for performance gains.
+    serialized_state: Vec<u8>,
+}
 
-    let (mut instance, stack, mut extra_data) = Instance::instantiate(module, imports, data.state.as_deref())?;
-    if stack.is_some() {
-        // reload dataset
-        let mut mem = instance.exported_memory_mut("memory")?;
+impl JobTask {
+    pub(crate) fn new(signal: Arc<AtomicU8>, data: WorkerData, result_sender: JobResultSender) -> Self {
+        let recorder = match &data.host_calls {
+            HostCalls::Record(recorder) => Some(recorder.clone()),
+            _ => None,
+        };
 
-        if mem.get_ignored_byte_region().1 == dataset.len() {
-            mem.copy_into_ignored_byte_region(&dataset);
+        Self {
+            job_id: data.job_id.clone(),
+            signal,
+            sender: data.sender.clone(),
+            result_sender,
+            setup: Some(data),
+            exec_handle: None,
+            host: None,
+            recorder,
+            sync_requested: None,
+            synced_cycles: 0,
+            serialized_state: Vec::with_capacity(PAGE_SIZE * 2),
         }
-        drop(dataset);
     }
 
-    job_output.borrow_mut().0 = reef_wasm_interface::num_to_content_type(extra_data.pop().unwrap_or(0))
-        .map_err(|_| reef_interpreter::Error::Other("invalid content type in state sync".into()))?;
-    job_output.borrow_mut().1 = extra_data;
+    /// Run the next slice of the job.
+    ///
+    /// Once [`StepResult::Done`] is returned, the task must not be stepped again.
+    pub(crate) fn step(&mut self) -> StepResult {
+        let res = match self.run_slice() {
+            Ok(StepResult::Done) => self.take_output(),
+            Ok(step) => return step,
+            Err(err) => {
+                // Drop the interpreter right away, it is not needed anymore.
+                let cycles = self.exec_handle.take().map_or(0, |exec_handle| exec_handle.cycles());
+                self.record_end(cycles, Err(&err));
+                Err(err)
+            }
+        };
 
-    let entry_fn_handle = instance.exported_func::<ReefMainArgs, ReefMainReturn>(REEF_MAIN_NAME)?;
-    let exec_handle = entry_fn_handle.call((), stack)?;
+        self.finish(res);
+        StepResult::Done
+    }
 
-    Ok(exec_handle)
-}
+    /// Report the job as failed after [`JobTask::step`] panicked, the task must not be stepped again.
+    pub(crate) fn fail_panicked(&mut self, panic: Box<dyn Any + Send>) {
+        let message = panic_message(&*panic);
+        warn!("Job '{}' panicked: {message}", self.job_id);
 
-fn reef_imports(
-    sender: WorkerSender,
-    sleep_until: Rc<Cell<Instant>>,
-    job_output: Rc<RefCell<(ResultContentType, Vec<u8>)>>,
-    dataset: Rc<Vec<u8>>,
-) -> Result<Imports, reef_interpreter::Error> {
-    let mut imports = Imports::new();
-
-    // Reef Log.
-    let sender_log = sender.clone();
-    imports.define(
-        REEF_MODULE_NAME,
-        REEF_LOG_NAME,
-        Extern::typed_func(move |ctx, (ptr, len): ReefLogArgs| {
-            let mem = ctx.exported_memory("memory")?;
-            let log_string = mem.load_string(ptr as usize, len as usize)?;
-
-            if log_string.len() > 0x400 {
-                return Err(reef_interpreter::Error::Other(
-                    "reef/log: log message longer than 0x400 bytes, aborting".into(),
-                ));
-            }
+        // The interpreter may have been left in any state.
+        self.exec_handle = None;
+        self.finish(Err(reef_interpreter::Error::Other(format!("internal error, job panicked: {message}"))));
+    }
+
+    fn finish(&mut self, res: Result<(JobOutput, StateClaim), reef_interpreter::Error>) {
+        if let Some(err) = self.recorder.as_ref().and_then(Recorder::error) {
+            warn!("Recording of '{}' is incomplete: {err}", self.job_id);
+        }
+
+        // Send the result first, so the manager thread does not block once it sees `Done`.
+        // The manager thread only drops the receivers once it has stopped caring about the job.
+        let _ = self.result_sender.send(res);
+        let _ = self.sender.send(FromWorkerMessage::Done);
+    }
+
+    fn run_slice(&mut self) -> Result<StepResult, reef_interpreter::Error> {
+        if let Some(data) = self.setup.take() {
+            debug!("Instantiating WASM interpreter...");
+
+            // send initial state sync to move job from starting to running
+            self.sender.send(FromWorkerMessage::State(data.state.clone().unwrap_or_default(), None)).unwrap();
 
-            sender_log.send(FromWorkerMessage::Log(ReefLog { content: log_string, kind: LOG_KIND_DEFAULT })).unwrap();
+            let (exec_handle, host) = setup_interpreter(data)?;
+            self.exec_handle = Some(exec_handle);
+            self.host = Some(host);
+
+            debug!("Executing '{}'...", self.job_id);
+            return Ok(StepResult::Runnable);
+        }
 
-            Ok(())
-        }),
-    )?;
+        let exec_handle = self.exec_handle.as_mut().expect("internal bug: job was stepped after it finished");
 
-    // Reef report progress.
-    let sender_progress = sender.clone();
-    imports.define(
-        REEF_MODULE_NAME,
-        REEF_PROGRESS_NAME,
-        Extern::typed_func(move |_ctx, (done,): ReefProgressArgs| {
-            if !(0.0..=1.0).contains(&done) {
-                return Err(reef_interpreter::Error::Other("reef/progress: value not in Range 0.0..=1.0".into()));
+        // Check for signal from manager thread.
+        match self.signal.swap(WorkerSignal::CONTINUE, Ordering::Relaxed) {
+            // No signal, perform normal execution.
+            WorkerSignal::CONTINUE => (),
+            // Perform a state sync, once the program allows it.
+            WorkerSignal::SAVE_STATE => {
+                self.sync_requested.get_or_insert_with(Instant::now);
             }
+            // Kill the worker.
+            WorkerSignal::ABORT => return Err(reef_interpreter::Error::host(ReefError::Aborted)),
+            other => {
+                unreachable!("internal bug: master thread has sent invalid signal: {other}")
+            }
+        }
+
+        // Programs can ask for a sync right away or defer syncs for a limited time.
+        let checkpoint = self.host.as_ref().is_some_and(|host| host.take_checkpoint());
+        let sync_due = self.sync_requested.is_some_and(|requested| {
+            !exec_handle.instance().data().sync_deferred || requested.elapsed() >= REEF_DEFER_SYNC_MAX
+        });
+        if checkpoint || sync_due {
+            self.sync_requested = None;
+            self.serialized_state.clear();
+            let mut writer = std::io::Cursor::new(&mut self.serialized_state);
 
-            sender_progress.send(FromWorkerMessage::Progress(done)).unwrap();
-
-            Ok(())
-        }),
-    )?;
-
-    // Reef sleep.
-    imports.define(
-        REEF_MODULE_NAME,
-        REEF_SLEEP_NAME,
-        Extern::typed_func::<_, ReefSleepReturn>(move |_ctx, (seconds,): ReefSleepArgs| {
-            sleep_until.set(
-                Instant::now()
-                    .checked_add(Duration::from_secs_f32(seconds))
-                    .ok_or_else(|| reef_interpreter::Error::Other("reef/sleep: invalid time".into()))?,
-            );
-
-            Err(reef_interpreter::Error::PauseExecution)
-        }),
-    )?;
-
-    // Reef dataset.
-    // Reef std implementations guarantee, that the dataset is at least 8 byte aligned.
-    let dataset_len = dataset.len();
-    let dataset = std::cell::RefCell::new(Some(dataset));
-    imports.define(
-        REEF_MODULE_NAME,
-        REEF_DATASET_LEN_NAME,
-        Extern::typed_func::<_, ReefDatasetLenReturn>(move |_ctx, _args: ReefDatasetLenArgs| Ok((dataset_len as i32,))),
-    )?;
-    imports.define(
-        REEF_MODULE_NAME,
-        REEF_DATASET_WRITE_NAME,
-        Extern::typed_func::<_, ReefDatasetWriteReturn>(move |mut ctx, (ptr,): ReefDatasetWriteArgs| {
-            let mut mem = ctx.exported_memory_mut("memory")?;
-
-            mem.set_ignored_byte_region(ptr as usize, dataset_len);
-            mem.copy_into_ignored_byte_region(dataset.borrow().as_deref().unwrap_or(&Rc::new(Vec::new())));
-
-            // Drop the remaining Rc reference to free the Vec
-            if dataset.borrow().is_some() {
-                *dataset.borrow_mut() = None;
+            exec_handle.serialize_with_data(&mut writer)?;
+
+            debug!("Serialized {} bytes for state of {}.", self.serialized_state.len(), self.job_id);
+
+            let state_hash = exec_handle.state_hash_with_data()?;
+            if let Some(recorder) = &self.recorder {
+                recorder.sync(exec_handle.cycles(), state_hash);
             }
 
-            Ok(())
-        }),
-    )?;
-
-    // Reef result.
-    imports.define(
-        REEF_MODULE_NAME,
-        REEF_RESULT_NAME,
-        Extern::typed_func::<_, ReefResultReturn>(move |ctx, (result_type, ptr, len): ReefResultArgs| {
-            let mem = ctx.exported_memory("memory")?;
-            let data = mem.load_vec(ptr as usize, len as usize)?;
-
-            let content_type = match result_type {
-                0 => ResultContentType::I32,
-                1 => ResultContentType::Bytes,
-                2 => ResultContentType::StringPlain,
-                3 => ResultContentType::StringJSON,
-                _ => return Err(reef_interpreter::Error::Other("invalid ResultContentType".into())),
+            let claim = StateClaim {
+                cycles: exec_handle.cycles() - self.synced_cycles,
+                state_hash,
+                wall_clock: mem::take(&mut exec_handle.instance_mut().data_mut().wall_clock_readings),
             };
+            self.synced_cycles = exec_handle.cycles();
 
-            *job_output.borrow_mut() = (content_type, data);
+            self.sender.send(FromWorkerMessage::State(self.serialized_state.clone(), Some(claim))).unwrap();
+        }
 
-            Ok(())
-        }),
-    )?;
+        let sleep_remaining = self.host.as_ref().map_or(Duration::ZERO, |host| host.sleep_remaining());
+        if sleep_remaining != Duration::ZERO {
+            return Ok(StepResult::Sleeping(sleep_remaining));
+        }
+
+        // Execute Wasm.
+        match exec_handle.run(ITERATION_CYCLES)? {
+            CallResultTyped::Done(_) => Ok(StepResult::Done),
+            CallResultTyped::Incomplete => Ok(StepResult::Runnable),
+            // None of the reef imports suspend their calls.
+            CallResultTyped::HostPending(_) => {
+                Err(reef_interpreter::Error::Other("unexpected pending host call".into()))
+            }
+        }
+    }
 
-    Ok(imports)
+    fn record_end(&self, cycles: u64, res: Result<&ReefJobOutput, &reef_interpreter::Error>) {
+        if let (Some(recorder), Some(end)) = (&self.recorder, end_record(cycles, res)) {
+            recorder.finish(end);
+        }
+    }
+
+    fn take_output(&mut self) -> Result<(JobOutput, StateClaim), reef_interpreter::Error> {
+        let exec_handle = self.exec_handle.take().expect("internal bug: job output was already taken");
+        let cycles = exec_handle.cycles();
+        // The output is part of the state of the reef imports, so the claim covers it.
+        let state_hash = exec_handle.state_hash_with_data()?;
+        let ReefState { output, wall_clock_readings, .. } = exec_handle.into_instance().into_data();
+        let claim = StateClaim { cycles: cycles - self.synced_cycles, state_hash, wall_clock: wall_clock_readings };
+        self.record_end(cycles, Ok(&output));
+
+        let content_type = reef_wasm_interface::num_to_content_type(output.content_type)
+            .map_err(|_| reef_interpreter::Error::host(ReefError::InvalidContentType(output.content_type as i32)))?;
+        Ok(((content_type, output.data), claim))
+    }
+}
+
+/// Instantiate the program of a job and start or resume it.
+pub(crate) fn setup_interpreter(
+    data: WorkerData,
+) -> Result<(ReefMainHandle<ReefState, Shared>, Arc<NativeHost>), reef_interpreter::Error> {
+    let module = data.module_cache.load(&data.program)?;
+    let host = Arc::new(NativeHost::new(data.job_id, data.sender, data.datasets));
+    let imports = reef_imports(host.clone(), abi_version(&module)?)?;
+    let imports = match &data.host_calls {
+        HostCalls::Live => imports,
+        HostCalls::Record(recorder) => recorder.wrap(imports),
+        HostCalls::Replay(replayer) => replayer.wrap(imports),
+    };
+
+    let exec_handle = start_job(module, imports, &*host, data.state.as_deref())?;
+    Ok((exec_handle, host))
+}
+
+/// The message a thread panicked with, as far as it can be recovered.
+pub(crate) fn panic_message(panic: &(dyn Any + Send)) -> &str {
+    match (panic.downcast_ref::<&str>(), panic.downcast_ref::<String>()) {
+        (Some(message), _) => message,
+        (_, Some(message)) => message.as_str(),
+        (None, None) => "unknown panic",
+    }
 }
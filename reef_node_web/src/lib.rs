@@ -1,19 +1,15 @@
 #![feature(sync_unsafe_cell)]
 
-use std::{
-    cell::{Cell, RefCell, SyncUnsafeCell},
-    rc::Rc,
+use std::cell::SyncUnsafeCell;
+use std::sync::{
+    atomic::{AtomicBool, Ordering},
+    Arc, Mutex,
 };
+use std::time::Duration;
 
 use wasm_bindgen::prelude::*;
 
-use reef_interpreter::{
-    exec::CallResultTyped,
-    imports::{Extern, Imports},
-    parse_bytes,
-    reference::MemoryStringExt,
-    Instance,
-};
+use reef_interpreter::{exec::CallResultTyped, parse_bytes_lazy, record::Recorder};
 use reef_wasm_interface::*;
 
 pub mod message;
@@ -35,11 +31,19 @@ pub fn get_connect_path() -> String {
     NODE_REGISTER_PATH.to_owned()
 }
 
+/// Longest time a sync is deferred while the job asks for it, see [`RunResult::sync_deferred`].
+#[wasm_bindgen]
+pub fn get_defer_sync_max_millis() -> f64 {
+    REEF_DEFER_SYNC_MAX.as_millis() as f64
+}
+
 #[derive(Debug)]
 struct NodeState {
-    handle: ReefMainHandle,
-    sleep_for: Rc<Cell<f32>>,
-    job_output: Rc<RefCell<JobOutput>>,
+    handle: ReefMainHandle<ReefState>,
+    host: Arc<WebHost>,
+    recorder: Option<Recorder>,
+    /// Instructions executed by the interpreter up to the last state sync.
+    synced_cycles: u64,
 }
 
 #[derive(Debug, Clone)]
@@ -47,65 +51,190 @@ struct NodeState {
 pub struct JobOutput {
     pub content_type: u8,
     pub data: Vec<u8>,
+    /// Claim on the final state like the one of [`StateSync`], the state hash covers the output.
+    pub cycles: u64,
+    pub state_hash: Vec<u8>,
+    pub wall_clock: Vec<u64>,
 }
 
 // SAFETY: this code is only ever expected to run in a single threaded environment
 unsafe impl Sync for NodeState {}
 
+/// Host of the reef imports, passing logs and progress on to the JS callbacks.
+#[derive(Debug)]
+struct WebHost {
+    job_id: String,
+    log_callback: js_sys::Function,
+    progress_callback: js_sys::Function,
+    datasets: Vec<ReefDataset>,
+    /// Set by `reef/sleep` and reset once reported to the caller of `run_node`.
+    sleep_for: Mutex<f32>,
+    /// Set by `reef/checkpoint` and reset once reported to the caller of `run_node`.
+    checkpoint: AtomicBool,
+}
+
+impl ReefHost for WebHost {
+    fn log(&self, message: String) -> Result<(), reef_interpreter::Error> {
+        self.log_callback.call1(&JsValue::null(), &JsValue::from(message)).map(|_| ()).map_err(|err| {
+            reef_interpreter::Error::Other(format!(
+                "reef/log: {}",
+                err.as_string().unwrap_or("log js callback error".into()),
+            ))
+        })
+    }
+
+    fn progress(&self, done: f32) -> Result<(), reef_interpreter::Error> {
+        let _ = self.progress_callback.call1(&JsValue::null(), &JsValue::from(done));
+        Ok(())
+    }
+
+    fn sleep(&self, duration: Duration) -> Result<(), reef_interpreter::Error> {
+        *self.sleep_for.lock().unwrap() = duration.as_secs_f32();
+        Ok(())
+    }
+
+    fn checkpoint(&self) -> Result<(), reef_interpreter::Error> {
+        self.checkpoint.store(true, Ordering::Relaxed);
+        Ok(())
+    }
+
+    fn datasets(&self) -> &[ReefDataset] {
+        &self.datasets
+    }
+
+    fn wall_time(&self) -> Duration {
+        // `SystemTime` is not available in the browser.
+        Duration::from_secs_f64(js_sys::Date::now() / 1000.0)
+    }
+
+    fn job_id(&self) -> &str {
+        &self.job_id
+    }
+}
+
 static NODE_STATE: SyncUnsafeCell<Option<Box<NodeState>>> = SyncUnsafeCell::new(None);
 
+/// Recording of the host calls of the current job, shared with its [`Recorder`].
+#[derive(Debug, Clone, Default)]
+struct RecordingBuffer(Arc<Mutex<Vec<u8>>>);
+
+impl std::io::Write for RecordingBuffer {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.0.lock().unwrap().extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+static RECORD_HOST_CALLS: AtomicBool = AtomicBool::new(false);
+// Kept apart from the node state, so the recording can still be read after the job finished.
+static RECORDING: SyncUnsafeCell<Option<RecordingBuffer>> = SyncUnsafeCell::new(None);
+
+/// Record the host calls of every job started afterwards, to reproduce them with the replay of the native node.
+#[wasm_bindgen]
+pub fn set_record_host_calls(enabled: bool) {
+    RECORD_HOST_CALLS.store(enabled, Ordering::Relaxed);
+}
+
+/// Get the recording of the host calls of the last started job, up to now.
+///
+/// Empty if recording was disabled when the job was started.
+#[wasm_bindgen]
+pub fn get_recording() -> Vec<u8> {
+    // SAFETY: no other call can be running at the same time
+    match unsafe { &*RECORDING.get() } {
+        Some(buffer) => buffer.0.lock().unwrap().clone(),
+        None => Vec::new(),
+    }
+}
+
 #[wasm_bindgen]
 pub fn reset_node() {
     // SAFETY: no other call can be running at the same time
     unsafe { *NODE_STATE.get() = None };
 }
 
+/// Error of a failed job, reported back to the manager together with its kind.
+#[derive(Debug, Clone)]
+#[wasm_bindgen(getter_with_clone)]
+pub struct JobError {
+    pub message: String,
+    pub failure_kind: FailureKind,
+}
+
+impl From<reef_interpreter::Error> for JobError {
+    fn from(err: reef_interpreter::Error) -> Self {
+        Self { failure_kind: failure_kind(&err).into(), message: err.to_string() }
+    }
+}
+
+/// Start a job, `datasets` holds the contents of the datasets named in `dataset_names` as `Uint8Array`s.
 #[wasm_bindgen]
 pub fn init_node(
+    job_id: String,
     program: &[u8],
     state: &[u8],
-    dataset: Vec<u8>,
+    dataset_names: Vec<String>,
+    datasets: js_sys::Array,
     log_callback: js_sys::Function,
     progress_callback: js_sys::Function,
-) -> Result<(), String> {
-    init_node_inner(program, state, dataset, log_callback, progress_callback).map_err(|e| e.to_string())
+) -> Result<(), JobError> {
+    if dataset_names.len() != datasets.length() as usize {
+        return Err(JobError {
+            message: format!("got {} dataset names for {} datasets", dataset_names.len(), datasets.length()),
+            failure_kind: FailureKind::Internal,
+        });
+    }
+
+    let datasets = dataset_names
+        .into_iter()
+        .zip(datasets.iter())
+        .map(|(name, data)| ReefDataset::new(name, js_sys::Uint8Array::new(&data).to_vec()))
+        .collect();
+    init_node_inner(job_id, program, state, datasets, log_callback, progress_callback).map_err(JobError::from)
 }
 
 fn init_node_inner(
+    job_id: String,
     program: &[u8],
     state: &[u8],
-    dataset: Vec<u8>,
+    datasets: Vec<ReefDataset>,
     log_callback: js_sys::Function,
     progress_callback: js_sys::Function,
 ) -> Result<(), reef_interpreter::Error> {
-    let module = parse_bytes(program)?;
-
-    let sleep_for = Rc::new(Cell::new(0.0));
-    let dataset = Rc::new(dataset);
-    let job_output = Rc::new(RefCell::new(JobOutput { content_type: 0, data: Vec::new() }));
-
-    let imports =
-        reef_imports(log_callback, progress_callback, sleep_for.clone(), dataset.clone(), job_output.clone())?;
-
-    let state = if state.is_empty() { None } else { Some(state) };
-    let (mut instance, stack, mut extra_data) = Instance::instantiate(module, imports, state)?;
-    if stack.is_some() {
-        // reload dataset
-        let mut mem = instance.exported_memory_mut("memory")?;
-
-        if mem.get_ignored_byte_region().1 == dataset.len() {
-            mem.copy_into_ignored_byte_region(&dataset);
+    let module = parse_bytes_lazy(program)?;
+
+    // The reef imports share their host through an `Arc`, the JS callbacks keep it on this thread anyway.
+    #[allow(clippy::arc_with_non_send_sync)]
+    let host = Arc::new(WebHost {
+        job_id,
+        log_callback,
+        progress_callback,
+        datasets,
+        sleep_for: Mutex::new(0.0),
+        checkpoint: AtomicBool::new(false),
+    });
+    let mut imports = reef_imports(host.clone(), abi_version(&module)?)?;
+
+    let (recorder, recording) = match RECORD_HOST_CALLS.load(Ordering::Relaxed) {
+        true => {
+            let buffer = RecordingBuffer::default();
+            let recorder = Recorder::new(buffer.clone())?;
+            imports = recorder.wrap(imports);
+            (Some(recorder), Some(buffer))
         }
-        drop(dataset);
-    }
-
-    job_output.borrow_mut().content_type = extra_data.pop().unwrap_or(0);
-    job_output.borrow_mut().data = extra_data;
+        false => (None, None),
+    };
+    // SAFETY: no other call can be running at the same time
+    unsafe { *RECORDING.get() = recording };
 
-    let entry_fn_handle = instance.exported_func::<ReefMainArgs, ReefMainReturn>(REEF_MAIN_NAME)?;
-    let exec_handle = entry_fn_handle.call((), stack)?;
+    let state = if state.is_empty() { None } else { Some(state) };
+    let exec_handle = start_job(module, imports, &*host, state)?;
 
-    let node_state = NodeState { handle: exec_handle, sleep_for, job_output };
+    let node_state = NodeState { handle: exec_handle, host, recorder, synced_cycles: 0 };
 
     // SAFETY: no other call can be running at the same time
     unsafe { *NODE_STATE.get() = Some(Box::new(node_state)) }
@@ -113,161 +242,93 @@ fn init_node_inner(
     Ok(())
 }
 
-fn reef_imports(
-    log_callback: js_sys::Function,
-    progress_callback: js_sys::Function,
-    sleep_for: Rc<Cell<f32>>,
-    dataset: Rc<Vec<u8>>,
-    job_output: Rc<RefCell<JobOutput>>,
-) -> Result<Imports, reef_interpreter::Error> {
-    let mut imports = Imports::new();
-
-    // Reef Log.
-    imports.define(
-        REEF_MODULE_NAME,
-        REEF_LOG_NAME,
-        Extern::typed_func(move |ctx, (ptr, len): ReefLogArgs| {
-            let mem = ctx.exported_memory("memory")?;
-            let log_string = mem.load_string(ptr as usize, len as usize)?;
-
-            if log_string.len() > 0x400 {
-                return Err(reef_interpreter::Error::Other(
-                    "reef/log: log message longer than 0x400 bytes, aborting".into(),
-                ));
-            }
-
-            let this = JsValue::null();
-            let log_string = JsValue::from(log_string);
-            log_callback.call1(&this, &log_string).map(|_| ()).map_err(|err| {
-                reef_interpreter::Error::Other(format!(
-                    "reef/log: {}",
-                    err.as_string().unwrap_or("log js callback error".into()),
-                ))
-            })
-        }),
-    )?;
-
-    // Reef report progress.
-    imports.define(
-        REEF_MODULE_NAME,
-        REEF_PROGRESS_NAME,
-        Extern::typed_func(move |_ctx, (done,): ReefProgressArgs| {
-            if !(0.0..=1.0).contains(&done) {
-                return Err(reef_interpreter::Error::Other("reef/progress: value not in Range 0.0..=1.0".into()));
-            }
-
-            let this = JsValue::null();
-            let done = JsValue::from(done);
-            let _ = progress_callback.call1(&this, &done);
-
-            Ok(())
-        }),
-    )?;
-
-    // Reef sleep.
-    imports.define(
-        REEF_MODULE_NAME,
-        REEF_SLEEP_NAME,
-        Extern::typed_func::<_, ReefSleepReturn>(move |_ctx, (seconds,): ReefSleepArgs| {
-            sleep_for.set(seconds);
-            Err(reef_interpreter::Error::PauseExecution)
-        }),
-    )?;
-
-    // Reef dataset.
-    // Reef std implementations guarantee, that the dataset is at least 8 byte aligned.
-    let dataset_len = dataset.len();
-    let dataset = std::cell::RefCell::new(Some(dataset));
-    imports.define(
-        REEF_MODULE_NAME,
-        REEF_DATASET_LEN_NAME,
-        Extern::typed_func::<_, ReefDatasetLenReturn>(move |_ctx, _args: ReefDatasetLenArgs| Ok((dataset_len as i32,))),
-    )?;
-    imports.define(
-        REEF_MODULE_NAME,
-        REEF_DATASET_WRITE_NAME,
-        Extern::typed_func::<_, ReefDatasetWriteReturn>(move |mut ctx, (ptr,): ReefDatasetWriteArgs| {
-            let mut mem = ctx.exported_memory_mut("memory")?;
-
-            mem.set_ignored_byte_region(ptr as usize, dataset_len);
-            mem.copy_into_ignored_byte_region(dataset.borrow().as_deref().unwrap_or(&Rc::new(Vec::new())));
-
-            // Drop the remaining Rc reference to free the Vec
-            if dataset.borrow().is_some() {
-                *dataset.borrow_mut() = None;
-            }
-
-            Ok(())
-        }),
-    )?;
-
-    // Reef result.
-    imports.define(
-        REEF_MODULE_NAME,
-        REEF_RESULT_NAME,
-        Extern::typed_func::<_, ReefResultReturn>(move |ctx, (result_type, ptr, len): ReefResultArgs| {
-            let mem = ctx.exported_memory("memory")?;
-            let data = mem.load_vec(ptr as usize, len as usize)?;
-
-            let content_type = match result_type {
-                0..3 => result_type as u8,
-                _ => return Err(reef_interpreter::Error::Other("invalid ResultContentType".into())),
-            };
-
-            *job_output.borrow_mut() = JobOutput { content_type, data };
-
-            Ok(())
-        }),
-    )?;
-
-    Ok(imports)
-}
-
 #[derive(Debug, Clone, Default)]
 #[wasm_bindgen(getter_with_clone)]
 pub struct RunResult {
     pub done: bool,
     pub sleep_for: Option<f32>,
+    /// The job asked for its state to be synced before it continues.
+    pub checkpoint: bool,
+    /// The job asked for syncs to be deferred, up to [`get_defer_sync_max_millis`].
+    pub sync_deferred: bool,
     pub job_output: Option<JobOutput>,
 }
 
 #[wasm_bindgen]
-pub fn run_node(max_cycles: usize) -> Result<RunResult, String> {
+pub fn run_node(max_cycles: usize) -> Result<RunResult, JobError> {
     // SAFETY: no other call can be running at the same time
     let mut node_state = unsafe { (*NODE_STATE.get()).take().unwrap() };
 
     let run_res = node_state.handle.run(max_cycles);
     match run_res {
         Ok(CallResultTyped::Done(_)) => {
-            drop(node_state.handle);
-            let job_output = Rc::try_unwrap(node_state.job_output).unwrap().into_inner();
-            Ok(RunResult { done: true, sleep_for: None, job_output: Some(job_output) })
+            let cycles = node_state.handle.cycles();
+            let state_hash = node_state.handle.state_hash_with_data()?.to_vec();
+            let ReefState { output, wall_clock_readings: wall_clock, .. } =
+                node_state.handle.into_instance().into_data();
+            record_end(node_state.recorder.as_ref(), cycles, Ok(&output));
+
+            let ReefJobOutput { content_type, data } = output;
+            let cycles = cycles - node_state.synced_cycles;
+            let job_output = JobOutput { content_type, data, cycles, state_hash, wall_clock };
+            Ok(RunResult { done: true, job_output: Some(job_output), ..Default::default() })
         }
         Ok(CallResultTyped::Incomplete) => {
-            let sleep_for = node_state.sleep_for.replace(0.0);
+            let sleep_for = std::mem::take(&mut *node_state.host.sleep_for.lock().unwrap());
+            let checkpoint = node_state.host.checkpoint.swap(false, Ordering::Relaxed);
+            let sync_deferred = node_state.handle.instance().data().sync_deferred;
             unsafe { *NODE_STATE.get() = Some(node_state) }
 
-            Ok(RunResult { done: false, sleep_for: Some(sleep_for), job_output: None })
+            Ok(RunResult { done: false, sleep_for: Some(sleep_for), checkpoint, sync_deferred, job_output: None })
+        }
+        // None of the reef imports suspend their calls.
+        Ok(CallResultTyped::HostPending(_)) => {
+            Err(JobError { message: "unexpected pending host call".into(), failure_kind: FailureKind::Internal })
+        }
+        Err(err) => {
+            record_end(node_state.recorder.as_ref(), node_state.handle.cycles(), Err(&err));
+            Err(err.into())
         }
-        Err(err) => Err(err.to_string()),
     }
 }
 
+fn record_end(recorder: Option<&Recorder>, cycles: u64, res: Result<&ReefJobOutput, &reef_interpreter::Error>) {
+    if let (Some(recorder), Some(end)) = (recorder, end_record(cycles, res)) {
+        recorder.finish(end);
+    }
+}
+
+/// State of the job for a state sync, together with the claim the manager can have verified.
+#[derive(Debug, Clone)]
+#[wasm_bindgen(getter_with_clone)]
+pub struct StateSync {
+    /// Uncompressed interpreter state.
+    pub state: Vec<u8>,
+    /// Instructions executed since the previous state sync.
+    pub cycles: u64,
+    pub state_hash: Vec<u8>,
+    /// Readings of the wall clock during these instructions.
+    pub wall_clock: Vec<u64>,
+}
+
 #[wasm_bindgen]
-pub fn serialize_state() -> Vec<u8> {
+pub fn serialize_state() -> StateSync {
     let mut buffer = Vec::new();
     let mut writer = std::io::Cursor::new(&mut buffer);
 
     // SAFETY: no other call can be running at the same time
     let mut node_state = unsafe { (*NODE_STATE.get()).take().unwrap() };
 
-    // this clone should generally be cheap because job output is either not set or
-    // very short during execution.
-    let mut extra_data = node_state.job_output.borrow().data.clone();
-    extra_data.push(node_state.job_output.borrow().content_type);
-    node_state.handle.serialize_raw(&mut writer, &extra_data).unwrap();
+    node_state.handle.serialize_raw_with_data(&mut writer).unwrap();
+    let cycles = node_state.handle.cycles() - node_state.synced_cycles;
+    let state_hash = node_state.handle.state_hash_with_data().unwrap();
+    if let Some(recorder) = &node_state.recorder {
+        recorder.sync(node_state.handle.cycles(), state_hash);
+    }
+    node_state.synced_cycles = node_state.handle.cycles();
+    let wall_clock = std::mem::take(&mut node_state.handle.instance_mut().data_mut().wall_clock_readings);
 
     unsafe { *NODE_STATE.get() = Some(node_state) }
 
-    buffer
+    StateSync { state: buffer, cycles, state_hash: state_hash.to_vec(), wall_clock }
 }
@@ -2,15 +2,18 @@ use capnp::{message::ReaderOptions, serialize};
 use reef_protocol_node::message_capnp::{
     assign_id_message,
     message_to_node::{self, body},
-    MessageFromNodeKind, MessageToNodeKind,
+    JobFailureKind, MessageFromNodeKind, MessageToNodeKind,
 };
+use reef_wasm_interface::{REEF_ABI_MIN_VERSION, REEF_ABI_VERSION};
 use wasm_bindgen::prelude::*;
 
 #[derive(Debug, Clone, Default)]
 #[wasm_bindgen(getter_with_clone)]
 pub struct StartJobRequest {
     pub job_id: String,
-    pub dataset_id: String,
+    /// Names and IDs of the datasets, in the order the program sees them.
+    pub dataset_names: Vec<String>,
+    pub dataset_ids: Vec<String>,
     pub progress: f32,
 
     pub program_byte_code: Vec<u8>,
@@ -30,6 +33,46 @@ pub enum NodeMessageKind {
     AbortJob,
 }
 
+/// Why a job failed, mirrors `JobFailureKind` of the protocol.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[wasm_bindgen]
+pub enum FailureKind {
+    #[default]
+    None,
+
+    Trap,
+    HostLimit,
+    Aborted,
+    InvalidProgram,
+    Internal,
+}
+
+impl From<JobFailureKind> for FailureKind {
+    fn from(value: JobFailureKind) -> Self {
+        match value {
+            JobFailureKind::None => Self::None,
+            JobFailureKind::Trap => Self::Trap,
+            JobFailureKind::HostLimit => Self::HostLimit,
+            JobFailureKind::Aborted => Self::Aborted,
+            JobFailureKind::InvalidProgram => Self::InvalidProgram,
+            JobFailureKind::Internal => Self::Internal,
+        }
+    }
+}
+
+impl From<FailureKind> for JobFailureKind {
+    fn from(value: FailureKind) -> Self {
+        match value {
+            FailureKind::None => Self::None,
+            FailureKind::Trap => Self::Trap,
+            FailureKind::HostLimit => Self::HostLimit,
+            FailureKind::Aborted => Self::Aborted,
+            FailureKind::InvalidProgram => Self::InvalidProgram,
+            FailureKind::Internal => Self::Internal,
+        }
+    }
+}
+
 #[derive(Debug, Clone, Default)]
 #[wasm_bindgen(getter_with_clone)]
 pub struct NodeMessage {
@@ -116,11 +159,23 @@ pub fn parse_websocket_data(data: &[u8]) -> Result<NodeMessage, ParseError> {
             let body = body?;
             let job_id = String::from_utf8(body.get_job_id()?.0.to_vec())?;
 
-            let dataset_id = String::from_utf8(body.get_dataset_id()?.0.to_vec())?;
+            // Without a list of datasets, the job only has a single unnamed one.
+            let (dataset_names, dataset_ids) = if body.get_datasets()?.is_empty() {
+                (vec![String::new()], vec![String::from_utf8(body.get_dataset_id()?.0.to_vec())?])
+            } else {
+                let mut names = Vec::new();
+                let mut ids = Vec::new();
+                for dataset in body.get_datasets()? {
+                    names.push(String::from_utf8(dataset.get_name()?.0.to_vec())?);
+                    ids.push(String::from_utf8(dataset.get_dataset_id()?.0.to_vec())?);
+                }
+                (names, ids)
+            };
 
             Ok(NodeMessage::start_job(StartJobRequest {
                 job_id,
-                dataset_id,
+                dataset_names,
+                dataset_ids,
                 progress: body.get_progress(),
 
                 program_byte_code: body.get_program_byte_code()?.to_vec(),
@@ -147,6 +202,10 @@ pub fn serialize_handshake_response(num_workers: u16, node_name: &str) -> Vec<u8
 
     handshake_response.set_num_workers(num_workers);
     handshake_response.set_node_name(node_name);
+    handshake_response.set_abi_version_min(REEF_ABI_MIN_VERSION);
+    handshake_response.set_abi_version_max(REEF_ABI_VERSION);
+    // Verifications are only run by native nodes.
+    handshake_response.set_can_verify(false);
 
     let mut buffer = vec![];
     capnp::serialize::write_message(&mut buffer, &message).unwrap();
@@ -154,7 +213,14 @@ pub fn serialize_handshake_response(num_workers: u16, node_name: &str) -> Vec<u8
 }
 
 #[wasm_bindgen]
-pub fn serialize_job_state_sync(progress: f32, interpreter_state: &[u8], logs: Vec<String>) -> Vec<u8> {
+pub fn serialize_job_state_sync(
+    progress: f32,
+    interpreter_state: &[u8],
+    logs: Vec<String>,
+    cycles: u64,
+    state_hash: &[u8],
+    wall_clock: &[u64],
+) -> Vec<u8> {
     let mut message = capnp::message::Builder::new_default();
     let mut encapsulating_message: reef_protocol_node::message_capnp::message_from_node::Builder = message.init_root();
     encapsulating_message.set_kind(MessageFromNodeKind::JobStateSync);
@@ -164,6 +230,9 @@ pub fn serialize_job_state_sync(progress: f32, interpreter_state: &[u8], logs: V
     state_sync.set_worker_index(0);
     state_sync.set_progress(progress);
     state_sync.set_interpreter(interpreter_state);
+    state_sync.set_cycles(cycles);
+    state_sync.set_state_hash(state_hash);
+    state_sync.set_wall_clock(wall_clock).unwrap();
 
     let mut logs_builder = state_sync.init_logs(logs.len() as u32);
 
@@ -178,8 +247,18 @@ pub fn serialize_job_state_sync(progress: f32, interpreter_state: &[u8], logs: V
     buffer
 }
 
+/// `cycles`, `state_hash` and `wall_clock` are the claim on the final state of a successful job, 0/empty for failed
+/// jobs.
 #[wasm_bindgen]
-pub fn serialize_job_result(success: bool, content: &[u8], content_type: u8) -> Vec<u8> {
+pub fn serialize_job_result(
+    success: bool,
+    content: &[u8],
+    content_type: u8,
+    failure_kind: FailureKind,
+    cycles: u64,
+    state_hash: &[u8],
+    wall_clock: &[u64],
+) -> Vec<u8> {
     let mut message = capnp::message::Builder::new_default();
     let mut encapsulating_message: reef_protocol_node::message_capnp::message_from_node::Builder = message.init_root();
     encapsulating_message.set_kind(MessageFromNodeKind::JobResult);
@@ -190,6 +269,10 @@ pub fn serialize_job_result(success: bool, content: &[u8], content_type: u8) ->
     job_result.set_success(success);
     job_result.set_contents(content);
     job_result.set_content_type(reef_wasm_interface::num_to_content_type(content_type).expect("invalid content type"));
+    job_result.set_failure_kind(failure_kind.into());
+    job_result.set_cycles(cycles);
+    job_result.set_state_hash(state_hash);
+    job_result.set_wall_clock(wall_clock).unwrap();
 
     let mut buffer = vec![];
     capnp::serialize::write_message(&mut buffer, &message).unwrap();
@@ -0,0 +1,135 @@
+//! Versioning of the reef imports
+//!
+//! Programs declare the ABI version they were built against in the [`REEF_ABI_SECTION`] custom section, as a
+//! little endian `u32`. Programs without the section predate versioning and use ABI version 1.
+//! Imports which changed since an older version are replaced by shims, so nodes keep running older programs.
+
+use std::sync::Arc;
+
+use reef_interpreter::{
+    imports::{Captured, Extern, FuncContext, Imports, Threading},
+    Error, Module,
+};
+
+use crate::*;
+
+/// Custom section in which programs declare their ABI version
+pub const REEF_ABI_SECTION: &str = "reef_abi";
+
+/// ABI version implemented by [`reef_imports`]
+///
+/// Versions:
+/// 1. Initial version, used by programs without a [`REEF_ABI_SECTION`].
+/// 2. `reef/dataset_len` returns an `i64`.
+/// 3. `reef/dataset_read` was added.
+/// 4. Jobs have multiple named datasets, the dataset imports take the index of a dataset.
+/// 5. `reef/checkpoint` and `reef/defer_sync` were added.
+/// 6. `reef/random_fill` was added.
+/// 7. `reef/time_now` was added.
+pub const REEF_ABI_VERSION: u32 = 7;
+
+/// Oldest ABI version which is still supported through shims
+pub const REEF_ABI_MIN_VERSION: u32 = 1;
+
+/// Read the ABI version declared by a program and check that it is supported
+pub fn abi_version(module: &Module) -> Result<u32, Error> {
+    let version = match module.custom_section(REEF_ABI_SECTION) {
+        Some(section) => {
+            let section = <[u8; 4]>::try_from(section).map_err(|_| Error::host(ReefError::InvalidAbiSection))?;
+            u32::from_le_bytes(section)
+        }
+        None => 1,
+    };
+
+    if !(REEF_ABI_MIN_VERSION..=REEF_ABI_VERSION).contains(&version) {
+        return Err(Error::host(ReefError::UnsupportedAbi(version)));
+    }
+
+    Ok(version)
+}
+
+/// Replace the imports which changed after `abi_version` with shims implementing the old behavior
+pub(crate) fn define_shims<H: ReefHost, S: Threading>(
+    imports: &mut Imports<ReefState, S>,
+    host: &Arc<H>,
+    abi_version: u32,
+) -> Result<(), Error>
+where
+    Arc<H>: Captured<S>,
+{
+    // Before version 4, programs could only access the first dataset.
+    if abi_version < 4 {
+        imports.define(
+            REEF_MODULE_NAME,
+            REEF_DATASET_LEN_NAME,
+            Extern::typed_func_with::<_, _, ReefDatasetLenReturn>(
+                host.clone(),
+                |host, _ctx, _args: ReefDatasetLenV3Args| Ok((dataset::dataset(&**host, 0)?.len() as i64,)),
+            ),
+        )?;
+        imports.define(
+            REEF_MODULE_NAME,
+            REEF_DATASET_WRITE_NAME,
+            Extern::typed_func_with::<_, _, ReefDatasetWriteReturn>(
+                host.clone(),
+                |host, mut ctx: FuncContext<'_, ReefState, S>, (ptr,): ReefDatasetWriteV3Args| {
+                    dataset::write_dataset(&mut ctx, &**host, 0, ptr)
+                },
+            ),
+        )?;
+        imports.define(
+            REEF_MODULE_NAME,
+            REEF_DATASET_READ_NAME,
+            Extern::typed_func_with::<_, _, ReefDatasetReadReturn>(
+                host.clone(),
+                |host, mut ctx: FuncContext<'_, ReefState, S>, (offset, ptr, len): ReefDatasetReadV3Args| {
+                    Ok((dataset::read_dataset(&mut ctx, &**host, 0, offset, ptr, len)?,))
+                },
+            ),
+        )?;
+    }
+
+    if abi_version < 2 {
+        imports.define(
+            REEF_MODULE_NAME,
+            REEF_DATASET_LEN_NAME,
+            Extern::typed_func_with::<_, _, ReefDatasetLenV1Return>(
+                host.clone(),
+                |host, _ctx, _args: ReefDatasetLenV3Args| {
+                    let len = dataset::dataset(&**host, 0)?.len();
+                    let len = i32::try_from(len).map_err(|_| Error::host(ReefError::DatasetTooLarge(len)))?;
+                    Ok((len,))
+                },
+            ),
+        )?;
+    }
+
+    Ok(())
+}
+
+/// `reef/dataset_len` of ABI version 1
+type ReefDatasetLenV1Return = (i32,);
+/// Dataset imports of ABI versions 1 to 3, which always refer to the first dataset
+type ReefDatasetLenV3Args = ();
+type ReefDatasetWriteV3Args = (i32,);
+type ReefDatasetReadV3Args = (i64, i32, i32);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(wat: &str) -> Module {
+        reef_interpreter::parse_bytes(&wat::parse_str(wat).expect("invalid wat")).unwrap()
+    }
+
+    #[test]
+    fn test_abi_version() {
+        assert_eq!(abi_version(&parse("(module)")).unwrap(), 1);
+        assert_eq!(abi_version(&parse(r#"(module (@custom "reef_abi" "\02\00\00\00"))"#)).unwrap(), 2);
+
+        let err = abi_version(&parse(r#"(module (@custom "reef_abi" "\ff\00\00\00"))"#)).unwrap_err();
+        assert_eq!(err.downcast_host::<ReefError>().unwrap(), ReefError::UnsupportedAbi(255));
+        let err = abi_version(&parse(r#"(module (@custom "reef_abi" "\02"))"#)).unwrap_err();
+        assert_eq!(err.downcast_host::<ReefError>().unwrap(), ReefError::InvalidAbiSection);
+    }
+}
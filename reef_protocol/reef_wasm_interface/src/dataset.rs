@@ -0,0 +1,137 @@
+//! Datasets of a job and the reef imports accessing them
+
+use std::sync::Mutex;
+
+use reef_interpreter::{
+    imports::{FuncContext, Threading},
+    Error, Instance,
+};
+
+use crate::*;
+
+/// A named dataset of a job, shared between the node and the reef imports
+#[derive(Debug)]
+pub struct ReefDataset {
+    name: String,
+    len: usize,
+    /// Dropped once the program copied all of it into its memory, reads are served from that copy afterwards.
+    data: Mutex<Option<Vec<u8>>>,
+}
+
+impl ReefDataset {
+    pub fn new(name: impl Into<String>, data: Vec<u8>) -> Self {
+        Self { name: name.into(), len: data.len(), data: Mutex::new(Some(data)) }
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    fn take(&self) -> Option<Vec<u8>> {
+        self.data.lock().unwrap().take()
+    }
+
+    /// Copy the dataset starting at `offset` into `buf`, the window has to be within the dataset
+    fn read(&self, offset: usize, buf: &mut [u8]) -> Result<(), Error> {
+        let data = self.data.lock().unwrap();
+        let data = data.as_ref().ok_or_else(|| Error::host(ReefError::DatasetAlreadyWritten))?;
+        buf.copy_from_slice(&data[offset..offset + buf.len()]);
+        Ok(())
+    }
+}
+
+/// Look up a dataset by the index the program passed
+pub(crate) fn dataset<H: ReefHost>(host: &H, index: i32) -> Result<&ReefDataset, Error> {
+    usize::try_from(index)
+        .ok()
+        .and_then(|index| host.datasets().get(index))
+        .ok_or_else(|| Error::host(ReefError::InvalidDataset(index)))
+}
+
+/// Implementation of `reef/dataset_write`
+///
+/// The dataset is excluded from snapshots and copied back in by [`restore_datasets`] when the job is resumed.
+pub(crate) fn write_dataset<H: ReefHost, S: Threading>(
+    ctx: &mut FuncContext<'_, ReefState, S>,
+    host: &H,
+    index: i32,
+    ptr: i32,
+) -> Result<(), Error> {
+    let data = dataset(host, index)?.take().ok_or_else(|| Error::host(ReefError::DatasetAlreadyWritten))?;
+
+    let mut mem = ctx.exported_memory_mut("memory")?;
+    mem.add_ignored_byte_region(ptr as usize, data.len())?;
+    mem.store(ptr as usize, data.len(), &data)?;
+
+    ctx.data_mut().written_datasets.push((index as u32, ptr as u32));
+    Ok(())
+}
+
+/// Implementation of `reef/dataset_read`
+///
+/// Reads only copy a window of the dataset into regular memory, so they need no special handling in snapshots.
+/// Once the program wrote the dataset into its memory with `reef/dataset_write`, the window is copied from there.
+pub(crate) fn read_dataset<H: ReefHost, S: Threading>(
+    ctx: &mut FuncContext<'_, ReefState, S>,
+    host: &H,
+    index: i32,
+    offset: i64,
+    ptr: i32,
+    len: i32,
+) -> Result<i32, Error> {
+    let dataset = dataset(host, index)?;
+
+    // Reads past the end of the dataset are cut short.
+    let dataset_len = dataset.len() as u64;
+    let offset = (offset as u64).min(dataset_len);
+    let len = (len as u32 as u64).min(dataset_len - offset) as usize;
+    if len == 0 {
+        return Ok(0);
+    }
+
+    let written = ctx.data().written_datasets.iter().find(|(written, _)| *written == index as u32).map(|w| w.1);
+    let mut mem = ctx.exported_memory_mut("memory")?;
+    match written {
+        Some(loaded) => mem.copy_within(loaded as usize + offset as usize, ptr as usize, len)?,
+        None => {
+            // Check the bounds before allocating the buffer
+            mem.load(ptr as usize, len)?;
+
+            let mut buf = vec![0; len];
+            dataset.read(offset as usize, &mut buf)?;
+            mem.store(ptr as usize, len, &buf)?;
+        }
+    }
+
+    Ok(len as i32)
+}
+
+/// Copy the datasets the program wrote into its memory back in, after resuming from a snapshot
+pub(crate) fn restore_datasets<H: ReefHost, S: Threading>(
+    instance: &mut Instance<ReefState, S>,
+    host: &H,
+) -> Result<(), Error> {
+    let written_datasets = instance.data().written_datasets.clone();
+    let mut mem = instance.exported_memory_mut("memory")?;
+
+    for (index, ptr) in written_datasets {
+        let data = dataset(host, index as i32)?.take().unwrap_or_default();
+        let region = (ptr as usize, data.len());
+
+        // Empty datasets don't need a region
+        if !data.is_empty() && !mem.ignored_byte_regions().any(|ignored| ignored == region) {
+            return Err(Error::Other(format!("dataset {index} does not match the one the job was started with")));
+        }
+        mem.store(region.0, region.1, &data)?;
+    }
+
+    Ok(())
+}
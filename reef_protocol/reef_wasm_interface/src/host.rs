@@ -0,0 +1,714 @@
+//! Implementation of the reef imports, shared by all node types
+
+use std::sync::Arc;
+use std::time::Duration;
+
+use rand_chacha::ChaCha20Rng;
+use reef_interpreter::{
+    imports::{Captured, Extern, FuncContext, Imports, Threading},
+    reference::MemoryStringExt,
+    types::Backend,
+    Error, Instance, Module,
+};
+
+use crate::*;
+
+/// Node specific side of the reef imports
+///
+/// The imports validate the arguments of the program before passing them on, so every node enforces the same limits.
+/// Hosts only have to be `Send + Sync` for [`Shared`](reef_interpreter::imports::Shared) imports.
+pub trait ReefHost: 'static {
+    /// Handle a message logged by the program, at most [`REEF_LOG_MAX_LEN`] bytes long
+    fn log(&self, message: String) -> Result<(), Error>;
+
+    /// Handle a progress report of the program, within `0.0..=1.0`
+    fn progress(&self, done: f32) -> Result<(), Error>;
+
+    /// Remember that the program wants to sleep, execution is paused once this returns
+    fn sleep(&self, duration: Duration) -> Result<(), Error>;
+
+    /// Remember that the program wants its state synced, execution is paused once this returns
+    ///
+    /// The node should sync before continuing execution, even if [`ReefState::sync_deferred`] is set.
+    fn checkpoint(&self) -> Result<(), Error>;
+
+    /// Datasets of the job, the program accesses them by their index
+    fn datasets(&self) -> &[ReefDataset];
+
+    /// Wall-clock time as the time since the Unix epoch
+    fn wall_time(&self) -> Duration;
+
+    /// ID of the job, which seeds the random numbers of the program, see [`random_seed`](crate::random_seed)
+    fn job_id(&self) -> &str;
+}
+
+/// State of the reef imports, owned by the interpreter instance and part of every snapshot
+#[derive(Debug, Default, serde::Serialize, serde::Deserialize)]
+pub struct ReefState {
+    /// Set by `reef/result`, kept in the state so it survives the migration of the job.
+    pub output: ReefJobOutput,
+    /// Datasets copied into memory by `reef/dataset_write` as `(index, ptr)`, they are excluded from snapshots.
+    pub(crate) written_datasets: Vec<(u32, u32)>,
+    /// Set by `reef/defer_sync`, nodes defer syncs while set, up to [`REEF_DEFER_SYNC_MAX`].
+    pub sync_deferred: bool,
+    /// Generator of `reef/random_fill`, seeded on first use.
+    pub(crate) rng: Option<ChaCha20Rng>,
+    /// Readings of [`REEF_CLOCK_WALL`] since the job was resumed or last synced.
+    ///
+    /// They are covered by the state hash, so nodes send them with the claim of the next sync for the verification to
+    /// replay them, and take them out of the state afterwards.
+    pub wall_clock_readings: Vec<u64>,
+}
+
+/// Define the reef imports, which pass the validated calls of the program on to `host`
+///
+/// `abi_version` is the version declared by the program, see [`abi_version`](crate::abi_version).
+pub fn reef_imports<H: ReefHost, S: Threading>(host: Arc<H>, abi_version: u32) -> Result<Imports<ReefState, S>, Error>
+where
+    Arc<H>: Captured<S>,
+{
+    let mut imports = Imports::default();
+
+    // Reef Log.
+    imports.define(
+        REEF_MODULE_NAME,
+        REEF_LOG_NAME,
+        Extern::typed_func_with(host.clone(), |host, ctx: FuncContext<'_, ReefState, S>, (ptr, len): ReefLogArgs| {
+            if len as u32 as usize > REEF_LOG_MAX_LEN {
+                return Err(Error::host(ReefError::LogTooLong(len as u32 as usize)));
+            }
+
+            let mem = ctx.exported_memory("memory")?;
+            host.log(mem.load_string(ptr as usize, len as usize)?)
+        }),
+    )?;
+
+    // Reef report progress.
+    imports.define(
+        REEF_MODULE_NAME,
+        REEF_PROGRESS_NAME,
+        Extern::typed_func_with(host.clone(), |host, _ctx, (done,): ReefProgressArgs| {
+            if !(0.0..=1.0).contains(&done) {
+                return Err(Error::host(ReefError::InvalidProgress(done)));
+            }
+
+            host.progress(done)
+        }),
+    )?;
+
+    // Reef sleep.
+    imports.define(
+        REEF_MODULE_NAME,
+        REEF_SLEEP_NAME,
+        Extern::typed_func_with::<_, _, ReefSleepReturn>(host.clone(), |host, _ctx, (seconds,): ReefSleepArgs| {
+            let duration =
+                Duration::try_from_secs_f32(seconds).map_err(|_| Error::host(ReefError::InvalidSleep(seconds)))?;
+            host.sleep(duration)?;
+
+            Err(Error::PauseExecution)
+        }),
+    )?;
+
+    // Reef sync control.
+    imports.define(
+        REEF_MODULE_NAME,
+        REEF_CHECKPOINT_NAME,
+        Extern::typed_func_with::<_, _, ReefCheckpointReturn>(host.clone(), |host, _ctx, _args: ReefCheckpointArgs| {
+            host.checkpoint()?;
+
+            Err(Error::PauseExecution)
+        }),
+    )?;
+    imports.define(
+        REEF_MODULE_NAME,
+        REEF_DEFER_SYNC_NAME,
+        Extern::typed_func_with::<_, _, ReefDeferSyncReturn>(
+            host.clone(),
+            |_, mut ctx: FuncContext<'_, ReefState, S>, (defer,): ReefDeferSyncArgs| {
+                ctx.data_mut().sync_deferred = defer != 0;
+                Ok(())
+            },
+        ),
+    )?;
+
+    // Reef dataset.
+    // Reef std implementations guarantee, that written datasets are at least 8 byte aligned.
+    imports.define(
+        REEF_MODULE_NAME,
+        REEF_DATASET_COUNT_NAME,
+        Extern::typed_func_with::<_, _, ReefDatasetCountReturn>(
+            host.clone(),
+            |host, _ctx, _args: ReefDatasetCountArgs| Ok((host.datasets().len() as i32,)),
+        ),
+    )?;
+    imports.define(
+        REEF_MODULE_NAME,
+        REEF_DATASET_FIND_NAME,
+        Extern::typed_func_with::<_, _, ReefDatasetFindReturn>(
+            host.clone(),
+            |host, ctx: FuncContext<'_, ReefState, S>, (ptr, len): ReefDatasetFindArgs| {
+                let mem = ctx.exported_memory("memory")?;
+                let name = mem.load(ptr as usize, len as usize)?;
+
+                let index = host.datasets().iter().position(|dataset| dataset.name().as_bytes() == name);
+                Ok((index.map_or(-1, |index| index as i32),))
+            },
+        ),
+    )?;
+    imports.define(
+        REEF_MODULE_NAME,
+        REEF_DATASET_NAME_NAME,
+        Extern::typed_func_with::<_, _, ReefDatasetNameReturn>(
+            host.clone(),
+            |host, mut ctx: FuncContext<'_, ReefState, S>, (index, ptr, len): ReefDatasetNameArgs| {
+                let name = dataset::dataset(&**host, index)?.name().as_bytes();
+                // Names longer than the buffer are cut short, the program can retry with the returned length.
+                let copied = name.len().min(len as u32 as usize);
+
+                let mut mem = ctx.exported_memory_mut("memory")?;
+                mem.store(ptr as usize, copied, &name[..copied])?;
+
+                Ok((name.len() as i32,))
+            },
+        ),
+    )?;
+    imports.define(
+        REEF_MODULE_NAME,
+        REEF_DATASET_LEN_NAME,
+        Extern::typed_func_with::<_, _, ReefDatasetLenReturn>(
+            host.clone(),
+            |host, _ctx, (index,): ReefDatasetLenArgs| Ok((dataset::dataset(&**host, index)?.len() as i64,)),
+        ),
+    )?;
+    imports.define(
+        REEF_MODULE_NAME,
+        REEF_DATASET_WRITE_NAME,
+        Extern::typed_func_with::<_, _, ReefDatasetWriteReturn>(
+            host.clone(),
+            |host, mut ctx: FuncContext<'_, ReefState, S>, (index, ptr): ReefDatasetWriteArgs| {
+                dataset::write_dataset(&mut ctx, &**host, index, ptr)
+            },
+        ),
+    )?;
+    imports.define(
+        REEF_MODULE_NAME,
+        REEF_DATASET_READ_NAME,
+        Extern::typed_func_with::<_, _, ReefDatasetReadReturn>(
+            host.clone(),
+            |host, mut ctx: FuncContext<'_, ReefState, S>, (index, offset, ptr, len): ReefDatasetReadArgs| {
+                Ok((dataset::read_dataset(&mut ctx, &**host, index, offset, ptr, len)?,))
+            },
+        ),
+    )?;
+
+    // Reef time.
+    imports.define(
+        REEF_MODULE_NAME,
+        REEF_TIME_NOW_NAME,
+        Extern::typed_func_with::<_, _, ReefTimeNowReturn>(
+            host.clone(),
+            |host, mut ctx: FuncContext<'_, ReefState, S>, (clock,): ReefTimeNowArgs| {
+                let time = match clock {
+                    REEF_CLOCK_CYCLES => ctx.total_cycles(),
+                    REEF_CLOCK_WALL => {
+                        let time = host.wall_time().as_millis() as u64;
+                        ctx.data_mut().wall_clock_readings.push(time);
+                        time
+                    }
+                    _ => return Err(Error::host(ReefError::InvalidClock(clock))),
+                };
+                Ok((time as i64,))
+            },
+        ),
+    )?;
+
+    // Reef random.
+    imports.define(
+        REEF_MODULE_NAME,
+        REEF_RANDOM_FILL_NAME,
+        Extern::typed_func_with::<_, _, ReefRandomFillReturn>(
+            host.clone(),
+            |host, mut ctx: FuncContext<'_, ReefState, S>, (ptr, len): ReefRandomFillArgs| {
+                random::random_fill(&mut ctx, &**host, ptr, len)
+            },
+        ),
+    )?;
+
+    // Reef result.
+    imports.define(
+        REEF_MODULE_NAME,
+        REEF_RESULT_NAME,
+        Extern::typed_func_with::<_, _, ReefResultReturn>(
+            host.clone(),
+            |_, mut ctx: FuncContext<'_, ReefState, S>, (result_type, ptr, len): ReefResultArgs| {
+                let content_type = u8::try_from(result_type)
+                    .ok()
+                    .filter(|content_type| num_to_content_type(*content_type).is_ok())
+                    .ok_or_else(|| Error::host(ReefError::InvalidContentType(result_type)))?;
+
+                let mem = ctx.exported_memory("memory")?;
+                let data = mem.load_vec(ptr as usize, len as usize)?;
+
+                ctx.data_mut().output = ReefJobOutput { content_type, data };
+
+                Ok(())
+            },
+        ),
+    )?;
+
+    abi::define_shims(&mut imports, &host, abi_version)?;
+
+    Ok(imports)
+}
+
+/// Instantiate a reef program and start its main function, or resume it from `state`
+///
+/// When resuming, the datasets of `host` are copied back into the memory regions the program wrote them to.
+pub fn start_job<H: ReefHost, S: Threading>(
+    mut module: Module,
+    imports: Imports<ReefState, S>,
+    host: &H,
+    state: Option<&[u8]>,
+) -> Result<ReefMainHandle<ReefState, S>, Error> {
+    // The cycles counted for `REEF_CLOCK_CYCLES` and state claims depend on the translation, so it is fixed for jobs
+    if module.backend != Backend::Stack {
+        module.set_backend(Backend::Stack)?;
+    }
+    if !module.superinstructions {
+        module.set_superinstructions(true);
+    }
+
+    let (mut instance, stack) = Instance::instantiate_with_data(module, imports, ReefState::default(), state)?;
+    // Jobs migrate between native and browser nodes, so results must not depend on the platform
+    instance.set_deterministic(true)?;
+
+    if stack.is_some() {
+        // The readings before the snapshot belong to the claim of the sync which took it
+        instance.data_mut().wall_clock_readings.clear();
+        dataset::restore_datasets(&mut instance, host)?;
+    }
+
+    let entry_fn_handle = instance.exported_func::<ReefMainArgs, ReefMainReturn>(REEF_MAIN_NAME)?;
+    entry_fn_handle.call(instance, (), stack).map_err(|err| err.1)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Mutex;
+
+    use reef_interpreter::{exec::CallResultTyped, imports::Shared, PAGE_SIZE};
+
+    use super::*;
+
+    #[derive(Debug)]
+    struct TestHost {
+        logs: Mutex<Vec<String>>,
+        progress: Mutex<Vec<f32>>,
+        sleeps: Mutex<Vec<Duration>>,
+        checkpoints: Mutex<u32>,
+        datasets: Vec<ReefDataset>,
+    }
+
+    impl TestHost {
+        fn new() -> Self {
+            Self {
+                logs: Mutex::default(),
+                progress: Mutex::default(),
+                sleeps: Mutex::default(),
+                checkpoints: Mutex::default(),
+                datasets: vec![ReefDataset::new("", vec![1, 2, 3, 4]), ReefDataset::new("weights", vec![5, 6, 7])],
+            }
+        }
+    }
+
+    impl ReefHost for TestHost {
+        fn log(&self, message: String) -> Result<(), Error> {
+            self.logs.lock().unwrap().push(message);
+            Ok(())
+        }
+
+        fn progress(&self, done: f32) -> Result<(), Error> {
+            self.progress.lock().unwrap().push(done);
+            Ok(())
+        }
+
+        fn sleep(&self, duration: Duration) -> Result<(), Error> {
+            self.sleeps.lock().unwrap().push(duration);
+            Ok(())
+        }
+
+        fn checkpoint(&self) -> Result<(), Error> {
+            *self.checkpoints.lock().unwrap() += 1;
+            Ok(())
+        }
+
+        fn datasets(&self) -> &[ReefDataset] {
+            &self.datasets
+        }
+
+        fn wall_time(&self) -> Duration {
+            Duration::from_millis(1234)
+        }
+
+        fn job_id(&self) -> &str {
+            "test"
+        }
+    }
+
+    /// Build a program against `abi_version`, whose main function executes `body`
+    fn program(abi_version: u32, body: &str) -> Module {
+        let abi_section = format!(r#"(@custom "reef_abi" "\{abi_version:02x}\00\00\00")"#);
+        let dataset_imports = match abi_version {
+            1 => {
+                r#"
+                (import "reef" "dataset_len" (func $dataset_len (result i32)))
+                (import "reef" "dataset_write" (func $dataset_write (param i32)))"#
+            }
+            2..=3 => {
+                r#"
+                (import "reef" "dataset_len" (func $dataset_len (result i64)))
+                (import "reef" "dataset_write" (func $dataset_write (param i32)))
+                (import "reef" "dataset_read" (func $dataset_read (param i64 i32 i32) (result i32)))"#
+            }
+            _ => {
+                r#"
+                (import "reef" "dataset_count" (func $dataset_count (result i32)))
+                (import "reef" "dataset_find" (func $dataset_find (param i32 i32) (result i32)))
+                (import "reef" "dataset_name" (func $dataset_name (param i32 i32 i32) (result i32)))
+                (import "reef" "dataset_len" (func $dataset_len (param i32) (result i64)))
+                (import "reef" "dataset_write" (func $dataset_write (param i32 i32)))
+                (import "reef" "dataset_read" (func $dataset_read (param i32 i64 i32 i32) (result i32)))"#
+            }
+        };
+        // imports added in later versions
+        let added_imports = [
+            (5, r#"(import "reef" "checkpoint" (func $checkpoint))"#),
+            (5, r#"(import "reef" "defer_sync" (func $defer_sync (param i32)))"#),
+            (6, r#"(import "reef" "random_fill" (func $random_fill (param i32 i32)))"#),
+            (7, r#"(import "reef" "time_now" (func $time_now (param i32) (result i64)))"#),
+        ];
+        let added_imports = added_imports
+            .iter()
+            .filter(|(version, _)| abi_version >= *version)
+            .map(|(_, import)| *import)
+            .collect::<Vec<_>>()
+            .join("\n");
+        let wasm = wat::parse_str(format!(
+            r#"
+            (module
+                {abi_section}
+                (import "reef" "log" (func $log (param i32 i32)))
+                (import "reef" "progress" (func $progress (param f32)))
+                (import "reef" "sleep" (func $sleep (param f32)))
+                {added_imports}
+                {dataset_imports}
+                (import "reef" "result" (func $result (param i32 i32 i32)))
+                (memory (export "memory") 1)
+                (data (i32.const 0) "hello")
+                (data (i32.const 16) "weights")
+                (func (export "reef_main") {body}))
+            "#
+        ))
+        .expect("invalid wat");
+        reef_interpreter::parse_bytes(&wasm).unwrap()
+    }
+
+    /// Run a program whose main function executes `body` and return its output
+    fn run(body: &str) -> (Arc<TestHost>, Result<ReefJobOutput, Error>) {
+        run_abi(1, body)
+    }
+
+    /// Run a program built against `abi_version`, see [`run`]
+    fn run_abi(abi_version: u32, body: &str) -> (Arc<TestHost>, Result<ReefJobOutput, Error>) {
+        let module = program(abi_version, body);
+
+        let host = Arc::new(TestHost::new());
+        let res = (|| {
+            let imports = reef_imports::<_, Shared>(host.clone(), crate::abi_version(&module)?)?;
+            let mut handle = start_job(module, imports, &*host, None)?;
+            while let CallResultTyped::Incomplete = handle.run(100)? {}
+            Ok(handle.into_instance().into_data().output)
+        })();
+        (host, res)
+    }
+
+    /// Start a job of `module` built against the current ABI, or resume it from `state`
+    fn start(module: &Module, state: Option<&[u8]>) -> ReefMainHandle<ReefState, Shared> {
+        let host = Arc::new(TestHost::new());
+        let imports = reef_imports::<_, Shared>(host.clone(), REEF_ABI_VERSION).unwrap();
+        let mut handle = start_job(module.clone(), imports, &*host, state).unwrap();
+        if state.is_none() {
+            // keep the snapshot small, the rest of the memory is not used
+            let mut mem = handle.instance_mut().exported_memory_mut("memory").unwrap();
+            mem.add_ignored_byte_region(2048, PAGE_SIZE - 2048).unwrap();
+        }
+        handle
+    }
+
+    /// Start a job of `module` and take a snapshot when it pauses for the first time
+    fn snapshot(module: &Module) -> Vec<u8> {
+        let mut handle = start(module, None);
+        assert!(matches!(handle.run(100).unwrap(), CallResultTyped::Incomplete));
+        let mut state = Vec::new();
+        handle.serialize_with_data(&mut state).unwrap();
+        state
+    }
+
+    /// Run a job to its end and return its result
+    fn finish(mut handle: ReefMainHandle<ReefState, Shared>) -> Vec<u8> {
+        while let CallResultTyped::Incomplete = handle.run(100).unwrap() {}
+        handle.into_instance().into_data().output.data
+    }
+
+    fn reef_error(res: Result<ReefJobOutput, Error>) -> ReefError {
+        res.unwrap_err().downcast_host::<ReefError>().expect("not a reef error")
+    }
+
+    #[test]
+    fn test_reef_imports() {
+        let (host, res) = run(r#"
+            (call $log (i32.const 0) (i32.const 5))
+            (call $progress (f32.const 0.5))
+            (call $sleep (f32.const 1.5))
+            (call $dataset_write (i32.const 1024))
+            (call $result (i32.const 1) (i32.const 1024) (call $dataset_len))
+        "#);
+        let output = res.unwrap();
+        assert_eq!((output.content_type, output.data), (1, vec![1, 2, 3, 4]));
+        assert_eq!(*host.logs.lock().unwrap(), ["hello"]);
+        assert_eq!(*host.progress.lock().unwrap(), [0.5]);
+        assert_eq!(*host.sleeps.lock().unwrap(), [Duration::from_secs_f32(1.5)]);
+    }
+
+    #[test]
+    fn test_reef_imports_current_abi() {
+        let (_, res) = run_abi(
+            REEF_ABI_VERSION,
+            r#"
+            (call $dataset_write (i32.const 0) (i32.const 1024))
+            (call $result (i32.const 1) (i32.const 1024) (i32.wrap_i64 (call $dataset_len (i32.const 0))))
+        "#,
+        );
+        assert_eq!(res.unwrap().data, [1, 2, 3, 4]);
+
+        // programs from before named datasets link against the shims
+        for abi_version in REEF_ABI_MIN_VERSION..4 {
+            let (_, res) = run_abi(abi_version, "(drop (call $dataset_len)) (call $dataset_write (i32.const 1024))");
+            res.unwrap();
+        }
+        let (_, res) = run_abi(REEF_ABI_VERSION + 1, "");
+        assert_eq!(reef_error(res), ReefError::UnsupportedAbi(REEF_ABI_VERSION + 1));
+    }
+
+    #[test]
+    fn test_reef_named_datasets() {
+        let (_, res) = run_abi(
+            REEF_ABI_VERSION,
+            r#"
+            ;; count, index of "weights" and the length of its name
+            (i32.store8 (i32.const 1024) (call $dataset_count))
+            (i32.store8 (i32.const 1025) (call $dataset_find (i32.const 16) (i32.const 7)))
+            (i32.store8 (i32.const 1026) (call $dataset_find (i32.const 16) (i32.const 6)))
+            (i32.store8 (i32.const 1027) (call $dataset_name (i32.const 1) (i32.const 1028) (i32.const 3)))
+            (drop (call $dataset_read (i32.const 1) (i64.const 1) (i32.const 1031) (i32.const 8)))
+            (call $result (i32.const 1) (i32.const 1024) (i32.const 9))
+        "#,
+        );
+        assert_eq!(res.unwrap().data, [2, 1, 255, 7, b'w', b'e', b'i', 6, 7]);
+
+        let (_, res) = run_abi(REEF_ABI_VERSION, "(drop (call $dataset_len (i32.const 2)))");
+        assert_eq!(reef_error(res), ReefError::InvalidDataset(2));
+        let (_, res) = run_abi(REEF_ABI_VERSION, "(call $dataset_write (i32.const -1) (i32.const 0))");
+        assert_eq!(reef_error(res), ReefError::InvalidDataset(-1));
+    }
+
+    #[test]
+    fn test_reef_datasets_restored() {
+        let module = program(
+            REEF_ABI_VERSION,
+            r#"
+            (call $dataset_write (i32.const 1) (i32.const 1024))
+            (call $dataset_write (i32.const 0) (i32.const 1027))
+            (call $sleep (f32.const 0))
+            (call $result (i32.const 1) (i32.const 1024) (i32.const 7))
+        "#,
+        );
+        let state = snapshot(&module);
+
+        // both datasets are excluded from the snapshot and copied back in on resume
+        let mut handle = start(&module, Some(&state));
+        let regions =
+            handle.instance_mut().exported_memory("memory").unwrap().ignored_byte_regions().collect::<Vec<_>>();
+        assert_eq!(regions, [(1024, 3), (1027, 4), (2048, PAGE_SIZE - 2048)]);
+        assert_eq!(finish(handle), [5, 6, 7, 1, 2, 3, 4]);
+    }
+
+    #[test]
+    fn test_reef_dataset_read() {
+        let (_, res) = run_abi(
+            3,
+            r#"
+            ;; reads past the end are cut short, the total number of bytes read is stored after them
+            (i32.store8 (i32.const 1027) (i32.add
+                (i32.add
+                    (call $dataset_read (i64.const 1) (i32.const 1024) (i32.const 2))
+                    (call $dataset_read (i64.const 3) (i32.const 1026) (i32.const 100)))
+                (i32.add
+                    (call $dataset_read (i64.const 4) (i32.const 1027) (i32.const 100))
+                    (call $dataset_read (i64.const -1) (i32.const 1027) (i32.const 100)))))
+            (call $result (i32.const 1) (i32.const 1024) (i32.const 4))
+        "#,
+        );
+        assert_eq!(res.unwrap().data, [2, 3, 4, 3]);
+
+        let (_, res) = run_abi(3, "(drop (call $dataset_read (i64.const 0) (i32.const 65535) (i32.const 2)))");
+        assert!(matches!(res.unwrap_err(), Error::Trap(_)));
+    }
+
+    #[test]
+    fn test_reef_dataset_read_after_write() {
+        let module = program(
+            REEF_ABI_VERSION,
+            r#"
+            (call $dataset_write (i32.const 0) (i32.const 1024))
+            (call $sleep (f32.const 0))
+            ;; the written dataset is read from memory, the other one from the host
+            (i32.store8 (i32.const 1030) (call $dataset_read (i32.const 0) (i64.const 1) (i32.const 1031) (i32.const 100)))
+            (i32.store8 (i32.const 1034) (call $dataset_read (i32.const 1) (i64.const 2) (i32.const 1035) (i32.const 1)))
+            (call $result (i32.const 1) (i32.const 1024) (i32.const 12))
+        "#,
+        );
+        let expected = [1, 2, 3, 4, 0, 0, 3, 2, 3, 4, 1, 7];
+        assert_eq!(finish(start(&module, None)), expected);
+
+        // reads after resuming are served from the dataset copied back into memory
+        let state = snapshot(&module);
+        assert_eq!(finish(start(&module, Some(&state))), expected);
+
+        // as well as for programs from before named datasets
+        let (_, res) = run_abi(
+            3,
+            r#"
+            (call $dataset_write (i32.const 1024))
+            (call $result (i32.const 1) (i32.const 0) (call $dataset_read (i64.const 2) (i32.const 0) (i32.const 8)))
+        "#,
+        );
+        assert_eq!(res.unwrap().data, [3, 4]);
+    }
+
+    #[test]
+    fn test_reef_sync_control() {
+        let module = program(
+            REEF_ABI_VERSION,
+            "(call $defer_sync (i32.const 1)) (call $checkpoint) (call $defer_sync (i32.const 0))",
+        );
+        let host = Arc::new(TestHost::new());
+        let imports = reef_imports::<_, Shared>(host.clone(), REEF_ABI_VERSION).unwrap();
+        let mut handle = start_job(module, imports, &*host, None).unwrap();
+
+        // the checkpoint pauses execution, the deferral is part of the state of the job
+        assert!(matches!(handle.run(100).unwrap(), CallResultTyped::Incomplete));
+        assert_eq!(*host.checkpoints.lock().unwrap(), 1);
+        assert!(handle.instance().data().sync_deferred);
+
+        while let CallResultTyped::Incomplete = handle.run(100).unwrap() {}
+        assert!(!handle.into_instance().into_data().sync_deferred);
+    }
+
+    #[test]
+    fn test_reef_random_fill() {
+        let module = program(
+            REEF_ABI_VERSION,
+            r#"
+            (call $random_fill (i32.const 1024) (i32.const 8))
+            (call $sleep (f32.const 0))
+            (call $random_fill (i32.const 1032) (i32.const 8))
+            (call $result (i32.const 1) (i32.const 1024) (i32.const 16))
+        "#,
+        );
+        let expected = finish(start(&module, None));
+        assert_ne!(expected[..8], expected[8..]);
+
+        // a resumed job continues the sequence of the generator
+        let state = snapshot(&module);
+        assert_eq!(finish(start(&module, Some(&state))), expected);
+
+        let (_, res) = run_abi(REEF_ABI_VERSION, "(call $random_fill (i32.const 65535) (i32.const 2))");
+        assert!(matches!(res.unwrap_err(), Error::Trap(_)));
+    }
+
+    #[test]
+    fn test_reef_time_now() {
+        let module = program(
+            REEF_ABI_VERSION,
+            r#"
+            (local $x i32)
+            (local.set $x (i32.mul (local.get $x) (local.get $x)))
+            (i64.store (i32.const 1024) (call $time_now (i32.const 0)))
+            (call $sleep (f32.const 0))
+            (i64.store (i32.const 1032) (call $time_now (i32.const 0)))
+            (i64.store (i32.const 1040) (call $time_now (i32.const 1)))
+            (call $result (i32.const 1) (i32.const 1024) (i32.const 24))
+        "#,
+        );
+        let times = |handle| {
+            let data = finish(handle);
+            data.chunks(8).map(|time| u64::from_le_bytes(time.try_into().unwrap())).collect::<Vec<_>>()
+        };
+
+        let expected = times(start(&module, None));
+        assert!(expected[0] < expected[1]);
+        assert_eq!(expected[2], 1234);
+
+        // the virtual clock continues where the snapshot left off
+        let state = snapshot(&module);
+        assert_eq!(times(start(&module, Some(&state))), expected);
+
+        // nor does it depend on how the module was translated before
+        let mut translated = module.clone();
+        translated.set_superinstructions(false);
+        translated.set_backend(Backend::Slots).unwrap();
+        assert_eq!(times(start(&translated, None)), expected);
+
+        // readings of the wall clock stay in the state until it is synced, resumed jobs start without them
+        let module = program(REEF_ABI_VERSION, "(drop (call $time_now (i32.const 1))) (call $sleep (f32.const 0))");
+        let host = Arc::new(TestHost::new());
+        let imports = || reef_imports::<_, Shared>(host.clone(), REEF_ABI_VERSION).unwrap();
+        let mut handle = start_job(module.clone(), imports(), &*host, None).unwrap();
+        assert!(matches!(handle.run(100).unwrap(), CallResultTyped::Incomplete));
+        assert_eq!(handle.instance().data().wall_clock_readings, [1234]);
+        let mut state = Vec::new();
+        handle.serialize_with_data(&mut state).unwrap();
+        let handle = start_job(module, imports(), &*host, Some(&state)).unwrap();
+        assert!(handle.instance().data().wall_clock_readings.is_empty());
+
+        let (_, res) = run_abi(REEF_ABI_VERSION, "(drop (call $time_now (i32.const 2)))");
+        assert_eq!(reef_error(res), ReefError::InvalidClock(2));
+    }
+
+    #[test]
+    fn test_reef_import_limits() {
+        let log_len = REEF_LOG_MAX_LEN + 1;
+        let res = run(&format!("(call $log (i32.const 0) (i32.const {log_len}))")).1;
+        assert_eq!(reef_error(res), ReefError::LogTooLong(log_len));
+
+        let res = run("(call $progress (f32.const 1.5))").1;
+        assert_eq!(reef_error(res), ReefError::InvalidProgress(1.5));
+
+        let res = run("(call $sleep (f32.const -1))").1;
+        assert_eq!(reef_error(res), ReefError::InvalidSleep(-1.0));
+
+        let res = run("(call $dataset_write (i32.const 1024)) (call $dataset_write (i32.const 2048))").1;
+        assert_eq!(reef_error(res), ReefError::DatasetAlreadyWritten);
+
+        // every content type, including JSON, is accepted
+        for content_type in 0..=3 {
+            let res = run(&format!("(call $result (i32.const {content_type}) (i32.const 0) (i32.const 5))")).1;
+            assert_eq!(res.unwrap().content_type, content_type as u8);
+        }
+        let res = run("(call $result (i32.const 4) (i32.const 0) (i32.const 5))").1;
+        assert_eq!(reef_error(res), ReefError::InvalidContentType(4));
+        let res = run("(call $result (i32.const -1) (i32.const 0) (i32.const 5))").1;
+        assert_eq!(reef_error(res), ReefError::InvalidContentType(-1));
+    }
+}
@@ -6,13 +6,24 @@
 pub const REEF_MAIN_NAME: &str = "reef_main";
 pub type ReefMainArgs = ();
 pub type ReefMainReturn = ();
-pub type ReefMainHandle = reef_interpreter::exec::ExecHandleTyped<ReefMainReturn>;
+pub type ReefMainHandle<T, S = reef_interpreter::imports::Local> =
+    reef_interpreter::exec::ExecHandleTyped<ReefMainReturn, T, S>;
 
 // Imports
 pub const REEF_MODULE_NAME: &str = "reef";
 
+mod abi;
+mod dataset;
+mod host;
+mod random;
+pub use abi::{abi_version, REEF_ABI_MIN_VERSION, REEF_ABI_SECTION, REEF_ABI_VERSION};
+pub use dataset::ReefDataset;
+pub use host::{reef_imports, start_job, ReefHost, ReefState};
+pub use random::random_seed;
+
 pub const REEF_LOG_NAME: &str = "log";
 pub type ReefLogArgs = (i32, i32);
+pub const REEF_LOG_MAX_LEN: usize = 0x400;
 // type ReefLogReturn = ();
 
 pub const REEF_PROGRESS_NAME: &str = "progress";
@@ -24,18 +35,168 @@ pub const REEF_SLEEP_NAME: &str = "sleep";
 pub type ReefSleepArgs = (f32,);
 pub type ReefSleepReturn = ();
 
+// Pause and sync the state of the job right away.
+pub const REEF_CHECKPOINT_NAME: &str = "checkpoint";
+pub type ReefCheckpointArgs = ();
+pub type ReefCheckpointReturn = ();
+
+// Whether syncs should be deferred, as a boolean.
+pub const REEF_DEFER_SYNC_NAME: &str = "defer_sync";
+pub type ReefDeferSyncArgs = (i32,);
+pub type ReefDeferSyncReturn = ();
+/// Longest time a node defers a sync for a program, afterwards it syncs anyway.
+pub const REEF_DEFER_SYNC_MAX: std::time::Duration = std::time::Duration::from_secs(60);
+
+// Datasets are identified by their index.
+pub const REEF_DATASET_COUNT_NAME: &str = "dataset_count";
+pub type ReefDatasetCountArgs = ();
+pub type ReefDatasetCountReturn = (i32,);
+
+pub const REEF_DATASET_FIND_NAME: &str = "dataset_find";
+// Pointer and length of the name.
+pub type ReefDatasetFindArgs = (i32, i32);
+// Index of the dataset, -1 if there is none with the name.
+pub type ReefDatasetFindReturn = (i32,);
+
+pub const REEF_DATASET_NAME_NAME: &str = "dataset_name";
+// Index, pointer and length of the buffer to copy the name into.
+pub type ReefDatasetNameArgs = (i32, i32, i32);
+// Length of the entire name.
+pub type ReefDatasetNameReturn = (i32,);
+
 pub const REEF_DATASET_LEN_NAME: &str = "dataset_len";
-pub type ReefDatasetLenArgs = ();
-pub type ReefDatasetLenReturn = (i32,);
+pub type ReefDatasetLenArgs = (i32,);
+pub type ReefDatasetLenReturn = (i64,);
 
 pub const REEF_DATASET_WRITE_NAME: &str = "dataset_write";
-pub type ReefDatasetWriteArgs = (i32,);
+pub type ReefDatasetWriteArgs = (i32, i32);
 pub type ReefDatasetWriteReturn = ();
 
+pub const REEF_DATASET_READ_NAME: &str = "dataset_read";
+// Index, offset into the dataset, pointer and length of the buffer to read into.
+pub type ReefDatasetReadArgs = (i32, i64, i32, i32);
+// Number of bytes read, less than requested only at the end of the dataset.
+pub type ReefDatasetReadReturn = (i32,);
+
+pub const REEF_TIME_NOW_NAME: &str = "time_now";
+// One of the `REEF_CLOCK_*` clocks.
+pub type ReefTimeNowArgs = (i32,);
+pub type ReefTimeNowReturn = (i64,);
+/// Instructions executed since the job was started, the same on every node since [`start_job`] fixes the backend
+pub const REEF_CLOCK_CYCLES: i32 = 0;
+/// Milliseconds since the Unix epoch, only for progress estimates since results must not depend on it
+///
+/// Readings are kept in [`ReefState::wall_clock_readings`], so verifications replay them.
+pub const REEF_CLOCK_WALL: i32 = 1;
+
+pub const REEF_RANDOM_FILL_NAME: &str = "random_fill";
+// Pointer and length of the buffer to fill with random bytes.
+pub type ReefRandomFillArgs = (i32, i32);
+pub type ReefRandomFillReturn = ();
+
 pub const REEF_RESULT_NAME: &str = "result";
 pub type ReefResultArgs = (i32, i32, i32);
 pub type ReefResultReturn = ();
 
+/// Output of a job as set by `reef/result`, this is persisted in the state of the job
+#[derive(Debug, Clone, Default, serde::Serialize, serde::Deserialize)]
+pub struct ReefJobOutput {
+    pub content_type: u8,
+    pub data: Vec<u8>,
+}
+
+/// The end of a job for recordings of its host calls, see [`reef_interpreter::record::Recorder::finish`]
+///
+/// Aborted jobs did not end, so there is no record for them.
+pub fn end_record(
+    cycles: u64,
+    res: Result<&ReefJobOutput, &reef_interpreter::Error>,
+) -> Option<reef_interpreter::record::EndRecord> {
+    let outcome = match res {
+        Ok(output) => Ok([&[output.content_type][..], &output.data].concat()),
+        Err(err) if matches!(err.downcast_host_ref::<ReefError>(), Some(ReefError::Aborted)) => return None,
+        Err(err) => Err(err.to_string()),
+    };
+    Some(reef_interpreter::record::EndRecord { cycles, outcome })
+}
+
+//
+// Errors
+//
+
+/// Errors returned by the reef imports if the guest violates a limit of the host
+#[derive(Debug, Clone, PartialEq)]
+pub enum ReefError {
+    /// `reef/log` was called with a message longer than [`REEF_LOG_MAX_LEN`]
+    LogTooLong(usize),
+    /// `reef/progress` was called with a value outside of `0.0..=1.0`
+    InvalidProgress(f32),
+    /// `reef/sleep` was called with a duration that can't be represented
+    InvalidSleep(f32),
+    /// `reef/time_now` was called with an unknown clock
+    InvalidClock(i32),
+    /// A dataset import was called with an index without a dataset
+    InvalidDataset(i32),
+    /// `reef/dataset_len` can't represent the length of the dataset in the ABI version of the program
+    DatasetTooLarge(usize),
+    /// `reef/dataset_write` was called more than once
+    DatasetAlreadyWritten,
+    /// `reef/result` was called with an unknown content type
+    InvalidContentType(i32),
+    /// The program declares an ABI version which is not supported by this node
+    UnsupportedAbi(u32),
+    /// The [`REEF_ABI_SECTION`] of the program is malformed
+    InvalidAbiSection,
+    /// The job was aborted by the manager or the node
+    Aborted,
+}
+
+impl std::fmt::Display for ReefError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::LogTooLong(len) => {
+                write!(f, "reef/log: log message of {len} bytes longer than {REEF_LOG_MAX_LEN:#x} bytes, aborting")
+            }
+            Self::InvalidProgress(done) => write!(f, "reef/progress: value {done} not in Range 0.0..=1.0"),
+            Self::InvalidSleep(seconds) => write!(f, "reef/sleep: invalid time {seconds}"),
+            Self::InvalidClock(clock) => write!(f, "reef/time_now: invalid clock {clock}"),
+            Self::InvalidDataset(index) => write!(f, "reef/dataset: no dataset with index {index}"),
+            Self::DatasetTooLarge(len) => {
+                write!(f, "reef/dataset_len: dataset of {len} bytes too large for the ABI version of the program")
+            }
+            Self::DatasetAlreadyWritten => write!(f, "reef/dataset_write: dataset already written"),
+            Self::InvalidContentType(content_type) => {
+                write!(f, "reef/result: invalid ResultContentType {content_type}")
+            }
+            Self::UnsupportedAbi(version) => write!(
+                f,
+                "program requires reef ABI version {version}, supported are {REEF_ABI_MIN_VERSION}..={REEF_ABI_VERSION}"
+            ),
+            Self::InvalidAbiSection => write!(f, "invalid '{REEF_ABI_SECTION}' section, expected a u32 ABI version"),
+            Self::Aborted => write!(f, "job was aborted"),
+        }
+    }
+}
+
+impl std::error::Error for ReefError {}
+
+/// Categorize why a job failed, to be reported in the job result
+pub fn failure_kind(err: &reef_interpreter::Error) -> JobFailureKind {
+    use reef_interpreter::Error;
+
+    match err {
+        Error::Trap(_) => JobFailureKind::Trap,
+        Error::Host(_) => match err.downcast_host_ref::<ReefError>() {
+            Some(ReefError::Aborted) => JobFailureKind::Aborted,
+            Some(ReefError::UnsupportedAbi(_) | ReefError::InvalidAbiSection) => JobFailureKind::InvalidProgram,
+            Some(_) => JobFailureKind::HostLimit,
+            None => JobFailureKind::Internal,
+        },
+        Error::Linker(_) | Error::ParseError(_) | Error::UnsupportedFeature(_) => JobFailureKind::InvalidProgram,
+        _ => JobFailureKind::Internal,
+    }
+}
+
 //
 // API definitions
 //
@@ -45,7 +206,7 @@ pub const NODE_REGISTER_PATH: &str = "/api/node/connect";
 // Utility
 //
 
-use reef_protocol_node::message_capnp::ResultContentType;
+use reef_protocol_node::message_capnp::{JobFailureKind, ResultContentType};
 
 #[derive(Debug)]
 pub struct ContentTypeConvertError;
@@ -0,0 +1,38 @@
+//! Deterministic random numbers for programs
+
+use rand_chacha::{
+    rand_core::{RngCore, SeedableRng},
+    ChaCha20Rng,
+};
+use reef_interpreter::{
+    imports::{FuncContext, Threading},
+    Error,
+};
+use sha2::{Digest, Sha256};
+
+use crate::*;
+
+/// Seed of the random numbers of a job, derived from its ID so every node produces the same ones
+pub fn random_seed(job_id: &str) -> [u8; 32] {
+    Sha256::new().chain_update(b"reef/random:").chain_update(job_id.as_bytes()).finalize().into()
+}
+
+/// Implementation of `reef/random_fill`
+///
+/// The generator is seeded on first use and part of [`ReefState`], so a resumed job continues its sequence.
+pub(crate) fn random_fill<H: ReefHost, S: Threading>(
+    ctx: &mut FuncContext<'_, ReefState, S>,
+    host: &H,
+    ptr: i32,
+    len: i32,
+) -> Result<(), Error> {
+    let len = len as u32 as usize;
+    // Check the bounds before allocating the buffer
+    ctx.exported_memory("memory")?.load(ptr as usize, len)?;
+
+    let rng = ctx.data_mut().rng.get_or_insert_with(|| ChaCha20Rng::from_seed(random_seed(host.job_id())));
+    let mut buf = vec![0; len];
+    rng.fill_bytes(&mut buf);
+
+    ctx.exported_memory_mut("memory")?.store(ptr as usize, len, &buf)
+}